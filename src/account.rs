@@ -1,26 +1,64 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::io;
 use std::sync::Arc;
 use std::sync::RwLock;
 
-use crate::address_decoder;
+use crate::address::Address;
+use crate::address_decoder::{self, AddressType};
+use crate::balance::{
+    BalanceBreakdown, ClassifiedCoin, CoinCategory, SpendPolicy, COINBASE_MATURITY,
+};
+use crate::blocks::block::Block;
+use crate::coin_control::{format_coin_id, CoinControl, CoinId, CoinInfo};
+use crate::coin_selection::{
+    coins_from_utxos, dust_threshold, fee_for_vsize, input_vsize, output_vsize, select_coins,
+    use_all_coins, Coin, CoinSelectionParams, Selection, TRANSACTION_OVERHEAD_VSIZE,
+};
+use crate::compact_size_uint::CompactSizeUint;
 use crate::custom_errors::NodeCustomErrors;
+use crate::descriptor::{Descriptor, DescriptorKey, ExtendedKey, KeySource};
+use crate::fee_estimator::MIN_RELAY_FEE_RATE;
+use crate::hd_wallet::bip32::{HARDENED_INDEX, TESTNET_PRIVATE_VERSION, TESTNET_PUBLIC_VERSION};
+use crate::hd_wallet::hd_keychain::{DerivationScheme, HdKeychain, RECEIVE_CHAIN};
+use crate::ledger::{BlockPosition, HistoryEntry, Ledger, LedgerEntry};
+use crate::psbt::Psbt;
+use crate::rescan::add_coins_to_utxo_set;
+use crate::transactions::recipient::{total_amount, Recipient};
+use crate::transactions::script::multisig_script::{MultisigKind, MultisigScript};
 use crate::transactions::transaction::Transaction;
+use crate::transactions::tx_out::TxOut;
 use crate::utxo_tuple::UtxoTuple;
 #[derive(Debug, Clone)]
 /// Representa una cuenta bitcoin
 /// Guarda la address comprimida y la private key (comprimida o no)
 /// También guarda las utxos de la cuenta, transacciones pendientes y confirmadas
+/// y el ledger con todas las transacciones que recibe, envia o se envia a si misma
+/// Si la cuenta es HD, guarda su llavero y la address y private key son las de su primera direccion de recepcion
+/// Si la cuenta es multisig, guarda el multisig, la address es la del multisig y la private key es la de uno de los firmantes
+/// Toda cuenta guarda sus output descriptors publicos (el de recepcion y el de cambio si es HD): de ellos se derivan
+/// los pubkey scripts que le pagan, con los que se buscan sus monedas, y las direcciones nuevas que entrega
+/// Si la cuenta es watch-only no tiene private key: observa los scripts de un output descriptor
+/// o las direcciones derivadas de una clave publica extendida, y solo puede crear transacciones sin firmar
+/// El coin control con las monedas bloqueadas y congeladas lo comparte con la wallet
 pub struct Account {
     pub private_key: String,
     pub address: String,
     pub utxo_set: Vec<UtxoTuple>,
     pub pending_transactions: Arc<RwLock<Vec<Transaction>>>,
     pub confirmed_transactions: Arc<RwLock<Vec<Transaction>>>,
+    pub ledger: Arc<RwLock<Ledger>>,
+    pub hd_keychain: Option<HdKeychain>,
+    pub multisig: Option<MultisigScript>,
+    pub descriptors: Vec<Descriptor>,
+    pub scripts: HashSet<Vec<u8>>,
+    pub coin_control: Arc<RwLock<CoinControl>>,
+    /// Cantidad de indices de cada descriptor de los que ya se derivaron los scripts
+    derived_ranges: Vec<u32>,
 }
 
-type TransactionInfo = (String, Transaction, i64);
+/// Cantidad de scripts que observa una cuenta watch-only de un descriptor con rango, como en Bitcoin Core
+const DESCRIPTOR_RANGE_END: u32 = 1000;
 impl Account {
     /// Recibe la address en formato comprimido
     /// Y la WIF private key, ya sea en formato comprimido o no comprimido
@@ -28,13 +66,385 @@ impl Account {
         let raw_private_key = address_decoder::decode_wif_private_key(wif_private_key.as_str())?;
 
         address_decoder::validate_address_private_key(&raw_private_key, &address)?;
-        Ok(Account {
-            private_key: wif_private_key,
+        let descriptor = Descriptor::for_key_address(
+            address_decoder::get_pubkey_compressed(&wif_private_key)?,
+            &address,
+        )?;
+        Account::with_descriptors(wif_private_key, address, vec![descriptor], None, None)
+    }
+
+    /// Crea la cuenta con los output descriptors publicos recibidos y deriva de ellos los pubkey scripts
+    /// que le pagan
+    fn with_descriptors(
+        private_key: String,
+        address: String,
+        descriptors: Vec<Descriptor>,
+        hd_keychain: Option<HdKeychain>,
+        multisig: Option<MultisigScript>,
+    ) -> Result<Account, Box<dyn Error>> {
+        let mut account = Account {
+            private_key,
             address,
             utxo_set: Vec::new(),
             pending_transactions: Arc::new(RwLock::new(Vec::new())),
             confirmed_transactions: Arc::new(RwLock::new(Vec::new())),
-        })
+            ledger: Arc::new(RwLock::new(Ledger::default())),
+            hd_keychain,
+            multisig,
+            derived_ranges: vec![0; descriptors.len()],
+            descriptors,
+            scripts: HashSet::new(),
+            coin_control: Arc::new(RwLock::new(CoinControl::default())),
+        };
+        account.derive_scripts()?;
+        Ok(account)
+    }
+
+    /// Deriva de los descriptors de la cuenta los pubkey scripts que todavia no tiene: si es HD hasta las claves
+    /// que derivo el llavero en cada cadena, y sino los primeros DESCRIPTOR_RANGE_END de los descriptors con rango.
+    /// Se ignoran los indices que no se pueden derivar
+    fn derive_scripts(&mut self) -> Result<(), Box<dyn Error>> {
+        let range_ends = match &self.hd_keychain {
+            Some(keychain) => {
+                let (receive_keys, change_keys) = keychain.derived_counts();
+                vec![receive_keys, change_keys]
+            }
+            None => vec![DESCRIPTOR_RANGE_END; self.descriptors.len()],
+        };
+        for (position, descriptor) in self.descriptors.iter().enumerate() {
+            let range_end = match range_ends.get(position) {
+                Some(range_end) if descriptor.is_range() => *range_end,
+                _ => 1,
+            };
+            let derived = &mut self.derived_ranges[position];
+            self.scripts.extend(
+                (*derived..range_end).filter_map(|index| descriptor.script_pubkey(index).ok()),
+            );
+            *derived = (*derived).max(range_end);
+        }
+        Ok(())
+    }
+
+    /// Crea una cuenta con una clave privada nueva generada con el generador aleatorio del sistema operativo
+    /// y la address del tipo recibido de su clave publica comprimida
+    pub fn generate(address_type: AddressType) -> Result<Account, Box<dyn Error>> {
+        let private_key = address_decoder::generate_private_key();
+        let address = address_type.address(&private_key)?;
+        Account::new(
+            address_decoder::encode_wif_private_key(&private_key),
+            address,
+        )
+    }
+
+    /// Crea una cuenta HD a partir del mnemonic de BIP39, la passphrase (puede ser vacia),
+    /// el esquema de derivacion y el indice de la cuenta. Devuelve error si el mnemonic es invalido
+    pub fn from_mnemonic(
+        mnemonic: &str,
+        passphrase: &str,
+        scheme: DerivationScheme,
+        account_index: u32,
+    ) -> Result<Account, Box<dyn Error>> {
+        let keychain = HdKeychain::from_mnemonic(mnemonic, passphrase, scheme, account_index)?;
+        Account::from_keychain(keychain)
+    }
+
+    /// Crea la cuenta HD del llavero recibido, identificada por su primera direccion de recepcion,
+    /// con los descriptors de su cadena de recepcion y de su cadena de cambio
+    fn from_keychain(keychain: HdKeychain) -> Result<Account, Box<dyn Error>> {
+        let first_key = keychain.first_receive_key();
+        let private_key = first_key
+            .private_key
+            .map(|private_key| address_decoder::encode_wif_private_key(&private_key))
+            .unwrap_or_default();
+        let address = first_key.address.clone();
+        let descriptors = keychain.descriptors(false)?.to_vec();
+        Account::with_descriptors(private_key, address, descriptors, Some(keychain), None)
+    }
+
+    /// Crea una cuenta watch-only a partir de lo que se quiere observar: una clave publica extendida (tpub),
+    /// cuyas direcciones se derivan con el esquema recibido, un output descriptor (del que se descartan las
+    /// claves privadas), una direccion o un pubkey script en hexadecimal.
+    /// Devuelve error si no es ninguno de ellos
+    pub fn new_watch_only(
+        watched: &str,
+        scheme: DerivationScheme,
+    ) -> Result<Account, Box<dyn Error>> {
+        let watched = watched.trim();
+        if watched.starts_with("tpub") {
+            return Account::new_watch_only_xpub(watched, scheme);
+        }
+        if watched.contains('(') {
+            return Account::new_watch_only_descriptor(Descriptor::parse(watched)?.to_public()?);
+        }
+        if let Ok(account) = Account::new_watch_only_address(watched) {
+            return Ok(account);
+        }
+        match hex_string_to_bytes(watched) {
+            Ok(script) if !script.is_empty() => Account::new_watch_only_script(script),
+            _ => Err(Box::new(std::io::Error::other(
+                "No es una clave publica extendida (tpub), una direccion ni un script en hexadecimal validos",
+            ))),
+        }
+    }
+
+    /// Crea una cuenta watch-only que observa la direccion recibida. Devuelve error si la direccion es invalida
+    pub fn new_watch_only_address(address: &str) -> Result<Account, Box<dyn Error>> {
+        Account::new_watch_only_descriptor(Descriptor::Addr(Address::parse(address)?))
+    }
+
+    /// Crea una cuenta watch-only que observa el pubkey script recibido
+    pub fn new_watch_only_script(script: Vec<u8>) -> Result<Account, Box<dyn Error>> {
+        Account::new_watch_only_descriptor(Descriptor::for_script(script))
+    }
+
+    /// Crea una cuenta watch-only que observa los scripts del descriptor recibido, los primeros
+    /// DESCRIPTOR_RANGE_END si es un rango. La cuenta se identifica por la direccion de su primer script
+    /// o, si no es de un tipo estandar, por el script en hexadecimal.
+    /// Devuelve error si el descriptor tiene claves privadas o no se puede derivar
+    pub fn new_watch_only_descriptor(descriptor: Descriptor) -> Result<Account, Box<dyn Error>> {
+        if descriptor.has_private_keys() {
+            return Err(Box::new(std::io::Error::other(
+                "El descriptor de una cuenta watch-only no puede tener claves privadas",
+            )));
+        }
+        let first_script = descriptor.script_pubkey(0)?;
+        let multisig = match descriptor {
+            Descriptor::Multi { .. } if !descriptor.is_range() => Some(descriptor.multisig(0)?),
+            _ => None,
+        };
+        let address = Address::from_script_pubkey(&first_script)
+            .map(|address| address.to_string())
+            .unwrap_or_else(|| bytes_to_hex_string(&first_script));
+        Account::with_descriptors(String::new(), address, vec![descriptor], None, multisig)
+    }
+
+    /// Crea la cuenta del output descriptor recibido. Si el descriptor tiene claves privadas y la wallet
+    /// puede firmar con ellas se crea una cuenta comun: pkh() o wpkh() de una WIF, de una tprv con el path
+    /// /0/* o de una tpub con el path /0/* (HD watch-only), o sortedmulti() con una sola WIF.
+    /// El resto de los descriptores sin claves privadas se importan como watch-only.
+    /// Devuelve error si el descriptor es invalido o tiene claves privadas que la wallet no sabe usar
+    pub fn from_descriptor(text: &str) -> Result<Account, Box<dyn Error>> {
+        let descriptor = Descriptor::parse(text)?;
+        let (key, scheme) = match &descriptor {
+            Descriptor::Pkh(key) => (Some(key), DerivationScheme::Bip44),
+            Descriptor::Wpkh(key) => (Some(key), DerivationScheme::Bip84),
+            _ => (None, DerivationScheme::Bip84),
+        };
+        if let Some(key) = key {
+            match &key.source {
+                KeySource::Wif(private_key) => {
+                    let wif_private_key = address_decoder::encode_wif_private_key(private_key);
+                    return Account::new(wif_private_key, descriptor.address(0)?);
+                }
+                KeySource::Extended {
+                    key: extended_key,
+                    path,
+                    wildcard: true,
+                } if *path == [RECEIVE_CHAIN] => {
+                    let keychain = match extended_key {
+                        ExtendedKey::Private(private_key) => HdKeychain::from_account_tprv(
+                            &private_key.to_base58(TESTNET_PRIVATE_VERSION),
+                            scheme,
+                            private_key.child_number & !HARDENED_INDEX,
+                        )?,
+                        ExtendedKey::Public(public_key) => HdKeychain::from_account_tpub(
+                            &public_key.to_base58(TESTNET_PUBLIC_VERSION),
+                            scheme,
+                        )?,
+                    };
+                    return Account::from_keychain(keychain);
+                }
+                _ => {}
+            }
+        }
+        if let Descriptor::Multi {
+            kind,
+            required,
+            keys,
+            sorted: true,
+        } = &descriptor
+        {
+            let private_keys: Vec<[u8; 32]> = keys
+                .iter()
+                .filter_map(|key| match key.source {
+                    KeySource::Wif(private_key) => Some(private_key),
+                    _ => None,
+                })
+                .collect();
+            if let [private_key] = private_keys.as_slice() {
+                return Account::new_multisig(
+                    address_decoder::encode_wif_private_key(private_key),
+                    *kind,
+                    *required,
+                    descriptor.multisig(0)?.pubkeys,
+                );
+            }
+        }
+        Account::new_watch_only_descriptor(descriptor)
+    }
+
+    /// Devuelve los output descriptors de la cuenta, que permiten importarla en otra wallet: el de recepcion
+    /// y el de cambio si es HD, o el unico descriptor de la cuenta si no. Si include_private es true las
+    /// claves publicas de la cuenta se reemplazan por las privadas.
+    /// Devuelve error si se piden las claves privadas de una cuenta watch-only
+    pub fn descriptors(&self, include_private: bool) -> Result<Vec<Descriptor>, Box<dyn Error>> {
+        if include_private {
+            self.ensure_can_sign()?;
+        }
+        if !include_private {
+            return Ok(self.descriptors.clone());
+        }
+        if let Some(keychain) = &self.hd_keychain {
+            return Ok(keychain.descriptors(include_private)?.to_vec());
+        }
+        let private_key = self.get_private_key()?;
+        self.descriptors
+            .iter()
+            .map(|descriptor| descriptor.with_private_key(private_key))
+            .collect()
+    }
+
+    /// Crea una cuenta HD watch-only a partir de la clave publica extendida de la cuenta (tpub) y el
+    /// esquema de derivacion con el que se generan sus direcciones. Devuelve error si la clave es invalida
+    pub fn new_watch_only_xpub(
+        tpub: &str,
+        scheme: DerivationScheme,
+    ) -> Result<Account, Box<dyn Error>> {
+        let keychain = HdKeychain::from_account_tpub(tpub, scheme)?;
+        Account::from_keychain(keychain)
+    }
+
+    /// Devuelve true si la cuenta es watch-only, es decir que no tiene claves privadas para firmar
+    pub fn is_watch_only(&self) -> bool {
+        self.private_key.is_empty()
+    }
+
+    /// Devuelve error si la cuenta es watch-only y por lo tanto no puede firmar transacciones
+    pub fn ensure_can_sign(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_watch_only() {
+            return Err(Box::new(std::io::Error::other(
+                "La cuenta es watch-only: no tiene claves privadas para firmar, solo puede crear PSBT sin firmar",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Crea una cuenta multisig m de n a partir de las claves publicas comprimidas de los firmantes y la
+    /// WIF private key de uno de ellos, con la que la cuenta agrega su firma a las transacciones.
+    /// Devuelve error si el multisig es invalido o la clave privada no es de ninguno de los firmantes
+    pub fn new_multisig(
+        wif_private_key: String,
+        kind: MultisigKind,
+        required: u8,
+        pubkeys: Vec<[u8; 33]>,
+    ) -> Result<Account, Box<dyn Error>> {
+        let own_pubkey = address_decoder::get_pubkey_compressed(&wif_private_key)?;
+        if !pubkeys.contains(&own_pubkey) {
+            return Err(Box::new(std::io::Error::other(
+                "La clave privada no corresponde a ninguna de las claves publicas del multisig",
+            )));
+        }
+        let multisig = MultisigScript::new(kind, required, pubkeys)?;
+        let descriptor = Descriptor::Multi {
+            kind: multisig.kind,
+            required: multisig.required,
+            keys: multisig
+                .pubkeys
+                .iter()
+                .map(|pubkey| DescriptorKey::public(*pubkey))
+                .collect(),
+            sorted: true,
+        };
+        Account::with_descriptors(
+            wif_private_key,
+            multisig.address().to_string(),
+            vec![descriptor],
+            None,
+            Some(multisig),
+        )
+    }
+
+    /// Devuelve un registro de una linea con las claves que permiten volver a crear la cuenta, para guardarlo
+    /// cifrado en el archivo de la wallet: "wif <wif> <address>", "hd <purpose> <indice> <tprv>",
+    /// "multisig <tipo> <firmas requeridas> <wif> <claves publicas separadas por comas>", o para las watch-only
+    /// "descriptor <descriptor>" o "watch xpub <purpose> <tpub>"
+    pub fn to_record(&self) -> String {
+        if self.is_watch_only() && self.hd_keychain.is_none() {
+            return format!("descriptor {}", self.descriptors[0]);
+        }
+        if let Some(keychain) = &self.hd_keychain {
+            return match keychain.account_tprv() {
+                Some(tprv) => format!(
+                    "hd {} {} {}",
+                    keychain.scheme.purpose(),
+                    keychain.account_index,
+                    tprv
+                ),
+                None => format!(
+                    "watch xpub {} {}",
+                    keychain.scheme.purpose(),
+                    keychain.account_tpub().unwrap_or_default()
+                ),
+            };
+        }
+        if let Some(multisig) = &self.multisig {
+            let pubkeys: Vec<String> = multisig
+                .pubkeys
+                .iter()
+                .map(|pubkey| bytes_to_hex_string(pubkey))
+                .collect();
+            return format!(
+                "multisig {} {} {} {}",
+                multisig.kind.name(),
+                multisig.required,
+                self.private_key,
+                pubkeys.join(",")
+            );
+        }
+        format!("wif {} {}", self.private_key, self.address)
+    }
+
+    /// Vuelve a crear la cuenta a partir del registro devuelto por to_record.
+    /// Devuelve error si el registro es invalido
+    pub fn from_record(record: &str) -> Result<Account, Box<dyn Error>> {
+        let fields: Vec<&str> = record.split(' ').collect();
+        match fields.as_slice() {
+            ["wif", wif_private_key, address] => {
+                Account::new(wif_private_key.to_string(), address.to_string())
+            }
+            ["hd", purpose, account_index, tprv] => {
+                let scheme = scheme_from_purpose(purpose).ok_or_else(|| invalid_record(record))?;
+                let keychain = HdKeychain::from_account_tprv(tprv, scheme, account_index.parse()?)?;
+                Account::from_keychain(keychain)
+            }
+            ["descriptor", descriptor] => {
+                Account::new_watch_only_descriptor(Descriptor::parse(descriptor)?)
+            }
+            ["watch", "script", script] => {
+                Account::new_watch_only_script(hex_string_to_bytes(script)?)
+            }
+            ["watch", "xpub", purpose, tpub] => {
+                let scheme = scheme_from_purpose(purpose).ok_or_else(|| invalid_record(record))?;
+                Account::new_watch_only_xpub(tpub, scheme)
+            }
+            ["multisig", kind, required, wif_private_key, pubkeys] => {
+                let pubkeys = pubkeys
+                    .split(',')
+                    .map(|pubkey| {
+                        hex_string_to_bytes(pubkey)?
+                            .try_into()
+                            .map_err(|_| invalid_record(record))
+                    })
+                    .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+                Account::new_multisig(
+                    wif_private_key.to_string(),
+                    MultisigKind::parse(kind)?,
+                    required.parse()?,
+                    pubkeys,
+                )
+            }
+            _ => Err(invalid_record(record)),
+        }
     }
 
     /// Devuelve la clave publica comprimida (33 bytes) a partir de la privada
@@ -50,15 +460,234 @@ impl Account {
     pub fn get_address(&self) -> &String {
         &self.address
     }
+
+    /// Devuelve una direccion para recibir fondos, derivada del descriptor de recepcion. Si la cuenta es HD
+    /// es una direccion nueva sin usar, sino es la primera direccion del descriptor
+    pub fn next_receive_address(&mut self) -> Result<String, Box<dyn Error>> {
+        let index = match self.hd_keychain.as_mut() {
+            Some(keychain) => keychain.reserve_receive_index()?,
+            None => 0,
+        };
+        self.derive_scripts()?;
+        self.descriptors[0].address(index)
+    }
+
+    /// Devuelve la direccion a la que se envia el cambio de una transaccion. Si la cuenta es HD es una
+    /// direccion nueva del descriptor de cambio, sino es la primera direccion del descriptor de la cuenta
+    fn next_change_address(&mut self) -> Result<String, Box<dyn Error>> {
+        let (descriptor, index) = match self.hd_keychain.as_mut() {
+            Some(keychain) => (1, keychain.reserve_change_index()?),
+            None => (0, 0),
+        };
+        self.derive_scripts()?;
+        self.descriptors[descriptor].address(index)
+    }
+
+    /// Restaura los indices de las proximas direcciones de recepcion y de cambio a entregar si la cuenta es HD,
+    /// derivando los scripts necesarios para el gap limit
+    pub fn restore_next_indexes(
+        &mut self,
+        next_receive_index: usize,
+        next_change_index: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(keychain) = self.hd_keychain.as_mut() {
+            keychain.restore_next_indexes(next_receive_index, next_change_index)?;
+        }
+        self.derive_scripts()
+    }
+
+    /// Devuelve la private key y la clave publica comprimida con las que se firma un input
+    /// que gasta el pubkey script recibido. Devuelve error si la cuenta no tiene la clave
+    pub fn signing_keys(
+        &self,
+        pubkey_script: &[u8],
+    ) -> Result<([u8; 32], [u8; 33]), Box<dyn Error>> {
+        self.ensure_can_sign()?;
+        match &self.hd_keychain {
+            Some(keychain) => match keychain
+                .key_for_script(pubkey_script)
+                .and_then(|key| Some((key.private_key?, key.public_key)))
+            {
+                Some(keys) => Ok(keys),
+                None => Err(Box::new(std::io::Error::other(
+                    "La cuenta no tiene la clave del output a gastar",
+                ))),
+            },
+            None => Ok((self.get_private_key()?, self.get_pubkey_compressed()?)),
+        }
+    }
+
+    /// Borra de memoria las claves privadas de la cuenta pisando sus bytes, al bloquear la wallet. Mientras tanto
+    /// la cuenta queda como watch-only
+    pub fn erase_private_keys(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut private_key = std::mem::take(&mut self.private_key).into_bytes();
+        erase_bytes(&mut private_key);
+        match self.hd_keychain.as_mut() {
+            Some(keychain) => keychain.erase_private_keys(),
+            None => Ok(()),
+        }
+    }
+
+    /// Vuelve a cargar las claves privadas de la cuenta recibida, leida del registro guardado en la wallet,
+    /// al desbloquearla. Devuelve error si no es la misma cuenta
+    pub fn restore_private_keys(&mut self, keys: &Account) -> Result<(), Box<dyn Error>> {
+        if keys.address != self.address {
+            return Err(Box::new(std::io::Error::other(
+                "Las claves privadas son de otra cuenta",
+            )));
+        }
+        if let (Some(keychain), Some(keys_keychain)) =
+            (self.hd_keychain.as_mut(), keys.hd_keychain.as_ref())
+        {
+            keychain.restore_private_keys(keys_keychain)?;
+        }
+        self.private_key = keys.private_key.clone();
+        Ok(())
+    }
+
+    /// Devuelve la private key y la clave publica comprimida de cada clave de la cuenta:
+    /// las derivadas si es HD, o la unica clave de la cuenta si no. Las cuentas watch-only no tienen claves
+    pub fn keys(&self) -> Vec<([u8; 32], [u8; 33])> {
+        match &self.hd_keychain {
+            Some(keychain) => keychain
+                .keys()
+                .filter_map(|key| Some((key.private_key?, key.public_key)))
+                .collect(),
+            None => match (self.get_private_key(), self.get_pubkey_compressed()) {
+                (Ok(private_key), Ok(public_key)) => vec![(private_key, public_key)],
+                _ => vec![],
+            },
+        }
+    }
+
+    /// Devuelve el multisig de la cuenta si el pubkey script recibido le paga
+    pub fn multisig_for_script(&self, pubkey_script: &[u8]) -> Option<&MultisigScript> {
+        self.multisig
+            .as_ref()
+            .filter(|multisig| multisig.script_pubkey() == pubkey_script)
+    }
+
+    /// Devuelve true si el output paga a alguno de los scripts derivados de los descriptors de la cuenta
+    pub fn owns_output(&self, tx_out: &TxOut) -> bool {
+        self.scripts.contains(tx_out.get_pub_key_script())
+    }
+    /// Devuelve los pub key scripts que pagan a la cuenta, derivados de sus descriptors
+    pub fn pubkey_scripts(&self) -> HashSet<Vec<u8>> {
+        self.scripts.clone()
+    }
     /// Guarda los utxos en la cuenta
     pub fn load_utxos(&mut self, utxos: Vec<UtxoTuple>) {
         self.utxo_set = utxos;
     }
 
-    /// Compara el monto recibido con el balance de la cuenta.
-    /// Devuelve true si el balance es mayor. Caso contrario false
-    pub fn has_balance(&self, value: i64) -> bool {
-        self.balance() > value
+    /// Compara el monto recibido con lo que se puede gastar segun las reglas recibidas, contando las monedas
+    /// bloqueadas porque se pueden elegir a mano. Devuelve true si alcanza para pagarlo. Caso contrario false
+    pub fn has_balance(&self, value: i64, policy: &SpendPolicy) -> Result<bool, Box<dyn Error>> {
+        let spendable: i64 = self
+            .classified_coins(policy)?
+            .iter()
+            .filter(|coin| coin.spendable)
+            .map(|coin| coin.value())
+            .sum();
+        Ok(spendable >= value)
+    }
+
+    /// Devuelve el balance de la cuenta separado en confirmado, pendiente de recibir, cambio pendiente,
+    /// coinbase inmaduro y bloqueado, segun el minimo de confirmaciones y la altura de las reglas recibidas
+    pub fn balance_breakdown(
+        &self,
+        policy: &SpendPolicy,
+    ) -> Result<BalanceBreakdown, Box<dyn Error>> {
+        Ok(BalanceBreakdown::from_coins(
+            &self.classified_coins(policy)?,
+        ))
+    }
+
+    /// Devuelve las monedas de la cuenta con la parte del balance en la que cae cada una y si las reglas
+    /// recibidas permiten gastarla. Son las utxos confirmadas y los outputs de la cuenta de las transacciones
+    /// pendientes, sin las que ya gasta alguna pendiente. Las pendientes que quedan en conflicto con una
+    /// transaccion confirmada se sacan del ledger al registrarla, asi que no se cuentan. Es cambio si la
+    /// transaccion que la creo gasta monedas de la cuenta. Las congeladas y los coinbase inmaduros nunca se
+    /// pueden gastar
+    fn classified_coins(
+        &self,
+        policy: &SpendPolicy,
+    ) -> Result<Vec<ClassifiedCoin>, Box<dyn Error>> {
+        let ledger = self
+            .ledger
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let coin_control = self
+            .coin_control
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let pending: Vec<&LedgerEntry> = ledger
+            .entries()
+            .iter()
+            .filter(|entry| entry.block.is_none())
+            .collect();
+        let spent_by_pending: HashSet<CoinId> = pending
+            .iter()
+            .flat_map(|entry| entry.transaction.tx_in.iter())
+            .map(|txin| {
+                (
+                    txin.get_previous_output_hash(),
+                    txin.get_previous_output_index(),
+                )
+            })
+            .collect();
+        let own_transactions: HashSet<[u8; 32]> = ledger
+            .entries()
+            .iter()
+            .filter(|entry| entry.spent > 0)
+            .map(|entry| entry.txid())
+            .collect();
+        let unconfirmed = pending.iter().map(|entry| {
+            let outputs = entry
+                .transaction
+                .tx_out
+                .iter()
+                .enumerate()
+                .filter(|(_, tx_out)| self.owns_output(tx_out))
+                .map(|(index, tx_out)| (tx_out.clone(), index))
+                .collect();
+            (UtxoTuple::new(entry.txid(), outputs), false)
+        });
+        let confirmed = self.utxo_set.iter().map(|utxo| (utxo.clone(), true));
+        let mut coins = Vec::new();
+        for (utxo, in_chain) in confirmed.chain(unconfirmed) {
+            let confirmations = utxo.confirmations(policy.tip_height);
+            for (tx_out, index) in &utxo.utxo_set {
+                let id = (utxo.hash, *index);
+                if spent_by_pending.contains(&id) {
+                    continue;
+                }
+                let frozen = coin_control.is_frozen(&id);
+                let immature = utxo.coinbase && confirmations < COINBASE_MATURITY;
+                let category = if frozen || coin_control.is_locked(&id) {
+                    CoinCategory::Locked
+                } else if immature {
+                    CoinCategory::Immature
+                } else if in_chain && confirmations >= policy.min_confirmations {
+                    CoinCategory::Confirmed
+                } else if own_transactions.contains(&utxo.hash) {
+                    CoinCategory::UnconfirmedChange
+                } else {
+                    CoinCategory::UnconfirmedIncoming
+                };
+                coins.push(ClassifiedCoin {
+                    utxo: UtxoTuple {
+                        hash: utxo.hash,
+                        utxo_set: vec![(tx_out.clone(), *index)],
+                        height: utxo.height,
+                        coinbase: utxo.coinbase,
+                    },
+                    category,
+                    spendable: !frozen && !immature && confirmations >= policy.min_confirmations,
+                });
+            }
+        }
+        Ok(coins)
     }
 
     /// Devuelve el balance de la cuenta
@@ -69,29 +698,124 @@ impl Account {
         }
         balance
     }
-    /// Devuelve un vector con las utxos a ser gastadas en una transaccion nueva, según el monto recibido.
-    fn get_utxos_for_amount(&mut self, value: i64) -> Vec<UtxoTuple> {
-        let mut utxos_to_spend = Vec::new();
-        let mut partial_amount: i64 = 0;
-        let mut position: usize = 0;
-        let lenght: usize = self.utxo_set.len();
-        while position < lenght {
-            if (partial_amount + self.utxo_set[position].balance()) < value {
-                partial_amount += self.utxo_set[position].balance();
-                utxos_to_spend.push(self.utxo_set[position].clone());
-                // No corresponde removerlas mientras la tx no está confirmada
-            } else {
-                utxos_to_spend
-                    .push(self.utxo_set[position].utxos_to_spend(value, &mut partial_amount));
-                break;
+    /// Devuelve las monedas de la cuenta con su monto, direccion, confirmaciones segun la altura del ultimo
+    /// bloque recibida y si estan bloqueadas o congeladas, de la de mayor a la de menor monto
+    pub fn coins(&self, tip_height: u32) -> Result<Vec<CoinInfo>, Box<dyn Error>> {
+        let coin_control = self
+            .coin_control
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let mut coins: Vec<CoinInfo> = self
+            .utxo_set
+            .iter()
+            .flat_map(|utxo| {
+                utxo.utxo_set.iter().map(|(tx_out, index)| {
+                    let id = (utxo.hash, *index);
+                    let script = tx_out.get_pub_key_script();
+                    CoinInfo {
+                        id,
+                        value: tx_out.value(),
+                        address: Address::from_script_pubkey(script)
+                            .map(|address| address.to_string())
+                            .unwrap_or_else(|| bytes_to_hex_string(script)),
+                        confirmations: utxo.confirmations(tip_height),
+                        locked: coin_control.is_locked(&id),
+                        frozen: coin_control.is_frozen(&id),
+                    }
+                })
+            })
+            .collect();
+        coins.sort_by(|a, b| b.value.cmp(&a.value).then(a.id.cmp(&b.id)));
+        Ok(coins)
+    }
+
+    /// Devuelve el primer pubkey script del descriptor de la cuenta
+    fn own_script(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.descriptors[0].script_pubkey(0)
+    }
+
+    /// Elige las utxos a gastar para pagar el monto a los pubkey scripts recibidos con el fee rate en sat/vB,
+    /// teniendo en cuenta lo que cuesta gastar cada input y evitando crear un cambio que sea dust.
+    /// Solo usa las monedas que permiten las reglas recibidas. Si se reciben monedas elegidas a mano se
+    /// gastan todas ellas, sino se eligen entre las que no estan bloqueadas ni congeladas.
+    /// Devuelve error si las utxos no alcanzan o alguna elegida no se puede gastar
+    fn select_coins(
+        &self,
+        amount: i64,
+        receiver_scripts: &[Vec<u8>],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+        policy: &SpendPolicy,
+    ) -> Result<Selection, Box<dyn Error>> {
+        let own_script = self.own_script()?;
+        let outputs_vsize: usize = receiver_scripts
+            .iter()
+            .map(|script| output_vsize(script))
+            .sum();
+        let params = CoinSelectionParams {
+            amount,
+            fixed_fee: fee_for_vsize(TRANSACTION_OVERHEAD_VSIZE + outputs_vsize, fee_rate),
+            fee_rate,
+            change_output_vsize: output_vsize(&own_script),
+            change_spend_vsize: input_vsize(&own_script, self.multisig.as_ref()),
+        };
+        let classified = self.classified_coins(policy)?;
+        let spendable: Vec<UtxoTuple> = classified
+            .iter()
+            .filter(|coin| coin.spendable)
+            .map(|coin| coin.utxo.clone())
+            .collect();
+        let coins = coins_from_utxos(&spendable, self.multisig.as_ref());
+        let coin_control = self
+            .coin_control
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        if chosen_coins.is_empty() {
+            let available: Vec<Coin> = coins
+                .into_iter()
+                .filter(|coin| {
+                    !coin_control.is_locked(&coin.id()) && !coin_control.is_frozen(&coin.id())
+                })
+                .collect();
+            return select_coins(&available, &params);
+        }
+        let mut selected: Vec<Coin> = Vec::new();
+        for coin_id in chosen_coins {
+            if coin_control.is_frozen(coin_id) {
+                return Err(Box::new(std::io::Error::other(format!(
+                    "La moneda {} esta congelada",
+                    format_coin_id(coin_id)
+                ))));
+            }
+            let coin = coins
+                .iter()
+                .find(|coin| coin.id() == *coin_id)
+                .ok_or_else(|| {
+                    let known = classified
+                        .iter()
+                        .any(|coin| coin.utxo.find_tx_out(coin_id.0, coin_id.1).is_some());
+                    std::io::Error::other(if known {
+                        format!(
+                            "La moneda {} no tiene las confirmaciones necesarias para gastarse",
+                            format_coin_id(coin_id)
+                        )
+                    } else {
+                        format!(
+                            "La moneda {} no es una utxo de la cuenta",
+                            format_coin_id(coin_id)
+                        )
+                    })
+                })?;
+            if !selected.contains(coin) {
+                selected.push(coin.clone());
             }
-            position += 1;
         }
-        utxos_to_spend
+        use_all_coins(&selected, &params)
     }
 
-    /// Agrega la transacción a la lista de transacciones pendientes.
-    fn add_transaction(&self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+    /// Agrega la transacción a la lista de transacciones pendientes y al ledger.
+    pub fn add_transaction(&self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        self.record_in_ledger(&transaction, None)?;
         let mut aux = self
             .pending_transactions
             .write()
@@ -99,100 +823,609 @@ impl Account {
         aux.push(transaction);
         Ok(())
     }
-    /// Realiza la transaccion con el monto recibido, devuelve el hash de dicha transaccion
-    /// para que el nodo envie dicho hash a lo restantes nodos de la red
+
+    /// Agrega al ledger la transaccion si recibe en alguna direccion de la cuenta o gasta alguna de sus monedas,
+    /// con el bloque en el que se confirmo o None si esta pendiente. Las monedas que gasta se buscan en las
+    /// utxos de la cuenta y en las transacciones del ledger, por lo que hay que registrarla antes de sacar del
+    /// utxo set las monedas que gasta. Si esta confirmada, antes saca las transacciones pendientes que gastan
+    /// las mismas monedas, aunque no afecte a la cuenta. Devuelve true si la transaccion afecta a la cuenta
+    pub fn record_in_ledger(
+        &self,
+        transaction: &Transaction,
+        block: Option<BlockPosition>,
+    ) -> Result<bool, Box<dyn Error>> {
+        if block.is_some() {
+            self.remove_conflicting_pending(transaction)?;
+        }
+        let mut ledger = self
+            .ledger
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let received: i64 = transaction
+            .tx_out
+            .iter()
+            .filter(|tx_out| self.owns_output(tx_out))
+            .map(|tx_out| tx_out.value())
+            .sum();
+        let input_values: Vec<Option<i64>> = transaction
+            .tx_in
+            .iter()
+            .map(|txin| {
+                self.own_output_value(
+                    &ledger,
+                    txin.get_previous_output_hash(),
+                    txin.get_previous_output_index(),
+                )
+            })
+            .collect();
+        let spent: i64 = input_values.iter().flatten().sum();
+        if received == 0 && spent == 0 {
+            return Ok(false);
+        }
+        // la comision solo se conoce si todos los outputs que gasta son de la cuenta
+        let fee = input_values
+            .into_iter()
+            .sum::<Option<i64>>()
+            .map(|inputs| inputs - transaction.amount());
+        ledger.record(LedgerEntry {
+            transaction: transaction.clone(),
+            block,
+            received,
+            spent,
+            fee,
+        });
+        Ok(true)
+    }
+
+    /// Saca del ledger y de las transacciones pendientes las que quedaron en conflicto con la transaccion
+    /// confirmada recibida, porque gastan alguna de sus monedas o dependen de una que lo hace
+    fn remove_conflicting_pending(&self, confirmed: &Transaction) -> Result<(), Box<dyn Error>> {
+        let removed = self
+            .ledger
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .remove_conflicts(confirmed);
+        if !removed.is_empty() {
+            self.pending_transactions
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .retain(|transaction| !removed.contains(&transaction.hash()));
+        }
+        Ok(())
+    }
+
+    /// Agrega al ledger las transacciones del bloque que afectan a la cuenta
+    pub fn record_block(&self, block: &Block) -> Result<(), Box<dyn Error>> {
+        let position = BlockPosition {
+            hash: block.hash(),
+            height: block.get_height(),
+        };
+        for transaction in &block.txn {
+            self.record_in_ledger(transaction, Some(position))?;
+        }
+        Ok(())
+    }
+
+    /// Devuelve el valor del output recibido si es de la cuenta: si esta entre sus utxos o es un output
+    /// de la cuenta de alguna transaccion del ledger
+    fn own_output_value(
+        &self,
+        ledger: &Ledger,
+        previous_hash: [u8; 32],
+        previous_index: usize,
+    ) -> Option<i64> {
+        self.utxo_set
+            .iter()
+            .find_map(|utxos| utxos.find_tx_out(previous_hash, previous_index))
+            .or_else(|| {
+                ledger
+                    .output(previous_hash, previous_index)
+                    .filter(|tx_out| self.owns_output(tx_out))
+            })
+            .map(|tx_out| tx_out.value())
+    }
+    /// Realiza la transaccion que paga a los receptores recibidos con el fee rate en sat/vB recibido, gastando
+    /// las monedas elegidas a mano o, si no se elige ninguna, las que elige la seleccion automatica, siempre
+    /// entre las que permiten las reglas recibidas. Devuelve la transaccion para que el nodo envie su hash a
+    /// lo restantes nodos de la red
     pub fn make_transaction(
         &mut self,
-        address_receiver: &str,
-        amount: i64,
-        fee: i64,
+        recipients: &[Recipient],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+        policy: &SpendPolicy,
     ) -> Result<Transaction, Box<dyn Error>> {
-        address_decoder::validate_address(address_receiver)?;
-        if !self.has_balance(amount + fee) {
+        self.ensure_can_sign()?;
+        let (mut unsigned_transaction, utxos_to_spend) =
+            self.generate_unsigned_transaction(recipients, fee_rate, chosen_coins, policy)?;
+        unsigned_transaction.sign(self, &utxos_to_spend)?;
+        if self.multisig.is_some() && !self.is_fully_signed(&unsigned_transaction) {
+            // faltan las firmas de los demas firmantes, se agrega a las pendientes cuando se completen
+            return Ok(unsigned_transaction);
+        }
+        // el mensaje cifrado creo que no hace falta chequearlo
+        unsigned_transaction.validate(&utxos_to_spend)?;
+
+        self.add_transaction(unsigned_transaction.clone())?;
+        Ok(unsigned_transaction)
+    }
+
+    /// Crea el PSBT de una transaccion que paga a los receptores recibidos con el fee rate recibido, sin firmar,
+    /// para que la firmen esta u otras wallets antes de finalizarla y hacer el broadcast.
+    /// Gasta las monedas elegidas a mano o, si no se elige ninguna, las de la seleccion automatica, entre las
+    /// que permiten las reglas recibidas
+    pub fn create_psbt(
+        &mut self,
+        recipients: &[Recipient],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+        policy: &SpendPolicy,
+    ) -> Result<Psbt, Box<dyn Error>> {
+        let (unsigned_transaction, utxos_to_spend) =
+            self.generate_unsigned_transaction(recipients, fee_rate, chosen_coins, policy)?;
+        Psbt::from_unsigned_transaction(
+            unsigned_transaction,
+            &utxos_to_spend,
+            self.multisig.as_ref(),
+        )
+    }
+
+    /// Reemplaza la transaccion pendiente con el txid recibido por otra que gasta los mismos inputs
+    /// y paga el fee rate en sat/vB recibido, descontando la diferencia del cambio (BIP125).
+    /// La comision nueva supera a la original en al menos el minimo de relay por el tamaño de la
+    /// transaccion, como exigen los nodos para aceptar el reemplazo. Si el cambio queda en dust se
+    /// elimina y pasa a la comision. Devuelve la transaccion firmada y su comision
+    pub fn bump_fee(
+        &mut self,
+        txid: [u8; 32],
+        fee_rate: f64,
+    ) -> Result<(Transaction, i64), Box<dyn Error>> {
+        self.ensure_can_sign()?;
+        if self.multisig.is_some() {
+            return Err(Box::new(std::io::Error::other(
+                "No se puede aumentar el fee de una transaccion de una cuenta multisig",
+            )));
+        }
+        let original = self.find_pending_transaction(txid)?;
+        if !original.signals_rbf() {
+            return Err(Box::new(std::io::Error::other(
+                "La transaccion no señala que se pueda reemplazar (BIP125)",
+            )));
+        }
+        let previous_outputs = self.previous_outputs(&original)?;
+        let old_fee = self.spent_amount(&original) - original.amount();
+        let vsize = TRANSACTION_OVERHEAD_VSIZE
+            + previous_outputs
+                .iter()
+                .map(|tx_out| input_vsize(tx_out.get_pub_key_script(), None))
+                .sum::<usize>()
+            + original
+                .tx_out
+                .iter()
+                .map(|tx_out| output_vsize(tx_out.get_pub_key_script()))
+                .sum::<usize>();
+        let new_fee =
+            fee_for_vsize(vsize, fee_rate).max(old_fee + fee_for_vsize(vsize, MIN_RELAY_FEE_RATE));
+
+        let own_scripts = self.pubkey_scripts();
+        let mut tx_outs = original.tx_out.clone();
+        let change_index = tx_outs
+            .iter()
+            .rposition(|tx_out| own_scripts.contains(tx_out.get_pub_key_script()))
+            .ok_or_else(|| {
+                std::io::Error::other("La transaccion no tiene un cambio del cual pagar el fee")
+            })?;
+        let change_script = tx_outs[change_index].get_pub_key_script().clone();
+        let change_amount = tx_outs[change_index].value() - (new_fee - old_fee);
+        if change_amount < 0 {
+            return Err(Box::new(std::io::Error::other(format!(
+                "El cambio de la transaccion no alcanza para pagar {} satoshis de comision",
+                new_fee
+            ))));
+        }
+        if change_amount
+            < dust_threshold(
+                output_vsize(&change_script),
+                input_vsize(&change_script, None),
+            )
+        {
+            tx_outs.remove(change_index);
+        } else {
+            tx_outs[change_index] = TxOut::new(
+                change_amount,
+                CompactSizeUint::new(change_script.len() as u128),
+                change_script,
+            );
+        }
+
+        let mut replacement = original.replacement(tx_outs);
+        replacement.sign(self, &self.utxo_set)?;
+        replacement.validate(&self.utxo_set)?;
+        let mut pending_transactions = self
+            .pending_transactions
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        pending_transactions.retain(|transaction| transaction.hash() != txid);
+        pending_transactions.push(replacement.clone());
+        self.ledger
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .remove(txid);
+        self.record_in_ledger(&replacement, None)?;
+        let fee = self.spent_amount(&replacement) - replacement.amount();
+        Ok((replacement, fee))
+    }
+
+    /// Crea una transaccion hija que gasta los outputs de la cuenta de la transaccion pendiente recibida,
+    /// para que el paquete de ambas pague el fee rate en sat/vB recibido (child pays for parent).
+    /// Recibe la comision que paga la transaccion padre. La hija envia todo a una direccion de cambio
+    /// de la cuenta y se agrega a las pendientes. Devuelve la transaccion hija firmada y su comision
+    pub fn make_cpfp_transaction(
+        &mut self,
+        parent: &Transaction,
+        parent_fee: i64,
+        fee_rate: f64,
+    ) -> Result<(Transaction, i64), Box<dyn Error>> {
+        self.ensure_can_sign()?;
+        if self.multisig.is_some() {
+            return Err(Box::new(std::io::Error::other(
+                "No se puede acelerar una transaccion con una cuenta multisig",
+            )));
+        }
+        if parent_fee as f64 / parent.vsize() as f64 >= fee_rate {
+            return Err(Box::new(std::io::Error::other(format!(
+                "La transaccion ya paga al menos {} sat/vB",
+                fee_rate
+            ))));
+        }
+        let owned_outputs: Vec<(TxOut, usize)> = parent
+            .tx_out
+            .iter()
+            .enumerate()
+            .filter(|(_, tx_out)| self.owns_output(tx_out))
+            .map(|(index, tx_out)| (tx_out.clone(), index))
+            .collect();
+        if owned_outputs.is_empty() {
+            return Err(Box::new(std::io::Error::other(
+                "La transaccion no tiene outputs de la cuenta para gastar",
+            )));
+        }
+        let input_balance: i64 = owned_outputs.iter().map(|(tx_out, _)| tx_out.value()).sum();
+        let child_vsize = TRANSACTION_OVERHEAD_VSIZE
+            + owned_outputs
+                .iter()
+                .map(|(tx_out, _)| input_vsize(tx_out.get_pub_key_script(), None))
+                .sum::<usize>();
+        let utxos_to_spend = vec![UtxoTuple::new(parent.hash(), owned_outputs)];
+
+        let change_address = self.next_change_address()?;
+        let change_script = Address::parse(&change_address)?.script_pubkey();
+        let child_vsize = child_vsize + output_vsize(&change_script);
+        let package_fee = fee_for_vsize(parent.vsize() + child_vsize, fee_rate);
+        let child_fee =
+            (package_fee - parent_fee).max(fee_for_vsize(child_vsize, MIN_RELAY_FEE_RATE));
+        let value = input_balance - child_fee;
+        if value
+            < dust_threshold(
+                output_vsize(&change_script),
+                input_vsize(&change_script, None),
+            )
+        {
+            return Err(Box::new(std::io::Error::other(format!(
+                "Los outputs de la cuenta no alcanzan para pagar {} satoshis de comision",
+                child_fee
+            ))));
+        }
+        let mut child = Transaction::generate_unsigned_transaction(
+            &[Recipient::new(change_address.clone(), value, false)],
+            &change_address,
+            child_fee,
+            &utxos_to_spend,
+        )?;
+        child.sign(self, &utxos_to_spend)?;
+        child.validate(&utxos_to_spend)?;
+        self.add_transaction(child.clone())?;
+        Ok((child, child_fee))
+    }
+
+    /// Devuelve la transaccion pendiente de la cuenta con el txid recibido o error si no esta
+    pub fn find_pending_transaction(&self, txid: [u8; 32]) -> Result<Transaction, Box<dyn Error>> {
+        self.pending_transactions
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter()
+            .find(|transaction| transaction.hash() == txid)
+            .cloned()
+            .ok_or_else(|| {
+                Box::new(std::io::Error::other(
+                    "La transaccion no esta entre las pendientes de la cuenta",
+                )) as Box<dyn Error>
+            })
+    }
+
+    /// Devuelve los outputs que gastan los inputs de la transaccion.
+    /// Devuelve error si alguno no es una utxo de la cuenta
+    fn previous_outputs(&self, transaction: &Transaction) -> Result<Vec<TxOut>, Box<dyn Error>> {
+        transaction
+            .tx_in
+            .iter()
+            .map(|txin| {
+                self.utxo_set
+                    .iter()
+                    .find_map(|utxos| {
+                        utxos.find_tx_out(
+                            txin.get_previous_output_hash(),
+                            txin.get_previous_output_index(),
+                        )
+                    })
+                    .cloned()
+                    .ok_or_else(|| {
+                        Box::new(std::io::Error::other(
+                            "La transaccion gasta outputs que ya no son utxos de la cuenta",
+                        )) as Box<dyn Error>
+                    })
+            })
+            .collect()
+    }
+
+    /// Genera la transaccion sin firmar que paga a los receptores recibidos, pagando el fee rate en sat/vB segun el
+    /// tamaño que tendra firmada. Si algun receptor descuenta la comision de su monto, la comision se reparte entre
+    /// ellos en lugar de pagarse con el cambio. Devuelve la transaccion y las utxos que gasta.
+    /// Devuelve error si alguna address es invalida o la cuenta no tiene balance suficiente
+    fn generate_unsigned_transaction(
+        &mut self,
+        recipients: &[Recipient],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+        policy: &SpendPolicy,
+    ) -> Result<(Transaction, Vec<UtxoTuple>), Box<dyn Error>> {
+        let mut receiver_scripts = Vec::new();
+        for recipient in recipients {
+            address_decoder::validate_address(&recipient.address)?;
+            receiver_scripts.push(Address::parse(&recipient.address)?.script_pubkey());
+        }
+        let amount = total_amount(recipients);
+        if !self.has_balance(amount, policy)? {
             return Err(Box::new(std::io::Error::new(
                 io::ErrorKind::Other,
                 format!(
-                    "El balance de la cuenta {} tiene menos de {} satoshis",
-                    self.address,
-                    amount + fee,
+                    "El balance que se puede gastar de la cuenta {} tiene menos de {} satoshis",
+                    self.address, amount,
                 ),
             )));
         }
         // Sabemos que tenemos monto para realizar la transaccion , ahora debemos obtener las utxos
-        // que utilizaremos para gastar
-        let utxos_to_spend: Vec<UtxoTuple> = self.get_utxos_for_amount(amount + fee);
-        let change_address: &str = self.address.as_str();
-        let mut unsigned_transaction = Transaction::generate_unsigned_transaction(
-            address_receiver,
-            change_address,
-            amount,
+        // que utilizaremos para gastar, incluyendo lo que cuesta gastarlas
+        let subtract_fee = recipients.iter().any(|recipient| recipient.subtract_fee);
+        let (recipients, fee, utxos_to_spend) = if subtract_fee {
+            // la comision sale de los montos, asi que las utxos solo tienen que cubrir los pagos
+            let selection =
+                self.select_coins(amount, &receiver_scripts, 0.0, chosen_coins, policy)?;
+            let fee = fee_for_vsize(
+                self.transaction_vsize(&selection, &receiver_scripts)?,
+                fee_rate,
+            );
+            let recipients =
+                subtract_fee_from_recipients(recipients, (fee - selection.fee).max(0))?;
+            (recipients, fee.max(selection.fee), selection.utxos())
+        } else {
+            let selection =
+                self.select_coins(amount, &receiver_scripts, fee_rate, chosen_coins, policy)?;
+            (recipients.to_vec(), selection.fee, selection.utxos())
+        };
+        let change_address = self.next_change_address()?;
+        let unsigned_transaction = Transaction::generate_unsigned_transaction(
+            &recipients,
+            &change_address,
             fee,
             &utxos_to_spend,
         )?;
-        unsigned_transaction.sign(self, &utxos_to_spend)?;
-        // el mensaje cifrado creo que no hace falta chequearlo
-        unsigned_transaction.validate(&utxos_to_spend)?;
+        Ok((unsigned_transaction, utxos_to_spend))
+    }
 
-        self.add_transaction(unsigned_transaction.clone())?;
-        Ok(unsigned_transaction)
+    /// Devuelve el tamaño en vbytes que tendra firmada la transaccion que gasta las monedas de la seleccion
+    /// y paga a los pubkey scripts recibidos, con el output de cambio si la seleccion lo tiene
+    fn transaction_vsize(
+        &self,
+        selection: &Selection,
+        receiver_scripts: &[Vec<u8>],
+    ) -> Result<usize, Box<dyn Error>> {
+        let own_script = self.own_script()?;
+        let change_vsize = if selection.change > 0 {
+            output_vsize(&own_script)
+        } else {
+            0
+        };
+        Ok(TRANSACTION_OVERHEAD_VSIZE
+            + selection
+                .coins
+                .iter()
+                .map(|coin| coin.input_vsize)
+                .sum::<usize>()
+            + receiver_scripts
+                .iter()
+                .map(|script| output_vsize(script))
+                .sum::<usize>()
+            + change_vsize)
     }
 
-    /// Recibe el utxo_set, lo recorre y setea el utxo_set de la cuenta.
+    /// Agrega la firma de la cuenta multisig a la transaccion parcialmente firmada por otros firmantes.
+    /// Si con esa firma la transaccion tiene las firmas requeridas, la agrega a las pendientes.
+    /// Devuelve error si la cuenta no es multisig o la transaccion gasta outputs que no son de la cuenta
+    pub fn sign_multisig_transaction(
+        &self,
+        mut transaction: Transaction,
+    ) -> Result<Transaction, Box<dyn Error>> {
+        if self.multisig.is_none() {
+            return Err(Box::new(std::io::Error::other(
+                "La cuenta seleccionada no es multisig",
+            )));
+        }
+        self.ensure_can_sign()?;
+        transaction.sign(self, &self.utxo_set)?;
+        if self.is_fully_signed(&transaction) {
+            self.add_transaction(transaction.clone())?;
+        }
+        Ok(transaction)
+    }
+
+    /// Devuelve true si todos los inputs de la transaccion gastan utxos de la cuenta y tienen
+    /// todas las firmas necesarias
+    pub fn is_fully_signed(&self, transaction: &Transaction) -> bool {
+        transaction.validate(&self.utxo_set).is_ok()
+    }
+
+    /// Devuelve la suma de los outputs de la cuenta que gasta la transaccion
+    pub fn spent_amount(&self, transaction: &Transaction) -> i64 {
+        transaction
+            .tx_in
+            .iter()
+            .filter_map(|txin| {
+                self.utxo_set.iter().find_map(|utxos| {
+                    utxos.find_tx_out(
+                        txin.get_previous_output_hash(),
+                        txin.get_previous_output_index(),
+                    )
+                })
+            })
+            .map(|tx_out| tx_out.value())
+            .sum()
+    }
+
+    /// Recibe el utxo_set, lo recorre y setea el utxo_set de la cuenta. Tambien agrega las monedas sin gastar del
+    /// ledger que no estan en el utxo_set, porque se confirmaron en bloques anteriores a la fecha de inicio del
+    /// proyecto y solo las encontro un rescan: quedan en las utxos de la cuenta sin tocar el utxo_set del nodo.
+    /// Si la cuenta es HD, cada vez que encuentra fondos en una direccion deriva nuevas claves
+    /// para mantener el gap limit y vuelve a recorrerlo, hasta no encontrar fondos en claves nuevas
     pub fn set_utxos(
         &mut self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
     ) -> Result<(), Box<dyn Error>> {
-        let mut account_utxo_set: Vec<UtxoTuple> = Vec::new();
-        for utxo in utxo_set
+        let utxo_set = utxo_set
             .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        loop {
+            let account_utxo_set = self.with_ledger_coins(self.referenced_utxos(&utxo_set))?;
+            if let Some(keychain) = self.hd_keychain.as_mut() {
+                let used_scripts: HashSet<Vec<u8>> = account_utxo_set
+                    .iter()
+                    .flat_map(|utxo| utxo.utxo_set.iter())
+                    .map(|(tx_out, _)| tx_out.get_pub_key_script().clone())
+                    .collect();
+                if keychain.mark_used(&used_scripts)? {
+                    self.derive_scripts()?;
+                    continue;
+                }
+            }
+            self.utxo_set = account_utxo_set;
+            return Ok(());
+        }
+    }
+
+    /// Combina el ledger armado por un rescan con el de la cuenta, sin sacar las transacciones que se registraron
+    /// mientras se escaneaba, y vuelve a cargar sus utxos sin tocar el utxo_set del nodo
+    pub fn merge_rescan(
+        &mut self,
+        scanned_ledger: &Ledger,
+        utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.ledger
+            .write()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .values()
-        {
-            let aux_utxo = utxo.referenced_utxos(&self.address);
+            .merge(scanned_ledger);
+        self.set_utxos(utxo_set)
+    }
+
+    /// Devuelve las utxos del utxo_set que pagan a alguno de los scripts de la cuenta
+    fn referenced_utxos(&self, utxo_set: &HashMap<[u8; 32], UtxoTuple>) -> Vec<UtxoTuple> {
+        let mut account_utxo_set: Vec<UtxoTuple> = Vec::new();
+        for utxo in utxo_set.values() {
+            let aux_utxo = utxo.referenced_utxos(&self.scripts);
             let utxo_to_push = match aux_utxo {
                 Some(value) => value,
                 None => continue,
             };
             account_utxo_set.push(utxo_to_push);
         }
-        self.utxo_set = account_utxo_set;
-        Ok(())
+        account_utxo_set
     }
 
-    /// Devuelve las transacciones pendientes y las confirmadas de la cuenta
-    /// Devuelve una lista de tuplas con el estado, transaccion y monto enviado por la cuenta
-    pub fn get_transactions(&self) -> Result<Vec<TransactionInfo>, Box<dyn Error>> {
-        let mut transactions: Vec<(String, Transaction, i64)> = Vec::new();
-        // itero las pending tx
-        for tx in self
-            .pending_transactions
+    /// Agrega a las utxos recibidas las monedas sin gastar del ledger de la cuenta que no estaban
+    fn with_ledger_coins(&self, utxos: Vec<UtxoTuple>) -> Result<Vec<UtxoTuple>, Box<dyn Error>> {
+        let coins = self
+            .ledger
             .read()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .iter()
-        {
-            transactions.push((
-                "Pending".to_string(),
-                tx.clone(),
-                tx.amount_spent_by_account(&self.address)?,
-            ));
-        }
+            .unspent_outputs(|tx_out| self.owns_output(tx_out));
+        let mut utxos_by_hash: HashMap<[u8; 32], UtxoTuple> =
+            utxos.into_iter().map(|utxo| (utxo.hash, utxo)).collect();
+        add_coins_to_utxo_set(&mut utxos_by_hash, coins);
+        Ok(utxos_by_hash.into_values().collect())
+    }
 
-        for tx in self
-            .confirmed_transactions
+    /// Devuelve el historial de transacciones de la cuenta del ledger, con sus confirmaciones segun la
+    /// altura recibida del ultimo bloque de la cadena
+    pub fn get_transactions(&self, tip_height: u32) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        Ok(self
+            .ledger
             .read()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .iter()
-        {
-            transactions.push((
-                "Confirmed".to_string(),
-                tx.clone(),
-                tx.amount_spent_by_account(&self.address)?,
-            ));
+            .history(tip_height))
+    }
+}
+/// Descuenta la comision recibida de los montos de los receptores que la pagan, repartiendola en partes
+/// iguales (el resto lo paga el primero). Devuelve error si algun monto queda en dust
+fn subtract_fee_from_recipients(
+    recipients: &[Recipient],
+    fee: i64,
+) -> Result<Vec<Recipient>, Box<dyn Error>> {
+    let payers = recipients
+        .iter()
+        .filter(|recipient| recipient.subtract_fee)
+        .count() as i64;
+    let mut remainder = fee % payers;
+    let mut recipients = recipients.to_vec();
+    for recipient in recipients
+        .iter_mut()
+        .filter(|recipient| recipient.subtract_fee)
+    {
+        recipient.amount -= fee / payers + remainder;
+        remainder = 0;
+        let script = Address::parse(&recipient.address)?.script_pubkey();
+        if recipient.amount < dust_threshold(output_vsize(&script), input_vsize(&script, None)) {
+            return Err(Box::new(std::io::Error::other(format!(
+                "El monto a {} no alcanza para pagar su parte de la comision",
+                recipient.address
+            ))));
         }
+    }
+    Ok(recipients)
+}
 
-        Ok(transactions)
+/// Devuelve el esquema de derivacion con el purpose recibido o None si no es soportado
+fn scheme_from_purpose(purpose: &str) -> Option<DerivationScheme> {
+    match purpose {
+        "44" => Some(DerivationScheme::Bip44),
+        "84" => Some(DerivationScheme::Bip84),
+        _ => None,
     }
 }
+
+/// Error de un registro invalido. Solo incluye el tipo de registro para no mostrar las claves
+fn invalid_record(record: &str) -> Box<dyn Error> {
+    let kind = record.split(' ').next().unwrap_or_default();
+    Box::new(io::Error::other(format!(
+        "Registro de cuenta invalido en el archivo de la wallet: {}",
+        kind
+    )))
+}
+
+/// Pisa con ceros los bytes recibidos, para no dejar secretos en memoria
+pub fn erase_bytes(bytes: &mut [u8]) {
+    bytes.fill(0);
+    std::hint::black_box(bytes);
+}
+
 /// Convierte la cadena de bytes a hexadecimal y la devuelve
 pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
     let hex_chars: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
@@ -200,16 +1433,74 @@ pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
     hex_chars.join("")
 }
 
+/// Convierte el string hexadecimal a bytes. Devuelve error si no es hexadecimal valido
+pub fn hex_string_to_bytes(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let hex = hex.trim();
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(Box::new(std::io::Error::other(
+            "El string hexadecimal es invalido",
+        )));
+    }
+    let mut bytes = Vec::new();
+    for index in (0..hex.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(&hex[index..index + 2], 16)?);
+    }
+    Ok(bytes)
+}
+
 #[cfg(test)]
 mod test {
 
     use crate::account::Account;
+    use crate::address::Address;
+    use crate::address_decoder;
+    use crate::balance::SpendPolicy;
+    use crate::coin_control::{CoinAction, CoinControl};
+    use crate::compact_size_uint::CompactSizeUint;
+    use crate::hd_wallet::hd_keychain::{DerivationScheme, HdKeychain, GAP_LIMIT};
+    use crate::ledger::{BlockPosition, Ledger, TransactionKind};
+    use crate::transactions::outpoint::Outpoint;
+    use crate::transactions::recipient::Recipient;
+    use crate::transactions::script::multisig_script::MultisigKind;
+    use crate::transactions::transaction::Transaction;
+    use crate::transactions::tx_in::TxIn;
+    use crate::transactions::tx_out::TxOut;
+    use crate::utxo_tuple::UtxoTuple;
     use std::{
+        collections::{HashMap, HashSet},
         error::Error,
         io,
         sync::{Arc, RwLock},
     };
 
+    /// Crea la cuenta de prueba con una moneda confirmada en el bloque 100 por cada valor recibido, de las
+    /// transacciones con hash [1; 32], [2; 32], etc
+    fn funded_account(values: &[i64]) -> Result<Account, Box<dyn Error>> {
+        let mut account = Account::new(
+            String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR"),
+            String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV"),
+        )?;
+        let script = Address::parse(&account.address)?.script_pubkey();
+        let utxo_set = values
+            .iter()
+            .enumerate()
+            .map(|(position, value)| {
+                let hash = [position as u8 + 1; 32];
+                let tx_out = TxOut::new(
+                    *value,
+                    CompactSizeUint::new(script.len() as u128),
+                    script.clone(),
+                );
+                (
+                    hash,
+                    UtxoTuple::new(hash, vec![(tx_out, 0)]).with_height(100),
+                )
+            })
+            .collect();
+        account.set_utxos(Arc::new(RwLock::new(utxo_set)))?;
+        Ok(account)
+    }
+
     /// Convierte el str recibido en hexadecimal, a bytes
     fn string_to_33_bytes(input: &str) -> Result<[u8; 33], Box<dyn Error>> {
         if input.len() != 66 {
@@ -265,6 +1556,13 @@ mod test {
             utxo_set: Vec::new(),
             pending_transactions: Arc::new(RwLock::new(Vec::new())),
             confirmed_transactions: Arc::new(RwLock::new(Vec::new())),
+            ledger: Arc::new(RwLock::new(Ledger::default())),
+            hd_keychain: None,
+            multisig: None,
+            descriptors: vec![],
+            scripts: HashSet::new(),
+            coin_control: Arc::new(RwLock::new(CoinControl::default())),
+            derived_ranges: vec![],
         };
         let expected_pubkey = string_to_33_bytes(
             "0345EC0AA86BAF64ED626EE86B4A76C12A92D5F6DD1C1D6E4658E26666153DAFA6",
@@ -273,6 +1571,46 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_cuenta_hd_restaurada_encuentra_fondos_mas_alla_del_gap_limit(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: un utxo set con fondos en las direcciones de recepcion 15 y 30 de una cuenta HD
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut keychain = HdKeychain::from_mnemonic(mnemonic, "", DerivationScheme::Bip44, 0)?;
+        let last_key_script = keychain
+            .keys()
+            .nth(GAP_LIMIT - 1)
+            .unwrap()
+            .pubkey_script
+            .clone();
+        keychain.mark_used(&HashSet::from([last_key_script]))?;
+        let tx_outs: Vec<(TxOut, usize)> = [15, 30]
+            .iter()
+            .enumerate()
+            .map(|(position, key_index)| {
+                let script = keychain
+                    .keys()
+                    .nth(*key_index)
+                    .unwrap()
+                    .pubkey_script
+                    .clone();
+                let tx_out = TxOut::new(1000, CompactSizeUint::new(script.len() as u128), script);
+                (tx_out, position)
+            })
+            .collect();
+        let utxo_set = HashMap::from([([1; 32], UtxoTuple::new([1; 32], tx_outs))]);
+        // WHEN: se restaura la cuenta desde el mnemonic y se cargan sus utxos
+        let mut account = Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip44, 0)?;
+        account.set_utxos(Arc::new(RwLock::new(utxo_set)))?;
+        // THEN: se encuentran ambos outputs y la proxima direccion es la siguiente a la ultima usada
+        assert_eq!(account.balance(), 2000);
+        assert_eq!(
+            account.next_receive_address()?,
+            keychain.keys().nth(31).unwrap().address
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_no_se_puede_realizar_transaccion_a_una_address_invalida() -> Result<(), Box<dyn Error>>
     {
@@ -280,9 +1618,660 @@ mod test {
         let private_key: String =
             String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
         let mut account = Account::new(private_key, address_expected)?;
-        let transaction_result =
-            account.make_transaction("mocD12x6BV3qK71FwG98h5VWZ4qVsbaoi8", 1000, 10);
+        let transaction_result = account.make_transaction(
+            &[Recipient::new(
+                "mocD12x6BV3qK71FwG98h5VWZ4qVsbaoi8".to_string(),
+                1000,
+                false,
+            )],
+            1.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        );
         assert!(transaction_result.is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_la_cuenta_watch_only_ve_sus_fondos_y_crea_psbt_pero_no_firma(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta watch-only de la clave publica extendida de una cuenta HD con fondos
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let keychain = HdKeychain::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 0)?;
+        let mut account =
+            Account::new_watch_only_xpub(&keychain.account_tpub()?, DerivationScheme::Bip84)?;
+        let script = keychain.first_receive_key().pubkey_script.clone();
+        let tx_out = TxOut::new(100000, CompactSizeUint::new(script.len() as u128), script);
+        // WHEN: se cargan las utxos
+        account.set_utxos(Arc::new(RwLock::new(HashMap::from([(
+            [1; 32],
+            UtxoTuple::new([1; 32], vec![(tx_out.clone(), 0)]),
+        )]))))?;
+        // THEN: ve el balance y el output, crea el PSBT sin firmar, pero no puede firmar
+        let recipients = [Recipient::new(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF".to_string(),
+            40000,
+            false,
+        )];
+        assert!(account.is_watch_only());
+        assert_eq!(account.balance(), 100000);
+        assert!(account.owns_output(&tx_out));
+        assert!(account.keys().is_empty());
+        assert!(account
+            .create_psbt(&recipients, 1.0, &[], &SpendPolicy::new(0, 0))
+            .is_ok());
+        assert!(account
+            .make_transaction(&recipients, 1.0, &[], &SpendPolicy::new(0, 0))
+            .is_err());
+        assert!(account.pending_transactions.read().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_las_direcciones_nuevas_y_los_scripts_de_la_cuenta_hd_salen_de_sus_descriptors(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta HD y su descriptor de recepcion
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut account = Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 0)?;
+        let receive_descriptor = account.descriptors(false)?[0].clone();
+        // WHEN: se piden direcciones nuevas
+        let first_address = account.next_receive_address()?;
+        let second_address = account.next_receive_address()?;
+        // THEN: son las del descriptor y la cuenta ve los scripts del descriptor
+        assert_eq!(first_address, receive_descriptor.address(0)?);
+        assert_eq!(second_address, receive_descriptor.address(1)?);
+        assert!(account
+            .pubkey_scripts()
+            .contains(&receive_descriptor.script_pubkey(1)?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_al_bloquear_se_borran_las_claves_privadas_y_se_restauran_del_registro(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta HD y su registro con las claves privadas
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut account = Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 0)?;
+        let keys = account.keys();
+        let record = account.to_record();
+        // WHEN: se borran las claves privadas
+        account.erase_private_keys()?;
+        // THEN: la cuenta no puede firmar hasta que se restauran las claves del registro
+        assert!(account.is_watch_only());
+        assert!(account.keys().is_empty());
+        account.restore_private_keys(&Account::from_record(&record)?)?;
+        assert!(!account.is_watch_only());
+        assert_eq!(account.keys(), keys);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aumentar_el_fee_reemplaza_la_transaccion_pendiente_descontandolo_del_cambio(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con una transaccion pendiente que paga 1 sat/vB
+        let address = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
+        let private_key = String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
+        let mut account = Account::new(private_key, address.clone())?;
+        let script = Address::parse(&address)?.script_pubkey();
+        let tx_out = TxOut::new(100000, CompactSizeUint::new(script.len() as u128), script);
+        account.set_utxos(Arc::new(RwLock::new(HashMap::from([(
+            [1; 32],
+            UtxoTuple::new([1; 32], vec![(tx_out, 0)]),
+        )]))))?;
+        let original = account.make_transaction(
+            &[Recipient::new(
+                "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF".to_string(),
+                40000,
+                false,
+            )],
+            1.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        let original_fee = account.spent_amount(&original) - original.amount();
+        assert!(original.signals_rbf());
+        // WHEN: se aumenta el fee a 5 sat/vB
+        let (replacement, fee) = account.bump_fee(original.hash(), 5.0)?;
+        // THEN: el reemplazo gasta los mismos inputs, paga el fee rate pedido y queda como unica pendiente
+        assert_eq!(replacement.tx_in.len(), original.tx_in.len());
+        assert_eq!(
+            replacement.tx_in[0].outpoint(),
+            original.tx_in[0].outpoint()
+        );
+        assert!(fee > original_fee);
+        assert!(fee as f64 / replacement.vsize() as f64 >= 5.0);
+        assert!(replacement
+            .tx_out
+            .iter()
+            .any(|tx_out| tx_out.value() == 40000));
+        let pending_transactions = account.pending_transactions.read().unwrap();
+        assert_eq!(pending_transactions.len(), 1);
+        assert_eq!(pending_transactions[0].hash(), replacement.hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_el_ledger_registra_lo_recibido_lo_enviado_y_sus_confirmaciones(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta que recibe 100000 satoshis en el bloque 100
+        let mut account = funded_account(&[])?;
+        let script = account.own_script()?;
+        let tx_out = TxOut::new(100000, CompactSizeUint::new(script.len() as u128), script);
+        let funding = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new([9; 32], 0))],
+            CompactSizeUint::new(1),
+            vec![tx_out.clone()],
+            0,
+        );
+        let block = BlockPosition {
+            hash: [1; 32],
+            height: 100,
+        };
+        assert!(account.record_in_ledger(&funding, Some(block))?);
+        account.set_utxos(Arc::new(RwLock::new(HashMap::from([(
+            funding.hash(),
+            UtxoTuple::new(funding.hash(), vec![(tx_out, 0)]),
+        )]))))?;
+        // WHEN: paga 40000 satoshis a otra direccion y la transaccion todavia no se confirma
+        let sent = account.make_transaction(
+            &[Recipient::new(
+                "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF".to_string(),
+                40000,
+                false,
+            )],
+            1.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        let pending = account.get_transactions(105)?;
+        // THEN: el historial tiene la recibida con 6 confirmaciones y la enviada pendiente con su comision
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].entry.kind(), TransactionKind::Received);
+        assert_eq!(pending[0].entry.net_amount(), 100000);
+        assert_eq!(pending[0].confirmations, 6);
+        let fee = 100000 - sent.amount();
+        assert_eq!(pending[1].entry.kind(), TransactionKind::Sent);
+        assert_eq!(pending[1].entry.fee, Some(fee));
+        assert_eq!(pending[1].entry.net_amount(), -(40000 + fee));
+        assert_eq!(pending[1].confirmations, 0);
+        // y cuando se confirma en el bloque 104 solo se actualiza el bloque
+        let block = BlockPosition {
+            hash: [2; 32],
+            height: 104,
+        };
+        assert!(account.record_in_ledger(&sent, Some(block))?);
+        let confirmed = account.get_transactions(105)?;
+        assert_eq!(confirmed.len(), 2);
+        assert_eq!(confirmed[1].confirmations, 2);
+        assert_eq!(confirmed[1].entry.fee, Some(fee));
+        // las transacciones que no tocan a la cuenta no se registran
+        let unrelated = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new([8; 32], 0))],
+            CompactSizeUint::new(0),
+            vec![],
+            0,
+        );
+        assert!(!account.record_in_ledger(&unrelated, None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_el_rescan_recupera_las_monedas_sin_gastar_de_bloques_viejos(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta sin utxos que en un bloque viejo recibio dos outputs y despues gasto uno
+        let mut account = funded_account(&[])?;
+        let script = account.own_script()?;
+        let outputs = [100000, 50000]
+            .into_iter()
+            .map(|value| {
+                TxOut::new(
+                    value,
+                    CompactSizeUint::new(script.len() as u128),
+                    script.clone(),
+                )
+            })
+            .collect();
+        let funding = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new([9; 32], 0))],
+            CompactSizeUint::new(2),
+            outputs,
+            0,
+        );
+        let other_script = Address::parse("mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF")?.script_pubkey();
+        let spending = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new(funding.hash(), 0))],
+            CompactSizeUint::new(1),
+            vec![TxOut::new(
+                99000,
+                CompactSizeUint::new(other_script.len() as u128),
+                other_script,
+            )],
+            0,
+        );
+        let old_block = BlockPosition {
+            hash: [1; 32],
+            height: 10,
+        };
+        let spending_block = BlockPosition {
+            hash: [2; 32],
+            height: 20,
+        };
+        // WHEN: se registran los bloques en el ledger y se cargan las utxos de un utxo set que no tiene sus monedas
+        account.record_in_ledger(&funding, Some(old_block))?;
+        account.record_in_ledger(&spending, Some(spending_block))?;
+        let utxo_set = Arc::new(RwLock::new(HashMap::new()));
+        account.set_utxos(utxo_set.clone())?;
+        // THEN: la cuenta solo tiene el output que no gasto, sin agregarlo al utxo set, y el historial tiene las dos
+        // transacciones
+        assert!(utxo_set.read().unwrap().is_empty());
+        assert_eq!(account.balance(), 50000);
+        assert_eq!(account.utxo_set[0].height, Some(10));
+        let history = account.get_transactions(20)?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].entry.kind(), TransactionKind::Sent);
+        assert_eq!(history[1].entry.fee, Some(1000));
+        // y si se saca el gasto para reescanear desde su bloque, los dos outputs vuelven a estar sin gastar
+        let mut ledger = account.ledger.write().unwrap();
+        ledger.remove_from_height(20);
+        let balance: i64 = ledger
+            .unspent_outputs(|tx_out| account.owns_output(tx_out))
+            .iter()
+            .map(|coin| coin.balance())
+            .sum();
+        assert_eq!(balance, 150000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_el_resultado_del_rescan_conserva_lo_registrado_mientras_se_escaneaba(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta cuyo rescan encuentra una moneda de un bloque viejo en una copia del ledger, mientras
+        // la cuenta registra una transaccion de un bloque nuevo que esta en el utxo set del nodo
+        let mut account = funded_account(&[])?;
+        let script = account.own_script()?;
+        let payment = |hash: [u8; 32], value: i64| {
+            Transaction::new(
+                2,
+                CompactSizeUint::new(1),
+                vec![TxIn::incomplete_txin(Outpoint::new(hash, 0))],
+                CompactSizeUint::new(1),
+                vec![TxOut::new(
+                    value,
+                    CompactSizeUint::new(script.len() as u128),
+                    script.clone(),
+                )],
+                0,
+            )
+        };
+        let old = payment([8; 32], 100000);
+        let recent = payment([9; 32], 30000);
+        let mut scanning = account.clone();
+        scanning.ledger = Arc::new(RwLock::new(account.ledger.read().unwrap().clone()));
+        scanning.record_in_ledger(
+            &old,
+            Some(BlockPosition {
+                hash: [1; 32],
+                height: 10,
+            }),
+        )?;
+        account.record_in_ledger(
+            &recent,
+            Some(BlockPosition {
+                hash: [2; 32],
+                height: 30,
+            }),
+        )?;
+        let utxo_set = Arc::new(RwLock::new(HashMap::from([(
+            recent.hash(),
+            UtxoTuple::new(recent.hash(), vec![(recent.tx_out[0].clone(), 0)]).with_height(30),
+        )])));
+        // WHEN: se combina el resultado del rescan con la cuenta
+        let scanned_ledger = scanning.ledger.read().unwrap().clone();
+        account.merge_rescan(&scanned_ledger, utxo_set.clone())?;
+        // THEN: la cuenta tiene las dos monedas y las dos transacciones, y el utxo set del nodo no cambio
+        assert_eq!(account.balance(), 130000);
+        assert_eq!(account.get_transactions(30)?.len(), 2);
+        let utxo_set = utxo_set.read().unwrap();
+        assert_eq!(utxo_set.len(), 1);
+        assert!(utxo_set.contains_key(&recent.hash()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_la_transaccion_hija_hace_que_el_paquete_pague_el_fee_rate_pedido(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una transaccion pendiente que le paga a la cuenta con una comision baja
+        let address = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
+        let private_key = String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
+        let mut account = Account::new(private_key, address.clone())?;
+        let script = Address::parse(&address)?.script_pubkey();
+        let tx_out = TxOut::new(50000, CompactSizeUint::new(script.len() as u128), script);
+        let parent = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new([9; 32], 0))],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        );
+        account.add_transaction(parent.clone())?;
+        let parent_fee = 100;
+        // WHEN: se acelera a 10 sat/vB
+        let (child, child_fee) = account.make_cpfp_transaction(&parent, parent_fee, 10.0)?;
+        // THEN: la hija gasta el output de la padre y el paquete paga al menos el fee rate pedido
+        assert_eq!(child.tx_in[0].get_previous_output_hash(), parent.hash());
+        assert_eq!(child.amount(), 50000 - child_fee);
+        let package_fee_rate =
+            (parent_fee + child_fee) as f64 / (parent.vsize() + child.vsize()) as f64;
+        assert!(package_fee_rate >= 10.0);
+        assert_eq!(account.pending_transactions.read().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pago_a_varios_receptores_descontando_la_comision_de_uno() -> Result<(), Box<dyn Error>>
+    {
+        // GIVEN: una cuenta con 100000 satoshis
+        let mut account = funded_account(&[100000])?;
+        let first_address = String::from("mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF");
+        let second_address = address_decoder::generate_p2wpkh_address(&[2; 32])?;
+        let first = Address::parse(&first_address)?.script_pubkey();
+        let second = Address::parse(&second_address)?.script_pubkey();
+        // WHEN: paga a dos receptores y el segundo paga la comision
+        let transaction = account.make_transaction(
+            &[
+                Recipient::new(first_address, 30000, false),
+                Recipient::new(second_address, 20000, true),
+            ],
+            5.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        // THEN: el primero recibe el monto completo, el segundo el monto menos la comision y el cambio el resto
+        let value_to = |script: &Vec<u8>| {
+            transaction
+                .tx_out
+                .iter()
+                .find(|tx_out| tx_out.get_pub_key_script() == script)
+                .map(|tx_out| tx_out.value())
+        };
+        let fee = account.spent_amount(&transaction) - transaction.amount();
+        assert_eq!(transaction.tx_out.len(), 3);
+        assert_eq!(value_to(&first), Some(30000));
+        assert_eq!(value_to(&second), Some(20000 - fee));
+        assert!(fee as f64 / transaction.vsize() as f64 >= 5.0);
+        assert_eq!(
+            value_to(&Address::parse(&account.address)?.script_pubkey()),
+            Some(50000)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_la_seleccion_automatica_saltea_las_monedas_bloqueadas_y_congeladas(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con tres monedas, la mas grande bloqueada y la siguiente congelada
+        let mut account = funded_account(&[100000, 80000, 60000])?;
+        {
+            let mut coin_control = account.coin_control.write().unwrap();
+            coin_control.apply(CoinAction::Lock, ([1; 32], 0))?;
+            coin_control.apply(CoinAction::Freeze, ([2; 32], 0))?;
+        }
+        let recipients = [Recipient::new(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF".to_string(),
+            30000,
+            false,
+        )];
+        // WHEN: se paga sin elegir monedas, eligiendo la bloqueada y eligiendo la congelada
+        let automatic = account.make_transaction(&recipients, 1.0, &[], &SpendPolicy::new(0, 0))?;
+        let manual =
+            account.make_transaction(&recipients, 1.0, &[([1; 32], 0)], &SpendPolicy::new(0, 0))?;
+        let frozen =
+            account.make_transaction(&recipients, 1.0, &[([2; 32], 0)], &SpendPolicy::new(0, 0));
+        // THEN: la automatica usa la unica moneda libre, la bloqueada se puede elegir a mano y la congelada no
+        assert_eq!(automatic.tx_in[0].get_previous_output_hash(), [3; 32]);
+        assert_eq!(manual.tx_in[0].get_previous_output_hash(), [1; 32]);
+        assert!(frozen.is_err());
+        let coins = account.coins(101)?;
+        assert_eq!(coins[0].confirmations, 2);
+        assert!(coins[0].locked && coins[1].frozen && !coins[2].locked);
+        assert_eq!(coins[2].address, account.address);
+        Ok(())
+    }
+
+    #[test]
+    fn test_el_balance_separa_lo_confirmado_el_cambio_pendiente_y_la_coinbase_inmadura(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con una coinbase y una moneda comun del bloque 100, con la cadena en el bloque 105
+        let mut account = funded_account(&[50000, 70000])?;
+        for utxo in account.utxo_set.iter_mut() {
+            utxo.coinbase = utxo.hash == [1; 32];
+        }
+        let policy = SpendPolicy::new(1, 105);
+        assert_eq!(account.balance_breakdown(&policy)?.confirmed, 70000);
+        assert_eq!(account.balance_breakdown(&policy)?.immature, 50000);
+        // WHEN: se pagan 30000 satoshis sin elegir monedas
+        let transaction = account.make_transaction(
+            &[Recipient::new(
+                "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF".to_string(),
+                30000,
+                false,
+            )],
+            1.0,
+            &[],
+            &policy,
+        )?;
+        // THEN: se gasta la moneda comun y su cambio queda pendiente, que solo se gasta sin pedir confirmaciones
+        assert_eq!(transaction.tx_in[0].get_previous_output_hash(), [2; 32]);
+        let breakdown = account.balance_breakdown(&policy)?;
+        let change = transaction.amount() - 30000;
+        assert_eq!(breakdown.confirmed, 0);
+        assert_eq!(breakdown.unconfirmed_change, change);
+        assert_eq!(breakdown.unconfirmed_incoming, 0);
+        assert_eq!(breakdown.total(), change + 50000);
+        assert!(!account.has_balance(change, &policy)?);
+        assert!(account.has_balance(change, &SpendPolicy::new(0, 105))?);
+        // THEN: la coinbase se puede gastar recien con 100 confirmaciones
+        assert_eq!(
+            account
+                .balance_breakdown(&SpendPolicy::new(1, 199))?
+                .confirmed,
+            50000
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_la_transaccion_pendiente_en_conflicto_con_una_confirmada_deja_de_contar_en_el_balance(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con una transaccion pendiente que deja cambio
+        let mut account = funded_account(&[100000])?;
+        let policy = SpendPolicy::new(0, 105);
+        let recipient = String::from("mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF");
+        account.make_transaction(
+            &[Recipient::new(recipient.clone(), 30000, false)],
+            1.0,
+            &[],
+            &policy,
+        )?;
+        assert!(account.balance_breakdown(&policy)?.unconfirmed_change > 0);
+        // WHEN: se confirma otra transaccion que gasta la misma moneda
+        let other_script = Address::parse(&recipient)?.script_pubkey();
+        let conflicting = Transaction::new(
+            2,
+            CompactSizeUint::new(1),
+            vec![TxIn::incomplete_txin(Outpoint::new([1; 32], 0))],
+            CompactSizeUint::new(1),
+            vec![TxOut::new(
+                99000,
+                CompactSizeUint::new(other_script.len() as u128),
+                other_script,
+            )],
+            0,
+        );
+        let block = BlockPosition {
+            hash: [7; 32],
+            height: 106,
+        };
+        account.record_in_ledger(&conflicting, Some(block))?;
+        account.set_utxos(Arc::new(RwLock::new(HashMap::new())))?;
+        // THEN: la pendiente sale del ledger y de las pendientes y su cambio deja de contar
+        let breakdown = account.balance_breakdown(&policy)?;
+        assert_eq!(breakdown.unconfirmed_change, 0);
+        assert_eq!(breakdown.total(), 0);
+        assert!(account.pending_transactions.read().unwrap().is_empty());
+        assert_eq!(account.get_transactions(106)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_las_cuentas_se_vuelven_a_crear_desde_su_registro() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con WIF, una HD y una multisig
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let private_keys: Vec<String> = [[1; 32], [2; 32]]
+            .iter()
+            .map(address_decoder::encode_wif_private_key)
+            .collect();
+        let pubkeys = private_keys
+            .iter()
+            .map(|key| address_decoder::get_pubkey_compressed(key))
+            .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+        let accounts = [
+            Account::new(
+                "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR".to_string(),
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+            )?,
+            Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 1)?,
+            Account::new_multisig(private_keys[1].clone(), MultisigKind::P2wsh, 1, pubkeys)?,
+            Account::new_watch_only_address("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV")?,
+            Account::new_watch_only_xpub(
+                &HdKeychain::from_mnemonic(mnemonic, "", DerivationScheme::Bip44, 2)?
+                    .account_tpub()?,
+                DerivationScheme::Bip44,
+            )?,
+        ];
+        for account in accounts {
+            // WHEN: se vuelve a crear a partir de su registro
+            let restored = Account::from_record(&account.to_record())?;
+            // THEN: tiene las mismas claves y la misma direccion
+            assert_eq!(restored.address, account.address);
+            assert_eq!(restored.private_key, account.private_key);
+            assert_eq!(restored.keys(), account.keys());
+            assert_eq!(restored.pubkey_scripts(), account.pubkey_scripts());
+            assert_eq!(restored.to_record(), account.to_record());
+        }
+        assert!(Account::from_record("hd 49 0 tprv").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_las_cuentas_se_exportan_e_importan_con_sus_descriptors() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta con WIF, una HD y una multisig
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let private_key = address_decoder::encode_wif_private_key(&[1; 32]);
+        let pubkeys = vec![
+            address_decoder::get_pubkey_compressed(&private_key)?,
+            address_decoder::get_pubkey_compressed(&address_decoder::encode_wif_private_key(
+                &[2; 32],
+            ))?,
+        ];
+        let accounts = [
+            Account::new(
+                "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR".to_string(),
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+            )?,
+            Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 1)?,
+            Account::new_multisig(private_key, MultisigKind::P2shP2wsh, 2, pubkeys)?,
+        ];
+        for account in accounts {
+            // WHEN: se importa su descriptor con claves privadas y el publico
+            let private_descriptor = account.descriptors(true)?[0].to_string();
+            let imported = Account::from_descriptor(&private_descriptor)?;
+            let watch_only = Account::from_descriptor(&account.descriptors(false)?[0].to_string())?;
+            // THEN: la primera es la misma cuenta y la segunda observa los mismos scripts sin poder firmar
+            assert_eq!(imported.address, account.address);
+            assert_eq!(imported.private_key, account.private_key);
+            assert_eq!(imported.pubkey_scripts(), account.pubkey_scripts());
+            assert_eq!(watch_only.address, account.address);
+            assert_eq!(watch_only.pubkey_scripts(), account.pubkey_scripts());
+            assert!(watch_only.is_watch_only());
+            assert!(watch_only.descriptors(true).is_err());
+        }
+        // un descriptor con claves privadas que la wallet no sabe usar no se importa
+        let keychain = HdKeychain::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 0)?;
+        let taproot = keychain.descriptors(true)?[0]
+            .to_string()
+            .replacen("wpkh(", "tr(", 1);
+        let taproot_body = taproot.split('#').next().unwrap_or_default();
+        assert!(Account::from_descriptor(taproot_body).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cuenta_multisig_2_de_3_gasta_con_las_firmas_de_dos_firmantes(
+    ) -> Result<(), Box<dyn Error>> {
+        let private_keys: Vec<String> = [[1; 32], [2; 32], [3; 32]]
+            .iter()
+            .map(address_decoder::encode_wif_private_key)
+            .collect();
+        let pubkeys = private_keys
+            .iter()
+            .map(|key| address_decoder::get_pubkey_compressed(key))
+            .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+        for kind in [
+            MultisigKind::P2sh,
+            MultisigKind::P2shP2wsh,
+            MultisigKind::P2wsh,
+        ] {
+            // GIVEN: dos firmantes de un multisig 2 de 3 con un output que le paga
+            let mut first_cosigner =
+                Account::new_multisig(private_keys[0].clone(), kind, 2, pubkeys.clone())?;
+            let mut third_cosigner =
+                Account::new_multisig(private_keys[2].clone(), kind, 2, pubkeys.clone())?;
+            assert_eq!(first_cosigner.address, third_cosigner.address);
+            let script = Address::parse(&first_cosigner.address)?.script_pubkey();
+            let tx_out = TxOut::new(10000, CompactSizeUint::new(script.len() as u128), script);
+            let utxo_set = Arc::new(RwLock::new(HashMap::from([(
+                [1; 32],
+                UtxoTuple::new([1; 32], vec![(tx_out, 0)]),
+            )])));
+            first_cosigner.set_utxos(utxo_set.clone())?;
+            third_cosigner.set_utxos(utxo_set)?;
+            // WHEN: el primero crea la transaccion y el tercero la firma
+            let transaction = first_cosigner.make_transaction(
+                &[Recipient::new(
+                    "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+                    4000,
+                    false,
+                )],
+                5.0,
+                &[],
+                &SpendPolicy::new(0, 0),
+            )?;
+            let mut bytes = Vec::new();
+            transaction.marshalling(&mut bytes);
+            let received_transaction = Transaction::unmarshalling(&bytes, &mut 0)?;
+            let signed_transaction =
+                third_cosigner.sign_multisig_transaction(received_transaction)?;
+            // THEN: con una sola firma no se puede gastar y con las dos la transaccion es valida
+            assert!(!first_cosigner.is_fully_signed(&transaction));
+            assert!(first_cosigner
+                .pending_transactions
+                .read()
+                .unwrap()
+                .is_empty());
+            assert!(third_cosigner.is_fully_signed(&signed_transaction));
+            assert_eq!(third_cosigner.pending_transactions.read().unwrap().len(), 1);
+            assert_eq!(third_cosigner.spent_amount(&signed_transaction), 10000);
+        }
+        Ok(())
+    }
 }
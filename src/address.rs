@@ -0,0 +1,303 @@
+use std::{error::Error, fmt, io};
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use crate::{
+    bech32::{decode_segwit_address, encode_segwit_address, TESTNET_HRP},
+    transactions::script::script_opcodes::ScriptOpcodes,
+};
+
+/// Largo en bytes de una direccion base58 decodificada: prefijo + hash160 + checksum
+const BASE58_ADDRESS_BYTES: usize = 25;
+/// Human readable part de las direcciones bech32 de mainnet
+const MAINNET_HRP: &str = "bc";
+
+/// Red de la que es una direccion. Define los prefijos base58 y el hrp de bech32 con que se codifica
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    /// Prefijo de las direcciones P2PKH (1 en mainnet, m o n en testnet)
+    fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet => 0x6f,
+        }
+    }
+
+    /// Prefijo de las direcciones P2SH (3 en mainnet, 2 en testnet)
+    fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet => 0xc4,
+        }
+    }
+
+    /// Human readable part de las direcciones bech32
+    fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => MAINNET_HRP,
+            Network::Testnet => TESTNET_HRP,
+        }
+    }
+}
+
+/// Direccion de alguno de los tipos de output estandar.
+/// Guarda el hash o el witness program que identifica al script que se paga
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    /// Pay to public key hash (base58, empieza con m o n)
+    P2pkh([u8; 20]),
+    /// Pay to script hash (base58, empieza con 2)
+    P2sh([u8; 20]),
+    /// Pay to witness public key hash (bech32, empieza con tb1q)
+    P2wpkh([u8; 20]),
+    /// Pay to witness script hash (bech32, empieza con tb1q)
+    P2wsh([u8; 32]),
+    /// Pay to taproot (bech32m, empieza con tb1p)
+    P2tr([u8; 32]),
+}
+
+impl Address {
+    /// Recibe una direccion de testnet, que es la red de la wallet, en base58 o bech32.
+    /// Devuelve la direccion o error si es inválida, es de otra red o es de un tipo no soportado
+    pub fn parse(address: &str) -> Result<Address, Box<dyn Error>> {
+        Self::parse_for_network(address, Network::Testnet)
+    }
+
+    /// Recibe una direccion en base58 o bech32 de la red recibida.
+    /// Devuelve la direccion o error si es inválida, es de otra red o es de un tipo no soportado
+    pub fn parse_for_network(address: &str, network: Network) -> Result<Address, Box<dyn Error>> {
+        let address = address.trim();
+        if address
+            .to_lowercase()
+            .starts_with(&format!("{}1", network.hrp()))
+        {
+            return Self::parse_segwit(address, network);
+        }
+        Self::parse_base58(address, network)
+    }
+
+    /// Codifica la direccion para la red recibida: en base58 las P2PKH y P2SH, en bech32 las
+    /// de witness version 0 y en bech32m las taproot
+    pub fn encode(&self, network: Network) -> Result<String, Box<dyn Error>> {
+        match self {
+            Address::P2pkh(hash) => Ok(encode_base58(network.p2pkh_prefix(), hash)),
+            Address::P2sh(hash) => Ok(encode_base58(network.p2sh_prefix(), hash)),
+            Address::P2wpkh(hash) => encode_segwit_address(network.hrp(), 0, hash),
+            Address::P2wsh(hash) => encode_segwit_address(network.hrp(), 0, hash),
+            Address::P2tr(key) => encode_segwit_address(network.hrp(), 1, key),
+        }
+    }
+
+    /// Devuelve el pubkey script que paga a la direccion
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        match self {
+            Address::P2pkh(pubkey_hash) => {
+                // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+                script.extend_from_slice(&[ScriptOpcodes::OP_DUP, ScriptOpcodes::OP_HASH160, 20]);
+                script.extend_from_slice(pubkey_hash);
+                script.extend_from_slice(&[
+                    ScriptOpcodes::OP_EQUALVERIFY,
+                    ScriptOpcodes::OP_CHECKSIG,
+                ]);
+            }
+            Address::P2sh(script_hash) => {
+                // OP_HASH160 <20 bytes> OP_EQUAL
+                script.extend_from_slice(&[ScriptOpcodes::OP_HASH160, 20]);
+                script.extend_from_slice(script_hash);
+                script.push(ScriptOpcodes::OP_EQUAL);
+            }
+            Address::P2wpkh(pubkey_hash) => {
+                // OP_0 <20 bytes>
+                script.extend_from_slice(&[ScriptOpcodes::OP_0, 20]);
+                script.extend_from_slice(pubkey_hash);
+            }
+            Address::P2wsh(script_hash) => {
+                // OP_0 <32 bytes>
+                script.extend_from_slice(&[ScriptOpcodes::OP_0, 32]);
+                script.extend_from_slice(script_hash);
+            }
+            Address::P2tr(output_key) => {
+                // OP_1 <32 bytes>
+                script.extend_from_slice(&[ScriptOpcodes::OP_1, 32]);
+                script.extend_from_slice(output_key);
+            }
+        }
+        script
+    }
+
+    /// Devuelve la direccion a la que paga el pubkey script recibido o None si no es de un tipo estandar
+    pub fn from_script_pubkey(script: &[u8]) -> Option<Address> {
+        match script {
+            [ScriptOpcodes::OP_DUP, ScriptOpcodes::OP_HASH160, 20, hash @ .., ScriptOpcodes::OP_EQUALVERIFY, ScriptOpcodes::OP_CHECKSIG] => {
+                Some(Address::P2pkh(hash.try_into().ok()?))
+            }
+            [ScriptOpcodes::OP_HASH160, 20, hash @ .., ScriptOpcodes::OP_EQUAL] => {
+                Some(Address::P2sh(hash.try_into().ok()?))
+            }
+            [ScriptOpcodes::OP_0, 20, hash @ ..] => Some(Address::P2wpkh(hash.try_into().ok()?)),
+            [ScriptOpcodes::OP_0, 32, hash @ ..] => Some(Address::P2wsh(hash.try_into().ok()?)),
+            [ScriptOpcodes::OP_1, 32, key @ ..] => Some(Address::P2tr(key.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Decodifica una direccion base58 (P2PKH o P2SH) verificando su checksum
+    fn parse_base58(address: &str, network: Network) -> Result<Address, Box<dyn Error>> {
+        let bytes = bs58::decode(address)
+            .into_vec()
+            .map_err(|_| invalid_address("La dirección no es base58 ni bech32 válido."))?;
+        if bytes.len() != BASE58_ADDRESS_BYTES {
+            return Err(invalid_address(
+                "La cantidad de caracteres de la address es inválida.",
+            ));
+        }
+        let checksum = sha256d::Hash::hash(&bytes[..21]).to_byte_array();
+        if bytes[21..] != checksum[..4] {
+            return Err(invalid_address(
+                "La dirección es inválida, falló la validación del checksum",
+            ));
+        }
+        let mut hash = [0; 20];
+        hash.copy_from_slice(&bytes[1..21]);
+        if bytes[0] == network.p2pkh_prefix() {
+            return Ok(Address::P2pkh(hash));
+        }
+        if bytes[0] == network.p2sh_prefix() {
+            return Ok(Address::P2sh(hash));
+        }
+        Err(invalid_address("La dirección no corresponde a la red."))
+    }
+
+    /// Decodifica una direccion bech32 o bech32m (P2WPKH, P2WSH o P2TR)
+    fn parse_segwit(address: &str, network: Network) -> Result<Address, Box<dyn Error>> {
+        let (version, program) = decode_segwit_address(network.hrp(), address)?;
+        match (version, program.len()) {
+            (0, 20) => {
+                let mut pubkey_hash = [0; 20];
+                pubkey_hash.copy_from_slice(&program);
+                Ok(Address::P2wpkh(pubkey_hash))
+            }
+            (0, 32) => {
+                let mut script_hash = [0; 32];
+                script_hash.copy_from_slice(&program);
+                Ok(Address::P2wsh(script_hash))
+            }
+            (1, 32) => {
+                let mut output_key = [0; 32];
+                output_key.copy_from_slice(&program);
+                Ok(Address::P2tr(output_key))
+            }
+            _ => Err(invalid_address(
+                "La versión del witness de la dirección no está soportada.",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    /// Codifica la direccion para testnet, que es la red de la wallet
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let encoded = self.encode(Network::Testnet).map_err(|_| fmt::Error)?;
+        write!(f, "{}", encoded)
+    }
+}
+
+/// Codifica el hash con el prefijo recibido en base58check
+fn encode_base58(prefix: u8, hash: &[u8; 20]) -> String {
+    let mut bytes = vec![prefix];
+    bytes.extend_from_slice(hash);
+    let checksum = sha256d::Hash::hash(&bytes).to_byte_array();
+    bytes.extend_from_slice(&checksum[..4]);
+    bs58::encode(bytes).into_string()
+}
+
+fn invalid_address(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::bytes_to_hex_string;
+
+    #[test]
+    fn every_standard_address_type_generates_its_script_pubkey() -> Result<(), Box<dyn Error>> {
+        // GIVEN: direcciones de testnet de cada tipo de output estandar
+        let addresses = [
+            (
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV",
+                "76a91449c0c4da389028c5f0aea40157fc1aa31de171bb88ac",
+            ),
+            (
+                "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc",
+                "a9144e9f39ca4688ff102128ea4ccda34105324305b087",
+            ),
+            (
+                "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+        ];
+        for (address, expected_script) in addresses {
+            // WHEN: se parsean
+            let parsed = Address::parse(address)?;
+            // THEN: generan el pubkey script esperado, se vuelven a codificar igual y se recuperan del script
+            assert_eq!(
+                bytes_to_hex_string(&parsed.script_pubkey()),
+                expected_script
+            );
+            assert_eq!(parsed.to_string(), address);
+            assert_eq!(
+                Address::from_script_pubkey(&parsed.script_pubkey()),
+                Some(parsed)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn addresses_are_rendered_for_each_network() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una direccion P2PKH y una P2WPKH de testnet
+        let p2pkh = Address::parse("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV")?;
+        let p2wpkh = Address::parse("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")?;
+        // WHEN: se codifican para mainnet
+        let mainnet_p2wpkh = p2wpkh.encode(Network::Mainnet)?;
+        // THEN: se usan los prefijos de mainnet y se pueden volver a parsear en esa red
+        assert!(p2pkh.encode(Network::Mainnet)?.starts_with('1'));
+        assert_eq!(mainnet_p2wpkh, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_eq!(
+            Address::parse_for_network(&mainnet_p2wpkh, Network::Mainnet)?,
+            p2wpkh
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_or_mainnet_addresses_are_rejected() {
+        // GIVEN: direcciones de mainnet, con checksum invalido o con una version de witness desconocida
+        let invalid_addresses = [
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqW",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "tb1zw508d6qejxtdg4y5r3zarvaryvqyzf3du",
+        ];
+        // WHEN: se parsean
+        // THEN: devuelven error
+        for address in invalid_addresses {
+            assert!(Address::parse(address).is_err());
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use crate::address::Address;
+use crate::bech32::{decode_segwit_address, encode_segwit_address, TESTNET_HRP};
 use bitcoin_hashes::{ripemd160, Hash};
 use k256::sha2::Digest;
 use k256::sha2::Sha256;
+use rand::{rngs::OsRng, RngCore};
 use secp256k1::SecretKey;
 use std::error::Error;
 use std::io;
@@ -9,6 +12,49 @@ const UNCOMPRESSED_WIF_LEN: usize = 51;
 const COMPRESSED_WIF_LEN: usize = 52;
 const ADDRESS_LEN: usize = 34;
 
+/// Tipo de direccion de una cuenta de una sola clave
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressType {
+    /// Direccion base58 (empieza con m o n)
+    P2pkh,
+    /// Direccion bech32 (empieza con tb1q)
+    P2wpkh,
+}
+
+impl AddressType {
+    /// Recibe el nombre del tipo de direccion (p2pkh o legacy, p2wpkh o segwit) sin importar mayusculas.
+    /// Devuelve error si no es ninguno de ellos
+    pub fn parse(name: &str) -> Result<AddressType, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "p2pkh" | "legacy" => Ok(AddressType::P2pkh),
+            "p2wpkh" | "segwit" => Ok(AddressType::P2wpkh),
+            _ => Err(Box::new(std::io::Error::other(
+                "El tipo de direccion debe ser p2pkh (legacy) o p2wpkh (segwit)",
+            ))),
+        }
+    }
+
+    /// Recibe la private key en bytes y devuelve la address de este tipo de su clave publica comprimida
+    pub fn address(&self, private_key: &[u8]) -> Result<String, Box<dyn Error>> {
+        match self {
+            AddressType::P2pkh => generate_address(private_key),
+            AddressType::P2wpkh => generate_p2wpkh_address(private_key),
+        }
+    }
+}
+
+/// Genera una private key nueva con el generador aleatorio del sistema operativo
+pub fn generate_private_key() -> [u8; 32] {
+    let mut private_key = [0u8; 32];
+    // casi todos los valores son claves validas, salvo el cero y los mayores al orden de la curva
+    loop {
+        OsRng.fill_bytes(&mut private_key);
+        if SecretKey::from_slice(&private_key).is_ok() {
+            return private_key;
+        }
+    }
+}
+
 /// Recibe la private key en bytes.
 /// Devuelve la address comprimida
 pub fn generate_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
@@ -37,6 +83,15 @@ pub fn generate_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
     Ok(encoded.into_string())
 }
 
+/// Recibe la private key en bytes.
+/// Devuelve la address P2WPKH (bech32) de testnet de la clave publica comprimida
+pub fn generate_p2wpkh_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
+    let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
+    let key = SecretKey::from_slice(private_key)?;
+    let public_key: secp256k1::PublicKey = secp256k1::PublicKey::from_secret_key(&secp, &key);
+    encode_segwit_address(TESTNET_HRP, 0, &hash_160(&public_key.serialize()))
+}
+
 /// Recibe el public key comprimido (33 bytes)
 /// Aplica RIPEMD160(SHA256(ECDSA(public_key)))
 pub fn hash_160(public_key_bytes_compressed: &[u8]) -> [u8; 20] {
@@ -49,7 +104,7 @@ pub fn hash_160(public_key_bytes_compressed: &[u8]) -> [u8; 20] {
 /// Si la address es invalida, devuelve error
 pub fn get_pubkey_hash_from_address(address: &str) -> Result<[u8; 20], Box<dyn Error>> {
     //se decodifican de &str a bytes , desde el formate base58  a bytes
-    validate_address(address)?;
+    validate_base58_address(address)?;
     let address_decoded_bytes = bs58::decode(address).into_vec()?;
     let lenght_bytes = address_decoded_bytes.len();
     let mut pubkey_hash: [u8; 20] = [0; 20];
@@ -61,6 +116,28 @@ pub fn get_pubkey_hash_from_address(address: &str) -> Result<[u8; 20], Box<dyn E
     Ok(pubkey_hash)
 }
 
+/// Recibe una address P2WPKH (bech32) de testnet
+/// Devuelve el PubkeyHash que forma su witness program
+/// Si la address es invalida o no es P2WPKH, devuelve error
+pub fn get_pubkey_hash_from_segwit_address(address: &str) -> Result<[u8; 20], Box<dyn Error>> {
+    let (version, program) = decode_segwit_address(TESTNET_HRP, address)?;
+    if version != 0 || program.len() != 20 {
+        return Err(Box::new(std::io::Error::other(
+            "La dirección bech32 no es P2WPKH.",
+        )));
+    }
+    let mut pubkey_hash: [u8; 20] = [0; 20];
+    pubkey_hash.copy_from_slice(&program);
+    Ok(pubkey_hash)
+}
+
+/// Devuelve true si la address es bech32 de testnet (comienza con "tb1")
+pub fn is_segwit_address(address: &str) -> bool {
+    address
+        .to_lowercase()
+        .starts_with(&format!("{}1", TESTNET_HRP))
+}
+
 /// Devuelve la clave publica comprimida (33 bytes) a partir de la privada
 pub fn get_pubkey_compressed(private_key: &str) -> Result<[u8; 33], Box<dyn Error>> {
     let private_key = decode_wif_private_key(private_key)?;
@@ -70,9 +147,17 @@ pub fn get_pubkey_compressed(private_key: &str) -> Result<[u8; 33], Box<dyn Erro
     Ok(public_key.serialize())
 }
 
-/// Recibe una bitcoin address.
+/// Recibe una bitcoin address de testnet de cualquiera de los tipos estandar
+/// (P2PKH, P2SH, P2WPKH, P2WSH o P2TR).
 /// Revisa el checksum y devuelve error si es inválida.
 pub fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
+    Address::parse(address)?;
+    Ok(())
+}
+
+/// Recibe una address P2PKH en base58.
+/// Revisa el checksum y devuelve error si es inválida.
+fn validate_base58_address(address: &str) -> Result<(), Box<dyn Error>> {
     if address.len() != ADDRESS_LEN {
         return Err(Box::new(std::io::Error::new(
             io::ErrorKind::Other,
@@ -97,13 +182,18 @@ pub fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-/// Recibe una private key en bytes y una address comprimida.
+/// Recibe una private key en bytes y una address comprimida (P2PKH o P2WPKH).
 /// Devuelve true o false dependiendo si se corresponden entre si o no.
 pub fn validate_address_private_key(
     private_key: &[u8],
     address: &String,
 ) -> Result<(), Box<dyn Error>> {
-    if !generate_address(private_key)?.eq(address) {
+    let generated_address = if is_segwit_address(address) {
+        generate_p2wpkh_address(private_key)?
+    } else {
+        generate_address(private_key)?
+    };
+    if !generated_address.eq(address) {
         return Err(Box::new(std::io::Error::new(
             io::ErrorKind::Other,
             "The private key does not correspond to the address",
@@ -112,6 +202,18 @@ pub fn validate_address_private_key(
     Ok(())
 }
 
+/// Recibe la private key en bytes.
+/// Devuelve la WIF private key comprimida de testnet
+pub fn encode_wif_private_key(private_key: &[u8; 32]) -> String {
+    let mut extended_key = vec![0xef];
+    extended_key.extend_from_slice(private_key);
+    // el byte 0x01 indica que la clave publica se usa comprimida
+    extended_key.push(0x01);
+    let checksum = Sha256::digest(Sha256::digest(&extended_key));
+    extended_key.extend_from_slice(&checksum[..4]);
+    bs58::encode(extended_key).into_string()
+}
+
 /// Recibe la WIF private key, ya sea en formato comprimido o no comprimido.
 /// Devuelve la private key en bytes
 pub fn decode_wif_private_key(wif_private_key: &str) -> Result<[u8; 32], Box<dyn Error>> {
@@ -149,7 +251,12 @@ pub fn decode_wif_private_key(wif_private_key: &str) -> Result<[u8; 32], Box<dyn
 mod test {
     use super::get_pubkey_hash_from_address;
     use crate::address_decoder::decode_wif_private_key;
+    use crate::address_decoder::encode_wif_private_key;
     use crate::address_decoder::generate_address;
+    use crate::address_decoder::{
+        generate_p2wpkh_address, generate_private_key, get_pubkey_hash_from_segwit_address,
+        validate_address, validate_address_private_key, AddressType,
+    };
     use secp256k1::SecretKey;
     use std::error::Error;
     use std::io;
@@ -212,6 +319,14 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_encoding_wif_comprimida_devuelve_la_wif_original() -> Result<(), Box<dyn Error>> {
+        let wif = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
+        let private_key = decode_wif_private_key(wif)?;
+        assert_eq!(encode_wif_private_key(&private_key), wif);
+        Ok(())
+    }
+
     #[test]
     fn test_address_se_genera_correctamente() -> Result<(), Box<dyn Error>> {
         let expected_address: &str = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
@@ -248,6 +363,43 @@ mod test {
         assert_eq!(pub_key_hash.len(), 20);
         Ok(())
     }
+    #[test]
+    fn test_address_p2wpkh_se_genera_y_decodifica_correctamente() -> Result<(), Box<dyn Error>> {
+        let private_key_bytes =
+            decode_wif_private_key("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR")?;
+        let address = generate_p2wpkh_address(&private_key_bytes)?;
+        assert!(address.starts_with("tb1q"));
+        assert!(validate_address(&address).is_ok());
+        assert!(validate_address_private_key(&private_key_bytes, &address).is_ok());
+        assert_eq!(
+            get_pubkey_hash_from_segwit_address(&address)?,
+            generate_pubkey_hash(&private_key_bytes)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_las_claves_generadas_son_distintas_y_tienen_direcciones_validas(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: dos claves privadas generadas
+        let private_key = generate_private_key();
+        let other_private_key = generate_private_key();
+        // WHEN: se generan sus direcciones de cada tipo y su WIF
+        let p2pkh = AddressType::parse("legacy")?.address(&private_key)?;
+        let p2wpkh = AddressType::parse("P2WPKH")?.address(&private_key)?;
+        let wif = encode_wif_private_key(&private_key);
+        // THEN: las claves son distintas, las direcciones validas y el WIF es comprimido
+        assert_ne!(private_key, other_private_key);
+        assert!(p2pkh.starts_with('m') || p2pkh.starts_with('n'));
+        assert!(p2wpkh.starts_with("tb1q"));
+        assert!(validate_address_private_key(&private_key, &p2pkh).is_ok());
+        assert!(validate_address_private_key(&private_key, &p2wpkh).is_ok());
+        assert!(wif.starts_with('c'));
+        assert_eq!(decode_wif_private_key(&wif)?, private_key);
+        assert!(AddressType::parse("p2tr").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_get_pubkey_hash_con_direccion_invalida_da_error() -> Result<(), Box<dyn Error>> {
         let address = "1nEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
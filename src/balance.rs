@@ -0,0 +1,89 @@
+use crate::utxo_tuple::UtxoTuple;
+
+/// Confirmaciones que necesita el output de una coinbase para poder gastarse
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// Reglas con las que se decide que monedas se pueden gastar: el minimo de confirmaciones que se les pide
+/// y la altura actual de la cadena con la que se cuentan. Con minimo 0 tambien se gastan las pendientes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpendPolicy {
+    pub min_confirmations: u32,
+    pub tip_height: u32,
+}
+
+impl SpendPolicy {
+    pub fn new(min_confirmations: u32, tip_height: u32) -> Self {
+        SpendPolicy {
+            min_confirmations,
+            tip_height,
+        }
+    }
+}
+
+/// Parte del balance en la que cae una moneda de la cuenta
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoinCategory {
+    /// Confirmada con al menos el minimo de confirmaciones
+    Confirmed,
+    /// Recibida de otros y todavia sin el minimo de confirmaciones
+    UnconfirmedIncoming,
+    /// Cambio de una transaccion de la cuenta todavia sin el minimo de confirmaciones
+    UnconfirmedChange,
+    /// Output de una coinbase con menos de COINBASE_MATURITY confirmaciones
+    Immature,
+    /// Bloqueada o congelada con coin control
+    Locked,
+}
+
+/// Moneda de la cuenta con la parte del balance en la que cae y si las reglas permiten gastarla.
+/// El utxo tiene solo el output de la moneda
+#[derive(Debug, Clone)]
+pub struct ClassifiedCoin {
+    pub utxo: UtxoTuple,
+    pub category: CoinCategory,
+    pub spendable: bool,
+}
+
+impl ClassifiedCoin {
+    /// Devuelve el monto en satoshis de la moneda
+    pub fn value(&self) -> i64 {
+        self.utxo.balance()
+    }
+}
+
+/// Balance de una cuenta separado segun si se puede gastar
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BalanceBreakdown {
+    pub confirmed: i64,
+    pub unconfirmed_incoming: i64,
+    pub unconfirmed_change: i64,
+    pub immature: i64,
+    pub locked: i64,
+}
+
+impl BalanceBreakdown {
+    /// Arma el balance sumando el monto de cada moneda en la parte que le corresponde
+    pub fn from_coins(coins: &[ClassifiedCoin]) -> Self {
+        let mut breakdown = BalanceBreakdown::default();
+        for coin in coins {
+            let part = match coin.category {
+                CoinCategory::Confirmed => &mut breakdown.confirmed,
+                CoinCategory::UnconfirmedIncoming => &mut breakdown.unconfirmed_incoming,
+                CoinCategory::UnconfirmedChange => &mut breakdown.unconfirmed_change,
+                CoinCategory::Immature => &mut breakdown.immature,
+                CoinCategory::Locked => &mut breakdown.locked,
+            };
+            *part += coin.value();
+        }
+        breakdown
+    }
+
+    /// Devuelve la suma de todas las partes del balance
+    pub fn total(&self) -> i64 {
+        self.confirmed
+            + self.unconfirmed_incoming
+            + self.unconfirmed_change
+            + self.immature
+            + self.locked
+    }
+}
@@ -0,0 +1,94 @@
+use std::error::Error;
+use std::io;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+/// Codifica los bytes en base64 con el alfabeto estandar y padding (RFC 4648)
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push(PADDING as char);
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodifica un string base64 con el alfabeto estandar. Ignora los espacios y saltos de linea.
+/// Devuelve error si tiene caracteres invalidos o el largo no es multiplo de 4
+pub fn decode(encoded: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let chars: Vec<u8> = encoded
+        .bytes()
+        .filter(|char| !char.is_ascii_whitespace())
+        .collect();
+    if !chars.len().is_multiple_of(4) {
+        return Err(invalid_base64());
+    }
+    let mut bytes = Vec::new();
+    for (chunk_index, chunk) in chars.chunks(4).enumerate() {
+        let is_last_chunk = (chunk_index + 1) * 4 == chars.len();
+        let padding = chunk
+            .iter()
+            .rev()
+            .take_while(|char| **char == PADDING)
+            .count();
+        if padding > 2 || (padding > 0 && !is_last_chunk) {
+            return Err(invalid_base64());
+        }
+        let mut group: u32 = 0;
+        for char in &chunk[..4 - padding] {
+            let value = ALPHABET
+                .iter()
+                .position(|alphabet_char| alphabet_char == char)
+                .ok_or_else(invalid_base64)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+        let group_bytes = group.to_be_bytes();
+        bytes.extend_from_slice(&group_bytes[1..4 - padding]);
+    }
+    Ok(bytes)
+}
+
+fn invalid_base64() -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "El string no es base64 válido.",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc4648_vectors_are_encoded_and_decoded() -> Result<(), Box<dyn Error>> {
+        // GIVEN: los vectores de prueba de RFC 4648
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (decoded, encoded) in vectors {
+            // WHEN: se codifican y decodifican
+            // THEN: se obtiene el resultado esperado
+            assert_eq!(encode(decoded.as_bytes()), encoded);
+            assert_eq!(decode(encoded)?, decoded.as_bytes());
+        }
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Zg==Zm9v").is_err());
+        Ok(())
+    }
+}
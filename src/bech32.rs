@@ -0,0 +1,228 @@
+use std::error::Error;
+use std::io;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+/// Constante con la que se termina el checksum bech32 (BIP173)
+const BECH32_CONST: u32 = 1;
+/// Constante con la que se termina el checksum bech32m (BIP350)
+const BECH32M_CONST: u32 = 0x2bc830a3;
+/// Largo maximo de una direccion bech32
+const MAX_ADDRESS_LENGTH: usize = 90;
+/// Human readable part de las direcciones bech32 de testnet
+pub const TESTNET_HRP: &str = "tb";
+
+/// Codifica una direccion segwit. Recibe el human readable part de la red ("bc" o "tb"),
+/// la version del witness y el witness program. La version 0 se codifica en bech32 (BIP173)
+/// y las siguientes en bech32m (BIP350). Devuelve error si el programa es invalido para la version
+pub fn encode_segwit_address(
+    hrp: &str,
+    version: u8,
+    program: &[u8],
+) -> Result<String, Box<dyn Error>> {
+    validate_witness_program(version, program)?;
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true)?);
+    Ok(encode(hrp, &data, checksum_const(version)))
+}
+
+/// Decodifica una direccion segwit de la red con el human readable part recibido.
+/// Devuelve la version del witness y el witness program. Devuelve error si la direccion es de otra red,
+/// si el checksum no corresponde a la version (bech32 para la 0 y bech32m para las demas) o si el programa es invalido
+pub fn decode_segwit_address(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), Box<dyn Error>> {
+    if address.len() > MAX_ADDRESS_LENGTH
+        || (address.to_lowercase() != address && address.to_uppercase() != address)
+    {
+        return Err(invalid_address(
+            "Direccion bech32 con largo o mayusculas invalidos",
+        ));
+    }
+    let address = address.to_lowercase();
+    let separator = address
+        .rfind('1')
+        .ok_or_else(|| invalid_address("La direccion bech32 no tiene separador"))?;
+    if &address[..separator] != hrp {
+        return Err(invalid_address("La direccion no corresponde a la red"));
+    }
+    let mut data = vec![];
+    for character in address[separator + 1..].bytes() {
+        let value = CHARSET
+            .iter()
+            .position(|charset_character| *charset_character == character)
+            .ok_or_else(|| invalid_address("Caracter invalido en la direccion bech32"))?;
+        data.push(value as u8);
+    }
+    if data.len() < 7 {
+        return Err(invalid_address("La direccion bech32 es demasiado corta"));
+    }
+    let version = data[0];
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(&data);
+    if polymod(&values) != checksum_const(version) {
+        return Err(invalid_address(
+            "El checksum de la direccion bech32 es invalido",
+        ));
+    }
+    let program = convert_bits(&data[1..data.len() - 6], 5, 8, false)?;
+    validate_witness_program(version, &program)?;
+    Ok((version, program))
+}
+
+/// Devuelve la constante del checksum que corresponde a la version del witness
+fn checksum_const(version: u8) -> u32 {
+    if version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    }
+}
+
+/// Devuelve error si la version o el largo del witness program son invalidos (BIP141)
+fn validate_witness_program(version: u8, program: &[u8]) -> Result<(), Box<dyn Error>> {
+    if version > 16 || program.len() < 2 || program.len() > 40 {
+        return Err(invalid_address(
+            "Version o largo de witness program invalido",
+        ));
+    }
+    if version == 0 && program.len() != 20 && program.len() != 32 {
+        return Err(invalid_address(
+            "El witness program de version 0 debe tener 20 o 32 bytes",
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_address(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+/// Codifica el hrp y los datos (en grupos de 5 bits) agregando el checksum correspondiente a la constante recibida
+fn encode(hrp: &str, data: &[u8], checksum_const: u32) -> String {
+    let checksum = create_checksum(hrp, data, checksum_const);
+    let mut encoded = format!("{}1", hrp);
+    for value in data.iter().chain(checksum.iter()) {
+        encoded.push(CHARSET[*value as usize] as char);
+    }
+    encoded
+}
+
+/// Calcula el polymod de BCH sobre los valores recibidos
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Expande el hrp para el calculo del checksum
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut expanded: Vec<u8> = hrp.bytes().map(|byte| byte >> 5).collect();
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|byte| byte & 31));
+    expanded
+}
+
+/// Devuelve los 6 valores del checksum para el hrp y los datos recibidos
+fn create_checksum(hrp: &str, data: &[u8], checksum_const: u32) -> Vec<u8> {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; 6]);
+    let polymod = polymod(&values) ^ checksum_const;
+    (0..6)
+        .map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8)
+        .collect()
+}
+
+/// Reagrupa los bits de los datos recibidos de grupos de `from` bits a grupos de `to` bits.
+/// Devuelve error si algun valor no entra en `from` bits o si sobran bits sin padding
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value: u32 = (1 << to) - 1;
+    let mut converted = vec![];
+    for value in data {
+        if (*value as u32) >> from != 0 {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Valor invalido al reagrupar los bits",
+            )));
+        }
+        accumulator = (accumulator << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            converted.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            converted.push(((accumulator << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || ((accumulator << (to - bits)) & max_value) != 0 {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Padding invalido al reagrupar los bits",
+        )));
+    }
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p2wpkh_program_is_encoded_as_the_bip173_address() -> Result<(), Box<dyn Error>> {
+        // GIVEN: el witness program del ejemplo de BIP173
+        let program = [
+            0x75, 0x1e, 0x76, 0xe8, 0x19, 0x91, 0x96, 0xd4, 0x54, 0x94, 0x1c, 0x45, 0xd1, 0xb3,
+            0xa3, 0x23, 0xf1, 0x43, 0x3b, 0xd6,
+        ];
+        // WHEN: se codifica para testnet
+        let address = encode_segwit_address("tb", 0, &program)?;
+        // THEN: se obtiene la direccion del BIP
+        assert_eq!(address, "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx");
+        assert_eq!(
+            decode_segwit_address("tb", &address)?,
+            (0, program.to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn taproot_program_is_encoded_with_bech32m() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la direccion taproot de ejemplo de BIP350
+        let address = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+        // WHEN: se decodifica y se vuelve a codificar
+        let (version, program) = decode_segwit_address("bc", address)?;
+        // THEN: es un witness de version 1 de 32 bytes y se obtiene la misma direccion
+        assert_eq!(version, 1);
+        assert_eq!(program.len(), 32);
+        assert_eq!(encode_segwit_address("bc", version, &program)?, address);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_bech32_addresses_are_rejected() {
+        // GIVEN: direcciones con checksum invalido, de otra red, con mayusculas mezcladas
+        // y una version 0 codificada con bech32m
+        let invalid_addresses = [
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsy",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "tb1qW508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+        ];
+        // WHEN: se decodifican para testnet
+        // THEN: devuelven error
+        for address in invalid_addresses {
+            assert!(decode_segwit_address("tb", address).is_err());
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use std::{
     collections::HashMap,
+    error::Error,
     sync::{Arc, RwLock},
 };
 
 use crate::{
+    blockchain_download::header_validation::chain_work,
     blocks::{block::Block, block_header::BlockHeader},
+    custom_errors::NodeCustomErrors,
     utxo_tuple::UtxoTuple,
 };
 type UtxoSetPointer = Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>;
@@ -60,4 +63,120 @@ impl Blockchain {
         }
         None
     }
+
+    /// Devuelve el trabajo acumulado de la cadena de headers
+    pub fn chain_work(&self) -> Result<u128, NodeCustomErrors> {
+        let headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        Ok(chain_work(&headers))
+    }
+
+    /// Devuelve los hashes del block locator de la cadena de headers, ordenados del mas nuevo al mas viejo:
+    /// los ultimos 10 headers uno por uno, luego con saltos que se duplican y por ultimo el genesis.
+    /// Se usa en el mensaje getheaders para que el otro nodo encuentre el ultimo header en comun
+    pub fn block_locator(&self) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        Ok(block_locator_from_headers(&headers))
+    }
+
+    /// Recorre en orden los bloques descargados de la cadena principal desde la altura recibida,
+    /// llamando a la funcion recibida con cada uno. Devuelve el primer error que devuelva la funcion
+    pub fn visit_blocks_from(
+        &self,
+        from_height: usize,
+        mut visit: impl FnMut(&Block) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let blocks = self
+            .blocks
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        for header in headers.iter().skip(from_height) {
+            if let Some(block) = blocks.get(&header.hash()) {
+                visit(block)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Devuelve los hashes de los bloques de la cadena principal desde la altura recibida que no estan descargados
+    pub fn missing_blocks_from(
+        &self,
+        from_height: usize,
+    ) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let blocks = self
+            .blocks
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        Ok(headers
+            .iter()
+            .skip(from_height)
+            .map(|header| header.hash())
+            .filter(|hash| !blocks.contains_key(hash))
+            .collect())
+    }
+
+    /// Devuelve la altura del ultimo header de la cadena, que se usa para calcular las confirmaciones
+    pub fn tip_height(&self) -> Result<u32, NodeCustomErrors> {
+        let headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        Ok(headers.len().saturating_sub(1) as u32)
+    }
+}
+
+/// Recibe la cadena de headers y devuelve los hashes de su block locator
+pub fn block_locator_from_headers(headers: &[BlockHeader]) -> Vec<[u8; 32]> {
+    let mut locator = vec![];
+    if headers.is_empty() {
+        return locator;
+    }
+    let mut index = headers.len() - 1;
+    let mut step = 1;
+    loop {
+        locator.push(headers[index].hash());
+        if index == 0 {
+            break;
+        }
+        if locator.len() >= 10 {
+            step *= 2;
+        }
+        index = index.saturating_sub(step);
+    }
+    locator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_locator_goes_from_tip_to_genesis() {
+        // GIVEN: una cadena de 100 headers
+        let headers: Vec<BlockHeader> = (0..100)
+            .map(|nonce| BlockHeader::new(1, [0; 32], [0; 32], 0, 0x1d00ffff, nonce))
+            .collect();
+        // WHEN: se arma el block locator
+        let locator = block_locator_from_headers(&headers);
+        // THEN: empieza por el ultimo header, tiene los ultimos 10 consecutivos y termina en el primero
+        assert_eq!(locator[0], headers[99].hash());
+        assert_eq!(locator[9], headers[90].hash());
+        assert_eq!(locator[10], headers[88].hash());
+        assert_eq!(locator[11], headers[84].hash());
+        assert_eq!(*locator.last().unwrap(), headers[0].hash());
+        assert!(locator.len() < 20);
+    }
 }
@@ -0,0 +1,400 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    blocks::{block::Block, block_header::BlockHeader},
+    custom_errors::NodeCustomErrors,
+};
+
+/// Cantidad maxima de bloques, contando desde el proximo a conectar, que pueden estar pedidos o
+/// recibidos a la espera de ser conectados. Mismo valor que usa Bitcoin Core
+pub const BLOCK_DOWNLOAD_WINDOW: usize = 1024;
+/// Tiempo que se espera al nodo que tiene pedido el bloque que frena la ventana antes de reasignarlo
+pub const BLOCK_STALLING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Tiempo maximo que puede tardar un nodo en responder cualquier bloque pedido
+pub const BLOCK_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pedido de un bloque que todavia no fue respondido
+#[derive(Debug, Clone, Copy)]
+struct InFlightRequest {
+    peer: usize,
+    requested_at: Instant,
+}
+
+/// Planificador de la descarga de bloques entre varios nodos.
+/// Mantiene una ventana deslizante de pedidos en vuelo repartidos entre todos los nodos, con un limite
+/// de bloques pedidos por nodo. Los pedidos de nodos lentos o trabados y los que responden notfound se
+/// vuelven a encolar para que los pida otro nodo. Los bloques recibidos se devuelven para conectar
+/// estrictamente en orden de altura.
+/// Los nodos se identifican con un numero, el planificador no maneja las conexiones.
+#[derive(Debug)]
+pub struct BlockDownloadScheduler {
+    headers: Vec<BlockHeader>,
+    positions: HashMap<[u8; 32], usize>,
+    pending: BTreeSet<usize>,
+    in_flight: HashMap<usize, InFlightRequest>,
+    not_found: HashMap<usize, HashSet<usize>>,
+    received: HashMap<usize, Block>,
+    next_to_connect: usize,
+    peers: HashSet<usize>,
+    max_in_flight_per_peer: usize,
+    window_size: usize,
+    headers_complete: bool,
+    failure: Option<String>,
+}
+
+impl BlockDownloadScheduler {
+    /// Crea el planificador sin bloques para descargar, con el limite de bloques en vuelo por nodo
+    /// y el tamaño de la ventana recibidos
+    pub fn new(max_in_flight_per_peer: usize, window_size: usize) -> Self {
+        BlockDownloadScheduler {
+            headers: Vec::new(),
+            positions: HashMap::new(),
+            pending: BTreeSet::new(),
+            in_flight: HashMap::new(),
+            not_found: HashMap::new(),
+            received: HashMap::new(),
+            next_to_connect: 0,
+            peers: HashSet::new(),
+            max_in_flight_per_peer: max_in_flight_per_peer.max(1),
+            window_size: window_size.max(1),
+            headers_complete: false,
+            failure: None,
+        }
+    }
+
+    /// Agrega al final de la cola los headers de los bloques a descargar. Deben llegar en orden de altura.
+    /// Los headers repetidos se ignoran
+    pub fn add_headers(&mut self, headers: Vec<BlockHeader>) {
+        for header in headers {
+            let hash = header.hash();
+            if self.positions.contains_key(&hash) {
+                continue;
+            }
+            let position = self.headers.len();
+            self.positions.insert(hash, position);
+            self.pending.insert(position);
+            self.headers.push(header);
+        }
+    }
+
+    /// Indica que no van a llegar mas headers para descargar
+    pub fn finish_headers(&mut self) {
+        self.headers_complete = true;
+    }
+
+    /// Devuelve true si ya no van a llegar mas headers y todos los bloques fueron conectados
+    pub fn is_finished(&self) -> bool {
+        self.headers_complete && self.next_to_connect == self.headers.len()
+    }
+
+    /// Devuelve la cantidad de bloques ya conectados
+    pub fn connected_blocks(&self) -> usize {
+        self.next_to_connect
+    }
+
+    /// Agrega un nodo a la descarga
+    pub fn add_peer(&mut self, peer: usize) {
+        self.peers.insert(peer);
+    }
+
+    /// Devuelve true si el nodo sigue participando de la descarga. Un nodo deja de participar
+    /// cuando se lo quita por fallar o por trabar la descarga
+    pub fn is_active(&self, peer: usize) -> bool {
+        self.peers.contains(&peer)
+    }
+
+    /// Devuelve la cantidad de nodos que participan de la descarga
+    pub fn active_peers(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Quita el nodo de la descarga y vuelve a encolar todos los bloques que tenia pedidos
+    pub fn remove_peer(&mut self, peer: usize) {
+        self.peers.remove(&peer);
+        self.requeue_requests_of(peer);
+    }
+
+    /// Devuelve la cantidad de bloques pedidos al nodo que todavia no respondio
+    pub fn in_flight_of(&self, peer: usize) -> usize {
+        self.in_flight
+            .values()
+            .filter(|request| request.peer == peer)
+            .count()
+    }
+
+    /// Devuelve los headers de los proximos bloques que el nodo tiene que pedir y los marca como pedidos.
+    /// Se respeta el limite de bloques en vuelo del nodo y la ventana de descarga, y no se le asignan
+    /// bloques que el nodo ya respondio como notfound
+    pub fn next_requests(&mut self, peer: usize, now: Instant) -> Vec<BlockHeader> {
+        if !self.is_active(peer) || self.failure.is_some() {
+            return vec![];
+        }
+        let available = self
+            .max_in_flight_per_peer
+            .saturating_sub(self.in_flight_of(peer));
+        let window_end = self.next_to_connect + self.window_size;
+        let positions: Vec<usize> = self
+            .pending
+            .range(..window_end)
+            .filter(|position| {
+                !self
+                    .not_found
+                    .get(position)
+                    .is_some_and(|peers| peers.contains(&peer))
+            })
+            .take(available)
+            .copied()
+            .collect();
+        let mut requests = vec![];
+        for position in positions {
+            self.pending.remove(&position);
+            self.in_flight.insert(
+                position,
+                InFlightRequest {
+                    peer,
+                    requested_at: now,
+                },
+            );
+            requests.push(self.headers[position]);
+        }
+        requests
+    }
+
+    /// Registra un bloque recibido. Lo saca de los pedidos en vuelo, sin importar a que nodo se le pidio,
+    /// y lo guarda hasta que pueda conectarse. Devuelve false si el bloque no fue pedido o ya se habia recibido
+    pub fn block_received(&mut self, block: Block) -> bool {
+        let position = match self.positions.get(&block.hash()) {
+            Some(position) => *position,
+            None => return false,
+        };
+        if position < self.next_to_connect || self.received.contains_key(&position) {
+            return false;
+        }
+        self.in_flight.remove(&position);
+        self.pending.remove(&position);
+        self.not_found.remove(&position);
+        self.received.insert(position, block);
+        true
+    }
+
+    /// Registra los bloques que el nodo respondio con notfound y los vuelve a encolar para pedirselos a otro nodo.
+    /// Devuelve error si ninguno de los nodos activos tiene alguno de esos bloques
+    pub fn blocks_not_found(
+        &mut self,
+        peer: usize,
+        hashes: &[[u8; 32]],
+    ) -> Result<(), NodeCustomErrors> {
+        for hash in hashes {
+            let position = match self.positions.get(hash) {
+                Some(position) => *position,
+                None => continue,
+            };
+            match self.in_flight.get(&position) {
+                Some(request) if request.peer == peer => {
+                    self.in_flight.remove(&position);
+                    self.pending.insert(position);
+                }
+                _ => continue,
+            }
+            let peers_without_block = self.not_found.entry(position).or_default();
+            peers_without_block.insert(peer);
+            if self.peers.is_subset(peers_without_block) {
+                let error = format!(
+                    "Ningun nodo tiene el bloque {:?} pedido en la descarga",
+                    hash
+                );
+                self.failure = Some(error.clone());
+                return Err(NodeCustomErrors::BlockchainDownloadError(error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Marca la descarga como fallida para que todos los nodos dejen de descargar
+    pub fn fail(&mut self, error: String) {
+        self.failure = Some(error);
+    }
+
+    /// Devuelve el error por el que fallo la descarga, si fallo
+    pub fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+
+    /// Busca los nodos que traban la descarga: el que tiene pedido el proximo bloque a conectar cuando la
+    /// ventana esta llena y lo tiene hace mas de BLOCK_STALLING_TIMEOUT, y los que tienen algun pedido sin
+    /// responder hace mas de BLOCK_DOWNLOAD_TIMEOUT. Sus pedidos se vuelven a encolar y, si no es el unico
+    /// nodo de la descarga, se lo quita. Devuelve los nodos encontrados
+    pub fn check_stalls(&mut self, now: Instant) -> Vec<usize> {
+        let mut stalling_peers = vec![];
+        if self.window_is_full() {
+            if let Some(request) = self.in_flight.get(&self.next_to_connect) {
+                if now.saturating_duration_since(request.requested_at) > BLOCK_STALLING_TIMEOUT {
+                    stalling_peers.push(request.peer);
+                }
+            }
+        }
+        for request in self.in_flight.values() {
+            if now.saturating_duration_since(request.requested_at) > BLOCK_DOWNLOAD_TIMEOUT
+                && !stalling_peers.contains(&request.peer)
+            {
+                stalling_peers.push(request.peer);
+            }
+        }
+        for peer in &stalling_peers {
+            if self.peers.len() > 1 {
+                self.remove_peer(*peer);
+            } else {
+                self.requeue_requests_of(*peer);
+            }
+        }
+        stalling_peers
+    }
+
+    /// Saca y devuelve, en orden de altura, los bloques recibidos que ya se pueden conectar
+    pub fn take_blocks_to_connect(&mut self) -> Vec<Block> {
+        let mut blocks = vec![];
+        while let Some(block) = self.received.remove(&self.next_to_connect) {
+            blocks.push(block);
+            self.next_to_connect += 1;
+        }
+        blocks
+    }
+
+    /// Devuelve true si no hay bloques pendientes dentro de la ventana pero si despues de ella,
+    /// es decir, si la descarga esta frenada esperando el proximo bloque a conectar
+    fn window_is_full(&self) -> bool {
+        let window_end = self.next_to_connect + self.window_size;
+        self.pending.range(..window_end).next().is_none()
+            && self.pending.range(window_end..).next().is_some()
+    }
+
+    /// Vuelve a encolar todos los bloques pedidos al nodo
+    fn requeue_requests_of(&mut self, peer: usize) {
+        let positions: Vec<usize> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| request.peer == peer)
+            .map(|(position, _)| *position)
+            .collect();
+        for position in positions {
+            self.in_flight.remove(&position);
+            self.pending.insert(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compact_size_uint::CompactSizeUint;
+
+    /// Devuelve una cadena de bloques sin transacciones de la cantidad recibida
+    fn chain_of_blocks(amount: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = vec![];
+        let mut previous_hash = [0; 32];
+        for nonce in 0..amount {
+            let header = BlockHeader::new(1, previous_hash, [0; 32], 0, 0x1d00ffff, nonce);
+            previous_hash = header.hash();
+            blocks.push(Block::new(header, CompactSizeUint::new(0), vec![]));
+        }
+        blocks
+    }
+
+    fn scheduler_with_blocks(
+        blocks: &[Block],
+        max_in_flight_per_peer: usize,
+        window_size: usize,
+    ) -> BlockDownloadScheduler {
+        let mut scheduler = BlockDownloadScheduler::new(max_in_flight_per_peer, window_size);
+        scheduler.add_headers(blocks.iter().map(|block| block.block_header).collect());
+        scheduler.finish_headers();
+        scheduler
+    }
+
+    #[test]
+    fn requests_respect_the_per_peer_limit_and_the_window() {
+        // GIVEN: 10 bloques, un limite de 3 bloques por nodo y una ventana de 5 bloques
+        let blocks = chain_of_blocks(10);
+        let mut scheduler = scheduler_with_blocks(&blocks, 3, 5);
+        scheduler.add_peer(0);
+        scheduler.add_peer(1);
+        let now = Instant::now();
+        // WHEN: los dos nodos piden bloques
+        let first_requests = scheduler.next_requests(0, now);
+        let second_requests = scheduler.next_requests(1, now);
+        // THEN: el primero recibe 3 bloques y el segundo solo los 2 que quedan dentro de la ventana
+        assert_eq!(first_requests.len(), 3);
+        assert_eq!(second_requests.len(), 2);
+        assert_eq!(first_requests[0].hash(), blocks[0].hash());
+        assert_eq!(second_requests[1].hash(), blocks[4].hash());
+        assert!(scheduler.next_requests(1, now).is_empty());
+    }
+
+    #[test]
+    fn blocks_are_connected_in_height_order() {
+        // GIVEN: 3 bloques pedidos a un nodo
+        let blocks = chain_of_blocks(3);
+        let mut scheduler = scheduler_with_blocks(&blocks, 16, 1024);
+        scheduler.add_peer(0);
+        scheduler.next_requests(0, Instant::now());
+        // WHEN: llegan el tercero y el segundo antes que el primero
+        scheduler.block_received(blocks[2].clone());
+        scheduler.block_received(blocks[1].clone());
+        let blocks_before_first = scheduler.take_blocks_to_connect();
+        scheduler.block_received(blocks[0].clone());
+        let blocks_to_connect = scheduler.take_blocks_to_connect();
+        // THEN: no se conecta ninguno hasta que llega el primero y despues se conectan los tres en orden
+        assert!(blocks_before_first.is_empty());
+        assert_eq!(blocks_to_connect.len(), 3);
+        for (block, expected) in blocks_to_connect.iter().zip(blocks.iter()) {
+            assert_eq!(block.hash(), expected.hash());
+        }
+        assert!(scheduler.is_finished());
+        assert!(!scheduler.block_received(blocks[0].clone()));
+    }
+
+    #[test]
+    fn not_found_blocks_are_requested_from_other_peer() -> Result<(), NodeCustomErrors> {
+        // GIVEN: un bloque pedido al nodo 0
+        let blocks = chain_of_blocks(1);
+        let mut scheduler = scheduler_with_blocks(&blocks, 16, 1024);
+        scheduler.add_peer(0);
+        scheduler.add_peer(1);
+        let now = Instant::now();
+        scheduler.next_requests(0, now);
+        // WHEN: el nodo 0 responde notfound
+        scheduler.blocks_not_found(0, &[blocks[0].hash()])?;
+        // THEN: no se le vuelve a pedir al nodo 0 pero si al nodo 1
+        assert!(scheduler.next_requests(0, now).is_empty());
+        assert_eq!(scheduler.next_requests(1, now).len(), 1);
+        // y si el nodo 1 tampoco lo tiene la descarga falla
+        assert!(scheduler.blocks_not_found(1, &[blocks[0].hash()]).is_err());
+        assert!(scheduler.failure().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn peer_stalling_the_window_is_removed_and_its_blocks_reassigned() {
+        // GIVEN: una ventana de 2 bloques donde el nodo 0 tiene el primero y el nodo 1 el segundo
+        let blocks = chain_of_blocks(4);
+        let mut scheduler = scheduler_with_blocks(&blocks, 1, 2);
+        scheduler.add_peer(0);
+        scheduler.add_peer(1);
+        let requested_at = Instant::now();
+        scheduler.next_requests(0, requested_at);
+        scheduler.next_requests(1, requested_at);
+        scheduler.block_received(blocks[1].clone());
+        // WHEN: pasa mas tiempo que BLOCK_STALLING_TIMEOUT sin que el nodo 0 responda
+        let stalling_peers =
+            scheduler.check_stalls(requested_at + BLOCK_STALLING_TIMEOUT + Duration::from_secs(1));
+        // THEN: se quita al nodo 0 y el nodo 1 pide el primer bloque
+        assert_eq!(stalling_peers, vec![0]);
+        assert!(!scheduler.is_active(0));
+        let requests = scheduler.next_requests(1, Instant::now());
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].hash(), blocks[0].hash());
+    }
+}
@@ -8,48 +8,73 @@ use crate::{
     gtk::ui_events::{send_event_to_ui, UIEvent},
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{
-        block_message::BlockMessage, get_data_message::GetDataMessage, inventory::Inventory,
+        block_message::BlockMessage,
+        get_data_message::GetDataMessage,
+        inventory::Inventory,
+        message_header::{read_payload, write_pong_message, HeaderMessage},
+        payload::get_data_payload::unmarshalling,
     },
 };
 use std::{
     collections::HashMap,
+    io::{self, Read},
     net::TcpStream,
     sync::{
         mpsc::{Receiver, Sender},
-        Arc, RwLock,
+        Arc, RwLock, RwLockWriteGuard,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use super::{
-    get_amount_of_headers_and_blocks, join_threads,
+    block_download_scheduler::{
+        BlockDownloadScheduler, BLOCK_DOWNLOAD_TIMEOUT, BLOCK_DOWNLOAD_WINDOW,
+    },
+    join_threads,
     utils::{get_node, return_node_to_vec},
 };
 
+/// Tipo de inventario de los bloques en los mensajes getdata y notfound
+const MSG_BLOCK: u32 = 2;
+/// Tiempo que se espera a que llegue un mensaje del nodo antes de revisar el estado de la descarga
+const MESSAGE_WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+/// Timeout de lectura con el que se devuelven los nodos a la lista al terminar la descarga
+const NODE_READ_TIMEOUT: Duration = Duration::from_secs(2);
+
 type BlocksAndHeaders = (
     Arc<RwLock<HashMap<[u8; 32], Block>>>,
     Arc<RwLock<Vec<BlockHeader>>>,
 );
 
-type BlocksTuple = (
-    Vec<BlockHeader>,
-    Arc<RwLock<HashMap<[u8; 32], Block>>>,
-    Arc<RwLock<Vec<BlockHeader>>>,
-);
+/// Lo que comparten todos los threads que descargan bloques
+#[derive(Clone)]
+struct BlockDownloadContext {
+    config: Arc<Config>,
+    log_sender: LogSender,
+    ui_sender: Option<glib::Sender<UIEvent>>,
+    scheduler: Arc<RwLock<BlockDownloadScheduler>>,
+    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    headers: Arc<RwLock<Vec<BlockHeader>>>,
+    nodes: Arc<RwLock<Vec<TcpStream>>>,
+    tx_utxo_set: Sender<Vec<Block>>,
+}
 
 /// # Descarga de bloques
-/// Realiza la descarga de bloques de forma concurrente.
+/// Realiza la descarga de bloques de forma concurrente desde varios nodos, con un thread por nodo.
+/// Los pedidos se reparten con un BlockDownloadScheduler, que mantiene una ventana deslizante de bloques
+/// pedidos entre todos los nodos y limita la cantidad de bloques pedidos a cada uno.
 /// ### Recibe:
 /// - La referencia a la lista de nodos a los que se conectar.
 /// - La referencia a la lista de bloques donde los almacenará
 /// - La referencia a los block headers descargados
-/// - El channel por donde recibe los block headers
-/// - El channel por donde devuelve los block headers cuando no los puede descargar
+/// - El channel por donde recibe los block headers y el channel por donde envia los bloques al utxo set
+/// - La cantidad maxima de nodos desde los que se descarga a la vez
 ///
 /// ### Manejo de errores:
-/// Vuelve a intentar la descarga con un nuevo nodo, en los siguientes casos:
-/// - No se pudo realizar la solicitud de los bloques
-/// - No se pudo recibir el bloque
+/// - Los bloques pedidos a un nodo que falla, que responde notfound o que traba la descarga se le piden a otro nodo
+/// - Los nodos que fallan o traban la descarga se descartan y se reemplazan por otros de la lista si los hay,
+///   tanto mientras llegan headers como despues de que terminan de llegar
 ///
 /// ### Devuelve:
 /// - Ok o un error si no se puede completar la descarga
@@ -59,271 +84,346 @@ pub fn download_blocks(
     ui_sender: &Option<glib::Sender<UIEvent>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
     (blocks, headers): BlocksAndHeaders,
-    (tx, rx): (Sender<Vec<BlockHeader>>, Receiver<Vec<BlockHeader>>),
-    tx_utxo_set: Sender<Vec<Block>>,
+    (rx, tx_utxo_set): (Receiver<Vec<BlockHeader>>, Sender<Vec<Block>>),
+    max_peers: usize,
 ) -> Result<(), NodeCustomErrors> {
+    let context = BlockDownloadContext {
+        config: config.clone(),
+        log_sender: log_sender.clone(),
+        ui_sender: ui_sender.clone(),
+        scheduler: Arc::new(RwLock::new(BlockDownloadScheduler::new(
+            config.blocks_download_per_node,
+            BLOCK_DOWNLOAD_WINDOW,
+        ))),
+        blocks,
+        headers,
+        nodes,
+        tx_utxo_set,
+    };
+    let mut join_handles = vec![];
+    let mut next_peer = 0;
     // recieves in the channel the vec of headers sent by the function downloading headers
     for blocks_to_download in rx {
         if blocks_to_download.is_empty() {
+            write_scheduler(&context.scheduler)?
+                .fail("Se recibio una lista con 0 elementos!".to_string());
             return Err(NodeCustomErrors::ThreadChannelError(
                 "Se recibio una lista con 0 elementos!".to_string(),
             ));
         }
-        // acá recibo 2000 block headers
-        let mut n_threads = config.n_threads;
-        if blocks_to_download.len() <= config.blocks_download_per_node {
-            n_threads = 1;
-        }
-        let blocks_to_download_chunks =
-            divide_blocks_to_download_in_equal_chunks(blocks_to_download, n_threads);
-        let mut join_handles = vec![];
-        for blocks_to_download_chunk in blocks_to_download_chunks
-            .read()
-            .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?
-            .iter()
-        {
-            join_handles.push(download_blocks_chunck(
-                config,
-                log_sender,
-                ui_sender,
-                (blocks_to_download_chunk.clone(), headers.clone()),
-                nodes.clone(),
-                (tx.clone(), tx_utxo_set.clone()),
-                blocks.clone(),
-            )?);
-        }
-        join_threads(join_handles)?;
-        let (amount_of_headers, amount_of_blocks) =
-            get_amount_of_headers_and_blocks(&headers, &blocks)?;
-        let total_blocks_to_download = amount_of_headers - config.height_first_block_to_download;
-        if amount_of_blocks == total_blocks_to_download {
-            write_in_log(&log_sender.info_log_sender, format!("Se terminaron de descargar todos los bloques correctamente! BLOQUES DESCARGADOS: {}\n", amount_of_blocks).as_str());
-            return Ok(());
-        }
+        write_scheduler(&context.scheduler)?.add_headers(blocks_to_download);
+        spawn_download_threads(&context, max_peers, &mut next_peer, &mut join_handles)?;
+    }
+    write_scheduler(&context.scheduler)?.finish_headers();
+    wait_for_download(&context, max_peers, &mut next_peer, &mut join_handles)?;
+    join_threads(join_handles)?;
+
+    let scheduler = write_scheduler(&context.scheduler)?;
+    if let Some(error) = scheduler.failure() {
+        return Err(NodeCustomErrors::BlockchainDownloadError(error));
     }
+    if !scheduler.is_finished() {
+        return Err(NodeCustomErrors::BlockchainDownloadError(
+            "Error no quedan nodos para terminar la descarga de bloques!\n".to_string(),
+        ));
+    }
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "Se terminaron de descargar todos los bloques correctamente! BLOQUES DESCARGADOS: {}\n",
+            scheduler.connected_blocks()
+        )
+        .as_str(),
+    );
     Ok(())
 }
 
-/// Se encarga de crear el thread desde el cual se van a descargar un vector de bloques.
-/// Devuelve el handle del thread creado o error en caso de no poder crearlo.
-fn download_blocks_chunck(
-    config: &Arc<Config>,
-    log_sender: &LogSender,
-    ui_sender: &Option<glib::Sender<UIEvent>>,
-    (block_headers, headers): (Vec<BlockHeader>, Arc<RwLock<Vec<BlockHeader>>>),
-    nodes: Arc<RwLock<Vec<TcpStream>>>,
-    (tx, tx_utxo_set): (Sender<Vec<BlockHeader>>, Sender<Vec<Block>>),
-    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
-) -> Result<JoinHandle<Result<(), NodeCustomErrors>>, NodeCustomErrors> {
-    let config_cloned = config.clone();
-    let log_sender_cloned = log_sender.clone();
-    let node = get_node(nodes.clone())?;
-    let ui_sender = ui_sender.clone();
-    Ok(thread::spawn(move || {
-        download_blocks_single_thread(
-            &config_cloned,
-            &log_sender_cloned,
-            &ui_sender,
-            (block_headers, blocks, headers),
-            node,
-            (tx, tx_utxo_set),
-            nodes,
-        )
-    }))
+/// Crea un thread de descarga por cada nodo disponible en la lista hasta llegar a la cantidad maxima
+/// de nodos descargando a la vez. Si no hay mas nodos en la lista no hace nada.
+/// Devuelve error en caso de no poder acceder al planificador
+fn spawn_download_threads(
+    context: &BlockDownloadContext,
+    max_peers: usize,
+    next_peer: &mut usize,
+    join_handles: &mut Vec<JoinHandle<Result<(), NodeCustomErrors>>>,
+) -> Result<(), NodeCustomErrors> {
+    while write_scheduler(&context.scheduler)?.active_peers() < max_peers {
+        let node = match get_node(context.nodes.clone()) {
+            Ok(node) => node,
+            Err(_) => break,
+        };
+        let peer = *next_peer;
+        *next_peer += 1;
+        write_scheduler(&context.scheduler)?.add_peer(peer);
+        let context = context.clone();
+        join_handles.push(thread::spawn(move || {
+            download_blocks_from_peer(&context, peer, node)
+        }));
+    }
+    Ok(())
 }
 
-/// Downloads all the blocks from the same node, in the same thread.
-/// The blocks are stored in the blocks list received by parameter.
-/// In the end, the node is also return to the list of nodes
-/// ## Errors
-/// In case of Read or Write error on the node, the function is terminated, discarding the problematic node.
-/// The downloaded blocks upon the error are discarded, so the whole block chunk can be downloaded again from another node
-/// In other cases, it returns error.
-fn download_blocks_single_thread(
-    config: &Arc<Config>,
-    log_sender: &LogSender,
-    ui_sender: &Option<glib::Sender<UIEvent>>,
-    (block_headers, blocks, headers): BlocksTuple,
+/// Espera a que termine la descarga una vez que llegaron todos los headers. Cada vez que un nodo deja la descarga,
+/// porque fallo o la trabo, lo reemplaza por otro de la lista. Termina cuando se descargaron todos los bloques,
+/// cuando la descarga falla o cuando no queda ningun nodo descargando ni en la lista para reemplazarlo.
+/// Devuelve error en caso de no poder acceder al planificador
+fn wait_for_download(
+    context: &BlockDownloadContext,
+    max_peers: usize,
+    next_peer: &mut usize,
+    join_handles: &mut Vec<JoinHandle<Result<(), NodeCustomErrors>>>,
+) -> Result<(), NodeCustomErrors> {
+    loop {
+        spawn_download_threads(context, max_peers, next_peer, join_handles)?;
+        let scheduler = write_scheduler(&context.scheduler)?;
+        if scheduler.failure().is_some() || scheduler.is_finished() || scheduler.active_peers() == 0
+        {
+            return Ok(());
+        }
+        drop(scheduler);
+        thread::sleep(MESSAGE_WAIT_TIMEOUT);
+    }
+}
+
+/// Descarga bloques del nodo hasta que termine la descarga. En cada vuelta le pide al nodo los bloques
+/// que le asigna el planificador, procesa el siguiente mensaje que llegue y revisa si algun nodo trabo la descarga.
+/// Al terminar devuelve el nodo a la lista de nodos.
+/// ## Errores
+/// Si falla la lectura o escritura con el nodo, o si este envia un bloque invalido, se descarta el nodo
+/// y sus bloques se le piden a otro. En otros casos se marca la descarga como fallida y se devuelve error.
+fn download_blocks_from_peer(
+    context: &BlockDownloadContext,
+    peer: usize,
     mut node: TcpStream,
-    (tx, tx_utxo_set): (Sender<Vec<BlockHeader>>, Sender<Vec<Block>>),
-    nodes: Arc<RwLock<Vec<TcpStream>>>,
 ) -> Result<(), NodeCustomErrors> {
-    let mut current_blocks: HashMap<[u8; 32], Block> = HashMap::new();
-    // el thread recibe 250 bloques
     write_in_log(
-        &log_sender.info_log_sender,
+        &context.log_sender.info_log_sender,
         format!(
-            "Voy a descargar {:?} bloques del nodo {:?}",
-            block_headers.len(),
+            "Empiezo a descargar bloques del nodo {:?}",
             node.peer_addr()
         )
         .as_str(),
     );
-    for blocks_to_download in block_headers.chunks(config.blocks_download_per_node) {
-        match request_blocks_from_node(
-            log_sender,
-            &mut node,
-            blocks_to_download,
-            block_headers.clone(),
-            Some(tx.clone()),
-        ) {
+    loop {
+        let requests = {
+            let mut scheduler = write_scheduler(&context.scheduler)?;
+            if scheduler.failure().is_some() || scheduler.is_finished() {
+                drop(scheduler);
+                node.set_read_timeout(Some(NODE_READ_TIMEOUT))
+                    .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+                return return_node_to_vec(context.nodes.clone(), node);
+            }
+            if !scheduler.is_active(peer) {
+                write_in_log(
+                    &context.log_sender.error_log_sender,
+                    format!(
+                        "El nodo {:?} trabo la descarga de bloques. Lo descarto y sus bloques se le piden a otro nodo",
+                        node.peer_addr()
+                    )
+                    .as_str(),
+                );
+                return Ok(());
+            }
+            scheduler.next_requests(peer, Instant::now())
+        };
+        let result = request_blocks_from_node(&mut node, &requests)
+            .and_then(|_| receive_message_from_node(context, peer, &mut node));
+        match result {
             Ok(_) => {}
-            Err(NodeCustomErrors::WriteNodeError(_)) => return Ok(()),
-            Err(error) => return Err(error),
+            Err(NodeCustomErrors::ReadNodeError(err))
+            | Err(NodeCustomErrors::WriteNodeError(err)) => {
+                return discard_peer(context, peer, node, err);
+            }
+            Err(err) => {
+                write_scheduler(&context.scheduler)?.fail(err.to_string());
+                return Err(err);
+            }
         }
-        let received_blocks = match receive_requested_blocks_from_node(
-            log_sender,
-            &mut node,
-            blocks_to_download,
-            block_headers.clone(),
-            Some(tx.clone()),
-        ) {
-            Ok(blocks) => blocks,
-            Err(NodeCustomErrors::ReadNodeError(_)) => return Ok(()),
-            Err(error) => return Err(error),
-        };
-        tx_utxo_set
-            .send(received_blocks.clone())
-            .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-        for block in received_blocks.into_iter() {
-            current_blocks.insert(block.hash(), block);
+        let stalling_peers = write_scheduler(&context.scheduler)?.check_stalls(Instant::now());
+        if !stalling_peers.is_empty() {
+            write_in_log(
+                &context.log_sender.info_log_sender,
+                format!(
+                    "{} nodo/s trabaron la descarga de bloques, se reasignan sus bloques",
+                    stalling_peers.len()
+                )
+                .as_str(),
+            );
         }
     }
-    add_blocks_downloaded_to_local_blocks(
-        config,
-        log_sender,
-        ui_sender,
-        headers,
-        blocks,
-        current_blocks,
-    )?;
-    return_node_to_vec(nodes, node)?;
+}
+
+/// Quita al nodo de la descarga para que sus bloques se le pidan a otro y lo descarta
+fn discard_peer(
+    context: &BlockDownloadContext,
+    peer: usize,
+    node: TcpStream,
+    error: String,
+) -> Result<(), NodeCustomErrors> {
+    write_scheduler(&context.scheduler)?.remove_peer(peer);
+    write_in_log(
+        &context.log_sender.error_log_sender,
+        format!(
+            "Fallo la descarga de bloques con el nodo {:?}. Lo descarto y sus bloques se le piden a otro nodo. Error: {}",
+            node.peer_addr(),
+            error
+        )
+        .as_str(),
+    );
     Ok(())
 }
 
-/// Requests the blocks to the node.
-/// ## Errors
-/// In case of error while sending the message, it returns the block headers back to the channel so
-/// they can be downloaded from another node. If this cannot be done, returns an error.
+/// Le pide al nodo los bloques de los headers recibidos con un mensaje getdata.
+/// Devuelve error del tipo WriteNodeError si no se pudo enviar el mensaje
 fn request_blocks_from_node(
-    log_sender: &LogSender,
     node: &mut TcpStream,
-    blocks_chunk_to_download: &[BlockHeader],
-    blocks_to_download: Vec<BlockHeader>,
-    tx: Option<Sender<Vec<BlockHeader>>>,
+    blocks_to_download: &[BlockHeader],
 ) -> Result<(), NodeCustomErrors> {
-    //  Acá ya separé los 250 en chunks de 16 para las llamadas
-    let mut inventory = vec![];
-    for block in blocks_chunk_to_download {
-        inventory.push(Inventory::new_block(block.hash()));
-    }
-    match GetDataMessage::new(inventory).write_to(node) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            write_in_log(&log_sender.error_log_sender,format!("Error: No puedo pedir {:?} cantidad de bloques del nodo: {:?}. Se los voy a pedir a otro nodo", blocks_chunk_to_download.len(), node.peer_addr()).as_str());
-            try_to_download_blocks_from_other_node(tx, blocks_to_download)?;
-            // falló el envio del mensaje, tengo que intentar con otro nodo
-            // si hago return, termino el thread.
-            // tengo que enviar todos los bloques que tenía ese thread
-            Err(NodeCustomErrors::WriteNodeError(format!("{:?}", err)))
-        }
+    if blocks_to_download.is_empty() {
+        return Ok(());
     }
+    let inventory = blocks_to_download
+        .iter()
+        .map(|header| Inventory::new_block(header.hash()))
+        .collect();
+    GetDataMessage::new(inventory)
+        .write_to(node)
+        .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))
 }
 
-/// Receives the blocks previously requested to the node.
-/// Returns an array with the blocks.
-/// In case of error while receiving the message, it returns the block headers back to the channel so
-/// they can be downloaded from another node. If this cannot be done, returns an error.
-fn receive_requested_blocks_from_node(
-    log_sender: &LogSender,
+/// Espera el siguiente mensaje del nodo y lo procesa: los bloques se entregan al planificador y se conectan
+/// los que ya estan en orden, los notfound se reasignan a otro nodo y los ping se responden. El resto se ignora.
+/// Si no llega ningun mensaje en MESSAGE_WAIT_TIMEOUT no hace nada.
+/// Devuelve ReadNodeError o WriteNodeError si falla la comunicacion con el nodo o si este envia algo invalido
+fn receive_message_from_node(
+    context: &BlockDownloadContext,
+    peer: usize,
     node: &mut TcpStream,
-    blocks_chunk_to_download: &[BlockHeader],
-    blocks_to_download: Vec<BlockHeader>,
-    tx: Option<Sender<Vec<BlockHeader>>>,
-) -> Result<Vec<Block>, NodeCustomErrors> {
-    // Acá tengo que recibir los 16 bloques (o menos) de la llamada
-    let mut current_blocks: Vec<Block> = Vec::new();
-    for _ in 0..blocks_chunk_to_download.len() {
-        let block = match BlockMessage::read_from(log_sender, node) {
-            Ok(block) => block,
-            Err(err) => {
-                write_in_log(&log_sender.error_log_sender,format!("No puedo descargar {:?} de bloques del nodo: {:?}. Se los voy a pedir a otro nodo y descarto este. Error: {err}", blocks_chunk_to_download.len(), node.peer_addr()).as_str());
-                try_to_download_blocks_from_other_node(tx, blocks_to_download)?;
-                // falló la recepción del mensaje, tengo que intentar con otro nodo
-                // termino el nodo con el return
+) -> Result<(), NodeCustomErrors> {
+    let (header, payload) =
+        match read_message(node).map_err(|err| NodeCustomErrors::ReadNodeError(err.to_string()))? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+    let command_name = header.command_name.trim_end_matches('\0');
+    match command_name {
+        "block" => {
+            let block = BlockMessage::unmarshalling(&payload)
+                .map_err(|err| NodeCustomErrors::ReadNodeError(err.to_string()))?;
+            let (is_valid, validation_error) = block.validate();
+            if !is_valid {
                 return Err(NodeCustomErrors::ReadNodeError(format!(
-                    "Error al recibir el mensaje `block`: {:?}",
-                    err
+                    "El bloque no pasó la validación. {}",
+                    validation_error
                 )));
             }
-        };
-        let validation_result = block.validate();
-        if !validation_result.0 {
-            write_in_log(&log_sender.error_log_sender,format!("El bloque no pasó la validación. {:?}. Se los voy a pedir a otro nodo y descarto este.", validation_result.1).as_str());
-            try_to_download_blocks_from_other_node(tx, blocks_to_download)?;
-            return Err(NodeCustomErrors::ReadNodeError(format!(
-                "Error al recibir el mensaje `block`: {:?}",
-                validation_result.1
-            )));
+            let mut scheduler = write_scheduler(&context.scheduler)?;
+            if scheduler.block_received(block) {
+                // se conecta con el planificador tomado para que los bloques se conecten en orden de altura
+                let blocks_to_connect = scheduler.take_blocks_to_connect();
+                if !blocks_to_connect.is_empty() {
+                    connect_blocks(context, blocks_to_connect)?;
+                }
+            }
+        }
+        "notfound" => {
+            let hashes: Vec<[u8; 32]> = unmarshalling(&payload)
+                .map_err(|err| NodeCustomErrors::ReadNodeError(err.to_string()))?
+                .iter()
+                .filter(|inventory| inventory.type_identifier == MSG_BLOCK)
+                .map(|inventory| inventory.hash())
+                .collect();
+            write_in_log(
+                &context.log_sender.info_log_sender,
+                format!(
+                    "El nodo {:?} no tiene {} de los bloques pedidos, se le piden a otro nodo",
+                    node.peer_addr(),
+                    hashes.len()
+                )
+                .as_str(),
+            );
+            write_scheduler(&context.scheduler)?.blocks_not_found(peer, &hashes)?;
+        }
+        "ping" => {
+            write_pong_message(node, &payload)
+                .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+        }
+        _ => {
+            write_in_log(
+                &context.log_sender.message_log_sender,
+                format!(
+                    "IGNORADO -- Recibo: {} -- Nodo: {:?}",
+                    command_name,
+                    node.peer_addr()
+                )
+                .as_str(),
+            );
         }
-        //block.set_utxos(); // seteo utxos de las transacciones del bloque
-        current_blocks.push(block);
     }
-    Ok(current_blocks)
+    Ok(())
 }
 
-/// Descarga todos los bloques desde un solo nodo
-/// Devuelve error en caso de falla
-pub fn download_blocks_single_node(
-    config: &Arc<Config>,
-    log_sender: &LogSender,
-    ui_sender: &Option<glib::Sender<UIEvent>>,
-    (blocks, headers): BlocksAndHeaders,
-    block_headers: Vec<BlockHeader>,
-    node: &mut TcpStream,
-    tx_utxo_set: Sender<Vec<Block>>,
-) -> Result<(), NodeCustomErrors> {
-    let mut current_blocks: HashMap<[u8; 32], Block> = HashMap::new();
-    write_in_log(
-        &log_sender.info_log_sender,
-        format!(
-            "Voy a descargar {:?} bloques del nodo {:?}",
-            block_headers.len(),
-            node.peer_addr()
-        )
-        .as_str(),
-    );
-    for blocks_to_download in block_headers.chunks(config.blocks_download_per_node) {
-        request_blocks_from_node(
-            log_sender,
-            node,
-            blocks_to_download,
-            block_headers.clone(),
-            None,
-        )?;
-        let received_blocks = receive_requested_blocks_from_node(
-            log_sender,
-            node,
-            blocks_to_download,
-            block_headers.clone(),
-            None,
-        )?;
-        tx_utxo_set
-            .send(received_blocks.clone())
-            .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-        for block in received_blocks.into_iter() {
-            current_blocks.insert(block.hash(), block);
+/// Espera hasta MESSAGE_WAIT_TIMEOUT a que el nodo envie algo. Si no envia nada devuelve None, y sino lee
+/// el mensaje completo y devuelve su header y su payload. Devuelve error si falla la lectura
+fn read_message(node: &mut TcpStream) -> io::Result<Option<(HeaderMessage, Vec<u8>)>> {
+    let mut buffer = [0; 24];
+    node.set_read_timeout(Some(MESSAGE_WAIT_TIMEOUT))?;
+    // se usa peek para no consumir bytes del stream si se cumple el timeout a mitad de un mensaje
+    match node.peek(&mut buffer) {
+        Ok(0) => {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "El nodo cerro la conexion",
+            ))
+        }
+        Ok(_) => {}
+        Err(err)
+            if err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut =>
+        {
+            return Ok(None)
         }
+        Err(err) => return Err(err),
     }
+    node.set_read_timeout(Some(BLOCK_DOWNLOAD_TIMEOUT))?;
+    node.read_exact(&mut buffer)?;
+    let header = HeaderMessage::from_le_bytes(buffer)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    let payload = read_payload(node, &header)?;
+    Ok(Some((header, payload)))
+}
+
+/// Conecta los bloques recibidos, que deben estar en orden de altura: los envia al thread que actualiza
+/// el utxo set y los agrega a los bloques locales.
+/// Devuelve error si el channel esta cerrado o si no se puede acceder a los bloques locales
+fn connect_blocks(
+    context: &BlockDownloadContext,
+    blocks_to_connect: Vec<Block>,
+) -> Result<(), NodeCustomErrors> {
+    context
+        .tx_utxo_set
+        .send(blocks_to_connect.clone())
+        .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+    let downloaded_blocks = blocks_to_connect
+        .into_iter()
+        .map(|block| (block.hash(), block))
+        .collect();
     add_blocks_downloaded_to_local_blocks(
-        config,
-        log_sender,
-        ui_sender,
-        headers,
-        blocks,
-        current_blocks,
-    )?;
-    Ok(())
+        &context.config,
+        &context.log_sender,
+        &context.ui_sender,
+        context.headers.clone(),
+        context.blocks.clone(),
+        downloaded_blocks,
+    )
+}
+
+/// Toma el lock de escritura del planificador de la descarga
+fn write_scheduler(
+    scheduler: &Arc<RwLock<BlockDownloadScheduler>>,
+) -> Result<RwLockWriteGuard<'_, BlockDownloadScheduler>, NodeCustomErrors> {
+    scheduler
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))
 }
 
 /*
@@ -332,23 +432,6 @@ pub fn download_blocks_single_node(
 ***************************************************************************
 */
 
-/// Recibe un vector de block headers y devuelve un vector de vectores de block headers, donde cada vector tiene la misma cantidad de elementos.
-/// Los separa en chunks de igual tamaño.
-fn divide_blocks_to_download_in_equal_chunks(
-    blocks_to_download: Vec<BlockHeader>,
-    n_threads: usize,
-) -> Arc<RwLock<Vec<Vec<BlockHeader>>>> {
-    let chunk_size = (blocks_to_download.len() as f64 / n_threads as f64).ceil() as usize;
-    // divides the vec into 8 with the same lenght (or same lenght but the last with less)
-    let blocks_to_download_chunks = Arc::new(RwLock::new(
-        blocks_to_download
-            .chunks(chunk_size)
-            .map(|chunk| chunk.to_vec())
-            .collect::<Vec<_>>(),
-    ));
-    blocks_to_download_chunks
-}
-
 /// Recibe un hashmap de bloques y devuelve la cantidad de bloques que hay en el mismo
 /// Error en caso de no poder leerlo
 pub fn amount_of_blocks(
@@ -390,18 +473,153 @@ pub fn add_blocks_downloaded_to_local_blocks(
     Ok(())
 }
 
-/// Envia por el channel los headers recibidos por parametro para que los respectivos bloques sean descargados desde otro nodo
-/// Devuelve error en caso de que el channel este cerrado
-fn try_to_download_blocks_from_other_node(
-    tx: Option<Sender<Vec<BlockHeader>>>,
-    headers_read: Vec<BlockHeader>,
-) -> Result<(), NodeCustomErrors> {
-    match tx {
-        Some(tx) => {
-            tx.send(headers_read)
-                .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compact_size_uint::CompactSizeUint,
+        messages::block_message::get_block_message,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+    use std::{net::TcpListener, sync::mpsc::channel};
+
+    /// Devuelve una cadena de bloques validos de la cantidad recibida, con una coinbase cada uno
+    fn chain_of_blocks(amount: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = vec![];
+        let mut previous_hash = [0; 32];
+        for lock_time in 0..amount {
+            let tx_in = TxIn::new(
+                Outpoint::new([0; 32], 0xffffffff),
+                CompactSizeUint::new(5),
+                Some(vec![3, lock_time as u8, 0, 0]),
+                SigScript::new(vec![1]),
+                0xffffffff,
+            );
+            let tx_out = TxOut::new(50, CompactSizeUint::new(0), vec![]);
+            let transaction = Transaction::new(
+                1,
+                CompactSizeUint::new(1),
+                vec![tx_in],
+                CompactSizeUint::new(1),
+                vec![tx_out],
+                lock_time,
+            );
+            // se busca un nonce que cumpla con la dificultad minima
+            let mut nonce = 0;
+            let header = loop {
+                let header =
+                    BlockHeader::new(1, previous_hash, transaction.hash(), 0, 0x207fffff, nonce);
+                if header.validate() {
+                    break header;
+                }
+                nonce += 1;
+            };
+            previous_hash = header.hash();
+            blocks.push(Block::new(
+                header,
+                CompactSizeUint::new(1),
+                vec![transaction],
+            ));
         }
-        None => return Ok(()),
+        blocks
+    }
+
+    /// Crea un nodo que responde los getdata con los bloques recibidos y devuelve la conexion con el
+    fn peer_serving_blocks(blocks: Vec<Block>) -> Result<TcpStream, io::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let node = TcpStream::connect(listener.local_addr()?)?;
+        thread::spawn(move || -> io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            let blocks: HashMap<[u8; 32], Block> = blocks
+                .into_iter()
+                .map(|block| (block.hash(), block))
+                .collect();
+            loop {
+                let mut buffer = [0; 24];
+                stream.read_exact(&mut buffer)?;
+                let header = HeaderMessage::from_le_bytes(buffer)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+                let payload = read_payload(&mut stream, &header)?;
+                if header.command_name.trim_end_matches('\0') != "getdata" {
+                    continue;
+                }
+                let inventories = unmarshalling(&payload)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+                for inventory in inventories {
+                    if let Some(block) = blocks.get(&inventory.hash()) {
+                        io::Write::write_all(&mut stream, &get_block_message(block))?;
+                    }
+                }
+            }
+        });
+        Ok(node)
+    }
+
+    /// Crea un nodo que espera el primer getdata y corta la conexion sin responderlo
+    fn peer_failing_after_first_request() -> Result<TcpStream, io::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let node = TcpStream::connect(listener.local_addr()?)?;
+        thread::spawn(move || -> io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            let mut buffer = [0; 24];
+            stream.read_exact(&mut buffer)?;
+            // se espera a que se terminen de recibir los headers antes de fallar
+            thread::sleep(Duration::from_millis(200));
+            stream.shutdown(std::net::Shutdown::Both)
+        });
+        Ok(node)
+    }
+
+    #[test]
+    fn peer_failing_after_headers_finish_is_replaced() -> Result<(), Box<dyn std::error::Error>> {
+        // GIVEN: una descarga de a un nodo por vez, donde el primer nodo falla despues de que llegan
+        // todos los headers y queda otro nodo de repuesto en la lista
+        let mut config = (*Config::from(&["".to_string(), "nodo.conf".to_string()])?).clone();
+        config.height_first_block_to_download = 0;
+        let config = Arc::new(config);
+        let (log_tx, _log_rx) = channel();
+        let log_sender = LogSender {
+            info_log_sender: log_tx.clone(),
+            error_log_sender: log_tx.clone(),
+            message_log_sender: log_tx,
+        };
+        let blocks_to_download = chain_of_blocks(3);
+        let nodes = Arc::new(RwLock::new(vec![
+            peer_serving_blocks(blocks_to_download.clone())?,
+            peer_failing_after_first_request()?,
+        ]));
+        let blocks = Arc::new(RwLock::new(HashMap::new()));
+        let (tx_headers, rx_headers) = channel();
+        tx_headers.send(
+            blocks_to_download
+                .iter()
+                .map(|block| block.block_header)
+                .collect(),
+        )?;
+        drop(tx_headers);
+        let (tx_utxo_set, rx_utxo_set) = channel();
+        // WHEN: se descargan los bloques
+        download_blocks(
+            &config,
+            &log_sender,
+            &None,
+            nodes,
+            (blocks.clone(), Arc::new(RwLock::new(vec![]))),
+            (rx_headers, tx_utxo_set),
+            1,
+        )?;
+        // THEN: el nodo de repuesto descarga todos los bloques y se conectan en orden
+        let connected: Vec<[u8; 32]> = rx_utxo_set
+            .try_iter()
+            .flatten()
+            .map(|block| block.hash())
+            .collect();
+        let expected: Vec<[u8; 32]> = blocks_to_download.iter().map(Block::hash).collect();
+        assert_eq!(connected, expected);
+        assert_eq!(blocks.read().map_err(|err| err.to_string())?.len(), 3);
+        Ok(())
     }
-    Ok(())
 }
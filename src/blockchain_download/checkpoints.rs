@@ -0,0 +1,127 @@
+use super::header_validation::ConsensusParams;
+
+const MAINNET_START_STRING: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+/// Checkpoints de mainnet: altura y hash (en hexadecimal, como se muestra en los exploradores)
+/// de bloques que la cadena tiene que contener. Tomados de Bitcoin Core
+pub const MAINNET_CHECKPOINTS: &[(usize, &str)] = &[
+    (
+        11111,
+        "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d",
+    ),
+    (
+        33333,
+        "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6",
+    ),
+    (
+        74000,
+        "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20",
+    ),
+    (
+        105000,
+        "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97",
+    ),
+    (
+        134444,
+        "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe",
+    ),
+    (
+        168000,
+        "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763",
+    ),
+    (
+        193000,
+        "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317",
+    ),
+    (
+        210000,
+        "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e",
+    ),
+    (
+        216116,
+        "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e",
+    ),
+    (
+        225430,
+        "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932",
+    ),
+    (
+        250000,
+        "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214",
+    ),
+    (
+        279000,
+        "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40",
+    ),
+    (
+        295000,
+        "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983",
+    ),
+];
+
+/// Checkpoints de testnet3. Tomados de Bitcoin Core
+pub const TESTNET_CHECKPOINTS: &[(usize, &str)] = &[(
+    546,
+    "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70",
+)];
+
+/// Trabajo minimo que tiene que tener la cadena de mainnet para considerar que el nodo esta sincronizado.
+/// Es el valor de Bitcoin Core 0.21
+pub const MAINNET_MIN_CHAIN_WORK: u128 = 0x1533efd8d716a517fe2c5008;
+/// Trabajo minimo que tiene que tener la cadena de testnet3 para considerar que el nodo esta sincronizado.
+/// Es el valor de Bitcoin Core 0.21
+pub const TESTNET_MIN_CHAIN_WORK: u128 = 0x01db6ec4ac88cf2272c6;
+
+/// Reglas de consenso de mainnet
+pub const MAINNET_CONSENSUS_PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit_bits: 0x1d00ffff,
+    allow_min_difficulty_blocks: false,
+    checkpoints: MAINNET_CHECKPOINTS,
+    min_chain_work: MAINNET_MIN_CHAIN_WORK,
+};
+
+/// Reglas de consenso de testnet3
+pub const TESTNET_CONSENSUS_PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit_bits: 0x1d00ffff,
+    allow_min_difficulty_blocks: true,
+    checkpoints: TESTNET_CHECKPOINTS,
+    min_chain_work: TESTNET_MIN_CHAIN_WORK,
+};
+
+/// Devuelve las reglas de consenso de la red que corresponde al start string de la configuracion.
+/// Si no es el de mainnet se usan las de testnet3, que es la red para la que esta hecho el nodo
+pub fn consensus_params(start_string: [u8; 4]) -> ConsensusParams {
+    if start_string == MAINNET_START_STRING {
+        return MAINNET_CONSENSUS_PARAMS;
+    }
+    TESTNET_CONSENSUS_PARAMS
+}
+
+/// Devuelve true si hay algun checkpoint despues de la altura de la bifurcacion y antes del final de la
+/// cadena local, es decir, si aceptar la bifurcacion implicaria reemplazar un bloque de un checkpoint
+pub fn forks_below_checkpoint(
+    params: &ConsensusParams,
+    fork_height: usize,
+    chain_len: usize,
+) -> bool {
+    params
+        .checkpoints
+        .iter()
+        .any(|(height, _)| *height > fork_height && *height < chain_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forks_replacing_a_checkpoint_are_detected() {
+        // GIVEN: las reglas de testnet, con un checkpoint en la altura 546, y una cadena local de 1000 headers
+        let params = consensus_params([0x0b, 0x11, 0x09, 0x07]);
+        // WHEN: se consulta por bifurcaciones antes y despues del checkpoint
+        // THEN: solo la que reemplaza el bloque del checkpoint lo viola
+        assert!(forks_below_checkpoint(&params, 500, 1000));
+        assert!(!forks_below_checkpoint(&params, 546, 1000));
+        assert!(!forks_below_checkpoint(&params, 100, 500));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{blocks::block_header::BlockHeader, custom_errors::NodeCustomErrors};
+
+/// Cantidad de bloques entre cada ajuste de dificultad
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: usize = 2016;
+/// Tiempo esperado entre ajustes de dificultad: dos semanas en segundos
+const TARGET_TIMESPAN: u64 = 14 * 24 * 60 * 60;
+/// Tiempo esperado entre bloques en segundos
+const TARGET_SPACING: u32 = 10 * 60;
+
+/// Reglas de consenso de la red necesarias para validar una cadena de headers
+#[derive(Debug, Clone, Copy)]
+pub struct ConsensusParams {
+    /// Dificultad minima (target maximo) en formato compacto
+    pub pow_limit_bits: u32,
+    /// Si se permiten bloques de dificultad minima cuando pasan mas de 20 minutos sin bloques (testnet)
+    pub allow_min_difficulty_blocks: bool,
+    /// Alturas y hashes (en hexadecimal) de bloques que la cadena tiene que contener
+    pub checkpoints: &'static [(usize, &'static str)],
+    /// Trabajo minimo que tiene que tener la cadena para considerar que el nodo esta sincronizado
+    pub min_chain_work: u128,
+}
+
+/// Valida los headers recibidos como continuacion de la cadena local a partir del header de altura `fork_height`:
+/// que esten encadenados, que tengan la proof of work correcta, que su dificultad sea la que corresponde segun
+/// los ajustes de dificultad de la red y que coincidan con los checkpoints.
+/// Devuelve el trabajo acumulado de los headers recibidos o error si alguno no es valido
+pub fn validate_header_chain(
+    params: &ConsensusParams,
+    chain: &[BlockHeader],
+    fork_height: usize,
+    new_headers: &[BlockHeader],
+) -> Result<u128, NodeCustomErrors> {
+    if fork_height >= chain.len() {
+        return Err(NodeCustomErrors::InvalidHeaderError(
+            "Los headers no se enlazan con la cadena local".to_string(),
+        ));
+    }
+    let header_at = |height: usize| -> Option<&BlockHeader> {
+        if height <= fork_height {
+            chain.get(height)
+        } else {
+            new_headers.get(height - fork_height - 1)
+        }
+    };
+    let mut previous_hash = chain[fork_height].hash();
+    let mut work: u128 = 0;
+    for (index, header) in new_headers.iter().enumerate() {
+        let height = fork_height + 1 + index;
+        if header.previous_block_header_hash != previous_hash {
+            return Err(NodeCustomErrors::InvalidHeaderError(format!(
+                "El header de altura {} no esta encadenado con el anterior",
+                height
+            )));
+        }
+        if !header.validate() {
+            return Err(NodeCustomErrors::InvalidHeaderError(format!(
+                "El header de altura {} no tiene una proof of work valida",
+                height
+            )));
+        }
+        let expected_bits = next_work_required(params, height, header.time, &header_at)?;
+        if header.n_bits != expected_bits {
+            return Err(NodeCustomErrors::InvalidHeaderError(format!(
+                "El header de altura {} tiene dificultad {:#x} y se esperaba {:#x}",
+                height, header.n_bits, expected_bits
+            )));
+        }
+        previous_hash = header.hash();
+        if let Some((_, checkpoint_hash)) = params
+            .checkpoints
+            .iter()
+            .find(|(checkpoint_height, _)| *checkpoint_height == height)
+        {
+            if header.hex_hash() != *checkpoint_hash {
+                return Err(NodeCustomErrors::InvalidHeaderError(format!(
+                    "El header de altura {} no coincide con el checkpoint",
+                    height
+                )));
+            }
+        }
+        work = work.saturating_add(header_work(header.n_bits));
+    }
+    Ok(work)
+}
+
+/// Devuelve el trabajo acumulado de todos los headers recibidos
+pub fn chain_work(headers: &[BlockHeader]) -> u128 {
+    headers.iter().fold(0u128, |work, header| {
+        work.saturating_add(header_work(header.n_bits))
+    })
+}
+
+/// Devuelve la dificultad en formato compacto que tiene que tener el header de la altura recibida,
+/// segun los headers anteriores que devuelve `header_at`
+fn next_work_required<'a>(
+    params: &ConsensusParams,
+    height: usize,
+    time: u32,
+    header_at: &dyn Fn(usize) -> Option<&'a BlockHeader>,
+) -> Result<u32, NodeCustomErrors> {
+    let missing_header = || {
+        NodeCustomErrors::InvalidHeaderError(
+            "Faltan headers para calcular la dificultad".to_string(),
+        )
+    };
+    let previous = header_at(height - 1).ok_or_else(missing_header)?;
+    if !height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+        if !params.allow_min_difficulty_blocks {
+            return Ok(previous.n_bits);
+        }
+        // en testnet si pasan mas de 20 minutos sin bloques se permite un bloque de dificultad minima
+        if time > previous.time.saturating_add(TARGET_SPACING * 2) {
+            return Ok(params.pow_limit_bits);
+        }
+        // sino se usa la dificultad del ultimo bloque que no sea de dificultad minima
+        let mut last_height = height - 1;
+        let mut last = previous;
+        while !last_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL)
+            && last.n_bits == params.pow_limit_bits
+        {
+            last_height -= 1;
+            last = header_at(last_height).ok_or_else(missing_header)?;
+        }
+        return Ok(last.n_bits);
+    }
+    let first = header_at(height - DIFFICULTY_ADJUSTMENT_INTERVAL).ok_or_else(missing_header)?;
+    Ok(retarget(
+        params,
+        previous.n_bits,
+        previous.time as i64 - first.time as i64,
+    ))
+}
+
+/// Calcula la nueva dificultad a partir de la dificultad anterior y el tiempo que tardaron los ultimos 2016 bloques
+fn retarget(params: &ConsensusParams, previous_bits: u32, actual_timespan: i64) -> u32 {
+    let timespan = actual_timespan.clamp(TARGET_TIMESPAN as i64 / 4, TARGET_TIMESPAN as i64 * 4);
+    let target = compact_to_target(previous_bits);
+    let pow_limit = compact_to_target(params.pow_limit_bits);
+    let new_target = match multiply_target(&target, timespan as u64) {
+        Some(multiplied) => divide_target(&multiplied, TARGET_TIMESPAN),
+        None => pow_limit,
+    };
+    if new_target > pow_limit {
+        return target_to_compact(&pow_limit);
+    }
+    target_to_compact(&new_target)
+}
+
+/// Devuelve el trabajo (cantidad esperada de hashes) necesario para encontrar un header con la dificultad recibida
+pub fn header_work(n_bits: u32) -> u128 {
+    let exponent = (n_bits >> 24) as i64;
+    let mantissa = (n_bits & 0x007fffff) as u128;
+    if mantissa == 0 {
+        return u128::MAX;
+    }
+    // target = mantissa * 2^(8 * (exponent - 3)), trabajo = 2^256 / target
+    let shift = 256 - 8 * (exponent - 3);
+    if shift < 0 {
+        return 0;
+    }
+    if shift <= 127 {
+        return (1u128 << shift) / mantissa;
+    }
+    let base = (1u128 << 127) / mantissa;
+    if shift - 127 >= base.leading_zeros() as i64 {
+        return u128::MAX;
+    }
+    base << (shift - 127)
+}
+
+/// Convierte la dificultad en formato compacto al target, en 32 bytes big endian
+fn compact_to_target(n_bits: u32) -> [u8; 32] {
+    let mut target = [0u8; 32];
+    let exponent = (n_bits >> 24) as i64;
+    let mantissa = (n_bits & 0x007fffff).to_be_bytes();
+    for (i, byte) in mantissa[1..].iter().enumerate() {
+        let position = 32 - exponent + i as i64;
+        if (0..32).contains(&position) {
+            target[position as usize] = *byte;
+        }
+    }
+    target
+}
+
+/// Convierte el target, en 32 bytes big endian, a formato compacto
+fn target_to_compact(target: &[u8; 32]) -> u32 {
+    let first_byte = match target.iter().position(|byte| *byte != 0) {
+        Some(position) => position,
+        None => return 0,
+    };
+    let mut size = (32 - first_byte) as u32;
+    let mut mantissa: u32 = 0;
+    for i in 0..3 {
+        mantissa <<= 8;
+        if let Some(byte) = target.get(first_byte + i) {
+            mantissa |= *byte as u32;
+        }
+    }
+    if mantissa & 0x00800000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    (size << 24) | mantissa
+}
+
+/// Multiplica el target por el numero recibido. Devuelve None si el resultado no entra en 32 bytes
+fn multiply_target(target: &[u8; 32], multiplier: u64) -> Option<[u8; 32]> {
+    let mut result = [0u8; 32];
+    let mut carry: u128 = 0;
+    for i in (0..32).rev() {
+        let value = target[i] as u128 * multiplier as u128 + carry;
+        result[i] = (value & 0xff) as u8;
+        carry = value >> 8;
+    }
+    if carry != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+/// Divide el target por el numero recibido
+fn divide_target(target: &[u8; 32], divisor: u64) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    let mut remainder: u128 = 0;
+    for i in 0..32 {
+        remainder = (remainder << 8) | target[i] as u128;
+        result[i] = (remainder / divisor as u128) as u8;
+        remainder %= divisor as u128;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reglas de consenso con dificultad minima muy baja para poder minar headers en los tests
+    const TEST_PARAMS: ConsensusParams = ConsensusParams {
+        pow_limit_bits: 0x207fffff,
+        allow_min_difficulty_blocks: false,
+        checkpoints: &[],
+        min_chain_work: 0,
+    };
+
+    /// Busca un nonce para que el header cumpla con la proof of work
+    fn mine(mut header: BlockHeader) -> BlockHeader {
+        while !header.validate() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    /// Devuelve una cadena de headers validos con la dificultad minima de TEST_PARAMS
+    fn mined_chain(length: u32) -> Vec<BlockHeader> {
+        let mut chain = vec![mine(BlockHeader::new(
+            1, [0; 32], [0; 32], 0, 0x207fffff, 0,
+        ))];
+        for i in 1..length {
+            let previous_hash = chain[i as usize - 1].hash();
+            chain.push(mine(BlockHeader::new(
+                1,
+                previous_hash,
+                [0; 32],
+                i * 600,
+                0x207fffff,
+                0,
+            )));
+        }
+        chain
+    }
+
+    #[test]
+    fn valid_headers_extending_the_chain_are_accepted() -> Result<(), NodeCustomErrors> {
+        // GIVEN: una cadena de 6 headers validos, de los cuales los primeros 3 son la cadena local
+        let chain = mined_chain(6);
+        // WHEN: se validan los ultimos 3 como continuacion de la cadena local
+        let work = validate_header_chain(&TEST_PARAMS, &chain[..3], 2, &chain[3..])?;
+        // THEN: son validos y se devuelve su trabajo
+        assert_eq!(work, 3 * header_work(0x207fffff));
+        Ok(())
+    }
+
+    #[test]
+    fn unlinked_headers_or_wrong_difficulty_are_rejected() {
+        // GIVEN: una cadena de 4 headers validos
+        let chain = mined_chain(4);
+        // WHEN: se validan headers salteando uno o con una dificultad distinta a la esperada
+        let wrong_bits = mine(BlockHeader::new(
+            1,
+            chain[2].hash(),
+            [0; 32],
+            1800,
+            0x1f7fffff,
+            0,
+        ));
+        // THEN: ambas cadenas se rechazan
+        assert!(validate_header_chain(&TEST_PARAMS, &chain[..2], 1, &chain[3..]).is_err());
+        assert!(validate_header_chain(&TEST_PARAMS, &chain[..3], 2, &[wrong_bits]).is_err());
+    }
+
+    #[test]
+    fn headers_not_matching_a_checkpoint_are_rejected() {
+        // GIVEN: reglas con un checkpoint en la altura 2 distinto al header de esa altura
+        let chain = mined_chain(3);
+        let params = ConsensusParams {
+            checkpoints: &[(
+                2,
+                "0707070707070707070707070707070707070707070707070707070707070707",
+            )],
+            ..TEST_PARAMS
+        };
+        // WHEN: se valida la cadena
+        // THEN: se rechaza
+        assert!(validate_header_chain(&params, &chain[..1], 0, &chain[1..]).is_err());
+        assert!(validate_header_chain(&TEST_PARAMS, &chain[..1], 0, &chain[1..]).is_ok());
+    }
+
+    #[test]
+    fn retarget_follows_the_time_of_the_last_period() {
+        // GIVEN: la dificultad de 0x1c0ffff0
+        // WHEN: los ultimos 2016 bloques tardaron el doble, la mitad o mas de 4 veces lo esperado
+        // THEN: el target se duplica, se divide por dos o se multiplica por 4 como maximo
+        let params = ConsensusParams {
+            pow_limit_bits: 0x1d00ffff,
+            ..TEST_PARAMS
+        };
+        let timespan = TARGET_TIMESPAN as i64;
+        assert_eq!(retarget(&params, 0x1c0ffff0, timespan * 2), 0x1c1fffe0);
+        assert_eq!(retarget(&params, 0x1c0ffff0, timespan / 2), 0x1c07fff8);
+        assert_eq!(retarget(&params, 0x1c0ffff0, timespan * 10), 0x1c3fffc0);
+        // y nunca supera la dificultad minima de la red
+        assert_eq!(retarget(&params, 0x1d00ffff, timespan * 4), 0x1d00ffff);
+    }
+
+    #[test]
+    fn compact_target_conversion_is_reversible() {
+        // GIVEN: dificultades en formato compacto
+        // WHEN: se convierten a target y de nuevo a formato compacto
+        // THEN: se obtiene el mismo valor
+        for n_bits in [0x1d00ffff, 0x1b0404cb, 0x207fffff, 0x1a05db8b] {
+            assert_eq!(target_to_compact(&compact_to_target(n_bits)), n_bits);
+        }
+        assert_eq!(header_work(0x1d00ffff), (1u128 << 48) / 0xffff);
+    }
+}
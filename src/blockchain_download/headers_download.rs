@@ -2,15 +2,18 @@ use std::{
     collections::HashMap,
     fs::File,
     io::Read,
-    net::TcpStream,
+    net::{SocketAddr, TcpStream},
     path::Path,
     sync::{mpsc::Sender, Arc, RwLock},
+    thread,
+    time::Duration,
 };
 
 use chrono::{TimeZone, Utc};
 use gtk::glib;
 
 use crate::{
+    blockchain::block_locator_from_headers,
     blocks::block_header::BlockHeader,
     config::Config,
     custom_errors::NodeCustomErrors,
@@ -20,11 +23,27 @@ use crate::{
 };
 
 use super::{
+    checkpoints::{consensus_params, forks_below_checkpoint},
+    header_validation::{chain_work, validate_header_chain, ConsensusParams},
     utils::{get_node, return_node_to_vec},
     GENESIS_BLOCK_HEADER,
 };
 
 const HEADERS_MESSAGE_SIZE: usize = 162003;
+/// Cantidad maxima de headers que se reciben en un mensaje headers
+const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+/// Cantidad de nodos a los que se les piden headers en paralelo
+const HEADERS_SYNC_PEERS: usize = 3;
+/// Cantidad de veces que un nodo puede enviar una cadena de headers con menos trabajo que la mejor antes de descartarlo
+const MAX_LOW_WORK_STRIKES: usize = 3;
+/// Tiempo maximo que se espera la respuesta de un nodo al getheaders
+const HEADERS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type HeadersAndHeights = (
+    Arc<RwLock<Vec<BlockHeader>>>,
+    Arc<RwLock<HashMap<[u8; 32], usize>>>,
+);
+type HeadersResponse = (TcpStream, Result<Vec<BlockHeader>, NodeCustomErrors>);
 
 const GENESIS_BLOCK_HASH: [u8; 32] = [
     0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01, 0xad, 0x0e, 0xe9, 0x84, 0x20, 0x97, 0x79, 0xba,
@@ -49,18 +68,21 @@ pub fn get_initial_headers(
     nodes: Arc<RwLock<Vec<TcpStream>>>,
 ) -> Result<(), NodeCustomErrors> {
     if config.read_headers_from_disk && Path::new(&config.archivo_headers).exists() {
-        if let Err(err) = read_headers_from_disk(
+        let read_result = read_headers_from_disk(
             config,
             log_sender,
             ui_sender,
             headers.clone(),
             header_heights.clone(),
-        ) {
+        )
+        .and_then(|_| verify_checkpoints(&consensus_params(config.start_string), &headers));
+        if let Err(err) = read_result {
             // si no se pudo descargar de disco, intento desde la red y guardo en disco
             write_in_log(
                 &log_sender.error_log_sender,
                 format!("Error al leer headers de disco: {}", err).as_str(),
             );
+            reset_headers_to_genesis(&headers, &header_heights)?;
         } else {
             return Ok(());
         }
@@ -76,6 +98,45 @@ pub fn get_initial_headers(
     Ok(())
 }
 
+/// Verifica que los headers locales coincidan con los checkpoints de la red que estan dentro de la cadena.
+/// Devuelve error si alguno no coincide
+fn verify_checkpoints(
+    params: &ConsensusParams,
+    headers: &Arc<RwLock<Vec<BlockHeader>>>,
+) -> Result<(), NodeCustomErrors> {
+    let chain = headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    for (height, checkpoint_hash) in params.checkpoints {
+        if let Some(header) = chain.get(*height) {
+            if header.hex_hash() != *checkpoint_hash {
+                return Err(NodeCustomErrors::InvalidHeaderError(format!(
+                    "El header de altura {} no coincide con el checkpoint",
+                    height
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deja la cadena de headers local solo con el header del bloque genesis, descartando los demas
+fn reset_headers_to_genesis(
+    headers: &Arc<RwLock<Vec<BlockHeader>>>,
+    header_heights: &Arc<RwLock<HashMap<[u8; 32], usize>>>,
+) -> Result<(), NodeCustomErrors> {
+    let mut header_heights = header_heights
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    header_heights.clear();
+    header_heights.insert([0u8; 32], 0);
+    let mut headers = headers
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    headers.truncate(1);
+    Ok(())
+}
+
 /// Lee los headers de disco y los guarda en el vector de headers.
 /// Devuelve un error en caso de no poder leer el archivo correctamente.
 fn read_headers_from_disk(
@@ -227,8 +288,12 @@ fn download_and_persist_initial_headers_from_node(
     {
         request_headers_from_node(config, node, headers.clone())?;
         let headers_read = receive_and_persist_initial_headers_from_node(log_sender, node, file)?;
-        load_header_heights(&headers_read, &header_heights, &headers)?;
-        store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
+        store_headers_in_local_headers_vec(
+            config,
+            log_sender,
+            (headers.clone(), header_heights.clone()),
+            &headers_read,
+        )?;
         let amount_of_headers = amount_of_headers(&headers)?;
         println!(
             "{:?} headers descargados y guardados en disco",
@@ -266,8 +331,15 @@ fn receive_and_persist_initial_headers_from_node(
 ***************************************************************************
 */
 
-/// Descarga los headers de la blockchain desde los nodos conectados
-/// En caso de que un nodo falle en la descarga, intenta con otro siempre y cuando tenga peers disponibles
+/// Descarga los headers que faltan de la blockchain pidiendoselos en paralelo a varios nodos.
+/// En cada vuelta se le envia a cada nodo un getheaders con el block locator de la cadena local y se validan
+/// las cadenas recibidas (encadenamiento, proof of work, ajustes de dificultad y checkpoints) antes de guardarlas.
+/// Se guarda la cadena valida con mas trabajo y el nodo que la envio pasa a ser el nodo de sincronizacion.
+/// Los nodos que envian cadenas invalidas se descartan, igual que los que envian repetidamente cadenas con menos
+/// trabajo que la mejor. Si falla un nodo se lo reemplaza por otro de la lista, siempre y cuando haya peers disponibles.
+/// Los headers de los bloques a descargar se envian por el channel para descargar los bloques en paralelo.
+/// Si al terminar la cadena no alcanza el trabajo minimo de la red, se descartan los nodos de sincronizacion y se sigue
+/// con otros mientras haya.
 /// Devuelve un error en caso de no poder descargar los headers desde nignun nodo peer
 pub fn download_missing_headers(
     config: &Arc<Config>,
@@ -278,49 +350,62 @@ pub fn download_missing_headers(
     header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
     tx: Sender<Vec<BlockHeader>>,
 ) -> Result<(), NodeCustomErrors> {
-    // get last node from list, if possible
-    let mut node = get_node(nodes.clone())?;
-    while let Err(err) = download_missing_headers_from_node(
-        config,
-        log_sender,
-        ui_sender,
-        &mut node,
-        headers.clone(),
-        header_heights.clone(),
-        tx.clone(),
-    ) {
+    let max_sync_peers = if config.ibd_single_node {
+        1
+    } else {
+        HEADERS_SYNC_PEERS
+    };
+    let mut sync = HeadersSync {
+        peers: vec![],
+        sync_peer: None,
+        low_work_strikes: HashMap::new(),
+        first_block_found: false,
+    };
+    let min_chain_work = consensus_params(config.start_string).min_chain_work;
+    let result = loop {
+        if let Err(err) = download_missing_headers_from_peers(
+            config,
+            log_sender,
+            ui_sender,
+            (&nodes, max_sync_peers),
+            (headers.clone(), header_heights.clone()),
+            &tx,
+            &mut sync,
+        ) {
+            break Err(err);
+        }
+        let work = match headers.read() {
+            Ok(chain) => chain_work(&chain),
+            Err(err) => break Err(NodeCustomErrors::LockError(err.to_string())),
+        };
+        if work >= min_chain_work {
+            break Ok(());
+        }
+        // los nodos de sincronizacion no tienen mas headers pero la cadena no llega al trabajo minimo,
+        // se descartan y se sigue con otros nodos
+        let no_more_nodes = match nodes.read() {
+            Ok(nodes) => nodes.is_empty(),
+            Err(err) => break Err(NodeCustomErrors::LockError(err.to_string())),
+        };
         write_in_log(
             &log_sender.error_log_sender,
             format!(
-                "Fallo la descarga con el nodo --{:?}--, lo descarto y voy a intentar con otro. Error: {}",
-                node.peer_addr(),
-                err
+                "La cadena de headers tiene trabajo {:#x}, menor al minimo esperado {:#x}. Descarto los nodos de sincronizacion: {:?}",
+                work, min_chain_work, sync.sync_peer
             )
             .as_str(),
         );
-        if let NodeCustomErrors::ThreadChannelError(_) = err {
-            return Err(NodeCustomErrors::ThreadChannelError("Error se cerro el channel que comunica la descarga de headers y bloques en paralelo".to_string()));
+        if no_more_nodes {
+            break Ok(());
         }
-        node = get_node(nodes.clone())?;
+        sync.peers.clear();
+        sync.sync_peer = None;
+    };
+    // return nodes to the list of nodes
+    for node in sync.peers {
+        return_node_to_vec(nodes.clone(), node)?;
     }
-    // return node again to the list of nodes
-    return_node_to_vec(nodes, node)?;
-    /*
-    let last_headers =
-        compare_and_ask_for_last_headers(config, log_sender, ui_sender, nodes, headers.clone(), header_heights)?;
-    if !last_headers.is_empty() {
-        write_in_log(
-            &log_sender.info_log_sender,
-            format!(
-                "Agrego ultimos {} headers enocontrados al comparar con todos los nodos",
-                last_headers.len()
-            )
-            .as_str(),
-        );
-        tx.send(last_headers)
-            .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-    }
-    */
+    result?;
     send_event_to_ui(
         ui_sender,
         UIEvent::FinsihDownloadingHeaders(amount_of_headers(&headers)?),
@@ -328,69 +413,378 @@ pub fn download_missing_headers(
     Ok(())
 }
 
-/// Descarga los headers de un nodo en particular y los guarda en el vector de headers
-/// En caso de que el parametro tx sea un Sender, envia los headers que va descargando al thread
-/// que descarga bloques para que se descarguen en paralelo, en caso contrario no envia nada.
-/// Devuelve error en caso de falla.
-fn download_missing_headers_from_node(
+/// Estado de la sincronizacion de headers entre vueltas
+struct HeadersSync {
+    peers: Vec<TcpStream>,
+    sync_peer: Option<SocketAddr>,
+    low_work_strikes: HashMap<SocketAddr, usize>,
+    first_block_found: bool,
+}
+
+/// Cadena de headers valida recibida de un nodo, con el header de la cadena local desde el que se bifurca
+/// y cuanto trabajo le agrega a la cadena local
+struct HeadersCandidate {
+    peer_index: usize,
+    fork_height: usize,
+    headers: Vec<BlockHeader>,
+    work_gain: i128,
+}
+
+/// Pide headers en paralelo a los nodos de sincronizacion hasta que ninguno tenga headers con mas trabajo
+/// que la cadena local. Devuelve error si no quedan nodos o si falla el channel con la descarga de bloques
+fn download_missing_headers_from_peers(
     config: &Arc<Config>,
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
-    node: &mut TcpStream,
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-    tx: Sender<Vec<BlockHeader>>,
+    (nodes, max_sync_peers): (&Arc<RwLock<Vec<TcpStream>>>, usize),
+    (headers, header_heights): HeadersAndHeights,
+    tx: &Sender<Vec<BlockHeader>>,
+    sync: &mut HeadersSync,
 ) -> Result<(), NodeCustomErrors> {
-    write_in_log(
-        &log_sender.info_log_sender,
-        format!(
-            "Empiezo la descarga de todos los headers que faltan con nodo: {:?}\n",
-            node.peer_addr()
-        )
-        .as_str(),
-    );
-    let mut first_block_found = false;
-    request_headers_from_node(config, node, headers.clone())?;
-    let mut headers_read = receive_headers_from_node(log_sender, node)?;
-
-    load_header_heights(&headers_read, &header_heights, &headers)?;
-
-    store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
-    while headers_read.len() == 2000 {
-        request_headers_from_node(config, node, headers.clone())?;
-        headers_read = receive_headers_from_node(log_sender, node)?;
-        load_header_heights(&headers_read, &header_heights, &headers)?;
-        store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
-        match first_block_found {
-            true => {
-                // si el primer bloque ya fue encontrado, envio al thread de descarga de bloques todos los headers
-                download_blocks_in_other_thread(tx.clone(), headers_read.clone())?;
+    loop {
+        while sync.peers.len() < max_sync_peers {
+            match get_node(nodes.clone()) {
+                Ok(node) => sync.peers.push(node),
+                Err(_) => break,
             }
-            false => {
-                // si el primer bloque no fue encontrado, me fijo si esta en los headers que acabo de recibir
-                if first_block_to_download_is_in_headers(config, &headers_read)? {
-                    // si el primer bloque esta en los headers que acabo de recibir, descargo los bloques que cumplan con la fecha configurada
-                    download_first_blocks_in_other_thread(
-                        config,
-                        log_sender,
-                        ui_sender,
-                        headers_read.clone(),
-                        tx.clone(),
-                        &mut first_block_found,
-                    )?;
+        }
+        if sync.peers.is_empty() {
+            return Err(NodeCustomErrors::BlockchainDownloadError(
+                "Error no hay mas nodos conectados para descargar los headers de la blockchain!\n"
+                    .to_string(),
+            ));
+        }
+        let locator = block_locator_from_headers(
+            &headers
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        );
+        let responses = request_headers_from_peers(
+            config,
+            log_sender,
+            std::mem::take(&mut sync.peers),
+            locator,
+        )?;
+        let mut candidates = vec![];
+        for (node, response) in responses {
+            let peer_addr = node.peer_addr().ok();
+            let headers_read = match response {
+                Ok(headers_read) => headers_read,
+                Err(err) => {
+                    discard_sync_peer(log_sender, peer_addr, err.to_string());
+                    continue;
+                }
+            };
+            match evaluate_headers_from_peer(config, &headers, &header_heights, &headers_read) {
+                Ok(Some((fork_height, work_gain))) => candidates.push(HeadersCandidate {
+                    peer_index: sync.peers.len(),
+                    fork_height,
+                    headers: headers_read,
+                    work_gain,
+                }),
+                Ok(None) => {}
+                Err(err) => {
+                    discard_sync_peer(log_sender, peer_addr, err.to_string());
+                    continue;
                 }
             }
+            sync.peers.push(node);
         }
+        let best = match choose_best_candidate(log_sender, sync, candidates) {
+            Some(best) => best,
+            // ningun nodo tiene headers con mas trabajo que los locales
+            None => return Ok(()),
+        };
+        let best_peer = sync.peers[best.peer_index].peer_addr().ok();
+        if best_peer != sync.sync_peer {
+            write_in_log(
+                &log_sender.info_log_sender,
+                format!(
+                    "Nodo de sincronizacion de headers: {:?} (antes {:?})",
+                    best_peer, sync.sync_peer
+                )
+                .as_str(),
+            );
+            sync.sync_peer = best_peer;
+        }
+        let best_len = best.headers.len();
+        if !commit_headers(
+            log_sender,
+            (headers.clone(), header_heights.clone()),
+            &best,
+            sync.first_block_found,
+        )? {
+            return Ok(());
+        }
+        forward_headers_to_blocks_download(
+            config,
+            log_sender,
+            ui_sender,
+            best.headers,
+            tx,
+            &mut sync.first_block_found,
+        )?;
         let amount_of_headers = amount_of_headers(&headers)?;
         println!("{:?} headers descargados", amount_of_headers - 1);
         send_event_to_ui(
             ui_sender,
             UIEvent::ActualizeHeadersDownloaded(amount_of_headers - 1),
         );
+        if best_len < MAX_HEADERS_PER_MESSAGE {
+            return Ok(());
+        }
+    }
+}
+
+/// Envia en paralelo, desde un thread por nodo, el mensaje getheaders con el block locator recibido y espera la respuesta.
+/// Devuelve cada nodo junto con los headers que envio o el error que ocurrio
+fn request_headers_from_peers(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    peers: Vec<TcpStream>,
+    locator: Vec<[u8; 32]>,
+) -> Result<Vec<HeadersResponse>, NodeCustomErrors> {
+    let mut handles = vec![];
+    for mut node in peers {
+        let log_sender = log_sender.clone();
+        let getheaders_message =
+            GetHeadersMessage::new(config.protocol_version as u32, locator.clone(), [0; 32]);
+        handles.push(thread::spawn(move || {
+            let response = getheaders_message
+                .write_to(&mut node)
+                .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))
+                .and_then(|_| receive_headers_with_timeout(&log_sender, &mut node));
+            (node, response)
+        }));
+    }
+    let mut responses = vec![];
+    for handle in handles {
+        responses.push(
+            handle
+                .join()
+                .map_err(|err| NodeCustomErrors::ThreadJoinError(format!("{:?}", err)))?,
+        );
+    }
+    Ok(responses)
+}
+
+/// Recibe los headers del nodo esperando como maximo HEADERS_RESPONSE_TIMEOUT y despues
+/// vuelve a dejar el timeout de lectura que tenia el nodo
+fn receive_headers_with_timeout(
+    log_sender: &LogSender,
+    node: &mut TcpStream,
+) -> Result<Vec<BlockHeader>, NodeCustomErrors> {
+    let previous_timeout = node
+        .read_timeout()
+        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+    node.set_read_timeout(Some(HEADERS_RESPONSE_TIMEOUT))
+        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+    let headers_read = receive_headers_from_node(log_sender, node);
+    node.set_read_timeout(previous_timeout)
+        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+    headers_read
+}
+
+/// Busca desde que header de la cadena local se bifurcan los headers recibidos y los valida como continuacion
+/// de la cadena local. Devuelve la altura de la bifurcacion y cuanto trabajo le agregan a la cadena local
+/// (negativo si tienen menos trabajo que los headers locales que reemplazarian), None si no se recibieron headers
+/// o error si no se enlazan con la cadena local, si se bifurcan antes de un checkpoint o si no son validos
+fn evaluate_headers_from_peer(
+    config: &Arc<Config>,
+    headers: &Arc<RwLock<Vec<BlockHeader>>>,
+    header_heights: &Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    headers_read: &[BlockHeader],
+) -> Result<Option<(usize, i128)>, NodeCustomErrors> {
+    let first_header = match headers_read.first() {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let chain = headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let fork_height = if first_header.previous_block_header_hash == chain[0].hash() {
+        0
+    } else {
+        *header_heights
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .get(&first_header.previous_block_header_hash)
+            .ok_or(NodeCustomErrors::InvalidHeaderError(
+                "Los headers recibidos no se enlazan con la cadena local".to_string(),
+            ))?
+    };
+    let params = consensus_params(config.start_string);
+    if forks_below_checkpoint(&params, fork_height, chain.len()) {
+        return Err(NodeCustomErrors::InvalidHeaderError(format!(
+            "Los headers recibidos se bifurcan en la altura {}, antes de un checkpoint",
+            fork_height
+        )));
+    }
+    let new_work = validate_header_chain(&params, &chain, fork_height, headers_read)?;
+    let replaced_work = chain_work(&chain[fork_height + 1..]);
+    Ok(Some((
+        fork_height,
+        new_work as i128 - replaced_work as i128,
+    )))
+}
+
+/// Elige la cadena con mas trabajo entre las recibidas. A los nodos cuya cadena no es parte de la mejor y tiene
+/// menos trabajo se les suma una falta, y al llegar a MAX_LOW_WORK_STRIKES se los descarta.
+/// Devuelve None si ninguna cadena le agrega trabajo a la cadena local
+fn choose_best_candidate(
+    log_sender: &LogSender,
+    sync: &mut HeadersSync,
+    candidates: Vec<HeadersCandidate>,
+) -> Option<HeadersCandidate> {
+    let best_index = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, candidate)| candidate.work_gain > 0)
+        .max_by_key(|(_, candidate)| candidate.work_gain)
+        .map(|(index, _)| index);
+    let mut low_work_peers = vec![];
+    for candidate in candidates.iter() {
+        let is_part_of_best = best_index.is_some_and(|best_index| {
+            let best = &candidates[best_index];
+            best.fork_height == candidate.fork_height
+                && best.headers.len() >= candidate.headers.len()
+                && best.headers[..candidate.headers.len()] == candidate.headers[..]
+        });
+        if !is_part_of_best {
+            low_work_peers.push(candidate.peer_index);
+        }
+    }
+    let mut discarded = vec![];
+    for peer_index in low_work_peers {
+        let peer_addr = match sync.peers[peer_index].peer_addr() {
+            Ok(peer_addr) => peer_addr,
+            Err(_) => continue,
+        };
+        let strikes = sync.low_work_strikes.entry(peer_addr).or_insert(0);
+        *strikes += 1;
+        write_in_log(
+            &log_sender.error_log_sender,
+            format!(
+                "El nodo {:?} envio una cadena de headers con menos trabajo que la mejor ({} de {} faltas)",
+                peer_addr, strikes, MAX_LOW_WORK_STRIKES
+            )
+            .as_str(),
+        );
+        if *strikes >= MAX_LOW_WORK_STRIKES {
+            discarded.push(peer_index);
+        }
+    }
+    let mut best = best_index.and_then(|index| candidates.into_iter().nth(index));
+    // se descartan los nodos de atras para adelante para no cambiar los indices de los que quedan
+    discarded.sort_unstable_by(|a, b| b.cmp(a));
+    for peer_index in discarded {
+        let node = sync.peers.remove(peer_index);
+        discard_sync_peer(
+            log_sender,
+            node.peer_addr().ok(),
+            "envio demasiadas cadenas de headers con poco trabajo".to_string(),
+        );
+        if let Some(best) = best.as_mut() {
+            if best.peer_index > peer_index {
+                best.peer_index -= 1;
+            }
+        }
+    }
+    best
+}
+
+/// Guarda en la cadena local los headers de la cadena elegida. Si se bifurca antes del ultimo header local
+/// se reemplazan los headers locales desde la bifurcacion, salvo que ya se hayan enviado a descargar sus bloques.
+/// Devuelve true si se guardaron los headers
+fn commit_headers(
+    log_sender: &LogSender,
+    (headers, header_heights): HeadersAndHeights,
+    best: &HeadersCandidate,
+    blocks_download_started: bool,
+) -> Result<bool, NodeCustomErrors> {
+    let mut header_heights_lock = header_heights
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut headers_lock = headers
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    if best.fork_height + 1 < headers_lock.len() {
+        if blocks_download_started {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "Se encontro una cadena de headers con mas trabajo que se bifurca en la altura {}, pero sus bloques ya se estan descargando",
+                    best.fork_height
+                )
+                .as_str(),
+            );
+            return Ok(false);
+        }
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "Reemplazo {} headers por una cadena con mas trabajo que se bifurca en la altura {}",
+                headers_lock.len() - best.fork_height - 1,
+                best.fork_height
+            )
+            .as_str(),
+        );
+        for header in headers_lock.drain(best.fork_height + 1..) {
+            header_heights_lock.remove(&header.hash());
+        }
+    }
+    for header in &best.headers {
+        header_heights_lock.insert(header.hash(), headers_lock.len());
+        headers_lock.push(*header);
+    }
+    Ok(true)
+}
+
+/// Envia al thread de descarga de bloques los headers recibidos, a partir del primer bloque a descargar
+/// segun la fecha configurada. Devuelve error en caso de que el channel este cerrado
+fn forward_headers_to_blocks_download(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    headers_read: Vec<BlockHeader>,
+    tx: &Sender<Vec<BlockHeader>>,
+    first_block_found: &mut bool,
+) -> Result<(), NodeCustomErrors> {
+    match first_block_found {
+        true => {
+            // si el primer bloque ya fue encontrado, envio al thread de descarga de bloques todos los headers
+            download_blocks_in_other_thread(tx.clone(), headers_read)?;
+        }
+        false => {
+            // si el primer bloque no fue encontrado, me fijo si esta en los headers que acabo de recibir
+            if first_block_to_download_is_in_headers(config, &headers_read)? {
+                // si el primer bloque esta en los headers que acabo de recibir, descargo los bloques que cumplan con la fecha configurada
+                download_first_blocks_in_other_thread(
+                    config,
+                    log_sender,
+                    ui_sender,
+                    headers_read,
+                    tx.clone(),
+                    first_block_found,
+                )?;
+            }
+        }
     }
     Ok(())
 }
 
+/// Escribe en el log que se descarta el nodo de la sincronizacion de headers y el motivo
+fn discard_sync_peer(log_sender: &LogSender, peer_addr: Option<SocketAddr>, reason: String) {
+    write_in_log(
+        &log_sender.error_log_sender,
+        format!(
+            "Fallo la descarga de headers con el nodo --{:?}--, lo descarto. Error: {}",
+            peer_addr, reason
+        )
+        .as_str(),
+    );
+}
+
 /*
 ***************************************************************************
 ************************ AUXILIAR FUNCTIONS *******************************
@@ -424,14 +818,21 @@ pub fn receive_headers_from_node(
     Ok(headers)
 }
 
-/// Recibe un vector de headers, los valida y los guarda en el vector de headers local
-/// en caso de que no sean validos no los guarda y devuelve un error
+/// Recibe un vector de headers, los valida como continuacion de la cadena local y los guarda en el vector de headers local
+/// junto con sus alturas. En caso de que no sean validos no los guarda y devuelve un error
 fn store_headers_in_local_headers_vec(
+    config: &Arc<Config>,
     log_sender: &LogSender,
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
+    (headers, header_heights): HeadersAndHeights,
     headers_read: &Vec<BlockHeader>,
 ) -> Result<(), NodeCustomErrors> {
-    validate_headers(log_sender, headers_read)?;
+    validate_headers(
+        &consensus_params(config.start_string),
+        log_sender,
+        &headers,
+        headers_read,
+    )?;
+    load_header_heights(headers_read, &header_heights, &headers)?;
     headers
         .write()
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -513,22 +914,23 @@ fn get_last_hash_header_downloaded(
     }
 }
 
-/// Valida que el header tenga la proof of work correcta
-/// Devuelve un error en caso de que no sea valido
+/// Valida que los headers continuen la cadena local: que esten encadenados, tengan la proof of work correcta,
+/// la dificultad que corresponde y coincidan con los checkpoints. Devuelve un error en caso de que no sean validos
 fn validate_headers(
+    params: &ConsensusParams,
     log_sender: &LogSender,
-    headers: &Vec<BlockHeader>,
+    headers: &Arc<RwLock<Vec<BlockHeader>>>,
+    headers_read: &[BlockHeader],
 ) -> Result<(), NodeCustomErrors> {
-    for header in headers {
-        if !header.validate() {
-            write_in_log(
-                &log_sender.error_log_sender,
-                "Error en validacion de la proof of work de header",
-            );
-            return Err(NodeCustomErrors::InvalidHeaderError(
-                "partial validation of header is invalid!".to_string(),
-            ));
-        }
+    let chain = headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    if let Err(err) = validate_header_chain(params, &chain, chain.len() - 1, headers_read) {
+        write_in_log(
+            &log_sender.error_log_sender,
+            format!("Error en validacion de headers: {}", err).as_str(),
+        );
+        return Err(err);
     }
     Ok(())
 }
@@ -1,8 +1,9 @@
 use gtk::glib;
 
-use self::blocks_download::{download_blocks, download_blocks_single_node};
+use self::blocks_download::download_blocks;
+use self::checkpoints::consensus_params;
 use self::headers_download::{download_missing_headers, get_initial_headers};
-use self::utils::{get_amount_of_headers_and_blocks, get_node, join_threads, return_node_to_vec};
+use self::utils::{get_amount_of_headers_and_blocks, join_threads};
 use super::blocks::block::Block;
 use super::blocks::block_header::BlockHeader;
 use super::config::Config;
@@ -15,11 +16,18 @@ use std::collections::HashMap;
 use std::net::TcpStream;
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use std::{thread, vec};
+mod block_download_scheduler;
 mod blocks_download;
+pub(crate) mod checkpoints;
+pub(crate) mod header_validation;
 pub(crate) mod headers_download;
 mod utils;
 
+/// Tiempo entre cada verificacion del trabajo de la cadena mientras se espera a que alcance el minimo
+const CHAIN_WORK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 type UtxoSetPointer = Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>;
 type BlocksAndHeaders = (
     Arc<RwLock<HashMap<[u8; 32], Block>>>,
@@ -116,6 +124,34 @@ pub fn initial_block_download(
     ))
 }
 
+/// Espera a que la cadena de headers alcance el trabajo minimo de la red, para no mostrar datos de la wallet
+/// de una cadena que todavia no esta sincronizada. Mientras tanto el nodo sigue recibiendo headers de los demas nodos.
+/// Devuelve error en caso de no poder leer la cadena
+pub fn wait_for_minimum_chain_work(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    blockchain: &Blockchain,
+) -> Result<(), NodeCustomErrors> {
+    let min_chain_work = consensus_params(config.start_string).min_chain_work;
+    let mut chain_work = blockchain.chain_work()?;
+    if chain_work < min_chain_work {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "La cadena tiene trabajo {:#x}, menor al minimo esperado {:#x}. Espero a sincronizar antes de iniciar la wallet",
+                chain_work, min_chain_work
+            )
+            .as_str(),
+        );
+        println!("Esperando a que la cadena alcance el trabajo minimo para iniciar la wallet...");
+    }
+    while chain_work < min_chain_work {
+        thread::sleep(CHAIN_WORK_CHECK_INTERVAL);
+        chain_work = blockchain.chain_work()?;
+    }
+    Ok(())
+}
+
 /// Se encarga de descargar todos los headers y bloques de la blockchain en multiples thread, en un thread descarga los headers
 /// y en el otro a medida que se van descargando los headers va pidiendo los bloques correspondientes.
 /// Devuelve error en caso de falla.
@@ -135,7 +171,6 @@ fn download_full_blockchain_from_multiple_nodes(
     let log_sender_cloned = log_sender.clone();
     let nodes_cloned = nodes.clone();
     let headers_cloned = headers.clone();
-    let tx_cloned = tx.clone();
     let ui_sender_clone = ui_sender.clone();
     threads_handle.push(thread::spawn(move || {
         download_missing_headers(
@@ -145,7 +180,7 @@ fn download_full_blockchain_from_multiple_nodes(
             nodes_cloned,
             headers_cloned,
             header_heights,
-            tx_cloned,
+            tx,
         )
     }));
     let config = config.clone();
@@ -163,8 +198,8 @@ fn download_full_blockchain_from_multiple_nodes(
             &ui_sender,
             nodes,
             (blocks, headers),
-            (tx, rx),
-            tx_utxo_set,
+            (rx, tx_utxo_set),
+            config.n_threads,
         )
     }));
     join_threads(threads_handle)?;
@@ -195,26 +230,22 @@ fn download_full_blockchain_from_single_node(
         header_heights,
         tx,
     )?;
-    let mut node = get_node(nodes.clone())?;
     let (tx_utxo_set, rx_utxo_set) = channel();
     let utxo_set_clone = utxo_set;
     let join_handle = thread::spawn(move || -> Result<(), NodeCustomErrors> {
         load_utxo_set(rx_utxo_set, utxo_set_clone)
     });
     send_event_to_ui(ui_sender, UIEvent::StartDownloadingBlocks);
-    for blocks_to_download in rx {
-        download_blocks_single_node(
-            config,
-            log_sender,
-            ui_sender,
-            (blocks.clone(), headers.clone()),
-            blocks_to_download,
-            &mut node,
-            tx_utxo_set.clone(),
-        )?;
-    }
-    return_node_to_vec(nodes, node)?;
-    drop(tx_utxo_set);
+    // los headers ya estan todos en el channel, se descargan sus bloques desde un solo nodo
+    download_blocks(
+        config,
+        log_sender,
+        ui_sender,
+        nodes,
+        (blocks, headers),
+        (rx, tx_utxo_set),
+        1,
+    )?;
     join_handle
         .join()
         .map_err(|err| NodeCustomErrors::ThreadJoinError(format!("{:?}", err)))??;
@@ -139,17 +139,18 @@ impl Block {
         &self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
     ) -> Result<(), Box<dyn Error>> {
+        let height = self.get_height();
         for tx in &self.txn {
             if tx.is_coinbase_transaction() {
                 // como se trata de una coinbase al ser la primera tx solo se cargaran
                 // las utxos de esta transaccion
-                tx.load_utxos(utxo_set.clone())?;
+                tx.load_utxos(utxo_set.clone(), height)?;
             } else {
                 //primero removemos las utxos que usa esta tx
                 tx.remove_utxos(utxo_set.clone())?;
                 //luego cargamos las utxos de esta tx para que en la siguiente iteracion
                 //se remuevan aquellas con son usadas
-                tx.load_utxos(utxo_set.clone())?;
+                tx.load_utxos(utxo_set.clone(), height)?;
             }
         }
         Ok(())
@@ -198,7 +199,8 @@ impl Block {
                     .pending_transactions
                     .read()
                     .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                    .contains(tx)
+                    .iter()
+                    .any(|pending_tx| pending_tx.hash() == tx.hash())
                 {
                     println!(
                         "EL BLOQUE {} \nCONTIENE LA TRANSACCION {} CONFIRMADA \nDE LA CUENTA {}\n",
@@ -0,0 +1,173 @@
+use std::{collections::HashSet, error::Error, fs, io};
+
+use crate::{account::bytes_to_hex_string, blocks::utils_block::string_to_bytes};
+
+/// Identifica a una moneda por el hash de la transaccion que la creo y el indice del output
+pub type CoinId = ([u8; 32], usize);
+
+/// Etiquetas con las que se guarda el estado de cada moneda en el archivo de coin control
+const LOCKED_TAG: &str = "locked";
+const FROZEN_TAG: &str = "frozen";
+
+/// Cambio de estado que el usuario le aplica a una moneda
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoinAction {
+    Lock,
+    Unlock,
+    Freeze,
+    Unfreeze,
+}
+
+/// Estado de las monedas que el usuario controla a mano.
+/// Las monedas bloqueadas no se usan en la seleccion automatica pero se pueden elegir a mano.
+/// Las congeladas no se gastan nunca, por ejemplo las de un posible ataque de dust.
+/// Si tiene un archivo, cada cambio se guarda en el para que se mantenga entre ejecuciones
+#[derive(Debug, Clone, Default)]
+pub struct CoinControl {
+    locked: HashSet<CoinId>,
+    frozen: HashSet<CoinId>,
+    path: Option<String>,
+}
+
+/// Datos de una moneda de la cuenta que se le muestran al usuario
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinInfo {
+    pub id: CoinId,
+    pub value: i64,
+    pub address: String,
+    pub confirmations: u32,
+    pub locked: bool,
+    pub frozen: bool,
+}
+
+impl CoinControl {
+    /// Carga el estado de las monedas del archivo recibido, donde se guardaran los cambios.
+    /// Si el archivo no existe empieza sin monedas bloqueadas ni congeladas.
+    /// Devuelve error si el archivo tiene un formato invalido
+    pub fn load(path: &str) -> Result<CoinControl, Box<dyn Error>> {
+        let mut coin_control = CoinControl {
+            path: Some(path.to_string()),
+            ..Default::default()
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(coin_control),
+            Err(err) => return Err(Box::new(err)),
+        };
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let (tag, coin) = line.split_once(' ').ok_or_else(|| invalid_line(line))?;
+            let coin = parse_coin_id(coin)?;
+            match tag {
+                LOCKED_TAG => coin_control.locked.insert(coin),
+                FROZEN_TAG => coin_control.frozen.insert(coin),
+                _ => return Err(invalid_line(line)),
+            };
+        }
+        Ok(coin_control)
+    }
+
+    /// Aplica la accion a la moneda y guarda el estado en el archivo, si tiene
+    pub fn apply(&mut self, action: CoinAction, coin: CoinId) -> Result<(), Box<dyn Error>> {
+        match action {
+            CoinAction::Lock => self.locked.insert(coin),
+            CoinAction::Unlock => self.locked.remove(&coin),
+            CoinAction::Freeze => self.frozen.insert(coin),
+            CoinAction::Unfreeze => self.frozen.remove(&coin),
+        };
+        self.save()
+    }
+
+    /// Devuelve true si la moneda esta bloqueada para la seleccion automatica
+    pub fn is_locked(&self, coin: &CoinId) -> bool {
+        self.locked.contains(coin)
+    }
+
+    /// Devuelve true si la moneda esta congelada
+    pub fn is_frozen(&self, coin: &CoinId) -> bool {
+        self.frozen.contains(coin)
+    }
+
+    /// Escribe una linea por moneda con su estado y su id
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut content = String::new();
+        for (tag, coins) in [(LOCKED_TAG, &self.locked), (FROZEN_TAG, &self.frozen)] {
+            for coin in coins {
+                content.push_str(&format!("{} {}\n", tag, format_coin_id(coin)));
+            }
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Devuelve la moneda como "txid:indice", con el txid en hexadecimal como lo muestran los exploradores
+pub fn format_coin_id(coin: &CoinId) -> String {
+    let mut hash = coin.0;
+    hash.reverse();
+    format!("{}:{}", bytes_to_hex_string(&hash), coin.1)
+}
+
+/// Recibe una moneda como "txid:indice" y la devuelve. Devuelve error si el formato es invalido
+pub fn parse_coin_id(coin: &str) -> Result<CoinId, Box<dyn Error>> {
+    let (txid, index) = coin.trim().split_once(':').ok_or_else(|| {
+        io::Error::other(format!(
+            "La moneda {} no tiene el formato txid:indice",
+            coin
+        ))
+    })?;
+    let mut hash = string_to_bytes(txid)?;
+    hash.reverse();
+    Ok((hash, index.parse::<usize>()?))
+}
+
+fn invalid_line(line: &str) -> Box<dyn Error> {
+    Box::new(io::Error::other(format!(
+        "Linea invalida en el archivo de coin control: {}",
+        line
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TXID: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+
+    #[test]
+    fn coin_ids_are_formatted_as_in_block_explorers() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una moneda escrita como txid:indice
+        let text = format!("{}:1", TXID);
+        // WHEN: se parsea y se vuelve a formatear
+        let coin = parse_coin_id(&text)?;
+        // THEN: el hash queda invertido internamente y el texto no cambia
+        assert_eq!(coin.0[0], 0x16);
+        assert_eq!(coin.1, 1);
+        assert_eq!(format_coin_id(&coin), text);
+        assert!(parse_coin_id(TXID).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn locked_and_frozen_coins_persist_in_the_file() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un coin control guardado en un archivo temporal
+        let path = std::env::temp_dir().join(format!("coin_control_test_{}", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let locked = parse_coin_id(&format!("{}:0", TXID))?;
+        let frozen = parse_coin_id(&format!("{}:1", TXID))?;
+        let mut coin_control = CoinControl::load(&path)?;
+        // WHEN: se bloquea una moneda, se congela otra y se desbloquea una que no estaba bloqueada
+        coin_control.apply(CoinAction::Lock, locked)?;
+        coin_control.apply(CoinAction::Freeze, frozen)?;
+        coin_control.apply(CoinAction::Unlock, frozen)?;
+        // THEN: al volver a cargar el archivo se mantiene el estado de cada moneda
+        let loaded = CoinControl::load(&path)?;
+        fs::remove_file(&path)?;
+        assert!(loaded.is_locked(&locked) && !loaded.is_frozen(&locked));
+        assert!(loaded.is_frozen(&frozen) && !loaded.is_locked(&frozen));
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+use std::{error::Error, io};
+
+use crate::{
+    coin_control::CoinId,
+    compact_size_uint::CompactSizeUint,
+    transactions::{
+        script::{
+            multisig_script::{MultisigKind, MultisigScript},
+            script_type::ScriptType,
+        },
+        tx_out::TxOut,
+    },
+    utxo_tuple::UtxoTuple,
+};
+
+/// Tasa con la que Bitcoin Core calcula el limite de dust de un output (3 sat/vB)
+const DUST_RELAY_FEE_RATE: f64 = 3.0;
+/// Cantidad maxima de nodos que recorre Branch and Bound antes de rendirse
+const BNB_MAX_TRIES: usize = 100_000;
+/// Vbytes de una transaccion sin inputs ni outputs: version, cantidades de inputs y outputs, lock time,
+/// y el marker y flag de segwit (medio vbyte) redondeado hacia arriba
+pub const TRANSACTION_OVERHEAD_VSIZE: usize = 11;
+/// Bytes de un input sin contar el signature script: outpoint (36) y sequence (4)
+const INPUT_BASE_SIZE: usize = 40;
+/// Bytes de una firma DER con el sighash y su push
+const SIGNATURE_PUSH_SIZE: usize = 73;
+/// Bytes de una clave publica comprimida con su push
+const PUBKEY_PUSH_SIZE: usize = 34;
+/// Bytes de un output sin contar el pubkey script: monto (8)
+const OUTPUT_BASE_SIZE: usize = 8;
+/// Tamaño en vbytes que se asume para los inputs de scripts desconocidos (el de un P2PKH)
+const DEFAULT_INPUT_VSIZE: usize = 148;
+
+/// Output sin gastar de la cuenta que se puede usar como input de una transaccion.
+/// Guarda el tamaño en vbytes que ocupa el input que lo gasta, ya firmado
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coin {
+    pub hash: [u8; 32],
+    pub index: usize,
+    pub tx_out: TxOut,
+    pub input_vsize: usize,
+}
+
+impl Coin {
+    /// Devuelve el id de la moneda: el hash de su transaccion y el indice del output
+    pub fn id(&self) -> CoinId {
+        (self.hash, self.index)
+    }
+
+    /// Monto del output en satoshis
+    pub fn value(&self) -> i64 {
+        self.tx_out.value()
+    }
+
+    /// Monto del output menos lo que cuesta gastarlo a la tasa recibida
+    pub fn effective_value(&self, fee_rate: f64) -> i64 {
+        self.value() - fee_for_vsize(self.input_vsize, fee_rate)
+    }
+}
+
+/// Datos de la transaccion a armar que necesita la seleccion de monedas
+#[derive(Debug, Clone)]
+pub struct CoinSelectionParams {
+    /// Monto total a pagar a los receptores
+    pub amount: i64,
+    /// Comision de la parte de la transaccion que no depende de los inputs (version, outputs, lock time)
+    pub fixed_fee: i64,
+    /// Tasa de comision en sat/vB que se paga por cada input agregado
+    pub fee_rate: f64,
+    /// Tamaño en vbytes del output de cambio
+    pub change_output_vsize: usize,
+    /// Tamaño en vbytes del input que gastara el output de cambio en el futuro
+    pub change_spend_vsize: usize,
+}
+
+impl CoinSelectionParams {
+    /// Valor efectivo que tienen que sumar los inputs
+    fn target(&self) -> i64 {
+        self.amount + self.fixed_fee
+    }
+
+    /// Lo que cuesta agregar el output de cambio
+    fn change_output_fee(&self) -> i64 {
+        fee_for_vsize(self.change_output_vsize, self.fee_rate)
+    }
+
+    /// Lo que cuesta crear el cambio y gastarlo despues. Un excedente menor conviene dejarlo de comision
+    fn cost_of_change(&self) -> i64 {
+        self.change_output_fee() + fee_for_vsize(self.change_spend_vsize, self.fee_rate)
+    }
+
+    /// Monto minimo del output de cambio para que no sea dust
+    fn dust_threshold(&self) -> i64 {
+        dust_threshold(self.change_output_vsize, self.change_spend_vsize)
+    }
+}
+
+/// Monto minimo de un output para que no sea dust, segun lo que pesa crearlo y gastarlo
+pub fn dust_threshold(output_vsize: usize, spend_vsize: usize) -> i64 {
+    fee_for_vsize(output_vsize + spend_vsize, DUST_RELAY_FEE_RATE)
+}
+
+/// Resultado de la seleccion: las monedas a gastar, la comision total y el cambio (0 si no hay)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub coins: Vec<Coin>,
+    pub fee: i64,
+    pub change: i64,
+}
+
+impl Selection {
+    /// Agrupa las monedas seleccionadas por la transaccion que las creo, manteniendo el orden
+    pub fn utxos(&self) -> Vec<UtxoTuple> {
+        let mut utxos: Vec<UtxoTuple> = Vec::new();
+        for coin in &self.coins {
+            let output = (coin.tx_out.clone(), coin.index);
+            match utxos.iter_mut().find(|utxo| utxo.hash == coin.hash) {
+                Some(utxo) => utxo.utxo_set.push(output),
+                None => utxos.push(UtxoTuple::new(coin.hash, vec![output])),
+            }
+        }
+        utxos
+    }
+}
+
+/// Elige las monedas a gastar para pagar el monto y las comisiones de los params.
+/// Descarta las monedas que cuestan mas de lo que valen a la tasa recibida. Primero busca con
+/// Branch and Bound una combinacion que no necesite cambio; si no la hay, toma las monedas de mayor
+/// valor efectivo hasta cubrir el monto. Si el cambio resultante seria dust, se suma a la comision.
+/// Devuelve error si el valor efectivo de las monedas no alcanza
+pub fn select_coins(
+    coins: &[Coin],
+    params: &CoinSelectionParams,
+) -> Result<Selection, Box<dyn Error>> {
+    let mut candidates: Vec<&Coin> = coins
+        .iter()
+        .filter(|coin| coin.effective_value(params.fee_rate) > 0)
+        .collect();
+    // de mayor a menor valor efectivo, desempatando por outpoint para que el resultado sea determinista
+    candidates.sort_by(|a, b| {
+        b.effective_value(params.fee_rate)
+            .cmp(&a.effective_value(params.fee_rate))
+            .then(a.hash.cmp(&b.hash))
+            .then(a.index.cmp(&b.index))
+    });
+    let effective_values: Vec<i64> = candidates
+        .iter()
+        .map(|coin| coin.effective_value(params.fee_rate))
+        .collect();
+    let available: i64 = effective_values.iter().sum();
+    if available < params.target() {
+        return Err(Box::new(io::Error::other(format!(
+            "Los fondos disponibles ({} satoshis descontando el costo de gastarlos) no alcanzan para pagar {} satoshis",
+            available,
+            params.target()
+        ))));
+    }
+    let selected_indexes = branch_and_bound(
+        &effective_values,
+        params.target(),
+        params.target() + params.cost_of_change(),
+    )
+    .unwrap_or_else(|| largest_first(&effective_values, params.target()));
+    let selected: Vec<Coin> = selected_indexes
+        .iter()
+        .map(|index| candidates[*index].clone())
+        .collect();
+    Ok(build_selection(selected, params))
+}
+
+/// Arma la seleccion con todas las monedas recibidas, elegidas a mano por el usuario (coin control).
+/// Si sobra lo suficiente se crea cambio, sino el excedente queda de comision.
+/// Devuelve error si el valor efectivo de las monedas no alcanza para pagar el monto y las comisiones
+pub fn use_all_coins(
+    coins: &[Coin],
+    params: &CoinSelectionParams,
+) -> Result<Selection, Box<dyn Error>> {
+    let available: i64 = coins
+        .iter()
+        .map(|coin| coin.effective_value(params.fee_rate))
+        .sum();
+    if available < params.target() {
+        return Err(Box::new(io::Error::other(format!(
+            "Las monedas elegidas ({} satoshis descontando el costo de gastarlas) no alcanzan para pagar {} satoshis",
+            available,
+            params.target()
+        ))));
+    }
+    Ok(build_selection(coins.to_vec(), params))
+}
+
+/// Calcula la comision y el cambio de las monedas elegidas. El cambio solo se crea si, despues de
+/// pagar su output, supera el limite de dust y lo que cuesta crearlo y gastarlo
+fn build_selection(coins: Vec<Coin>, params: &CoinSelectionParams) -> Selection {
+    let total_value: i64 = coins.iter().map(Coin::value).sum();
+    let effective_value: i64 = coins
+        .iter()
+        .map(|coin| coin.effective_value(params.fee_rate))
+        .sum();
+    let excess = effective_value - params.target();
+    let change = excess - params.change_output_fee();
+    if excess > params.cost_of_change() && change >= params.dust_threshold() {
+        return Selection {
+            fee: total_value - params.amount - change,
+            coins,
+            change,
+        };
+    }
+    Selection {
+        fee: total_value - params.amount,
+        coins,
+        change: 0,
+    }
+}
+
+/// Busca en profundidad un subconjunto de valores cuya suma este entre el target y el limite
+/// superior, minimizando el excedente. Los valores tienen que estar ordenados de mayor a menor.
+/// Devuelve los indices elegidos o None si no encuentra ninguno en la cantidad de intentos
+fn branch_and_bound(values: &[i64], target: i64, upper_bound: i64) -> Option<Vec<usize>> {
+    let mut search = BnbSearch {
+        values,
+        target,
+        upper_bound,
+        tries: BNB_MAX_TRIES,
+        best: None,
+    };
+    let remaining = values.iter().sum();
+    search.explore(0, &mut Vec::new(), 0, remaining);
+    search.best.map(|(indexes, _)| indexes)
+}
+
+struct BnbSearch<'a> {
+    values: &'a [i64],
+    target: i64,
+    upper_bound: i64,
+    tries: usize,
+    best: Option<(Vec<usize>, i64)>,
+}
+
+impl BnbSearch<'_> {
+    /// Decide si incluir el valor del indice recibido. Corta la rama si se pasa del limite superior
+    /// o si con los valores que quedan no llega al target
+    fn explore(&mut self, index: usize, selected: &mut Vec<usize>, current: i64, remaining: i64) {
+        if self.tries == 0 || current > self.upper_bound || current + remaining < self.target {
+            return;
+        }
+        self.tries -= 1;
+        if current >= self.target {
+            // agregar mas valores solo aumenta el excedente
+            let excess = current - self.target;
+            if self.best.as_ref().is_none_or(|(_, best)| excess < *best) {
+                self.best = Some((selected.clone(), excess));
+                if excess == 0 {
+                    self.tries = 0;
+                }
+            }
+            return;
+        }
+        if index == self.values.len() {
+            return;
+        }
+        let value = self.values[index];
+        // si el valor anterior es igual y no se incluyo, incluir este da combinaciones ya exploradas
+        let repeats_excluded_value =
+            index > 0 && self.values[index - 1] == value && selected.last() != Some(&(index - 1));
+        if !repeats_excluded_value {
+            selected.push(index);
+            self.explore(index + 1, selected, current + value, remaining - value);
+            selected.pop();
+        }
+        self.explore(index + 1, selected, current, remaining - value);
+    }
+}
+
+/// Toma los valores de mayor a menor hasta cubrir el target. Devuelve los indices elegidos
+fn largest_first(values: &[i64], target: i64) -> Vec<usize> {
+    let mut selected = Vec::new();
+    let mut current = 0;
+    for (index, value) in values.iter().enumerate() {
+        if current >= target {
+            break;
+        }
+        selected.push(index);
+        current += value;
+    }
+    selected
+}
+
+/// Devuelve la comision de la cantidad de vbytes recibida a la tasa en sat/vB, redondeada hacia arriba
+pub fn fee_for_vsize(vsize: usize, fee_rate: f64) -> i64 {
+    (vsize as f64 * fee_rate).ceil() as i64
+}
+
+/// Devuelve el tamaño en vbytes de un output con el pubkey script recibido
+pub fn output_vsize(pubkey_script: &[u8]) -> usize {
+    OUTPUT_BASE_SIZE
+        + CompactSizeUint::new(pubkey_script.len() as u128)
+            .value()
+            .len()
+        + pubkey_script.len()
+}
+
+/// Estima el tamaño en vbytes del input firmado que gasta el pubkey script recibido.
+/// Los outputs del multisig de la cuenta se estiman con las firmas requeridas por el multisig
+pub fn input_vsize(pubkey_script: &[u8], multisig: Option<&MultisigScript>) -> usize {
+    if let Some(multisig) = multisig.filter(|multisig| multisig.script_pubkey() == pubkey_script) {
+        return multisig_input_vsize(multisig);
+    }
+    match ScriptType::classify(pubkey_script) {
+        ScriptType::P2pk(_) => INPUT_BASE_SIZE + script_vsize(SIGNATURE_PUSH_SIZE),
+        ScriptType::P2wpkh(_) => {
+            INPUT_BASE_SIZE + 1 + witness_vsize(&[SIGNATURE_PUSH_SIZE, PUBKEY_PUSH_SIZE])
+        }
+        _ => DEFAULT_INPUT_VSIZE,
+    }
+}
+
+/// Tamaño del input que gasta un multisig: OP_0, las firmas requeridas y el redeem script
+fn multisig_input_vsize(multisig: &MultisigScript) -> usize {
+    let redeem_script_len = multisig.redeem_script().len();
+    let redeem_script_push = CompactSizeUint::new(redeem_script_len as u128)
+        .value()
+        .len()
+        + redeem_script_len;
+    let signatures = vec![SIGNATURE_PUSH_SIZE; multisig.required as usize];
+    let mut witness_items = vec![1];
+    witness_items.extend_from_slice(&signatures);
+    witness_items.push(redeem_script_push);
+    match multisig.kind {
+        MultisigKind::P2sh => {
+            INPUT_BASE_SIZE
+                + script_vsize(1 + signatures.iter().sum::<usize>() + redeem_script_push + 1)
+        }
+        // el signature script es el push del witness program de 34 bytes
+        MultisigKind::P2shP2wsh => {
+            INPUT_BASE_SIZE + script_vsize(35) + witness_vsize(&witness_items)
+        }
+        MultisigKind::P2wsh => INPUT_BASE_SIZE + 1 + witness_vsize(&witness_items),
+    }
+}
+
+/// Bytes de un signature script con su largo
+fn script_vsize(script_len: usize) -> usize {
+    CompactSizeUint::new(script_len as u128).value().len() + script_len
+}
+
+/// Vbytes de un witness con items de los tamaños recibidos (cada uno con su largo), que pesa un cuarto
+fn witness_vsize(items: &[usize]) -> usize {
+    let weight = 1 + items.iter().sum::<usize>();
+    weight.div_ceil(4)
+}
+
+/// Arma las monedas de las utxos recibidas con el tamaño de su input
+pub fn coins_from_utxos(utxos: &[UtxoTuple], multisig: Option<&MultisigScript>) -> Vec<Coin> {
+    utxos
+        .iter()
+        .flat_map(|utxo| {
+            utxo.utxo_set.iter().map(move |(tx_out, index)| Coin {
+                hash: utxo.hash,
+                index: *index,
+                input_vsize: input_vsize(tx_out.get_pub_key_script(), multisig),
+                tx_out: tx_out.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+
+    const P2WPKH_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+    fn coins(values: &[i64]) -> Vec<Coin> {
+        let script = match Address::parse(P2WPKH_ADDRESS) {
+            Ok(address) => address.script_pubkey(),
+            Err(err) => panic!("{}", err),
+        };
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| Coin {
+                hash: [index as u8; 32],
+                index: 0,
+                tx_out: TxOut::new(
+                    *value,
+                    CompactSizeUint::new(script.len() as u128),
+                    script.clone(),
+                ),
+                input_vsize: input_vsize(&script, None),
+            })
+            .collect()
+    }
+
+    fn params(amount: i64, fee_rate: f64) -> CoinSelectionParams {
+        CoinSelectionParams {
+            amount,
+            fixed_fee: fee_for_vsize(42, fee_rate),
+            fee_rate,
+            change_output_vsize: 31,
+            change_spend_vsize: 68,
+        }
+    }
+
+    fn selected_values(selection: &Selection) -> Vec<i64> {
+        selection.coins.iter().map(Coin::value).collect()
+    }
+
+    #[test]
+    fn branch_and_bound_finds_a_changeless_selection() -> Result<(), Box<dyn Error>> {
+        // GIVEN: monedas donde dos suman exactamente el monto sin comisiones
+        let coins = coins(&[1000, 2000, 5000, 10000]);
+        // WHEN: se seleccionan 7000 satoshis a tasa 0
+        let selection = select_coins(&coins, &params(7000, 0.0))?;
+        // THEN: se eligen esas dos sin cambio
+        assert_eq!(selected_values(&selection), vec![5000, 2000]);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn branch_and_bound_accepts_an_excess_lower_than_the_cost_of_change(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: monedas a 10 sat/vB, donde 30000 y 20000 cubren el monto con poco excedente
+        let coins = coins(&[50_000, 30_000, 20_000, 8_000]);
+        let params = params(48_000, 10.0);
+        // WHEN: se seleccionan
+        let selection = select_coins(&coins, &params)?;
+        // THEN: no se crea cambio y el excedente queda de comision
+        assert_eq!(selected_values(&selection), vec![30_000, 20_000]);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 2000);
+        assert!(selection.fee - (420 + 2 * 680) < params.cost_of_change());
+        Ok(())
+    }
+
+    #[test]
+    fn largest_first_is_used_when_there_is_no_changeless_solution() -> Result<(), Box<dyn Error>> {
+        // GIVEN: monedas que no tienen una combinacion sin cambio
+        let coins = coins(&[3_000, 100_000, 40_000]);
+        let params = params(50_000, 2.0);
+        // WHEN: se seleccionan 50000 satoshis a 2 sat/vB
+        let selection = select_coins(&coins, &params)?;
+        // THEN: se toma la moneda mas grande y el cambio paga su output
+        assert_eq!(selected_values(&selection), vec![100_000]);
+        assert_eq!(selection.fee, 84 + 136 + 62);
+        assert_eq!(selection.change, 100_000 - 50_000 - selection.fee);
+        assert_eq!(selection.utxos().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn dust_change_is_added_to_the_fee() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una moneda que deja un cambio menor al limite de dust
+        let coins = coins(&[10_500, 9_000]);
+        let params = CoinSelectionParams {
+            change_output_vsize: 34,
+            change_spend_vsize: 148,
+            ..params(10_000, 1.0)
+        };
+        // WHEN: se seleccionan
+        let selection = select_coins(&coins, &params)?;
+        // THEN: no se crea el cambio y se suma a la comision
+        assert_eq!(selected_values(&selection), vec![10_500]);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 500);
+        Ok(())
+    }
+
+    #[test]
+    fn the_cost_of_spending_inputs_is_considered_and_uneconomical_coins_are_skipped() {
+        // GIVEN: monedas que en bruto suman mas que el monto, una de ellas cuesta mas que lo que vale
+        let coins = coins(&[6_000, 4_000, 500]);
+        // WHEN: se seleccionan 9900 satoshis a 10 sat/vB
+        let result = select_coins(&coins, &params(9_900, 10.0));
+        // THEN: no alcanza descontando el costo de los inputs
+        assert!(result.is_err());
+        let selection = select_coins(&coins, &params(8_000, 10.0));
+        assert!(selection.is_ok_and(|selection| selection.coins.len() == 2));
+    }
+
+    #[test]
+    fn manually_chosen_coins_are_all_spent() -> Result<(), Box<dyn Error>> {
+        // GIVEN: monedas elegidas a mano, donde la mas grande alcanzaria sola
+        let coins = coins(&[100_000, 2_000]);
+        // WHEN: se arma la seleccion con ellas
+        let selection = use_all_coins(&coins, &params(50_000, 1.0))?;
+        // THEN: se gastan las dos y el excedente vuelve de cambio
+        assert_eq!(selected_values(&selection), vec![100_000, 2_000]);
+        assert_eq!(selection.change, 102_000 - 50_000 - selection.fee);
+        assert!(use_all_coins(&coins[1..], &params(50_000, 1.0)).is_err());
+        Ok(())
+    }
+}
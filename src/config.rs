@@ -11,7 +11,7 @@ use crate::custom_errors::NodeCustomErrors;
 
 /// Permite validar la cantidad de atributos en el archivo de configuración
 /// Si se agregan hay que incrementarlo
-const CANTIDAD_ATRIBUTOS: usize = 23;
+const CANTIDAD_ATRIBUTOS: usize = 28;
 
 /// Almacena los campos leidos del archivo de configuración
 #[derive(Debug, Clone)]
@@ -39,6 +39,11 @@ pub struct Config {
     pub height_first_block_to_download: usize,
     pub archivo_headers: String,
     pub logs_folder_path: String,
+    pub min_relay_fee: u64,
+    pub archivo_coin_control: String,
+    pub archivo_wallet: String,
+    pub wallet_lock_timeout: u64,
+    pub min_confirmations: u32,
 }
 impl Config {
     /// Crea un config leyendo un archivo de configuracion ubicado en la
@@ -94,6 +99,11 @@ impl Config {
             height_first_block_to_download: 0,
             archivo_headers: String::new(),
             logs_folder_path: String::new(),
+            min_relay_fee: 0,
+            archivo_coin_control: String::new(),
+            archivo_wallet: String::new(),
+            wallet_lock_timeout: 0,
+            min_confirmations: 0,
         };
 
         let mut number_of_settings_loaded: usize = 0;
@@ -237,6 +247,26 @@ impl Config {
                 self.logs_folder_path = String::from(value);
                 *number_of_settings_loaded += 1;
             }
+            "MIN_RELAY_FEE" => {
+                self.min_relay_fee = u64::from_str(value)?;
+                *number_of_settings_loaded += 1;
+            }
+            "ARCHIVO_COIN_CONTROL" => {
+                self.archivo_coin_control = String::from(value);
+                *number_of_settings_loaded += 1;
+            }
+            "ARCHIVO_WALLET" => {
+                self.archivo_wallet = String::from(value);
+                *number_of_settings_loaded += 1;
+            }
+            "WALLET_LOCK_TIMEOUT" => {
+                self.wallet_lock_timeout = u64::from_str(value)?;
+                *number_of_settings_loaded += 1;
+            }
+            "MIN_CONFIRMATIONS" => {
+                self.min_confirmations = u32::from_str(value)?;
+                *number_of_settings_loaded += 1;
+            }
             _ => {
                 return Err(Box::new(io::Error::new(
                     io::ErrorKind::InvalidInput,
@@ -0,0 +1,785 @@
+use std::{collections::HashSet, error::Error, fmt, io};
+
+use bitcoin_hashes::{sha256, Hash};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::{
+    account::{bytes_to_hex_string, hex_string_to_bytes},
+    address::Address,
+    address_decoder::{decode_wif_private_key, encode_wif_private_key, hash_160},
+    hd_wallet::bip32::{
+        parse_path, ExtendedPrivateKey, ExtendedPublicKey, HARDENED_INDEX, TESTNET_PRIVATE_VERSION,
+        TESTNET_PUBLIC_VERSION,
+    },
+    transactions::script::multisig_script::{MultisigKind, MultisigScript},
+};
+
+// Output script descriptors (BIP380 a BIP386). Describen los pubkey scripts que paga una cuenta:
+//
+// pkh(KEY)              P2PKH
+// wpkh(KEY)             P2WPKH
+// sh(wpkh(KEY))         P2SH-P2WPKH
+// sh(multi(k,KEY,...))  multisig P2SH. sortedmulti ordena las claves como BIP67
+// wsh(multi(k,KEY,...)) multisig P2WSH, y sh(wsh(multi(...))) el P2SH-P2WSH
+// tr(KEY)               P2TR solo con key path
+// addr(ADDRESS)         la direccion recibida
+// raw(HEX)              el script recibido
+//
+// KEY puede tener el origen "[fingerprint/path]" y ser una clave publica en hexadecimal, una WIF, o una
+// clave extendida (tpub o tprv) seguida de un path que puede terminar en /* para describir un rango de scripts.
+// El descriptor puede terminar en "#" y el checksum de 8 caracteres.
+
+/// Caracteres que pueden aparecer en un descriptor, en el orden que usa el checksum
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+/// Caracteres con los que se escribe el checksum
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Generadores del codigo BCH del checksum
+const CHECKSUM_GENERATOR: [u64; 5] = [
+    0xf5dee51989,
+    0xa9fdca3312,
+    0x1bab10e32d,
+    0x3706b1677a,
+    0x644d626ffd,
+];
+/// Largo del checksum
+const CHECKSUM_LEN: usize = 8;
+
+/// Origen de una clave: el fingerprint de la clave maestra y el path con que se derivo
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrigin {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>,
+}
+
+/// Clave extendida de un descriptor, publica (tpub) o privada (tprv)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtendedKey {
+    Public(ExtendedPublicKey),
+    Private(ExtendedPrivateKey),
+}
+
+/// Material de una clave de un descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    /// Clave publica comprimida (33 bytes) o x-only (32 bytes, solo dentro de tr())
+    Public(Vec<u8>),
+    /// Clave privada que se escribe como WIF
+    Wif([u8; 32]),
+    /// Clave extendida y el path que se deriva desde ella. Si wildcard es true el path termina en /*
+    Extended {
+        key: ExtendedKey,
+        path: Vec<u32>,
+        wildcard: bool,
+    },
+}
+
+/// Clave de un descriptor con su origen, si se conoce
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorKey {
+    pub origin: Option<KeyOrigin>,
+    pub source: KeySource,
+}
+
+impl DescriptorKey {
+    /// Crea la clave de la clave publica comprimida recibida, sin origen
+    pub fn public(public_key: [u8; 33]) -> DescriptorKey {
+        DescriptorKey {
+            origin: None,
+            source: KeySource::Public(public_key.to_vec()),
+        }
+    }
+
+    /// Crea la clave que deriva desde la clave extendida recibida el path recibido seguido de /*, sin origen
+    pub fn ranged(key: ExtendedKey, path: Vec<u32>) -> DescriptorKey {
+        DescriptorKey {
+            origin: None,
+            source: KeySource::Extended {
+                key,
+                path,
+                wildcard: true,
+            },
+        }
+    }
+
+    /// Parsea la clave. Solo se aceptan claves x-only si x_only_allowed es true (dentro de tr()).
+    /// Devuelve error si la clave, su origen o su path son invalidos
+    fn parse(text: &str, x_only_allowed: bool) -> Result<DescriptorKey, Box<dyn Error>> {
+        let (origin, key) = match text.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or_else(|| invalid_descriptor("el origen de una clave no termina en ]"))?;
+                (Some(parse_origin(origin)?), key)
+            }
+            None => (None, text),
+        };
+        let mut parts = key.split('/');
+        let encoded = parts.next().unwrap_or_default();
+        let steps: Vec<&str> = parts.collect();
+        let source = if encoded.starts_with("tpub") || encoded.starts_with("tprv") {
+            let key = if encoded.starts_with("tpub") {
+                ExtendedKey::Public(ExtendedPublicKey::from_base58(
+                    encoded,
+                    TESTNET_PUBLIC_VERSION,
+                )?)
+            } else {
+                ExtendedKey::Private(ExtendedPrivateKey::from_base58(
+                    encoded,
+                    TESTNET_PRIVATE_VERSION,
+                )?)
+            };
+            let wildcard = steps.last() == Some(&"*");
+            let steps = &steps[..steps.len() - wildcard as usize];
+            if steps.iter().any(|step| step.contains('*')) {
+                return Err(invalid_descriptor(
+                    "solo se soportan rangos no hardened al final del path",
+                ));
+            }
+            let path = if steps.is_empty() {
+                vec![]
+            } else {
+                parse_path(&format!("m/{}", steps.join("/")))?
+            };
+            KeySource::Extended {
+                key,
+                path,
+                wildcard,
+            }
+        } else if !steps.is_empty() {
+            return Err(invalid_descriptor(
+                "solo las claves extendidas pueden tener un path",
+            ));
+        } else if let Ok(bytes) = hex_string_to_bytes(encoded) {
+            match bytes.len() {
+                33 => {
+                    PublicKey::from_slice(&bytes)?;
+                }
+                32 if x_only_allowed => {
+                    XOnlyPublicKey::from_slice(&bytes)?;
+                }
+                _ => {
+                    return Err(invalid_descriptor(
+                        "el largo de una clave publica es invalido",
+                    ))
+                }
+            }
+            KeySource::Public(bytes)
+        } else {
+            let private_key = decode_wif_private_key(encoded)?;
+            SecretKey::from_slice(&private_key)?;
+            KeySource::Wif(private_key)
+        };
+        Ok(DescriptorKey { origin, source })
+    }
+
+    /// Devuelve true si la clave describe un rango de claves
+    pub fn is_range(&self) -> bool {
+        matches!(self.source, KeySource::Extended { wildcard: true, .. })
+    }
+
+    /// Devuelve true si la clave incluye la clave privada
+    pub fn has_private_key(&self) -> bool {
+        matches!(
+            self.source,
+            KeySource::Wif(_)
+                | KeySource::Extended {
+                    key: ExtendedKey::Private(_),
+                    ..
+                }
+        )
+    }
+
+    /// Devuelve la clave publica con el indice recibido del rango, o la unica clave si no es un rango.
+    /// Es comprimida salvo las claves x-only. Devuelve error si la derivacion no es posible
+    pub fn public_key(&self, index: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.source {
+            KeySource::Public(public_key) => Ok(public_key.clone()),
+            KeySource::Wif(private_key) => {
+                let secp = Secp256k1::new();
+                let secret_key = SecretKey::from_slice(private_key)?;
+                Ok(PublicKey::from_secret_key(&secp, &secret_key)
+                    .serialize()
+                    .to_vec())
+            }
+            KeySource::Extended {
+                key,
+                path,
+                wildcard,
+            } => {
+                let mut path = path.clone();
+                if *wildcard {
+                    path.push(index);
+                }
+                match key {
+                    ExtendedKey::Public(key) => {
+                        let mut key = key.clone();
+                        for index in path {
+                            key = key.derive_child(index)?;
+                        }
+                        Ok(key.public_key.to_vec())
+                    }
+                    ExtendedKey::Private(key) => {
+                        let mut key = key.clone();
+                        for index in path {
+                            key = key.derive_child(index)?;
+                        }
+                        Ok(key.public_key()?.to_vec())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Devuelve la clave publica comprimida con el indice recibido. Devuelve error si es x-only
+    fn compressed_public_key(&self, index: u32) -> Result<[u8; 33], Box<dyn Error>> {
+        self.public_key(index)?
+            .try_into()
+            .map_err(|_| invalid_descriptor("se esperaba una clave publica comprimida"))
+    }
+
+    /// Devuelve la misma clave sin la clave privada
+    pub fn to_public(&self) -> Result<DescriptorKey, Box<dyn Error>> {
+        let source = match &self.source {
+            KeySource::Wif(_) => KeySource::Public(self.public_key(0)?),
+            KeySource::Extended {
+                key: ExtendedKey::Private(key),
+                path,
+                wildcard,
+            } => KeySource::Extended {
+                key: ExtendedKey::Public(key.to_public()?),
+                path: path.clone(),
+                wildcard: *wildcard,
+            },
+            source => source.clone(),
+        };
+        Ok(DescriptorKey {
+            origin: self.origin.clone(),
+            source,
+        })
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(
+                f,
+                "[{}{}]",
+                bytes_to_hex_string(&origin.fingerprint),
+                format_path(&origin.path)
+            )?;
+        }
+        match &self.source {
+            KeySource::Public(public_key) => write!(f, "{}", bytes_to_hex_string(public_key)),
+            KeySource::Wif(private_key) => write!(f, "{}", encode_wif_private_key(private_key)),
+            KeySource::Extended {
+                key,
+                path,
+                wildcard,
+            } => {
+                let encoded = match key {
+                    ExtendedKey::Public(key) => key.to_base58(TESTNET_PUBLIC_VERSION),
+                    ExtendedKey::Private(key) => key.to_base58(TESTNET_PRIVATE_VERSION),
+                };
+                write!(f, "{}{}", encoded, format_path(path))?;
+                if *wildcard {
+                    write!(f, "/*")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Descriptor de los pubkey scripts que paga una cuenta
+#[derive(Debug, Clone, PartialEq)]
+pub enum Descriptor {
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    ShWpkh(DescriptorKey),
+    /// multi() o sortedmulti() dentro de sh(), wsh() o sh(wsh()) segun el tipo de multisig
+    Multi {
+        kind: MultisigKind,
+        required: u8,
+        keys: Vec<DescriptorKey>,
+        sorted: bool,
+    },
+    Tr(DescriptorKey),
+    Addr(Address),
+    Raw(Vec<u8>),
+}
+
+impl Descriptor {
+    /// Parsea el descriptor. Si tiene checksum lo verifica.
+    /// Devuelve error si el descriptor es invalido, no esta soportado o el checksum no coincide
+    pub fn parse(text: &str) -> Result<Descriptor, Box<dyn Error>> {
+        let text = text.trim();
+        let body = match text.split_once('#') {
+            Some((body, checksum)) => {
+                if descriptor_checksum(body)? != checksum {
+                    return Err(invalid_descriptor("el checksum es incorrecto"));
+                }
+                body
+            }
+            None => text,
+        };
+        let descriptor = parse_descriptor(body)?;
+        // se deriva el primer script para detectar claves o paths que no se pueden usar
+        descriptor.script_pubkey(0)?;
+        Ok(descriptor)
+    }
+
+    /// Devuelve el descriptor del pubkey script de la direccion recibida: pkh(), wpkh() o sh(wpkh()) de la
+    /// clave publica recibida si le corresponde, o addr() si no (por ejemplo si la direccion es de la clave
+    /// no comprimida)
+    pub fn for_key_address(
+        public_key: [u8; 33],
+        address: &str,
+    ) -> Result<Descriptor, Box<dyn Error>> {
+        let key = DescriptorKey::public(public_key);
+        for descriptor in [
+            Descriptor::Pkh(key.clone()),
+            Descriptor::Wpkh(key.clone()),
+            Descriptor::ShWpkh(key),
+        ] {
+            if descriptor.address(0).is_ok_and(|own| own == address) {
+                return Ok(descriptor);
+            }
+        }
+        Ok(Descriptor::Addr(Address::parse(address)?))
+    }
+
+    /// Devuelve el descriptor del pubkey script recibido: addr() si es de un tipo estandar, sino raw()
+    pub fn for_script(script: Vec<u8>) -> Descriptor {
+        match Address::from_script_pubkey(&script) {
+            Some(address) => Descriptor::Addr(address),
+            None => Descriptor::Raw(script),
+        }
+    }
+
+    /// Devuelve las claves del descriptor
+    pub fn keys(&self) -> Vec<&DescriptorKey> {
+        match self {
+            Descriptor::Pkh(key)
+            | Descriptor::Wpkh(key)
+            | Descriptor::ShWpkh(key)
+            | Descriptor::Tr(key) => vec![key],
+            Descriptor::Multi { keys, .. } => keys.iter().collect(),
+            Descriptor::Addr(_) | Descriptor::Raw(_) => vec![],
+        }
+    }
+
+    /// Devuelve true si el descriptor describe un rango de scripts
+    pub fn is_range(&self) -> bool {
+        self.keys().iter().any(|key| key.is_range())
+    }
+
+    /// Devuelve true si el descriptor incluye alguna clave privada
+    pub fn has_private_keys(&self) -> bool {
+        self.keys().iter().any(|key| key.has_private_key())
+    }
+
+    /// Devuelve el pubkey script con el indice recibido del rango, o el unico script si no es un rango.
+    /// Devuelve error si alguna clave no se puede derivar
+    pub fn script_pubkey(&self, index: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(match self {
+            Descriptor::Pkh(key) => {
+                Address::P2pkh(hash_160(&key.compressed_public_key(index)?)).script_pubkey()
+            }
+            Descriptor::Wpkh(key) => {
+                Address::P2wpkh(hash_160(&key.compressed_public_key(index)?)).script_pubkey()
+            }
+            Descriptor::ShWpkh(key) => {
+                let witness_program =
+                    Address::P2wpkh(hash_160(&key.compressed_public_key(index)?)).script_pubkey();
+                Address::P2sh(hash_160(&witness_program)).script_pubkey()
+            }
+            Descriptor::Multi { .. } => self.multisig(index)?.script_pubkey(),
+            Descriptor::Tr(key) => {
+                let public_key = key.public_key(index)?;
+                let internal_key = &public_key[public_key.len() - 32..];
+                Address::P2tr(taproot_output_key(internal_key)?).script_pubkey()
+            }
+            Descriptor::Addr(address) => address.script_pubkey(),
+            Descriptor::Raw(script) => script.clone(),
+        })
+    }
+
+    /// Devuelve los pubkey scripts de los primeros indices del rango recibidos, o el unico script si
+    /// no es un rango. Se ignoran los indices que no se pueden derivar
+    pub fn script_pubkeys(&self, range_end: u32) -> HashSet<Vec<u8>> {
+        let range_end = if self.is_range() { range_end } else { 1 };
+        (0..range_end)
+            .filter_map(|index| self.script_pubkey(index).ok())
+            .collect()
+    }
+
+    /// Devuelve la direccion del script con el indice recibido.
+    /// Devuelve error si el script no es de un tipo estandar
+    pub fn address(&self, index: u32) -> Result<String, Box<dyn Error>> {
+        Address::from_script_pubkey(&self.script_pubkey(index)?)
+            .map(|address| address.to_string())
+            .ok_or_else(|| invalid_descriptor("el script no tiene una direccion estandar"))
+    }
+
+    /// Devuelve el multisig con el indice recibido si el descriptor es multi() o sortedmulti()
+    pub fn multisig(&self, index: u32) -> Result<MultisigScript, Box<dyn Error>> {
+        match self {
+            Descriptor::Multi {
+                kind,
+                required,
+                keys,
+                sorted,
+            } => {
+                let pubkeys = keys
+                    .iter()
+                    .map(|key| key.compressed_public_key(index))
+                    .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+                // se valida como lo hace la wallet, pero multi() mantiene el orden de las claves
+                let sorted_multisig = MultisigScript::new(*kind, *required, pubkeys.clone())?;
+                if *sorted {
+                    return Ok(sorted_multisig);
+                }
+                Ok(MultisigScript {
+                    kind: *kind,
+                    required: *required,
+                    pubkeys,
+                })
+            }
+            _ => Err(invalid_descriptor("el descriptor no es un multisig")),
+        }
+    }
+
+    /// Devuelve el mismo descriptor sin claves privadas
+    pub fn to_public(&self) -> Result<Descriptor, Box<dyn Error>> {
+        self.map_keys(|key| key.to_public())
+    }
+
+    /// Devuelve el mismo descriptor con la clave privada recibida en lugar de su clave publica,
+    /// donde aparezca como clave publica
+    pub fn with_private_key(&self, private_key: [u8; 32]) -> Result<Descriptor, Box<dyn Error>> {
+        let own_key = DescriptorKey {
+            origin: None,
+            source: KeySource::Wif(private_key),
+        };
+        let public_key = own_key.public_key(0)?;
+        self.map_keys(|key| match &key.source {
+            KeySource::Public(bytes) if *bytes == public_key => Ok(DescriptorKey {
+                origin: key.origin.clone(),
+                source: own_key.source.clone(),
+            }),
+            _ => Ok(key.clone()),
+        })
+    }
+
+    /// Devuelve el descriptor con cada clave reemplazada por el resultado de la funcion recibida
+    fn map_keys<F>(&self, function: F) -> Result<Descriptor, Box<dyn Error>>
+    where
+        F: Fn(&DescriptorKey) -> Result<DescriptorKey, Box<dyn Error>>,
+    {
+        Ok(match self {
+            Descriptor::Pkh(key) => Descriptor::Pkh(function(key)?),
+            Descriptor::Wpkh(key) => Descriptor::Wpkh(function(key)?),
+            Descriptor::ShWpkh(key) => Descriptor::ShWpkh(function(key)?),
+            Descriptor::Tr(key) => Descriptor::Tr(function(key)?),
+            Descriptor::Multi {
+                kind,
+                required,
+                keys,
+                sorted,
+            } => Descriptor::Multi {
+                kind: *kind,
+                required: *required,
+                keys: keys
+                    .iter()
+                    .map(function)
+                    .collect::<Result<Vec<DescriptorKey>, Box<dyn Error>>>()?,
+                sorted: *sorted,
+            },
+            descriptor => descriptor.clone(),
+        })
+    }
+
+    /// Devuelve el descriptor sin el checksum
+    fn body(&self) -> String {
+        match self {
+            Descriptor::Pkh(key) => format!("pkh({})", key),
+            Descriptor::Wpkh(key) => format!("wpkh({})", key),
+            Descriptor::ShWpkh(key) => format!("sh(wpkh({}))", key),
+            Descriptor::Multi {
+                kind,
+                required,
+                keys,
+                sorted,
+            } => {
+                let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+                let multi = format!(
+                    "{}({},{})",
+                    if *sorted { "sortedmulti" } else { "multi" },
+                    required,
+                    keys.join(",")
+                );
+                match kind {
+                    MultisigKind::P2sh => format!("sh({})", multi),
+                    MultisigKind::P2wsh => format!("wsh({})", multi),
+                    MultisigKind::P2shP2wsh => format!("sh(wsh({}))", multi),
+                }
+            }
+            Descriptor::Tr(key) => format!("tr({})", key),
+            Descriptor::Addr(address) => format!("addr({})", address),
+            Descriptor::Raw(script) => format!("raw({})", bytes_to_hex_string(script)),
+        }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    /// Escribe el descriptor con su checksum
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let body = self.body();
+        let checksum = descriptor_checksum(&body).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", body, checksum)
+    }
+}
+
+/// Parsea el descriptor sin checksum
+fn parse_descriptor(text: &str) -> Result<Descriptor, Box<dyn Error>> {
+    let (function, argument) = split_function(text)?;
+    match function {
+        "pkh" => Ok(Descriptor::Pkh(DescriptorKey::parse(argument, false)?)),
+        "wpkh" => Ok(Descriptor::Wpkh(DescriptorKey::parse(argument, false)?)),
+        "tr" => Ok(Descriptor::Tr(DescriptorKey::parse(argument, true)?)),
+        "addr" => Ok(Descriptor::Addr(Address::parse(argument)?)),
+        "raw" => Ok(Descriptor::Raw(hex_string_to_bytes(argument)?)),
+        "wsh" => parse_multi(argument, MultisigKind::P2wsh),
+        "sh" => match split_function(argument)? {
+            ("wpkh", key) => Ok(Descriptor::ShWpkh(DescriptorKey::parse(key, false)?)),
+            ("wsh", multi) => parse_multi(multi, MultisigKind::P2shP2wsh),
+            _ => parse_multi(argument, MultisigKind::P2sh),
+        },
+        _ => Err(invalid_descriptor(&format!(
+            "la funcion {} no esta soportada",
+            function
+        ))),
+    }
+}
+
+/// Parsea multi(k,KEY,...) o sortedmulti(k,KEY,...) con el tipo de multisig recibido
+fn parse_multi(text: &str, kind: MultisigKind) -> Result<Descriptor, Box<dyn Error>> {
+    let (function, argument) = split_function(text)?;
+    let sorted = match function {
+        "multi" => false,
+        "sortedmulti" => true,
+        _ => {
+            return Err(invalid_descriptor(
+                "sh() y wsh() solo pueden contener wpkh(), wsh(), multi() o sortedmulti()",
+            ))
+        }
+    };
+    let mut arguments = argument.split(',');
+    let required = arguments
+        .next()
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| invalid_descriptor("la cantidad de firmas del multisig es invalida"))?;
+    let keys = arguments
+        .map(|key| DescriptorKey::parse(key, false))
+        .collect::<Result<Vec<DescriptorKey>, Box<dyn Error>>>()?;
+    Ok(Descriptor::Multi {
+        kind,
+        required,
+        keys,
+        sorted,
+    })
+}
+
+/// Separa "funcion(argumento)" en la funcion y el argumento
+fn split_function(text: &str) -> Result<(&str, &str), Box<dyn Error>> {
+    text.strip_suffix(')')
+        .and_then(|text| text.split_once('('))
+        .ok_or_else(|| invalid_descriptor(&format!("se esperaba funcion(...) en {}", text)))
+}
+
+/// Parsea el origen de una clave: el fingerprint en hexadecimal seguido del path
+fn parse_origin(text: &str) -> Result<KeyOrigin, Box<dyn Error>> {
+    let (fingerprint, path) = match text.split_once('/') {
+        Some((fingerprint, path)) => (fingerprint, format!("m/{}", path)),
+        None => (text, "m".to_string()),
+    };
+    let fingerprint = hex_string_to_bytes(fingerprint)?
+        .try_into()
+        .map_err(|_| invalid_descriptor("el fingerprint del origen debe tener 4 bytes"))?;
+    Ok(KeyOrigin {
+        fingerprint,
+        path: parse_path(&path)?,
+    })
+}
+
+/// Escribe los indices del path, cada uno precedido por /, con ' en los hardened
+fn format_path(path: &[u32]) -> String {
+    path.iter()
+        .map(|index| match index.checked_sub(HARDENED_INDEX) {
+            Some(index) => format!("/{}'", index),
+            None => format!("/{}", index),
+        })
+        .collect()
+}
+
+/// Devuelve la clave de salida de taproot de la clave interna x-only recibida, sin arbol de scripts (BIP86)
+fn taproot_output_key(internal_key: &[u8]) -> Result<[u8; 32], Box<dyn Error>> {
+    let tag = sha256::Hash::hash(b"TapTweak").to_byte_array();
+    let mut data = tag.to_vec();
+    data.extend_from_slice(&tag);
+    data.extend_from_slice(internal_key);
+    let tweak = sha256::Hash::hash(&data).to_byte_array();
+    let secp = Secp256k1::verification_only();
+    let (output_key, _) = XOnlyPublicKey::from_slice(internal_key)?
+        .add_tweak(&secp, &Scalar::from_be_bytes(tweak)?)?;
+    Ok(output_key.serialize())
+}
+
+/// Devuelve el checksum del descriptor sin checksum recibido (BIP380).
+/// Devuelve error si tiene caracteres que no pueden aparecer en un descriptor
+pub fn descriptor_checksum(body: &str) -> Result<String, Box<dyn Error>> {
+    let mut symbols = Vec::new();
+    let mut groups = Vec::new();
+    for character in body.chars() {
+        let position = INPUT_CHARSET
+            .find(character)
+            .ok_or_else(|| invalid_descriptor("tiene caracteres invalidos"))?
+            as u64;
+        symbols.push(position & 31);
+        groups.push(position >> 5);
+        if groups.len() == 3 {
+            symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.as_slice() {
+        [group] => symbols.push(*group),
+        [first, second] => symbols.push(first * 3 + second),
+        _ => {}
+    }
+    symbols.extend_from_slice(&[0; CHECKSUM_LEN]);
+    let checksum = polymod(&symbols) ^ 1;
+    Ok((0..CHECKSUM_LEN)
+        .map(|index| {
+            CHECKSUM_CHARSET[((checksum >> (5 * (CHECKSUM_LEN - 1 - index))) & 31) as usize] as char
+        })
+        .collect())
+}
+
+/// Calcula el codigo BCH de los simbolos recibidos
+fn polymod(symbols: &[u64]) -> u64 {
+    let mut checksum: u64 = 1;
+    for symbol in symbols {
+        let top = checksum >> 35;
+        checksum = ((checksum & 0x7_ffff_ffff) << 5) ^ symbol;
+        for (index, generator) in CHECKSUM_GENERATOR.iter().enumerate() {
+            if (top >> index) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn invalid_descriptor(reason: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("El descriptor es invalido: {}", reason),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hd_wallet::mnemonic::mnemonic_to_seed;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn checksums_are_computed_and_verified() -> Result<(), Box<dyn Error>> {
+        // GIVEN: el descriptor de ejemplo de BIP380
+        // WHEN: se calcula su checksum y se parsea con checksums correctos e incorrectos
+        // THEN: el checksum es el del BIP y solo se acepta si coincide
+        assert_eq!(descriptor_checksum("raw(deadbeef)")?, "89f8spxm");
+        let descriptor = Descriptor::parse("raw(deadbeef)#89f8spxm")?;
+        assert_eq!(descriptor.to_string(), "raw(deadbeef)#89f8spxm");
+        assert!(Descriptor::parse("raw(deadbeef)#89f8spxn").is_err());
+        assert!(Descriptor::parse("foo(deadbeef)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ranged_descriptors_derive_the_wallet_addresses() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la clave publica extendida de la cuenta BIP84 del mnemonic de prueba
+        let seed = mnemonic_to_seed(MNEMONIC, "");
+        let account_key = ExtendedPrivateKey::from_seed(&seed)?.derive_path("m/84'/1'/0'")?;
+        let text = format!(
+            "wpkh([73c5da0a/84'/1'/0']{}/0/*)",
+            account_key.to_public()?.to_base58(TESTNET_PUBLIC_VERSION)
+        );
+        // WHEN: se parsea el descriptor con rango
+        let descriptor = Descriptor::parse(&text)?;
+        // THEN: deriva las direcciones de recepcion de la cuenta y se vuelve a escribir igual
+        assert!(descriptor.is_range());
+        assert!(!descriptor.has_private_keys());
+        assert_eq!(
+            descriptor.address(0)?,
+            "tb1q6rz28mcfaxtmd6v789l9rrlrusdprr9pqcpvkl"
+        );
+        assert_eq!(descriptor.script_pubkeys(20).len(), 20);
+        assert_eq!(Descriptor::parse(&descriptor.to_string())?, descriptor);
+        assert!(descriptor.to_string().starts_with(&text));
+        Ok(())
+    }
+
+    #[test]
+    fn taproot_and_multisig_descriptors_produce_the_expected_scripts() -> Result<(), Box<dyn Error>>
+    {
+        // GIVEN: la clave interna del primer vector de BIP86 y dos claves publicas
+        let seed = mnemonic_to_seed(MNEMONIC, "");
+        let internal_key = ExtendedPrivateKey::from_seed(&seed)?
+            .derive_path("m/86'/0'/0'/0/0")?
+            .public_key()?;
+        let keys: Vec<String> = [[1; 32], [2; 32]]
+            .iter()
+            .map(|private_key| {
+                let key = DescriptorKey {
+                    origin: None,
+                    source: KeySource::Wif(*private_key),
+                };
+                key.to_public().map(|key| key.to_string())
+            })
+            .collect::<Result<Vec<String>, Box<dyn Error>>>()?;
+        // WHEN: se evaluan tr() y los multisig con las claves en ambos ordenes
+        let taproot = Descriptor::parse(&format!("tr({})", bytes_to_hex_string(&internal_key)))?;
+        let multisig = |function: &str, first: &str, second: &str| {
+            Descriptor::parse(&format!("wsh({}(1,{},{}))", function, first, second))?
+                .script_pubkey(0)
+        };
+        // THEN: el output key es el del BIP, sortedmulti no depende del orden y paga al mismo multisig
+        // que la wallet, y multi() si depende del orden
+        assert_eq!(
+            bytes_to_hex_string(&taproot.script_pubkey(0)?),
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+        let pubkeys = keys
+            .iter()
+            .map(|key| DescriptorKey::parse(key, false)?.compressed_public_key(0))
+            .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+        let sorted = multisig("sortedmulti", &keys[1], &keys[0])?;
+        assert_eq!(sorted, multisig("sortedmulti", &keys[0], &keys[1])?);
+        assert_eq!(
+            sorted,
+            MultisigScript::new(MultisigKind::P2wsh, 1, pubkeys)?.script_pubkey()
+        );
+        assert_ne!(
+            multisig("multi", &keys[0], &keys[1])?,
+            multisig("multi", &keys[1], &keys[0])?
+        );
+        assert!(Descriptor::parse(&format!("wsh(multi(3,{},{}))", keys[0], keys[1])).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+use crate::{transactions::transaction::Transaction, utxo_tuple::UtxoTuple};
+
+/// Fee rate minimo en sat/vB con el que los nodos retransmiten una transaccion
+pub const MIN_RELAY_FEE_RATE: f64 = 1.0;
+/// Fee rate minimo del primer bucket en sat/vB
+const FIRST_BUCKET_FEE_RATE: f64 = 1.0;
+/// Cada bucket empieza en el fee rate del anterior multiplicado por este factor
+const BUCKET_SPACING: f64 = 1.1;
+/// A partir de este fee rate en sat/vB todas las transacciones caen en el ultimo bucket
+const MAX_BUCKET_FEE_RATE: f64 = 10_000.0;
+/// Bloques luego de los que una transaccion que no se confirmo se cuenta como fallida
+const MAX_CONFIRMATION_BLOCKS: usize = 48;
+/// Proporcion de transacciones que se tienen que haber confirmado a tiempo para aceptar un fee rate
+const SUCCESS_THRESHOLD: f64 = 0.85;
+/// Cantidad minima de transacciones que tiene que tener un grupo de buckets para sacar conclusiones
+const MIN_DATA_POINTS: f64 = 10.0;
+/// Por cada bloque nuevo los datos viejos pesan un poco menos, para seguir los cambios del mercado
+const DECAY: f64 = 0.998;
+
+type TxId = [u8; 32];
+
+/// Objetivos de tiempo de confirmacion que se le ofrecen al usuario al enviar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeTarget {
+    Economical,
+    Normal,
+    Priority,
+}
+
+impl FeeTarget {
+    /// Devuelve los objetivos de menor a mayor prioridad
+    pub fn all() -> [FeeTarget; 3] {
+        [
+            FeeTarget::Economical,
+            FeeTarget::Normal,
+            FeeTarget::Priority,
+        ]
+    }
+
+    /// Cantidad de bloques en que se espera que se confirme la transaccion
+    pub fn blocks(&self) -> usize {
+        match self {
+            FeeTarget::Economical => 24,
+            FeeTarget::Normal => 6,
+            FeeTarget::Priority => 2,
+        }
+    }
+
+    /// Fee rate en sat/vB que se usa mientras no haya datos suficientes para estimar
+    fn fallback_fee_rate(&self) -> f64 {
+        match self {
+            FeeTarget::Economical => 1.0,
+            FeeTarget::Normal => 2.0,
+            FeeTarget::Priority => 5.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeeTarget::Economical => "Economical",
+            FeeTarget::Normal => "Normal",
+            FeeTarget::Priority => "Priority",
+        }
+    }
+
+    /// Devuelve el objetivo con el nombre recibido, sin importar mayusculas
+    pub fn from_name(name: &str) -> Option<FeeTarget> {
+        FeeTarget::all()
+            .into_iter()
+            .find(|target| target.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Transacciones confirmadas y fallidas con fee rate dentro de un rango
+#[derive(Debug, Clone)]
+struct FeeBucket {
+    min_fee_rate: f64,
+    /// Cantidad de transacciones que se confirmaron en i + 1 bloques
+    confirmed: Vec<f64>,
+    /// Cantidad de transacciones que no se confirmaron en MAX_CONFIRMATION_BLOCKS bloques
+    failed: f64,
+    /// Suma de los fee rates de las transacciones, para devolver el promedio del bucket
+    fee_rate_sum: f64,
+}
+
+impl FeeBucket {
+    fn new(min_fee_rate: f64) -> Self {
+        FeeBucket {
+            min_fee_rate,
+            confirmed: vec![0.0; MAX_CONFIRMATION_BLOCKS],
+            failed: 0.0,
+            fee_rate_sum: 0.0,
+        }
+    }
+
+    fn decay(&mut self) {
+        self.confirmed.iter_mut().for_each(|count| *count *= DECAY);
+        self.failed *= DECAY;
+        self.fee_rate_sum *= DECAY;
+    }
+}
+
+/// Transaccion vista en la red que todavia no se confirmo
+#[derive(Debug, Clone)]
+struct TrackedTransaction {
+    fee_rate: f64,
+    height: usize,
+}
+
+/// Estima el fee rate necesario para confirmar en una cantidad de bloques a partir de cuanto tardaron
+/// en confirmarse las transacciones que los nodos nos enviaron con mensajes tx, agrupadas por fee rate
+#[derive(Debug, Clone)]
+pub struct FeeEstimator {
+    buckets: Vec<FeeBucket>,
+    unconfirmed: HashMap<TxId, TrackedTransaction>,
+    best_height: usize,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FeeEstimator {
+    /// Crea el estimador sin datos, con los buckets de fee rate espaciados geometricamente
+    pub fn new() -> Self {
+        let mut buckets = Vec::new();
+        let mut fee_rate = FIRST_BUCKET_FEE_RATE;
+        while fee_rate < MAX_BUCKET_FEE_RATE {
+            buckets.push(FeeBucket::new(fee_rate));
+            fee_rate *= BUCKET_SPACING;
+        }
+        FeeEstimator {
+            buckets,
+            unconfirmed: HashMap::new(),
+            best_height: 0,
+        }
+    }
+
+    /// Empieza a seguir una transaccion vista en la red con la altura de la cadena en ese momento.
+    /// Se ignoran las transacciones ya seguidas y las que pagan menos que el minimo de relay
+    pub fn track_transaction(&mut self, txid: TxId, fee_rate: f64, height: usize) {
+        if fee_rate < MIN_RELAY_FEE_RATE || self.unconfirmed.contains_key(&txid) {
+            return;
+        }
+        self.best_height = self.best_height.max(height);
+        self.unconfirmed
+            .insert(txid, TrackedTransaction { fee_rate, height });
+    }
+
+    /// Procesa un bloque nuevo de la altura recibida: registra cuantos bloques tardaron en confirmarse
+    /// las transacciones seguidas que incluye y cuenta como fallidas las que esperan hace demasiado
+    pub fn process_block(&mut self, height: usize, txids: &[TxId]) {
+        if height <= self.best_height {
+            // un bloque viejo o repetido no dice nada de los tiempos de confirmacion actuales
+            txids.iter().for_each(|txid| {
+                self.unconfirmed.remove(txid);
+            });
+            return;
+        }
+        self.best_height = height;
+        self.buckets.iter_mut().for_each(FeeBucket::decay);
+        for txid in txids {
+            if let Some(tracked) = self.unconfirmed.remove(txid) {
+                let blocks = height.saturating_sub(tracked.height).max(1);
+                let bucket = self.bucket_index(tracked.fee_rate);
+                self.buckets[bucket].confirmed[blocks.min(MAX_CONFIRMATION_BLOCKS) - 1] += 1.0;
+                self.buckets[bucket].fee_rate_sum += tracked.fee_rate;
+            }
+        }
+        let expired: Vec<TxId> = self
+            .unconfirmed
+            .iter()
+            .filter(|(_, tracked)| height.saturating_sub(tracked.height) >= MAX_CONFIRMATION_BLOCKS)
+            .map(|(txid, _)| *txid)
+            .collect();
+        for txid in expired {
+            if let Some(tracked) = self.unconfirmed.remove(&txid) {
+                let bucket = self.bucket_index(tracked.fee_rate);
+                self.buckets[bucket].failed += 1.0;
+                self.buckets[bucket].fee_rate_sum += tracked.fee_rate;
+            }
+        }
+    }
+
+    /// Devuelve el menor fee rate en sat/vB con el que al menos el 85% de las transacciones se
+    /// confirmaron en la cantidad de bloques recibida, o None si no hay datos suficientes.
+    /// Recorre los buckets de mayor a menor fee rate, juntando buckets hasta tener suficientes datos
+    pub fn estimate_fee_rate(&self, blocks: usize) -> Option<f64> {
+        let blocks = blocks.clamp(1, MAX_CONFIRMATION_BLOCKS);
+        let waiting = self.waiting_longer_than(blocks);
+        let mut estimate = None;
+        let (mut observed, mut not_confirmed, mut on_time, mut fee_rate_sum) = (0.0, 0.0, 0.0, 0.0);
+        for (index, bucket) in self.buckets.iter().enumerate().rev() {
+            observed += bucket.confirmed.iter().sum::<f64>() + bucket.failed;
+            not_confirmed += waiting[index];
+            on_time += bucket.confirmed[..blocks].iter().sum::<f64>();
+            fee_rate_sum += bucket.fee_rate_sum;
+            let total = observed + not_confirmed;
+            if total < MIN_DATA_POINTS {
+                continue;
+            }
+            if on_time / total < SUCCESS_THRESHOLD {
+                break;
+            }
+            if observed > 0.0 {
+                estimate = Some(fee_rate_sum / observed);
+            }
+            (observed, not_confirmed, on_time, fee_rate_sum) = (0.0, 0.0, 0.0, 0.0);
+        }
+        estimate.map(|fee_rate: f64| fee_rate.max(MIN_RELAY_FEE_RATE))
+    }
+
+    /// Devuelve el fee rate estimado para el objetivo recibido, o uno por defecto si no hay datos
+    pub fn fee_rate_for(&self, target: FeeTarget) -> f64 {
+        self.estimate_fee_rate(target.blocks())
+            .unwrap_or(target.fallback_fee_rate())
+    }
+
+    /// Cantidad de transacciones de cada bucket que llevan esperando mas bloques que los recibidos,
+    /// que tambien cuentan como que no se confirmaron a tiempo
+    fn waiting_longer_than(&self, blocks: usize) -> Vec<f64> {
+        let mut waiting = vec![0.0; self.buckets.len()];
+        for tracked in self.unconfirmed.values() {
+            if self.best_height.saturating_sub(tracked.height) > blocks {
+                waiting[self.bucket_index(tracked.fee_rate)] += 1.0;
+            }
+        }
+        waiting
+    }
+
+    /// Indice del bucket al que pertenece el fee rate
+    fn bucket_index(&self, fee_rate: f64) -> usize {
+        self.buckets
+            .iter()
+            .rposition(|bucket| bucket.min_fee_rate <= fee_rate)
+            .unwrap_or(0)
+    }
+}
+
+/// Calcula el fee rate en sat/vB de una transaccion con los outputs que gasta del utxo set.
+/// Devuelve None si algun output gastado no esta en el utxo set (por ejemplo si su padre no se confirmo)
+pub fn transaction_fee_rate(
+    transaction: &Transaction,
+    utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+) -> Option<f64> {
+    let fee = transaction_fee(transaction, utxo_set)?;
+    Some(fee as f64 / transaction.vsize() as f64)
+}
+
+/// Calcula la comision en satoshis de una transaccion con los outputs que gasta del utxo set.
+/// Devuelve None si algun output gastado no esta en el utxo set
+pub fn transaction_fee(
+    transaction: &Transaction,
+    utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+) -> Option<i64> {
+    let mut input_value = 0;
+    for txin in &transaction.tx_in {
+        let previous_hash = txin.get_previous_output_hash();
+        let previous_output = utxo_set
+            .get(&previous_hash)?
+            .find_tx_out(previous_hash, txin.get_previous_output_index())?;
+        input_value += previous_output.value();
+    }
+    let fee = input_value - transaction.amount();
+    if fee < 0 {
+        return None;
+    }
+    Some(fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txid(number: usize) -> TxId {
+        let mut txid = [0; 32];
+        txid[..8].copy_from_slice(&number.to_le_bytes());
+        txid
+    }
+
+    /// Sigue 20 transacciones de cada fee rate y confirma cada grupo a los bloques recibidos
+    fn estimator_with(groups: &[(f64, usize)]) -> FeeEstimator {
+        let mut estimator = FeeEstimator::new();
+        let mut number = 0;
+        for (fee_rate, blocks) in groups {
+            let first = number;
+            for _ in 0..20 {
+                estimator.track_transaction(txid(number), *fee_rate, 100);
+                number += 1;
+            }
+            let txids: Vec<TxId> = (first..number).map(txid).collect();
+            estimator.process_block(100 + blocks, &txids);
+        }
+        estimator
+    }
+
+    #[test]
+    fn higher_targets_need_lower_fee_rates() {
+        // GIVEN: transacciones de 20 sat/vB que se confirmaron en 1 bloque, de 8 en 4 y de 2 en 20
+        let estimator = estimator_with(&[(20.0, 1), (8.0, 4), (2.0, 20)]);
+        // WHEN: se estima para cada objetivo
+        let priority = estimator.fee_rate_for(FeeTarget::Priority);
+        let normal = estimator.fee_rate_for(FeeTarget::Normal);
+        let economical = estimator.fee_rate_for(FeeTarget::Economical);
+        // THEN: cada objetivo usa el menor fee rate que se confirmo a tiempo
+        assert!((priority - 20.0).abs() < 0.01);
+        assert!((normal - 8.0).abs() < 0.01);
+        assert!((economical - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn fallback_fee_rates_are_used_without_data() {
+        // GIVEN: un estimador con pocas transacciones
+        let mut estimator = FeeEstimator::new();
+        estimator.track_transaction(txid(1), 50.0, 10);
+        estimator.process_block(11, &[txid(1)]);
+        // WHEN: se estima
+        // THEN: no hay estimacion y se usan los valores por defecto
+        assert_eq!(estimator.estimate_fee_rate(2), None);
+        assert_eq!(estimator.fee_rate_for(FeeTarget::Normal), 2.0);
+    }
+
+    #[test]
+    fn transactions_that_never_confirm_count_as_failures() {
+        // GIVEN: transacciones de 3 sat/vB que no se confirman y de 10 sat/vB que se confirman en 1 bloque
+        let mut estimator = estimator_with(&[(10.0, 1)]);
+        for number in 1000..1020 {
+            estimator.track_transaction(txid(number), 3.0, 101);
+        }
+        // WHEN: pasan mas bloques que el maximo de espera
+        estimator.process_block(101 + MAX_CONFIRMATION_BLOCKS, &[]);
+        // THEN: el fee rate bajo no se considera suficiente para ningun objetivo
+        assert!((estimator.fee_rate_for(FeeTarget::Economical) - 10.0).abs() < 0.01);
+    }
+}
@@ -2,7 +2,17 @@ use super::ui_functions::{
     disable_buttons_and_entries, get_buttons, get_entries, hex_string_to_bytes,
     show_dialog_message_pop_up,
 };
-use crate::wallet_event::WalletEvent;
+use crate::{
+    address::Address,
+    address_decoder::AddressType,
+    coin_control::{parse_coin_id, CoinAction, CoinId},
+    fee_estimator::FeeTarget,
+    hd_wallet::hd_keychain::DerivationScheme,
+    psbt::Psbt,
+    rescan::RescanStart,
+    transactions::recipient::Recipient,
+    wallet_event::WalletEvent,
+};
 use gtk::{prelude::*, Builder, Spinner};
 use std::{
     cell::RefCell,
@@ -11,6 +21,11 @@ use std::{
     time::Duration,
 };
 
+/// Id de un boton y el evento que le envia al nodo con el texto ingresado
+type PassphraseAction = (&'static str, fn(String) -> WalletEvent);
+/// Id de un boton y el evento que le envia al nodo con el hash de la transaccion y el fee rate ingresados
+type FeeBumpAction = (&'static str, fn(String, f64) -> WalletEvent);
+
 /// Recibe un builder y un sender para enviarle eventos al nodo
 /// Conecta los callbacks de los botones y elementos dinamicos de la UI
 pub fn connect_ui_callbacks(builder: &Builder, sender_to_node: &Sender<WalletEvent>) {
@@ -21,10 +36,21 @@ pub fn connect_ui_callbacks(builder: &Builder, sender_to_node: &Sender<WalletEve
     search_blocks_button_clicked(builder, sender_to_node.clone());
     search_headers_button_clicked(builder, sender_to_node.clone());
     login_button_clicked(builder, sender_to_node.clone());
+    new_account_button_clicked(builder, sender_to_node.clone());
     dropdown_accounts_changed(builder, sender_to_node.clone());
     close_main_window_on_exit(builder, sender_to_node.clone());
     change_loading_account_label_periodically(builder);
     search_tx_poi_button_clicked(builder, sender_to_node.clone());
+    watch_only_button_clicked(builder, sender_to_node.clone());
+    descriptor_buttons_clicked(builder, sender_to_node.clone());
+    rescan_button_clicked(builder, sender_to_node.clone());
+    message_buttons_clicked(builder, sender_to_node.clone());
+    fee_bump_buttons_clicked(builder, sender_to_node.clone());
+    psbt_buttons_clicked(builder, sender_to_node.clone());
+    recipients_buttons_clicked(builder);
+    coin_control_buttons_clicked(builder, sender_to_node.clone());
+    wallet_lock_buttons_clicked(builder, sender_to_node.clone());
+    fee_target_changed(builder, sender_to_node.clone());
 }
 
 /// Esta funcion realiza la accion que corresponde al presionar el boton de start
@@ -73,40 +99,213 @@ fn sync_account_labels(builder: &Builder) {
 }
 
 /// Esta funcion realiza la accion que corresponde al presionar el boton de send creando una nueva
-/// transaccion en caso de que los datos ingresados sean validos, la informacion de la transaccion
-/// es mostrada en la interfaz a traves de un pop up
+/// transaccion que paga a los receptores ingresados en caso de que los datos sean validos, la informacion
+/// de la transaccion es mostrada en la interfaz a traves de un pop up
 fn send_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
     let send_button: gtk::Button = builder
         .object("send-button")
         .expect("error al obtener el boton send");
-    let pay_to_entry: gtk::Entry = builder
-        .object("pay to entry")
-        .expect("error al obtener el entry de pay to");
-    let fee_entry: gtk::Entry = builder
-        .object("fee")
-        .expect("error al obtener el entry de fee");
-    let amount_entry: gtk::Entry = builder
-        .object("amount-entry")
-        .expect("error al obtener el entry de amount");
+    let form = PaymentForm::new(builder);
     send_button.connect_clicked(move |_| {
-        let address_to_send = String::from(pay_to_entry.text());
-        let amount = String::from(amount_entry.text());
-        let fee: String = String::from(fee_entry.text());
-        pay_to_entry.set_text("");
-        amount_entry.set_text("");
-        fee_entry.set_text("");
-        if let Some((valid_amount, valid_fee)) = validate_amount_and_fee(amount, fee) {
+        if let Some((recipients, fee_rate, coins)) = form.payments("Failed to make transaction") {
+            form.clear();
             sender
-                .send(WalletEvent::MakeTransaction(
-                    address_to_send,
-                    valid_amount,
-                    valid_fee,
-                ))
+                .send(WalletEvent::MakeTransaction(recipients, fee_rate, coins))
                 .expect("error al enviar evento de crear una transaccion al nodo");
         }
     });
 }
 
+/// Conecta los botones que agregan el pago ingresado a la lista de receptores y que vacian la lista
+fn recipients_buttons_clicked(builder: &Builder) {
+    let add_button: gtk::Button = builder
+        .object("add-recipient-button")
+        .expect("error al obtener el boton de agregar receptor");
+    let clear_button: gtk::Button = builder
+        .object("clear-recipients-button")
+        .expect("error al obtener el boton de borrar receptores");
+    let form = PaymentForm::new(builder);
+    let clear_form = form.clone();
+    add_button.connect_clicked(move |_| {
+        form.add_entered_recipient("Failed to add recipient");
+    });
+    clear_button.connect_clicked(move |_| {
+        clear_form.recipients.clear();
+    });
+}
+
+/// Conecta los botones de la pestaña Coins. Refresh pide las monedas de la cuenta actual y los demas
+/// bloquean, desbloquean, congelan o descongelan las monedas seleccionadas en la tabla
+fn coin_control_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let refresh_button: gtk::Button = builder
+        .object("refresh-coins-button")
+        .expect("error al obtener el boton de actualizar monedas");
+    let refresh_sender = sender.clone();
+    refresh_button.connect_clicked(move |_| {
+        refresh_sender
+            .send(WalletEvent::ListCoins)
+            .expect("error al enviar evento de listar monedas al nodo");
+    });
+    let coins_selection: gtk::TreeSelection = builder
+        .object("coins-selection")
+        .expect("error al obtener la seleccion de monedas");
+    let actions = [
+        ("lock-coin-button", CoinAction::Lock),
+        ("unlock-coin-button", CoinAction::Unlock),
+        ("freeze-coin-button", CoinAction::Freeze),
+        ("unfreeze-coin-button", CoinAction::Unfreeze),
+    ];
+    for (button_id, action) in actions {
+        let button: gtk::Button = builder
+            .object(button_id)
+            .expect("error al obtener el boton de coin control");
+        let coins_selection = coins_selection.clone();
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            let coins = selected_coins(&coins_selection);
+            if coins.is_empty() {
+                show_dialog_message_pop_up(
+                    "Error, please select at least one coin",
+                    "Coin control",
+                );
+                return;
+            }
+            for coin in coins {
+                sender
+                    .send(WalletEvent::ChangeCoinState(coin, action))
+                    .expect("error al enviar evento de coin control al nodo");
+            }
+        });
+    }
+}
+
+/// Conecta los botones de la passphrase de la pestaña Account. Definen o cambian la passphrase con la que se
+/// guarda la wallet, la desbloquean con la passphrase ingresada o la bloquean
+fn wallet_lock_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let passphrase_entry: gtk::Entry = builder
+        .object("wallet-passphrase-entry")
+        .expect("error al obtener el entry de la passphrase de la wallet");
+    let actions: [PassphraseAction; 2] = [
+        ("set-passphrase-button", WalletEvent::SetWalletPassphrase),
+        ("unlock-wallet-button", WalletEvent::UnlockWallet),
+    ];
+    for (button_id, event) in actions {
+        let button: gtk::Button = builder
+            .object(button_id)
+            .expect("error al obtener el boton de la passphrase de la wallet");
+        let passphrase_entry = passphrase_entry.clone();
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            let passphrase = String::from(passphrase_entry.text());
+            if passphrase.is_empty() {
+                show_dialog_message_pop_up("Error, please enter the passphrase", "Wallet");
+                return;
+            }
+            passphrase_entry.set_text("");
+            sender
+                .send(event(passphrase))
+                .expect("error al enviar evento de la passphrase de la wallet al nodo");
+        });
+    }
+    let lock_button: gtk::Button = builder
+        .object("lock-wallet-button")
+        .expect("error al obtener el boton de bloquear la wallet");
+    lock_button.connect_clicked(move |_| {
+        sender
+            .send(WalletEvent::LockWallet)
+            .expect("error al enviar evento de bloquear la wallet al nodo");
+    });
+}
+
+/// Conecta los botones de PSBT de la pestaña Send. Crear usa los datos de pago ingresados, firmar,
+/// combinar y finalizar usan el PSBT del entry (en base64 o la ruta de un archivo, varios separados
+/// por espacios al combinar) y exportar guarda el PSBT en el archivo ingresado
+fn psbt_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let psbt_entry: gtk::Entry = builder
+        .object("psbt-entry")
+        .expect("error al obtener el entry de psbt");
+    let psbt_file_entry: gtk::Entry = builder
+        .object("psbt-file-entry")
+        .expect("error al obtener el entry del archivo de psbt");
+    let form = PaymentForm::new(builder);
+    let create_button: gtk::Button = builder
+        .object("create-psbt-button")
+        .expect("error al obtener el boton de crear psbt");
+    let create_sender = sender.clone();
+    create_button.connect_clicked(move |_| {
+        if let Some((recipients, fee_rate, coins)) = form.payments("Failed to create PSBT") {
+            form.clear();
+            create_sender
+                .send(WalletEvent::CreatePsbt(recipients, fee_rate, coins))
+                .expect("error al enviar evento de crear un psbt al nodo");
+        }
+    });
+    for (button_id, to_event) in [
+        (
+            "sign-psbt-button",
+            WalletEvent::SignPsbt as fn(String) -> WalletEvent,
+        ),
+        ("combine-psbt-button", |psbts: String| {
+            WalletEvent::CombinePsbts(psbts.split_whitespace().map(String::from).collect())
+        }),
+        ("finalize-psbt-button", WalletEvent::FinalizePsbt),
+    ] {
+        let button: gtk::Button = builder
+            .object(button_id)
+            .expect("error al obtener el boton de psbt");
+        let psbt_entry = psbt_entry.clone();
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            let psbt = String::from(psbt_entry.text());
+            if psbt.trim().is_empty() {
+                show_dialog_message_pop_up(
+                    "Enter a PSBT in base64 or a file path",
+                    "PSBT's status",
+                );
+                return;
+            }
+            sender
+                .send(to_event(psbt))
+                .expect("error al enviar evento de psbt al nodo");
+        });
+    }
+    let export_button: gtk::Button = builder
+        .object("export-psbt-button")
+        .expect("error al obtener el boton de exportar psbt");
+    export_button.connect_clicked(move |_| {
+        let path = String::from(psbt_file_entry.text());
+        let exported = Psbt::import(&psbt_entry.text()).and_then(|psbt| psbt.write_to_file(&path));
+        match exported {
+            Ok(()) => show_dialog_message_pop_up(
+                format!("PSBT saved to {path}").as_str(),
+                "PSBT's status",
+            ),
+            Err(err) => show_dialog_message_pop_up(
+                format!("Error saving the PSBT: {err}").as_str(),
+                "PSBT's status",
+            ),
+        }
+    });
+}
+
+/// Al elegir un objetivo de confirmacion en el dropdown de fees, le pide a la wallet el fee rate
+/// estimado para ese objetivo, que se muestra en el entry de fee
+fn fee_target_changed(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let dropdown: gtk::ComboBoxText = builder
+        .object("fee-target-dropdown")
+        .expect("error al obtener el dropdown de fee target");
+    dropdown.connect_changed(move |combobox| {
+        if let Some(target) = combobox
+            .active_text()
+            .and_then(|text| FeeTarget::from_name(text.as_str()))
+        {
+            sender
+                .send(WalletEvent::EstimateFee(target))
+                .expect("error al enviar evento de estimar fee al nodo");
+        }
+    });
+}
+
 /// Realiza la accion correspondiente a apretar el boton de buscar bloques. Envia un evento al nodo para que busque el bloque
 /// en caso de que el hash ingresado sea valido. En caso contrario muestra un mensaje de error
 fn search_blocks_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
@@ -198,6 +397,145 @@ fn login_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
     });
 }
 
+/// Esta funcion realiza la accion que corresponde al presionar el boton de nueva cuenta. Le pide al nodo
+/// que cree una cuenta con una clave privada nueva y la direccion del tipo elegido en el dropdown
+fn new_account_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let new_account_button: gtk::Button = builder
+        .object("new-account-button")
+        .expect("error al obtener el boton de nueva cuenta");
+    let address_type_dropdown: gtk::ComboBoxText = builder
+        .object("new-account-type-dropdown")
+        .expect("error al obtener el dropdown del tipo de direccion de la nueva cuenta");
+    let account_loading_spinner: Spinner = builder
+        .object("account-spin")
+        .expect("error al obtener el spinner de account en callback");
+    let loading_account_label: gtk::Label = builder
+        .object("load-account")
+        .expect("error al obtener el label de loading account en callback");
+    let dropdown: gtk::ComboBoxText = builder
+        .object("dropdown-menu")
+        .expect("error al obtener el dropdown menu en callback");
+    let ref_to_buttons = get_buttons(builder);
+    let ref_to_entries = get_entries(builder);
+    new_account_button.connect_clicked(move |_| {
+        let address_type = match address_type_dropdown
+            .active_id()
+            .and_then(|id| AddressType::parse(id.as_str()).ok())
+        {
+            Some(address_type) => address_type,
+            None => {
+                show_dialog_message_pop_up("Error, please select an address type", "New account");
+                return;
+            }
+        };
+        disable_buttons_and_entries(&ref_to_buttons, &ref_to_entries);
+        dropdown.set_sensitive(false);
+        account_loading_spinner.set_visible(true);
+        loading_account_label.set_visible(true);
+        sender
+            .send(WalletEvent::CreateAccount(address_type))
+            .expect("error al enviar evento de nueva cuenta al nodo");
+    });
+}
+
+/// Esta funcion realiza la accion que corresponde al presionar el boton de cuenta watch-only. Le pide al nodo
+/// que agregue una cuenta que observa la direccion, el script o la clave publica extendida ingresada, con
+/// las direcciones del tipo elegido en el dropdown si es una clave publica extendida
+fn watch_only_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let watch_only_button: gtk::Button = builder
+        .object("watch-only-button")
+        .expect("error al obtener el boton de cuenta watch-only");
+    let watch_only_entry: gtk::Entry = builder
+        .object("watch-only-entry")
+        .expect("error al obtener el entry de cuenta watch-only");
+    let scheme_dropdown: gtk::ComboBoxText = builder
+        .object("watch-only-scheme-dropdown")
+        .expect("error al obtener el dropdown del tipo de direcciones de la cuenta watch-only");
+    let account_loading_spinner: Spinner = builder
+        .object("account-spin")
+        .expect("error al obtener el spinner de account en callback");
+    let loading_account_label: gtk::Label = builder
+        .object("load-account")
+        .expect("error al obtener el label de loading account en callback");
+    let dropdown: gtk::ComboBoxText = builder
+        .object("dropdown-menu")
+        .expect("error al obtener el dropdown menu en callback");
+    let ref_to_buttons = get_buttons(builder);
+    let ref_to_entries = get_entries(builder);
+    watch_only_button.connect_clicked(move |_| {
+        let watched = watch_only_entry.text().to_string();
+        if watched.trim().is_empty() {
+            show_dialog_message_pop_up(
+                "Error, please enter an address, a script or a tpub to watch",
+                "Watch-only account",
+            );
+            return;
+        }
+        let scheme = match scheme_dropdown.active_id().as_deref() {
+            Some("44") => DerivationScheme::Bip44,
+            _ => DerivationScheme::Bip84,
+        };
+        watch_only_entry.set_text("");
+        disable_buttons_and_entries(&ref_to_buttons, &ref_to_entries);
+        dropdown.set_sensitive(false);
+        account_loading_spinner.set_visible(true);
+        loading_account_label.set_visible(true);
+        sender
+            .send(WalletEvent::AddWatchOnlyAccount(watched, scheme))
+            .expect("error al enviar evento de cuenta watch-only al nodo");
+    });
+}
+
+/// Esta funcion realiza la accion que corresponde al presionar los botones de descriptors. Import le pide al
+/// nodo que agregue la cuenta del descriptor ingresado y Export le pide los descriptors de la cuenta actual,
+/// con las claves privadas si esta marcado el check
+fn descriptor_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let import_button: gtk::Button = builder
+        .object("import-descriptor-button")
+        .expect("error al obtener el boton de importar descriptor");
+    let export_button: gtk::Button = builder
+        .object("export-descriptors-button")
+        .expect("error al obtener el boton de exportar descriptors");
+    let descriptor_entry: gtk::Entry = builder
+        .object("descriptor-entry")
+        .expect("error al obtener el entry de descriptor");
+    let private_check: gtk::CheckButton = builder
+        .object("export-private-descriptors-check")
+        .expect("error al obtener el check de exportar claves privadas");
+    let account_loading_spinner: Spinner = builder
+        .object("account-spin")
+        .expect("error al obtener el spinner de account en callback");
+    let loading_account_label: gtk::Label = builder
+        .object("load-account")
+        .expect("error al obtener el label de loading account en callback");
+    let dropdown: gtk::ComboBoxText = builder
+        .object("dropdown-menu")
+        .expect("error al obtener el dropdown menu en callback");
+    let ref_to_buttons = get_buttons(builder);
+    let ref_to_entries = get_entries(builder);
+    let import_sender = sender.clone();
+    import_button.connect_clicked(move |_| {
+        let descriptor = descriptor_entry.text().to_string();
+        if descriptor.trim().is_empty() {
+            show_dialog_message_pop_up("Error, please enter a descriptor to import", "Descriptors");
+            return;
+        }
+        descriptor_entry.set_text("");
+        disable_buttons_and_entries(&ref_to_buttons, &ref_to_entries);
+        dropdown.set_sensitive(false);
+        account_loading_spinner.set_visible(true);
+        loading_account_label.set_visible(true);
+        import_sender
+            .send(WalletEvent::ImportDescriptor(descriptor))
+            .expect("error al enviar evento de importar descriptor al nodo");
+    });
+    export_button.connect_clicked(move |_| {
+        sender
+            .send(WalletEvent::ExportDescriptors(private_check.is_active()))
+            .expect("error al enviar evento de exportar descriptors al nodo");
+    });
+}
+
 /// Realiza la accion correspondiente a apretar una opcion del dropdown de cuentas. Envia un evento al nodo para que cambie de cuenta
 /// y muestra el address de la cuenta seleccionada
 fn dropdown_accounts_changed(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
@@ -306,31 +644,141 @@ pub fn search_tx_poi_button_clicked(builder: &Builder, sender: mpsc::Sender<Wall
 ***************************************************************************
 */
 
-/// esta funcion chequea si el usuario ingreso un amount y un fee validos
-/// en caso de que no sea asi, se muestra un pop up con un mensaje de error
-fn validate_amount_and_fee(amount: String, fee: String) -> Option<(i64, i64)> {
-    let valid_amount = match amount.parse::<i64>() {
-        Ok(amount) => amount,
-        Err(_) => {
+/// Devuelve las monedas seleccionadas en la tabla de la pestaña Coins
+fn selected_coins(coins_selection: &gtk::TreeSelection) -> Vec<CoinId> {
+    let (paths, model) = coins_selection.selected_rows();
+    paths
+        .iter()
+        .filter_map(|path| model.iter(path))
+        .filter_map(|row| model.value(&row, 0).get::<String>().ok())
+        .filter_map(|coin| parse_coin_id(&coin).ok())
+        .collect()
+}
+
+/// Widgets del formulario de pagos de la pestaña Send: el pago que se esta ingresando, el fee rate
+/// y la lista de receptores ya agregados. Tambien las monedas elegidas en la pestaña Coins
+#[derive(Clone)]
+struct PaymentForm {
+    pay_to_entry: gtk::Entry,
+    amount_entry: gtk::Entry,
+    fee_entry: gtk::Entry,
+    subtract_fee_check: gtk::CheckButton,
+    recipients: gtk::ListStore,
+    use_selected_coins_check: gtk::CheckButton,
+    coins_selection: gtk::TreeSelection,
+}
+
+impl PaymentForm {
+    /// Obtiene los widgets del formulario
+    fn new(builder: &Builder) -> Self {
+        PaymentForm {
+            pay_to_entry: builder
+                .object("pay to entry")
+                .expect("error al obtener el entry de pay to"),
+            amount_entry: builder
+                .object("amount-entry")
+                .expect("error al obtener el entry de amount"),
+            fee_entry: builder
+                .object("fee")
+                .expect("error al obtener el entry de fee"),
+            subtract_fee_check: builder
+                .object("subtract-fee-check")
+                .expect("error al obtener el check de descontar fee"),
+            recipients: builder
+                .object("liststore-recipients")
+                .expect("error al obtener el liststore de receptores"),
+            use_selected_coins_check: builder
+                .object("use-selected-coins-check")
+                .expect("error al obtener el check de usar las monedas seleccionadas"),
+            coins_selection: builder
+                .object("coins-selection")
+                .expect("error al obtener la seleccion de monedas"),
+        }
+    }
+
+    /// Agrega el pago ingresado a la lista de receptores y vacia sus entries.
+    /// Si la address o el monto son invalidos muestra el error con el titulo recibido y devuelve false
+    fn add_entered_recipient(&self, title: &str) -> bool {
+        let address = String::from(self.pay_to_entry.text());
+        if let Err(err) = Address::parse(&address) {
             show_dialog_message_pop_up(
-                "Error, please enter a valid amount of Satoshis",
-                "Failed to make transaction",
+                format!("Error, {address} is not a valid address: {err}").as_str(),
+                title,
             );
+            return false;
+        }
+        let amount = match self.amount_entry.text().parse::<i64>() {
+            Ok(amount) if amount > 0 => amount,
+            _ => {
+                show_dialog_message_pop_up("Error, please enter a valid amount of Satoshis", title);
+                return false;
+            }
+        };
+        let subtract_fee = self.subtract_fee_check.is_active();
+        self.recipients.set(
+            &self.recipients.append(),
+            &[(0, &address), (1, &amount), (2, &subtract_fee)],
+        );
+        self.pay_to_entry.set_text("");
+        self.amount_entry.set_text("");
+        self.subtract_fee_check.set_active(false);
+        true
+    }
+
+    /// Devuelve los pagos de la lista de receptores, agregando antes el que se este ingresando, el fee rate y las
+    /// monedas a gastar (vacio si se eligen automaticamente). Si algun dato es invalido, no hay receptores o se
+    /// piden las monedas seleccionadas y no hay ninguna, muestra el error con el titulo recibido y devuelve None
+    fn payments(&self, title: &str) -> Option<(Vec<Recipient>, f64, Vec<CoinId>)> {
+        if !self.pay_to_entry.text().is_empty() && !self.add_entered_recipient(title) {
             return None;
         }
-    };
-    let valid_fee = match fee.parse::<i64>() {
-        Ok(fee) => fee,
-        Err(_) => {
+        let mut recipients = Vec::new();
+        if let Some(row) = self.recipients.iter_first() {
+            loop {
+                recipients.push(Recipient::new(
+                    self.recipients.value(&row, 0).get().unwrap_or_default(),
+                    self.recipients.value(&row, 1).get().unwrap_or_default(),
+                    self.recipients.value(&row, 2).get().unwrap_or_default(),
+                ));
+                if !self.recipients.iter_next(&row) {
+                    break;
+                }
+            }
+        }
+        if recipients.is_empty() {
+            show_dialog_message_pop_up("Error, please enter at least one recipient", title);
+            return None;
+        }
+        let fee_rate = match self.fee_entry.text().parse::<f64>() {
+            Ok(fee_rate) => fee_rate,
+            Err(_) => {
+                show_dialog_message_pop_up("Error, please enter a valid fee rate in sat/vB", title);
+                return None;
+            }
+        };
+        if !self.use_selected_coins_check.is_active() {
+            return Some((recipients, fee_rate, Vec::new()));
+        }
+        let coins = selected_coins(&self.coins_selection);
+        if coins.is_empty() {
             show_dialog_message_pop_up(
-                "Error, please enter a valid fee of Satoshis",
-                "Failed to make transaction",
+                "Error, please select the coins to spend in the Coins tab",
+                title,
             );
             return None;
         }
-    };
+        Some((recipients, fee_rate, coins))
+    }
 
-    Some((valid_amount, valid_fee))
+    /// Vacia el formulario y la lista de receptores
+    fn clear(&self) {
+        self.pay_to_entry.set_text("");
+        self.amount_entry.set_text("");
+        self.fee_entry.set_text("");
+        self.subtract_fee_check.set_active(false);
+        self.recipients.clear();
+        self.use_selected_coins_check.set_active(false);
+    }
 }
 
 /// Recibe un Label y cambia su texto por el siguiente en la lista de waiting_labels
@@ -350,3 +798,127 @@ fn update_label(label: Rc<RefCell<gtk::Label>>) -> Continue {
     }
     Continue(true)
 }
+
+/// Esta funcion realiza la accion que corresponde al presionar los botones de bump fee y de acelerar.
+/// Toma el hash de la transaccion pendiente del entry de search tx y el fee rate en sat/vB y le pide a
+/// la wallet que la reemplace (RBF) o que la acelere con una transaccion hija (CPFP)
+fn fee_bump_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let tx_hash_entry: gtk::Entry = builder
+        .object("search-tx")
+        .expect("error al obtener el entry de search tx en callback");
+    let fee_rate_entry: gtk::Entry = builder
+        .object("bump-fee-entry")
+        .expect("error al obtener el entry de bump fee en callback");
+    let actions: [FeeBumpAction; 2] = [
+        ("bump-fee-button", WalletEvent::BumpFee),
+        ("accelerate-button", WalletEvent::AccelerateTransaction),
+    ];
+    for (button_id, event) in actions {
+        let button: gtk::Button = builder
+            .object(button_id)
+            .expect("error al obtener el boton de fee en callback");
+        let tx_hash_entry = tx_hash_entry.clone();
+        let fee_rate_entry = fee_rate_entry.clone();
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            let tx_hash_string = tx_hash_entry.text().to_string();
+            if hex_string_to_bytes(tx_hash_string.as_str()).is_none() {
+                show_dialog_message_pop_up(
+                    format!("Error {tx_hash_string} is not a valid tx hash").as_str(),
+                    "Error paying more fee",
+                );
+                return;
+            }
+            let fee_rate = match fee_rate_entry.text().to_string().parse::<f64>() {
+                Ok(fee_rate) => fee_rate,
+                Err(_) => {
+                    show_dialog_message_pop_up(
+                        "Error: the fee rate must be a number of sat/vB",
+                        "Error paying more fee",
+                    );
+                    return;
+                }
+            };
+            sender
+                .send(event(tx_hash_string, fee_rate))
+                .expect("Error al enviar el evento de fee al nodo");
+            tx_hash_entry.set_text("");
+            fee_rate_entry.set_text("");
+        });
+    }
+}
+
+/// Esta funcion realiza la accion que corresponde al presionar el boton de rescan. Lee la altura o la fecha
+/// ingresada y le pide al nodo que reescanee la cadena para la cuenta actual desde ahi
+fn rescan_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let rescan_button: gtk::Button = builder
+        .object("rescan-button")
+        .expect("error al obtener el boton de rescan");
+    let rescan_entry: gtk::Entry = builder
+        .object("rescan-entry")
+        .expect("error al obtener el entry de rescan");
+    let rescan_label: gtk::Label = builder
+        .object("rescan-progress-label")
+        .expect("error al obtener el label de progreso del rescan");
+    rescan_button.connect_clicked(move |_| {
+        let start = match RescanStart::parse(&rescan_entry.text()) {
+            Ok(start) => start,
+            Err(err) => {
+                show_dialog_message_pop_up(err.to_string().as_str(), "Rescan");
+                return;
+            }
+        };
+        rescan_label.set_text("Rescanning...");
+        sender
+            .send(WalletEvent::Rescan(start))
+            .expect("error al enviar evento de rescan al nodo");
+    });
+}
+
+/// Esta funcion realiza la accion que corresponde al presionar los botones de mensajes firmados. Sign le pide al
+/// nodo que firme el mensaje con la direccion ingresada de la cuenta actual y Verify que verifique la firma
+/// ingresada del mensaje con la direccion
+fn message_buttons_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let sign_button: gtk::Button = builder
+        .object("sign-message-button")
+        .expect("error al obtener el boton de firmar mensaje");
+    let verify_button: gtk::Button = builder
+        .object("verify-message-button")
+        .expect("error al obtener el boton de verificar mensaje");
+    let message_entry: gtk::Entry = builder
+        .object("message-entry")
+        .expect("error al obtener el entry de mensaje");
+    let address_entry: gtk::Entry = builder
+        .object("message-address-entry")
+        .expect("error al obtener el entry de la direccion del mensaje");
+    let signature_entry: gtk::Entry = builder
+        .object("message-signature-entry")
+        .expect("error al obtener el entry de la firma del mensaje");
+    let sign_sender = sender.clone();
+    let sign_message_entry = message_entry.clone();
+    let sign_address_entry = address_entry.clone();
+    sign_button.connect_clicked(move |_| {
+        sign_sender
+            .send(WalletEvent::SignMessage(
+                sign_address_entry.text().to_string(),
+                sign_message_entry.text().to_string(),
+            ))
+            .expect("error al enviar evento de firmar mensaje al nodo");
+    });
+    verify_button.connect_clicked(move |_| {
+        if address_entry.text().trim().is_empty() || signature_entry.text().trim().is_empty() {
+            show_dialog_message_pop_up(
+                "Error, please enter the address and the signature to verify",
+                "Signed message",
+            );
+            return;
+        }
+        sender
+            .send(WalletEvent::VerifyMessage(
+                address_entry.text().to_string(),
+                message_entry.text().to_string(),
+                signature_entry.text().to_string(),
+            ))
+            .expect("error al enviar evento de verificar mensaje al nodo");
+    });
+}
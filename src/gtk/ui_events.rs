@@ -6,8 +6,9 @@ use std::{
 use gtk::glib;
 
 use crate::{
-    account::Account, blocks::block::Block, blocks::block_header::BlockHeader,
-    transactions::transaction::Transaction,
+    account::Account, balance::BalanceBreakdown, blocks::block::Block,
+    blocks::block_header::BlockHeader, coin_control::CoinInfo, fee_estimator::FeeTarget,
+    ledger::HistoryEntry, transactions::transaction::Transaction,
 };
 
 type Blocks = Arc<RwLock<HashMap<[u8; 32], Block>>>;
@@ -23,8 +24,10 @@ pub enum UIEvent {
     StartDownloadingBlocks,
     ShowConfirmedTransaction(Block, Account, Transaction),
     AccountAddedSuccesfully(Account),
+    AccountCreated(String, String),
     AddAccountError(String),
     AccountChanged(Account),
+    BalanceUpdated(BalanceBreakdown),
     ChangeAccountError(String),
     ShowPendingTransaction(Account, Transaction),
     AddBlock(Block),
@@ -33,10 +36,19 @@ pub enum UIEvent {
     ActualizeBlocksDownloaded(usize, usize),
     MakeTransactionStatus(String),
     NewPendingTx(),
-    UpdateTransactions(Vec<(String, Transaction, i64)>),
+    UpdateTransactions(Vec<HistoryEntry>),
     BlockFound(Block),
     HeaderFound(BlockHeader, Height),
     POIResult(String),
+    PsbtUpdated(String),
+    PsbtStatus(String),
+    FeeEstimated(FeeTarget, f64),
+    CoinsListed(Vec<CoinInfo>),
+    WalletStatus(String),
+    DescriptorsExported(Vec<String>),
+    RescanProgress(usize, usize),
+    MessageSigned(String),
+    MessageStatus(String),
     NotFound,
 }
 
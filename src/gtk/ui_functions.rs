@@ -16,7 +16,8 @@ use gtk::{
 use crate::{
     account::Account,
     blocks::{block::Block, block_header::BlockHeader},
-    transactions::transaction::Transaction,
+    coin_control::{format_coin_id, CoinInfo},
+    ledger::HistoryEntry,
     wallet_event::WalletEvent,
 };
 
@@ -79,6 +80,16 @@ pub fn handle_ui_event(
         UIEvent::AccountAddedSuccesfully(account) => {
             update_account_tab(&builder, account);
         }
+        UIEvent::AccountCreated(wif_private_key, address) => {
+            show_dialog_message_pop_up(
+                format!(
+                    "Account {} created!\n\nBack up its private key, it is the only way to recover the funds of the account:\n\n{}",
+                    address, wif_private_key
+                )
+                .as_str(),
+                "Back up your private key",
+            );
+        }
         UIEvent::AddAccountError(error) => {
             render_account_tab(&builder);
             show_dialog_message_pop_up(error.as_str(), "Error trying to add account");
@@ -99,10 +110,90 @@ pub fn handle_ui_event(
                 .expect(
                     "Error al enviar el evento de get transactions request al cambiar de cuenta",
                 );
+            // actualiza la pestana de monedas
+            sender_to_node
+                .send(WalletEvent::ListCoins)
+                .expect("Error al enviar el evento de listar monedas al cambiar de cuenta");
         }
         UIEvent::MakeTransactionStatus(status) => {
             show_dialog_message_pop_up(status.as_str(), "transaction's status");
         }
+        UIEvent::PsbtUpdated(psbt) => {
+            let psbt_entry: gtk::Entry = builder
+                .object("psbt-entry")
+                .expect("Error al obtener el entry de psbt");
+            psbt_entry.set_text(&psbt);
+            show_dialog_message_pop_up(
+                "The PSBT was updated, share it with the other signers",
+                "PSBT's status",
+            );
+        }
+        UIEvent::FeeEstimated(target, fee_rate) => {
+            let fee_entry: gtk::Entry = builder
+                .object("fee")
+                .expect("Error al obtener el entry de fee");
+            fee_entry.set_text(format!("{:.1}", fee_rate).as_str());
+            fee_entry.set_tooltip_text(Some(
+                format!("{} target: ~{} blocks", target.name(), target.blocks()).as_str(),
+            ));
+        }
+        UIEvent::PsbtStatus(status) => {
+            show_dialog_message_pop_up(status.as_str(), "PSBT's status");
+        }
+        UIEvent::CoinsListed(coins) => {
+            render_coins(&coins, &builder);
+        }
+        UIEvent::WalletStatus(status) => {
+            show_dialog_message_pop_up(status.as_str(), "Wallet");
+        }
+        UIEvent::DescriptorsExported(descriptors) => {
+            let descriptor_entry: gtk::Entry = builder
+                .object("descriptor-entry")
+                .expect("Error al obtener el entry de descriptor");
+            descriptor_entry.set_text(descriptors.join(" ").as_str());
+            show_dialog_message_pop_up(
+                format!("Account descriptors:\n{}", descriptors.join("\n")).as_str(),
+                "Descriptors",
+            );
+        }
+        UIEvent::BalanceUpdated(breakdown) => {
+            let available_label: gtk::Label = builder
+                .object("available label")
+                .expect("No se pudo obtener el label de available account");
+            available_label.set_label(format!("{}", breakdown.confirmed).as_str());
+            let breakdown_label: gtk::Label = builder
+                .object("balance-breakdown-label")
+                .expect("Error al obtener el label del detalle del balance");
+            breakdown_label.set_text(
+                format!(
+                    "Incoming: {} | Change: {} | Immature: {} | Locked: {}",
+                    breakdown.unconfirmed_incoming,
+                    breakdown.unconfirmed_change,
+                    breakdown.immature,
+                    breakdown.locked
+                )
+                .as_str(),
+            );
+        }
+        UIEvent::RescanProgress(done, total) => {
+            let rescan_label: gtk::Label = builder
+                .object("rescan-progress-label")
+                .expect("Error al obtener el label de progreso del rescan");
+            rescan_label.set_text(format!("Rescanning: {}/{} blocks", done, total).as_str());
+        }
+        UIEvent::MessageSigned(signature) => {
+            let signature_entry: gtk::Entry = builder
+                .object("message-signature-entry")
+                .expect("Error al obtener el entry de la firma del mensaje");
+            signature_entry.set_text(&signature);
+            show_dialog_message_pop_up(
+                format!("Signature of the message:\n{}", signature).as_str(),
+                "Signed message",
+            );
+        }
+        UIEvent::MessageStatus(status) => {
+            show_dialog_message_pop_up(status.as_str(), "Signed message");
+        }
         UIEvent::AddBlock(block) => {
             handle_add_block(sender_to_node, &builder, &block);
         }
@@ -181,23 +272,37 @@ pub fn handle_ui_event(
 }
 
 /// Muestra las transacciones en la pestana de transacciones
-fn render_transactions(transactions: &Vec<(String, Transaction, i64)>, tx_table: TreeView) {
+fn render_transactions(transactions: &[HistoryEntry], tx_table: TreeView) {
     let tree_model = gtk::ListStore::new(&[
         gdk_pixbuf::Pixbuf::static_type(),
         String::static_type(),
         String::static_type(),
         String::static_type(),
-        i32::static_type(),
+        i64::static_type(),
+        u32::static_type(),
+        String::static_type(),
+        String::static_type(),
     ]);
 
-    for tx in transactions {
-        let status_icon_pixbuf = if tx.0 == "Pending" {
+    for history_entry in transactions {
+        let entry = &history_entry.entry;
+        let (status, status_icon_pixbuf) = if history_entry.confirmations == 0 {
             // Cargar la imagen "Pending.png" y convertirla en un GdkPixbuf
-            Pixbuf::from_file("src/gtk/resources/pending.png").ok()
+            (
+                "Pending",
+                Pixbuf::from_file("src/gtk/resources/pending.png").ok(),
+            )
         } else {
             // Cargar la imagen "Confirmed.png" y convertirla en un GdkPixbuf
-            Pixbuf::from_file("src/gtk/resources/confirmed.png").ok()
+            (
+                "Confirmed",
+                Pixbuf::from_file("src/gtk/resources/confirmed.png").ok(),
+            )
         };
+        let fee = entry.fee.map_or("-".to_string(), |fee| fee.to_string());
+        let block = entry
+            .block
+            .map_or("-".to_string(), |block| block.height.to_string());
 
         let row = tree_model.append();
         if let Some(pixbuf) = status_icon_pixbuf {
@@ -205,10 +310,13 @@ fn render_transactions(transactions: &Vec<(String, Transaction, i64)>, tx_table:
                 &row,
                 &[
                     (0, &pixbuf.to_value()),
-                    (1, &tx.0.to_value()),
-                    (2, &tx.1.hex_hash().to_value()),
-                    (3, &"P2PKH".to_value()),
-                    (4, &tx.2.to_value()),
+                    (1, &status.to_value()),
+                    (2, &entry.transaction.hex_hash().to_value()),
+                    (3, &entry.kind().name().to_value()),
+                    (4, &entry.net_amount().to_value()),
+                    (5, &history_entry.confirmations.to_value()),
+                    (6, &fee.to_value()),
+                    (7, &block.to_value()),
                 ],
             );
         }
@@ -216,11 +324,36 @@ fn render_transactions(transactions: &Vec<(String, Transaction, i64)>, tx_table:
     tx_table.set_model(Some(&tree_model));
 }
 
+/// Muestra las monedas de la cuenta en la tabla de la pestaña Coins
+fn render_coins(coins: &[CoinInfo], builder: &Builder) {
+    let liststore_coins: gtk::ListStore = builder
+        .object("liststore-coins")
+        .expect("Error al obtener el liststore de monedas");
+    liststore_coins.clear();
+    for coin in coins {
+        let state = match (coin.locked, coin.frozen) {
+            (_, true) => "Frozen",
+            (true, false) => "Locked",
+            _ => "",
+        };
+        liststore_coins.set(
+            &liststore_coins.append(),
+            &[
+                (0, &format_coin_id(&coin.id)),
+                (1, &coin.value),
+                (2, &coin.confirmations),
+                (3, &coin.address),
+                (4, &state),
+            ],
+        );
+    }
+}
+
 /// Shows the recent transactions in the overview tab
-fn render_recent_transactions(transactions: &Vec<(String, Transaction, i64)>, builder: &Builder) {
+fn render_recent_transactions(transactions: &[HistoryEntry], builder: &Builder) {
     // Get the last five elements or all elements if there are fewer than five
     let recent_transactions = if transactions.len() <= 5 {
-        &transactions[..]
+        transactions
     } else {
         &transactions[transactions.len() - 5..]
     };
@@ -252,21 +385,22 @@ fn render_recent_transactions(transactions: &Vec<(String, Transaction, i64)>, bu
         "recent-tx-4",
         "recent-tx-5",
     ];
-    for (i, tx) in recent_transactions.iter().enumerate() {
+    for (i, history_entry) in recent_transactions.iter().enumerate() {
+        let entry = &history_entry.entry;
         let hash: gtk::AccelLabel = builder
             .object(recent_tx[i])
             .expect("error al obtener el label del hash de la transaccion reciente");
-        hash.set_label(&tx.1.hex_hash());
+        hash.set_label(&entry.transaction.hex_hash());
         hash.set_visible(true);
         let amount_label: gtk::AccelLabel = builder
             .object(amount_labels[i])
             .expect("error al obtener el label del monto de la transaccion reciente");
-        amount_label.set_label(format!("{} Satoshis", tx.2).as_str());
+        amount_label.set_label(format!("{} Satoshis", entry.net_amount()).as_str());
         amount_label.set_visible(true);
         let icon: gtk::Image = builder
             .object(icons[i])
             .expect("error al obtener el icono de la transaccion reciente");
-        if tx.0 == "Pending" {
+        if history_entry.confirmations == 0 {
             icon.set_from_file(Some("src/gtk/resources/ov_pending.png"));
         } else {
             icon.set_from_file(Some("src/gtk/resources/ov_confirmed.png"));
@@ -275,6 +409,7 @@ fn render_recent_transactions(transactions: &Vec<(String, Transaction, i64)>, bu
         let type_label: gtk::AccelLabel = builder
             .object(type_labels[i])
             .expect("error al obtener el label del tipo de la transaccion reciente");
+        type_label.set_label(entry.kind().name());
         type_label.set_visible(true);
     }
 }
@@ -437,6 +572,78 @@ pub fn get_buttons(builder: &Builder) -> Vec<gtk::Button> {
         builder
             .object("login-button")
             .expect("Error al obtener el boton de login"),
+        builder
+            .object("create-psbt-button")
+            .expect("Error al obtener el boton de crear psbt"),
+        builder
+            .object("sign-psbt-button")
+            .expect("Error al obtener el boton de firmar psbt"),
+        builder
+            .object("combine-psbt-button")
+            .expect("Error al obtener el boton de combinar psbt"),
+        builder
+            .object("finalize-psbt-button")
+            .expect("Error al obtener el boton de finalizar psbt"),
+        builder
+            .object("export-psbt-button")
+            .expect("Error al obtener el boton de exportar psbt"),
+        builder
+            .object("bump-fee-button")
+            .expect("Error al obtener el boton de bump fee"),
+        builder
+            .object("accelerate-button")
+            .expect("Error al obtener el boton de acelerar transaccion"),
+        builder
+            .object("add-recipient-button")
+            .expect("Error al obtener el boton de agregar receptor"),
+        builder
+            .object("clear-recipients-button")
+            .expect("Error al obtener el boton de borrar receptores"),
+        builder
+            .object("refresh-coins-button")
+            .expect("Error al obtener el boton de actualizar monedas"),
+        builder
+            .object("lock-coin-button")
+            .expect("Error al obtener el boton de bloquear monedas"),
+        builder
+            .object("unlock-coin-button")
+            .expect("Error al obtener el boton de desbloquear monedas"),
+        builder
+            .object("freeze-coin-button")
+            .expect("Error al obtener el boton de congelar monedas"),
+        builder
+            .object("unfreeze-coin-button")
+            .expect("Error al obtener el boton de descongelar monedas"),
+        builder
+            .object("set-passphrase-button")
+            .expect("Error al obtener el boton de definir la passphrase"),
+        builder
+            .object("unlock-wallet-button")
+            .expect("Error al obtener el boton de desbloquear la wallet"),
+        builder
+            .object("lock-wallet-button")
+            .expect("Error al obtener el boton de bloquear la wallet"),
+        builder
+            .object("new-account-button")
+            .expect("Error al obtener el boton de nueva cuenta"),
+        builder
+            .object("watch-only-button")
+            .expect("Error al obtener el boton de cuenta watch-only"),
+        builder
+            .object("import-descriptor-button")
+            .expect("Error al obtener el boton de importar descriptor"),
+        builder
+            .object("export-descriptors-button")
+            .expect("Error al obtener el boton de exportar descriptors"),
+        builder
+            .object("rescan-button")
+            .expect("Error al obtener el boton de rescan"),
+        builder
+            .object("sign-message-button")
+            .expect("Error al obtener el boton de firmar mensaje"),
+        builder
+            .object("verify-message-button")
+            .expect("Error al obtener el boton de verificar mensaje"),
     ];
     buttons
 }
@@ -467,6 +674,36 @@ pub fn get_entries(builder: &Builder) -> Vec<gtk::Entry> {
         builder
             .object("private-key")
             .expect("Error al obtener el entry de private key"),
+        builder
+            .object("psbt-entry")
+            .expect("Error al obtener el entry de psbt"),
+        builder
+            .object("psbt-file-entry")
+            .expect("Error al obtener el entry del archivo de psbt"),
+        builder
+            .object("bump-fee-entry")
+            .expect("Error al obtener el entry de bump fee"),
+        builder
+            .object("wallet-passphrase-entry")
+            .expect("Error al obtener el entry de la passphrase de la wallet"),
+        builder
+            .object("watch-only-entry")
+            .expect("Error al obtener el entry de cuenta watch-only"),
+        builder
+            .object("descriptor-entry")
+            .expect("Error al obtener el entry de descriptor"),
+        builder
+            .object("rescan-entry")
+            .expect("Error al obtener el entry de rescan"),
+        builder
+            .object("message-entry")
+            .expect("Error al obtener el entry de mensaje"),
+        builder
+            .object("message-address-entry")
+            .expect("Error al obtener el entry de la direccion del mensaje"),
+        builder
+            .object("message-signature-entry")
+            .expect("Error al obtener el entry de la firma del mensaje"),
     ];
     entries
 }
@@ -720,8 +957,8 @@ pub fn add_css_to_screen() {
 /// Setea el icono a la app
 pub fn set_icon(window: &gtk::Window) {
     if let Ok(icon_pixbuf) = Pixbuf::from_file(ICON_FILE) {
-            if let Some(icon) = icon_pixbuf.scale_simple(64, 64, gdk_pixbuf::InterpType::Bilinear) {
-                window.set_icon(Some(&icon));
-                }
+        if let Some(icon) = icon_pixbuf.scale_simple(64, 64, gdk_pixbuf::InterpType::Bilinear) {
+            window.set_icon(Some(&icon));
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,20 @@
 use gtk::glib;
 
-use crate::blockchain_download::headers_download::load_header_heights;
 use crate::gtk::ui_events::{send_event_to_ui, UIEvent};
 use crate::{
     account::Account,
+    blockchain_download::header_validation::validate_header_chain,
     blocks::{block::Block, block_header::BlockHeader},
     compact_size_uint::CompactSizeUint,
+    fee_estimator::transaction_fee_rate,
+    handler::side_chains::main_chain_height,
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{
+        addrv2_message::unmarshalling_addrv2,
         block_message::{get_block_message, BlockMessage},
+        feefilter_message::unmarshalling_feefilter,
         get_data_message::GetDataMessage,
+        getheaders_message::GetHeadersMessage,
         headers_message::HeadersMessage,
         inventory::Inventory,
         message_header::{get_checksum, HeaderMessage},
@@ -17,11 +22,13 @@ use crate::{
         payload::{get_data_payload::unmarshalling, getheaders_payload::GetHeadersPayload},
     },
     node_data_pointers::NodeDataPointers,
+    peer_state::{PeerState, PeersState},
     transactions::transaction::Transaction,
     utxo_tuple::UtxoTuple,
 };
 use std::{
     collections::HashMap,
+    net::SocketAddr,
     sync::{mpsc::Sender, Arc, RwLock},
 };
 
@@ -33,6 +40,10 @@ type NodeSender = Sender<Vec<u8>>;
 const START_STRING: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
 const MSG_TX: u32 = 1;
 const MSG_BLOCK: u32 = 2;
+const MSG_WTX: u32 = 5;
+/// MSG_TX con el flag de witness (BIP144): se pide la transaccion serializada con witness
+const MSG_WITNESS_TX: u32 = 0x40000001;
+const MAX_HEADERS_PER_MESSAGE: usize = 2000;
 const GENESIS_BLOCK_HASH: [u8; 32] = [
     0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01, 0xad, 0x0e, 0xe9, 0x84, 0x20, 0x97, 0x79, 0xba,
     0xae, 0xc3, 0xce, 0xd9, 0x0f, 0xa3, 0xf4, 0x08, 0x71, 0x95, 0x26, 0xf8, 0xd7, 0x7f, 0x49, 0x43,
@@ -44,39 +55,65 @@ const GENESIS_BLOCK_HASH: [u8; 32] = [
 ***************************************************************************
 */
 
-/// Deserializa el payload del mensaje headers y en caso de ser validos se fijan si no estan incluidos en la cadena de headers. En caso
-/// de no estarlo, manda por el channel que escribe en el nodo el mensaje getData con el bloque a pedir
+/// Deserializa el payload del mensaje headers con los nuevos bloques anunciados por el nodo y los valida (proof of work y
+/// que esten encadenados entre si). Si no se conoce el padre del primer header le pide al nodo los headers faltantes con getheaders.
+/// Los headers que extienden la cadena se agregan a la cadena de headers y se piden los bloques con getdata. Los que no la extienden
+/// se guardan como una rama lateral y, si la rama pasa a tener mas trabajo, se reorganiza la cadena y se piden sus bloques.
+/// Si el mensaje trae la cantidad maxima de headers, se piden los siguientes
 pub fn handle_headers_message(
     log_sender: &LogSender,
     tx: NodeSender,
     payload: &[u8],
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
     node_pointers: NodeDataPointers,
 ) -> NodeMessageHandlerResult {
     let new_headers = HeadersMessage::unmarshalling(&payload.to_vec())
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
-    for header in new_headers {
-        if !header.validate() {
-            write_in_log(
-                &log_sender.error_log_sender,
-                "Error en validacion de la proof of work de nuevo header",
-            );
-        } else {
-            // se fija que el header que recibio no este ya incluido en la cadena de headers (con verificar los ultimos 10 alcanza)
-            let header_not_included = header_is_not_included(header, headers.clone())?;
-            if header_not_included {
-                let get_data_message =
-                    GetDataMessage::new(vec![Inventory::new_block(header.hash())]);
-                let get_data_message_bytes = get_data_message.marshalling();
-                tx.send(get_data_message_bytes)
-                    .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-            }
+    if new_headers.is_empty() {
+        return Ok(());
+    }
+    let first_parent = new_headers[0].previous_block_header_hash;
+    if !header_is_known(first_parent, &node_pointers)?
+        && !node_pointers
+            .side_chains
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .contains(&first_parent)
+    {
+        write_in_log(
+            &log_sender.info_log_sender,
+            "Recibo headers de padre desconocido, pido los headers faltantes",
+        );
+        return ask_for_missing_headers(&tx, &node_pointers);
+    }
+    if !announced_headers_are_valid(log_sender, &new_headers, &node_pointers)? {
+        return Ok(());
+    }
+    let mut blocks_to_ask = vec![];
+    for header in new_headers.iter() {
+        if header_is_known(header.hash(), &node_pointers)? {
+            continue;
         }
-        load_header_heights(
-            &vec![header],
-            &node_pointers.blockchain.header_heights,
-            &headers,
+        let header_included = include_new_header(
+            log_sender,
+            *header,
+            node_pointers.blockchain.headers.clone(),
+            node_pointers.blockchain.header_heights.clone(),
         )?;
+        if !header_included {
+            blocks_to_ask.extend(track_side_chain_header(
+                log_sender,
+                *header,
+                &node_pointers,
+            )?);
+            continue;
+        }
+        blocks_to_ask.push(Inventory::new_block(header.hash()));
+    }
+    if !blocks_to_ask.is_empty() {
+        write_to_node(&tx, GetDataMessage::new(blocks_to_ask).marshalling())?;
+    }
+    if new_headers.len() == MAX_HEADERS_PER_MESSAGE {
+        ask_for_missing_headers(&tx, &node_pointers)?;
     }
     Ok(())
 }
@@ -147,7 +184,10 @@ pub fn handle_getdata_message(
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
     let mut notfound_inventories: Vec<Inventory> = Vec::new();
     for inv in inventories {
-        if inv.type_identifier == MSG_TX {
+        if inv.type_identifier == MSG_TX
+            || inv.type_identifier == MSG_WTX
+            || inv.type_identifier == MSG_WITNESS_TX
+        {
             handle_tx_inventory(log_sender, &inv, &accounts, &node_sender)?;
         }
         if inv.type_identifier == MSG_BLOCK {
@@ -202,7 +242,9 @@ fn handle_block_inventory(
     Ok(())
 }
 
-/// Se fija si la transaccion del inventory esta en alguna de las cuentas de la wallet y si es asi la envia por el channel para que se escriba en el nodo
+/// Se fija si la transaccion del inventory esta en alguna de las cuentas de la wallet y si es asi la envia por el channel para que se escriba en el nodo.
+/// Si el inventory es del tipo MSG_WTX se busca la transaccion por wtxid y sino por txid.
+/// Para MSG_WTX y MSG_WITNESS_TX se envia serializada con witness y para MSG_TX sin witness
 fn handle_tx_inventory(
     log_sender: &LogSender,
     inventory: &Inventory,
@@ -220,8 +262,14 @@ fn handle_tx_inventory(
             .read()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
         {
-            if tx.hash() == inventory.hash {
-                let tx_message = get_tx_message(tx);
+            let tx_id = if inventory.type_identifier == MSG_WTX {
+                tx.wtxid()
+            } else {
+                tx.hash()
+            };
+            if tx_id == inventory.hash {
+                let with_witness = inventory.type_identifier != MSG_TX;
+                let tx_message = get_tx_message(tx, with_witness);
                 write_to_node(node_sender, tx_message)?;
                 write_in_log(
                     &log_sender.info_log_sender,
@@ -233,68 +281,126 @@ fn handle_tx_inventory(
     Ok(())
 }
 
-/// Deserializa el payload del mensaje blocks y en caso de que el bloque es valido y todavia no este incluido, agrega el header a la cadena de headers
-/// y el bloque a la cadena de bloques. Se fija si alguna transaccion del bloque involucra a alguna de las cuentas del programa.
+/// Deserializa el payload del mensaje block y en caso de que el bloque sea valido y todavia no este incluido lo agrega a la cadena.
+/// Si no se conoce el bloque padre, se guarda en el pool de bloques huerfanos y se piden los headers faltantes, o el bloque padre
+/// si ya se conoce su header. Cada vez que se agrega un bloque se procesan los huerfanos que lo tenian como padre. Los bloques
+/// viejos pedidos para reescanear la cadena solo se guardan, sin conectarlos.
 pub fn handle_block_message(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
+    tx: NodeSender,
     payload: &[u8],
     node_pointers: NodeDataPointers,
 ) -> NodeMessageHandlerResult {
     let new_block = BlockMessage::unmarshalling(&payload.to_vec())
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
-    if new_block.validate().0 {
-        let header_is_not_included_yet = header_is_not_included(
-            new_block.block_header,
-            node_pointers.blockchain.headers.clone(),
-        )?;
-        if header_is_not_included_yet {
-            include_new_header(
-                log_sender,
-                new_block.block_header,
-                node_pointers.blockchain.headers.clone(),
-                node_pointers.blockchain.header_heights.clone(),
-            )?;
-            new_block
-                .give_me_utxos(node_pointers.blockchain.utxo_set.clone())
-                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
-            update_accounts_utxo_set(
-                node_pointers.accounts.clone(),
-                node_pointers.blockchain.utxo_set,
-            )?;
-            new_block.contains_pending_tx(log_sender, ui_sender, node_pointers.accounts.clone())?;
-            include_new_block(
-                log_sender,
-                ui_sender,
-                new_block,
-                node_pointers.blockchain.blocks,
-            )?;
-        }
-    } else {
+    if !new_block.validate().0 {
         write_in_log(
             &log_sender.error_log_sender,
             "NUEVO BLOQUE ES INVALIDO, NO LO AGREGO!",
         );
+        return Ok(());
+    }
+    if store_rescan_block(log_sender, &new_block, &node_pointers)? {
+        return Ok(());
+    }
+    if block_is_included(&new_block.hash(), &node_pointers)? {
+        return Ok(());
+    }
+    let parent_hash = new_block.block_header.previous_block_header_hash;
+    if !block_is_included(&parent_hash, &node_pointers)? {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "Bloque {} huerfano, lo guardo hasta recibir su padre",
+                new_block.hex_hash()
+            )
+            .as_str(),
+        );
+        let mut orphan_blocks = node_pointers
+            .orphan_blocks
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        orphan_blocks.add(new_block);
+        let parent_is_orphan = orphan_blocks.contains(&parent_hash);
+        drop(orphan_blocks);
+        if !header_is_known(parent_hash, &node_pointers)? {
+            ask_for_missing_headers(&tx, &node_pointers)?;
+        } else if !parent_is_orphan {
+            // se conoce el header del padre pero no su bloque: se lo pide para poder conectar al huerfano
+            write_to_node(
+                &tx,
+                GetDataMessage::new(vec![Inventory::new_block(parent_hash)]).marshalling(),
+            )?;
+        }
+        return Ok(());
+    }
+    let mut blocks_to_connect = vec![new_block];
+    while let Some(block) = blocks_to_connect.pop() {
+        let block_hash = block.hash();
+        if !connect_block(log_sender, ui_sender, &tx, block, &node_pointers)? {
+            continue;
+        }
+        let orphan_children = node_pointers
+            .orphan_blocks
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .take_children(&block_hash);
+        blocks_to_connect.extend(orphan_children);
     }
     Ok(())
 }
 
+/// Recibe el payload del mensaje feefilter (BIP133), el estado de los nodos y la direccion del nodo que lo envio.
+/// Guarda en su estado el fee rate minimo de las transacciones que quiere que le anunciemos.
+/// Devuelve error si no se pudo deserializar el payload
+pub fn handle_feefilter_message(
+    payload: &[u8],
+    peers_state: &PeersState,
+    peer_addr: Option<SocketAddr>,
+) -> NodeMessageHandlerResult {
+    let fee_rate = unmarshalling_feefilter(payload)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    update_peer_state(peers_state, peer_addr, |peer_state| {
+        peer_state.fee_filter = fee_rate
+    })
+}
+
+/// Recibe el payload del mensaje addrv2 (BIP155), el estado de los nodos y la direccion del nodo que lo envio.
+/// Guarda en su estado las direcciones recibidas (IPv4, IPv6, Tor, I2P y CJDNS).
+/// Devuelve error si no se pudo deserializar el payload
+pub fn handle_addrv2_message(
+    payload: &[u8],
+    peers_state: &PeersState,
+    peer_addr: Option<SocketAddr>,
+) -> NodeMessageHandlerResult {
+    let addresses = unmarshalling_addrv2(payload)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    update_peer_state(peers_state, peer_addr, |peer_state| {
+        peer_state.add_addresses(addresses)
+    })
+}
+
 /// Recieves a NodeSender and the payload of the inv message and creates the inventories to ask for the incoming
-/// txs the node sent via inv. Returns error in case of failure or Ok(())
+/// txs the node sent via inv. If a block we don't know is announced, asks for the headers with getheaders
+/// so it goes through the headers-first pipeline. Returns error in case of failure or Ok(())
 pub fn handle_inv_message(
     tx: NodeSender,
     payload: &[u8],
     transactions_received: Arc<RwLock<Vec<[u8; 32]>>>,
+    node_pointers: &NodeDataPointers,
 ) -> NodeMessageHandlerResult {
     let mut offset: usize = 0;
     let count = CompactSizeUint::unmarshalling(payload, &mut offset)
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
     let mut inventories = vec![];
+    let mut unknown_block_announced = false;
     for _ in 0..count.decoded_value() as usize {
         let mut inventory_bytes = vec![0; 36];
         inventory_bytes.copy_from_slice(&payload[offset..(offset + 36)]);
         let inv = Inventory::from_le_bytes(&inventory_bytes);
-        if inv.type_identifier == 1
+        // los nodos que negociaron wtxid relay anuncian las transacciones por wtxid
+        if (inv.type_identifier == MSG_TX || inv.type_identifier == MSG_WTX)
             && !transactions_received
                 .read()
                 .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -305,9 +411,14 @@ pub fn handle_inv_message(
                 .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
                 .push(inv.hash());
             inventories.push(inv);
+        } else if inv.type_identifier == MSG_BLOCK && !header_is_known(inv.hash(), node_pointers)? {
+            unknown_block_announced = true;
         }
         offset += 36;
     }
+    if unknown_block_announced {
+        ask_for_missing_headers(&tx, node_pointers)?;
+    }
     if !inventories.is_empty() {
         ask_for_incoming_tx(tx, inventories)?;
     }
@@ -332,17 +443,19 @@ pub fn handle_ping_message(tx: NodeSender, payload: &[u8]) -> NodeMessageHandler
     Ok(())
 }
 
-/// Recibe un LogSender, el Payload del mensaje tx y un puntero a un puntero con las cuentas de la wallet. Se fija si la tx involucra una cuenta de nuestra wallet. Devuelve Ok(())
+/// Recibe un LogSender, el Payload del mensaje tx y los punteros del nodo. Se fija si la tx involucra una cuenta de nuestra wallet
+/// y la agrega al estimador de fees con su fee rate, si se conocen los outputs que gasta. Devuelve Ok(())
 /// en caso de que se pueda leer bien el payload y recorrer las tx o error en caso contrario
 pub fn handle_tx_message(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     payload: &[u8],
-    accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+    node_pointers: &NodeDataPointers,
 ) -> NodeMessageHandlerResult {
     let tx = Transaction::unmarshalling(&payload.to_vec(), &mut 0)
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
-    tx.check_if_tx_involves_user_account(log_sender, ui_sender, accounts)?;
+    track_transaction_fee_rate(&tx, node_pointers)?;
+    tx.check_if_tx_involves_user_account(log_sender, ui_sender, node_pointers.accounts.clone())?;
     Ok(())
 }
 
@@ -361,6 +474,398 @@ fn ask_for_incoming_tx(tx: NodeSender, inventories: Vec<Inventory>) -> NodeMessa
     Ok(())
 }
 
+/// Recibe headers anunciados por un nodo cuyo primer padre ya se conoce, en la cadena principal o en una rama
+/// lateral, y los valida con las reglas de consenso de la red como continuacion de la cadena a la que se enlazan.
+/// Devuelve false y lo escribe en el log si algun header no es valido
+fn announced_headers_are_valid(
+    log_sender: &LogSender,
+    new_headers: &[BlockHeader],
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    let header_heights = node_pointers
+        .blockchain
+        .header_heights
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let headers = node_pointers
+        .blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let first_parent = new_headers[0].previous_block_header_hash;
+    let (fork_height, mut chain) = match main_chain_height(&first_parent, &headers, &header_heights)
+    {
+        Some(fork_height) => (fork_height, vec![]),
+        None => {
+            match node_pointers
+                .side_chains
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .branch(first_parent, &headers, &header_heights)
+            {
+                Ok(branch) => branch,
+                Err(err) => {
+                    write_in_log(&log_sender.error_log_sender, err);
+                    return Ok(false);
+                }
+            }
+        }
+    };
+    chain.extend_from_slice(new_headers);
+    match validate_header_chain(
+        &node_pointers.consensus_params,
+        &headers,
+        fork_height,
+        &chain,
+    ) {
+        Ok(_) => Ok(true),
+        Err(NodeCustomErrors::InvalidHeaderError(err)) => {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!("Descarto headers anunciados invalidos: {}", err).as_str(),
+            );
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Le envia al nodo el mensaje getheaders con el block locator de nuestra cadena de headers para que nos
+/// envie los headers que nos faltan
+fn ask_for_missing_headers(
+    tx: &NodeSender,
+    node_pointers: &NodeDataPointers,
+) -> NodeMessageHandlerResult {
+    let locator = node_pointers.blockchain.block_locator()?;
+    let getheaders_message =
+        GetHeadersMessage::new(node_pointers.protocol_version, locator, [0; 32]);
+    write_to_node(tx, getheaders_message.marshalling())
+}
+
+/// Agrega a la cadena un bloque cuyo padre ya esta incluido. Si su header todavia no esta en la cadena de headers
+/// lo agrega siempre que extienda la cadena, y sino lo guarda como rama lateral y pide los bloques de la nueva cadena
+/// principal si hubo una reorganizacion. Guarda los outputs que gasta para poder deshacerlo, actualiza el utxo set,
+/// las cuentas y le avisa a la UI. Devuelve true si el bloque se agrego o false si no extiende la cadena principal
+fn connect_block(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    tx: &NodeSender,
+    block: Block,
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    let header_is_new = !header_is_known(block.hash(), node_pointers)?;
+    if header_is_new
+        && !announced_headers_are_valid(log_sender, &[block.block_header], node_pointers)?
+    {
+        return Ok(false);
+    }
+    if header_is_new
+        && !include_new_header(
+            log_sender,
+            block.block_header,
+            node_pointers.blockchain.headers.clone(),
+            node_pointers.blockchain.header_heights.clone(),
+        )?
+    {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "Bloque {} no extiende la cadena principal",
+                block.hex_hash()
+            )
+            .as_str(),
+        );
+        let blocks_to_ask = track_side_chain_header(log_sender, block.block_header, node_pointers)?;
+        if !blocks_to_ask.is_empty() {
+            write_to_node(tx, GetDataMessage::new(blocks_to_ask).marshalling())?;
+        }
+        return Ok(false);
+    }
+    let utxo_set = node_pointers
+        .blockchain
+        .utxo_set
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let spent_outputs = spent_outputs(&block, &utxo_set);
+    drop(utxo_set);
+    node_pointers
+        .side_chains
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .record_undo(block.hash(), spent_outputs);
+    block
+        .give_me_utxos(node_pointers.blockchain.utxo_set.clone())
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    // se registra antes de actualizar las utxos de las cuentas para conocer las monedas que gasta
+    record_block_in_ledgers(&block, node_pointers.accounts.clone())?;
+    update_accounts_utxo_set(
+        node_pointers.accounts.clone(),
+        node_pointers.blockchain.utxo_set.clone(),
+    )?;
+    block.contains_pending_tx(log_sender, ui_sender, node_pointers.accounts.clone())?;
+    update_fee_estimator(&block, node_pointers)?;
+    include_new_block(
+        log_sender,
+        ui_sender,
+        block,
+        node_pointers.blockchain.blocks.clone(),
+    )?;
+    Ok(true)
+}
+
+/// Guarda el header que no extiende la cadena principal como parte de una rama lateral, si se conoce su padre en la
+/// cadena principal o en otra rama lateral. Si la rama pasa a tener mas trabajo que la cadena principal se reorganiza
+/// la cadena. Devuelve los bloques de la nueva cadena principal que hay que pedir
+fn track_side_chain_header(
+    log_sender: &LogSender,
+    header: BlockHeader,
+    node_pointers: &NodeDataPointers,
+) -> Result<Vec<Inventory>, NodeCustomErrors> {
+    let parent_hash = header.previous_block_header_hash;
+    let parent_in_main_chain = header_is_known(parent_hash, node_pointers)?;
+    let mut side_chains = node_pointers
+        .side_chains
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    if !parent_in_main_chain && !side_chains.contains(&parent_hash) {
+        return Ok(vec![]);
+    }
+    if side_chains.add_header(header) {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "Header {} de una rama lateral, lo guardo",
+                header.hex_hash()
+            )
+            .as_str(),
+        );
+    }
+    drop(side_chains);
+    reorganize_chain(log_sender, header.hash(), node_pointers)
+}
+
+/// Si la rama lateral que termina en el header recibido tiene mas trabajo que la cadena principal, la convierte en
+/// la cadena principal: desconecta los bloques que dejan de ser de la cadena, con los outputs que habian gastado,
+/// y actualiza las cuentas. Devuelve los bloques de la rama que hay que pedir para conectarlos
+fn reorganize_chain(
+    log_sender: &LogSender,
+    tip_hash: [u8; 32],
+    node_pointers: &NodeDataPointers,
+) -> Result<Vec<Inventory>, NodeCustomErrors> {
+    let mut header_heights = node_pointers
+        .blockchain
+        .header_heights
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut headers = node_pointers
+        .blockchain
+        .headers
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let blocks = node_pointers
+        .blockchain
+        .blocks
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut side_chains = node_pointers
+        .side_chains
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let reorganization = match side_chains.reorganize(
+        &node_pointers.consensus_params,
+        tip_hash,
+        &mut headers,
+        &mut header_heights,
+        |hash| blocks.contains_key(hash),
+    ) {
+        Ok(Some(reorganization)) => reorganization,
+        Ok(None) => return Ok(vec![]),
+        Err(err) => {
+            write_in_log(&log_sender.error_log_sender, err);
+            return Ok(vec![]);
+        }
+    };
+    // se deshacen del mas nuevo al mas viejo
+    let blocks_to_disconnect: Vec<([u8; 32], Option<Vec<UtxoTuple>>)> = reorganization
+        .disconnected
+        .iter()
+        .rev()
+        .map(|header| (header.hash(), side_chains.take_undo(&header.hash())))
+        .collect();
+    drop(side_chains);
+    drop(blocks);
+    drop(headers);
+    drop(header_heights);
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "Reorganizacion de la cadena desde la altura {}: salen {} headers y entran {}",
+            reorganization.fork_height,
+            reorganization.disconnected.len(),
+            reorganization.connected.len()
+        )
+        .as_str(),
+    );
+    for (block_hash, spent_outputs) in blocks_to_disconnect {
+        if let Some(spent_outputs) = spent_outputs {
+            disconnect_block(block_hash, spent_outputs, node_pointers)?;
+        }
+    }
+    update_accounts_utxo_set(
+        node_pointers.accounts.clone(),
+        node_pointers.blockchain.utxo_set.clone(),
+    )?;
+    Ok(reorganization
+        .connected
+        .iter()
+        .map(|header| Inventory::new_block(header.hash()))
+        .collect())
+}
+
+/// Saca de la cadena el bloque con el hash recibido: borra del utxo set los outputs que creo, vuelve a agregar
+/// los que gasto y deja pendientes sus transacciones en los ledgers de las cuentas
+fn disconnect_block(
+    block_hash: [u8; 32],
+    spent_outputs: Vec<UtxoTuple>,
+    node_pointers: &NodeDataPointers,
+) -> NodeMessageHandlerResult {
+    let block = match node_pointers
+        .blockchain
+        .blocks
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .remove(&block_hash)
+    {
+        Some(block) => block,
+        None => return Ok(()),
+    };
+    let mut utxo_set = node_pointers
+        .blockchain
+        .utxo_set
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    for transaction in &block.txn {
+        utxo_set.remove(&transaction.hash());
+    }
+    for spent in spent_outputs {
+        utxo_set
+            .entry(spent.hash)
+            .or_insert_with(|| UtxoTuple {
+                hash: spent.hash,
+                utxo_set: vec![],
+                height: spent.height,
+                coinbase: spent.coinbase,
+            })
+            .utxo_set
+            .extend(spent.utxo_set);
+    }
+    drop(utxo_set);
+    for account in &*node_pointers
+        .accounts
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+    {
+        account
+            .ledger
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .unconfirm_block(block_hash);
+    }
+    Ok(())
+}
+
+/// Devuelve los outputs del utxo set que gastan las transacciones del bloque, agrupados por transaccion, para poder
+/// volver a agregarlos si el bloque deja de ser parte de la cadena principal
+fn spent_outputs(block: &Block, utxo_set: &HashMap<[u8; 32], UtxoTuple>) -> Vec<UtxoTuple> {
+    let mut spent: HashMap<[u8; 32], UtxoTuple> = HashMap::new();
+    for transaction in block.txn.iter().filter(|tx| !tx.is_coinbase_transaction()) {
+        for txin in &transaction.tx_in {
+            let previous_hash = txin.get_previous_output_hash();
+            let previous_index = txin.get_previous_output_index();
+            let Some(utxo) = utxo_set.get(&previous_hash) else {
+                continue;
+            };
+            if let Some((tx_out, _)) = utxo
+                .utxo_set
+                .iter()
+                .find(|(_, index)| *index == previous_index)
+            {
+                spent
+                    .entry(previous_hash)
+                    .or_insert_with(|| UtxoTuple {
+                        hash: previous_hash,
+                        utxo_set: vec![],
+                        height: utxo.height,
+                        coinbase: utxo.coinbase,
+                    })
+                    .utxo_set
+                    .push((tx_out.clone(), previous_index));
+            }
+        }
+    }
+    spent.into_values().collect()
+}
+
+/// Devuelve true si el header con el hash recibido esta en la cadena de headers
+fn header_is_known(
+    header_hash: [u8; 32],
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    if header_hash == GENESIS_BLOCK_HASH {
+        return Ok(true);
+    }
+    Ok(node_pointers
+        .blockchain
+        .header_heights
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .contains_key(&header_hash))
+}
+
+/// Si el bloque recibido se pidio para reescanear la cadena lo guarda con los bloques descargados sin conectarlo,
+/// porque es anterior a los bloques con los que se armo el utxo set. Devuelve true si se lo habia pedido
+fn store_rescan_block(
+    log_sender: &LogSender,
+    block: &Block,
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    let block_hash = block.hash();
+    if !node_pointers
+        .rescan_requests
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .deliver(&block_hash)
+    {
+        return Ok(false);
+    }
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!("Bloque {} recibido para el rescan", block.hex_hash()).as_str(),
+    );
+    node_pointers
+        .blockchain
+        .blocks
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .insert(block_hash, block.clone());
+    Ok(true)
+}
+
+/// Devuelve true si el bloque con el hash recibido esta en la cadena de bloques
+fn block_is_included(
+    block_hash: &[u8; 32],
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    Ok(node_pointers
+        .blockchain
+        .blocks
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .contains_key(block_hash))
+}
+
 /// Recibe un bloque a agregar a la cadena y el puntero Arc apuntando a la cadena de bloques y lo agrega.
 /// Devuelve Ok(()) en caso de poder agregarlo correctamente o error del tipo NodeHandlerError en caso de no poder.
 fn include_new_block(
@@ -384,55 +889,108 @@ fn include_new_block(
 }
 
 /// Recibe un header a agregar a la cadena de headers y el Arc apuntando a la cadena de headers y lo agrega
-/// a la lista de headers y al diccionario de alturas de headers.
-/// Devuelve Ok(()) en caso de poder agregarlo correctamente o error del tipo NodeHandlerError en caso de no poder
+/// a la lista de headers y al diccionario de alturas de headers, solo si extiende la cadena (su padre es el ultimo header).
+/// La verificacion y el agregado se hacen con los locks tomados para que dos nodos no agreguen el mismo header.
+/// Devuelve Ok(true) si lo agrego, Ok(false) si no extiende la cadena o error del tipo NodeHandlerError en caso de no poder
 fn include_new_header(
     log_sender: &LogSender,
     header: BlockHeader,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
     headers_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-) -> NodeMessageHandlerResult {
-    headers
+) -> Result<bool, NodeCustomErrors> {
+    let mut headers_heights_lock = headers_heights
         .write()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .push(header);
-    headers_heights
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut headers_lock = headers
         .write()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .insert(
-            header.hash(),
-            headers
-                .read()
-                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                .len()
-                - 1,
-        );
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let tip_hash = headers_lock
+        .last()
+        .map(|header| header.hash())
+        .unwrap_or(GENESIS_BLOCK_HASH);
+    if header.previous_block_header_hash != tip_hash {
+        return Ok(false);
+    }
+    headers_lock.push(header);
+    headers_heights_lock.insert(header.hash(), headers_lock.len() - 1);
     write_in_log(
         &log_sender.info_log_sender,
         "Recibo un nuevo header, lo agrego a la cadena de headers!",
     );
-    Ok(())
+    Ok(true)
 }
 
-/// Recibe un header y la lista de headers y se fija en los ulitmos 10 headers de la lista, si es que existen, que el header
-/// no este incluido ya. En caso de estar incluido devuelve false y en caso de nos estar incluido devuelve true. Devuelve error en caso de
-/// que no se pueda leer la lista de headers
-fn header_is_not_included(
-    header: BlockHeader,
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
-) -> Result<bool, NodeCustomErrors> {
-    let headers_guard = headers
+/// Agrega la transaccion recibida de un nodo al estimador de fees, con la altura actual de la cadena.
+/// Si no se conocen todos los outputs que gasta no se puede calcular su fee rate y se ignora
+fn track_transaction_fee_rate(
+    tx: &Transaction,
+    node_pointers: &NodeDataPointers,
+) -> NodeMessageHandlerResult {
+    let utxo_set = node_pointers
+        .blockchain
+        .utxo_set
         .read()
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
-    let start_index = headers_guard.len().saturating_sub(10);
-    let last_10_headers = &headers_guard[start_index..];
-    // Verificar si el header está en los ultimos 10 headers
-    for included_header in last_10_headers.iter() {
-        if *included_header == header {
-            return Ok(false);
-        }
+    let fee_rate = match transaction_fee_rate(tx, &utxo_set) {
+        Some(fee_rate) => fee_rate,
+        None => return Ok(()),
+    };
+    drop(utxo_set);
+    let height = node_pointers
+        .blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .len()
+        .saturating_sub(1);
+    node_pointers
+        .fee_estimator
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .track_transaction(tx.hash(), fee_rate, height);
+    Ok(())
+}
+
+/// Le informa al estimador de fees las transacciones del bloque agregado a la cadena
+fn update_fee_estimator(
+    block: &Block,
+    node_pointers: &NodeDataPointers,
+) -> NodeMessageHandlerResult {
+    let height = match node_pointers
+        .blockchain
+        .header_heights
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .get(&block.hash())
+    {
+        Some(height) => *height,
+        None => return Ok(()),
+    };
+    let txids: Vec<[u8; 32]> = block.txn.iter().map(Transaction::hash).collect();
+    node_pointers
+        .fee_estimator
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .process_block(height, &txids);
+    Ok(())
+}
+
+/// Agrega al ledger de cada cuenta las transacciones del bloque que la afectan
+fn record_block_in_ledgers(
+    block: &Block,
+    accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+) -> Result<(), NodeCustomErrors> {
+    for account in &*accounts
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+    {
+        account
+            .record_block(block)
+            .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
     }
-    Ok(true)
+    Ok(())
 }
 
 /// Actualiza el utxo_set de cada cuenta
@@ -455,10 +1013,14 @@ fn update_accounts_utxo_set(
     Ok(())
 }
 
-// Devuelve el mensaje tx según la transacción recibida
-fn get_tx_message(tx: &Transaction) -> Vec<u8> {
+// Devuelve el mensaje tx según la transacción recibida, con o sin los datos de witness
+fn get_tx_message(tx: &Transaction, with_witness: bool) -> Vec<u8> {
     let mut tx_payload = vec![];
-    tx.marshalling(&mut tx_payload);
+    if with_witness {
+        tx.marshalling(&mut tx_payload);
+    } else {
+        tx.marshalling_without_witness(&mut tx_payload);
+    }
     let header = HeaderMessage::new("tx".to_string(), Some(&tx_payload));
     let mut tx_message = vec![];
     tx_message.extend_from_slice(&header.to_le_bytes());
@@ -466,6 +1028,25 @@ fn get_tx_message(tx: &Transaction) -> Vec<u8> {
     tx_message
 }
 
+/// Recibe el estado de los nodos, la direccion de uno de ellos y una funcion que modifica su estado y la aplica.
+/// Si no se conoce la direccion del nodo o este no tiene estado no hace nada
+fn update_peer_state(
+    peers_state: &PeersState,
+    peer_addr: Option<SocketAddr>,
+    update: impl FnOnce(&mut PeerState),
+) -> NodeMessageHandlerResult {
+    if let Some(addr) = peer_addr {
+        if let Some(peer_state) = peers_state
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .get_mut(&addr)
+        {
+            update(peer_state);
+        }
+    }
+    Ok(())
+}
+
 /// Manda por el channel el mensaje recibido para que se escriba en el nodo
 pub fn write_to_node(tx: &NodeSender, message: Vec<u8>) -> NodeMessageHandlerResult {
     tx.send(message)
@@ -1,2 +1,4 @@
 pub mod message_handlers;
 pub mod node_message_handler;
+pub mod orphan_blocks;
+pub mod side_chains;
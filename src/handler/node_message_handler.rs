@@ -6,11 +6,12 @@ use crate::{
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{message_header::is_terminated, message_header::HeaderMessage},
     node_data_pointers::NodeDataPointers,
+    peer_state::PeersState,
 };
 use std::{
     io::{self, Read, Write},
     mem,
-    net::TcpStream,
+    net::{SocketAddr, TcpStream},
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex, RwLock,
@@ -19,9 +20,9 @@ use std::{
 };
 
 use super::message_handlers::{
-    handle_block_message, handle_getdata_message, handle_getheaders_message,
-    handle_headers_message, handle_inv_message, handle_ping_message, handle_tx_message,
-    write_to_node,
+    handle_addrv2_message, handle_block_message, handle_feefilter_message, handle_getdata_message,
+    handle_getheaders_message, handle_headers_message, handle_inv_message, handle_ping_message,
+    handle_tx_message, write_to_node,
 };
 
 type NodeMessageHandlerResult = Result<(), NodeCustomErrors>;
@@ -33,7 +34,7 @@ type NodeReceiver = Receiver<Vec<u8>>;
 /// a estos y decide que hacer con los mensajes que llegan y con los que tiene que escribir
 pub struct NodeMessageHandler {
     nodes_handle: Arc<Mutex<Vec<JoinHandle<()>>>>,
-    nodes_sender: Vec<NodeSender>,
+    nodes_sender: Vec<(NodeSender, Option<SocketAddr>)>,
     transactions_recieved: Arc<RwLock<Vec<[u8; 32]>>>,
     finish: Arc<RwLock<bool>>,
 }
@@ -61,8 +62,8 @@ impl NodeMessageHandler {
         let transactions_recieved: Arc<RwLock<Vec<[u8; 32]>>> = Arc::new(RwLock::new(Vec::new()));
         for _ in 0..cant_nodos {
             let (tx, rx) = channel();
-            nodes_sender.push(tx.clone());
             let node = get_last_node(node_pointers.connected_nodes.clone())?;
+            nodes_sender.push((tx.clone(), node.peer_addr().ok()));
             println!(
                 "Nodo -{:?}- Escuchando por nuevos bloques...\n",
                 node.peer_addr()
@@ -91,7 +92,7 @@ impl NodeMessageHandler {
     /// Devuelve Ok(()) en caso exitoso o un error ThreadChannelError en caso contrario
     pub fn broadcast_to_nodes(&self, message: Vec<u8>) -> NodeMessageHandlerResult {
         let mut amount_of_failed_nodes = 0;
-        for node_sender in &self.nodes_sender {
+        for (node_sender, _) in &self.nodes_sender {
             // si alguno de los channels esta cerrado significa que por alguna razon el nodo fallo entonces lo ignoro y pruebo broadcastear
             // en los siguientes nodos restantes
             if write_to_node(node_sender, message.clone()).is_err() {
@@ -108,6 +109,63 @@ impl NodeMessageHandler {
         Ok(())
     }
 
+    /// Recibe un mensaje serializado y se lo manda a un solo nodo, empezando a probar por el de la posicion recibida
+    /// (modulo la cantidad de nodos) y siguiendo con los demas si el channel del nodo esta cerrado. Permite repartir
+    /// los pedidos entre los nodos cambiando la posicion. Devuelve error ThreadChannelError si no se le pudo enviar a ninguno
+    pub fn send_to_one_node(
+        &self,
+        first_node: usize,
+        message: Vec<u8>,
+    ) -> NodeMessageHandlerResult {
+        let amount_of_nodes = self.nodes_sender.len();
+        for offset in 0..amount_of_nodes {
+            let (node_sender, _) = &self.nodes_sender[(first_node + offset) % amount_of_nodes];
+            if write_to_node(node_sender, message.clone()).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(NodeCustomErrors::ThreadChannelError(
+            "Todos los channels cerrados, no se pudo enviar el mensaje".to_string(),
+        ))
+    }
+
+    /// Recibe el estado de los nodos, el fee rate de una transaccion en satoshis por kilobyte y el mensaje inv
+    /// de la transaccion anunciada por txid y por wtxid. Le envia el inv solo a los nodos cuyo fee filter (BIP133)
+    /// acepta ese fee rate, usando el anuncio por wtxid con los que negociaron wtxid relay (BIP339).
+    /// Devuelve error ThreadChannelError si no se le pudo enviar a ninguno de los nodos que debian recibirlo
+    pub fn broadcast_tx_to_nodes(
+        &self,
+        peers_state: &PeersState,
+        fee_rate: u64,
+        txid_inv_message: Vec<u8>,
+        wtxid_inv_message: Vec<u8>,
+    ) -> NodeMessageHandlerResult {
+        let mut amount_of_nodes_to_announce = 0;
+        let mut amount_of_failed_nodes = 0;
+        let peers_state = peers_state
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        for (node_sender, peer_addr) in &self.nodes_sender {
+            let peer_state = peer_addr.and_then(|addr| peers_state.get(&addr));
+            let message = match peer_state {
+                Some(state) if !state.accepts_fee_rate(fee_rate) => continue,
+                Some(state) if state.wtxid_relay => wtxid_inv_message.clone(),
+                _ => txid_inv_message.clone(),
+            };
+            amount_of_nodes_to_announce += 1;
+            if write_to_node(node_sender, message).is_err() {
+                amount_of_failed_nodes += 1;
+            }
+        }
+        if amount_of_nodes_to_announce > 0 && amount_of_failed_nodes == amount_of_nodes_to_announce
+        {
+            return Err(NodeCustomErrors::ThreadChannelError(
+                "Todos los channels cerrados, no se pudo boradcastear tx".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     /// Se encarga de actualizar el valor del puntero finish que corta los ciclos de los nodos que estan siendo esuchados.
     /// Hace el join en cada uno de los threads por cada nodo que estaba siendo escuchado.
     /// A cada extremo del channel para escribir en los nodos realiza drop() para que se cierre el channel.
@@ -129,7 +187,7 @@ impl NodeMessageHandler {
                 .join()
                 .map_err(|err| NodeCustomErrors::ThreadJoinError(format!("{:?}", err)))?;
         }
-        for node_sender in self.nodes_sender.clone() {
+        for (node_sender, _) in self.nodes_sender.clone() {
             drop(node_sender);
         }
         Ok(())
@@ -147,7 +205,8 @@ impl NodeMessageHandler {
         connection: TcpStream,
     ) -> NodeMessageHandlerResult {
         let (tx, rx) = channel();
-        self.nodes_sender.push(tx.clone());
+        self.nodes_sender
+            .push((tx.clone(), connection.peer_addr().ok()));
         println!(
             "Nodo -{:?}- Escuchando por nuevos bloques...\n NUEVA CONECCION AGREGADA!!!",
             connection.peer_addr()
@@ -183,6 +242,7 @@ pub fn handle_messages_from_node(
 ) -> JoinHandle<()> {
     let log_sender = log_sender.clone();
     let ui_sender = ui_sender.clone();
+    let peer_addr = node.peer_addr().ok();
     thread::spawn(move || {
         // si ocurre algun error se guarda en esta variable
         let mut error: Option<NodeCustomErrors> = None;
@@ -219,13 +279,7 @@ pub fn handle_messages_from_node(
 
             match command_name {
                 "headers" => handle_message(&mut error, || {
-                    handle_headers_message(
-                        &log_sender,
-                        tx.clone(),
-                        &payload,
-                        node_pointers.blockchain.headers.clone(),
-                        node_pointers.clone(),
-                    )
+                    handle_headers_message(&log_sender, tx.clone(), &payload, node_pointers.clone())
                 }),
                 "getdata" => handle_message(&mut error, || {
                     handle_getdata_message(
@@ -237,19 +291,25 @@ pub fn handle_messages_from_node(
                     )
                 }),
                 "block" => handle_message(&mut error, || {
-                    handle_block_message(&log_sender, &ui_sender, &payload, node_pointers.clone())
+                    handle_block_message(
+                        &log_sender,
+                        &ui_sender,
+                        tx.clone(),
+                        &payload,
+                        node_pointers.clone(),
+                    )
                 }),
                 "inv" => handle_message(&mut error, || {
-                    handle_inv_message(tx.clone(), &payload, transactions_recieved.clone())
+                    handle_inv_message(
+                        tx.clone(),
+                        &payload,
+                        transactions_recieved.clone(),
+                        &node_pointers,
+                    )
                 }),
                 "ping" => handle_message(&mut error, || handle_ping_message(tx.clone(), &payload)),
                 "tx" => handle_message(&mut error, || {
-                    handle_tx_message(
-                        &log_sender,
-                        &ui_sender,
-                        &payload,
-                        node_pointers.accounts.clone(),
-                    )
+                    handle_tx_message(&log_sender, &ui_sender, &payload, &node_pointers)
                 }),
                 "getheaders" => handle_message(&mut error, || {
                     handle_getheaders_message(
@@ -259,6 +319,12 @@ pub fn handle_messages_from_node(
                         node_pointers.clone(),
                     )
                 }),
+                "feefilter" => handle_message(&mut error, || {
+                    handle_feefilter_message(&payload, &node_pointers.peers_state, peer_addr)
+                }),
+                "addrv2" => handle_message(&mut error, || {
+                    handle_addrv2_message(&payload, &node_pointers.peers_state, peer_addr)
+                }),
                 _ => {
                     write_in_log(
                         &log_sender.message_log_sender,
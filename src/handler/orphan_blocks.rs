@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::blocks::block::Block;
+
+/// Cantidad maxima de bloques huerfanos que se guardan a la espera de su padre
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+
+/// Almacena los bloques que llegaron antes que su bloque padre. Cuando el padre llega y se agrega
+/// a la cadena, se sacan del pool sus hijos para procesarlos. Esta acotado: si se llena se descarta
+/// el bloque huerfano mas viejo
+#[derive(Debug, Clone)]
+pub struct OrphanBlockPool {
+    blocks: HashMap<[u8; 32], Block>,
+    children: HashMap<[u8; 32], Vec<[u8; 32]>>,
+    arrival_order: VecDeque<[u8; 32]>,
+    max_orphans: usize,
+}
+
+impl Default for OrphanBlockPool {
+    fn default() -> Self {
+        Self::new(MAX_ORPHAN_BLOCKS)
+    }
+}
+
+impl OrphanBlockPool {
+    /// Crea el pool vacio con la cantidad maxima de bloques recibida
+    pub fn new(max_orphans: usize) -> Self {
+        OrphanBlockPool {
+            blocks: HashMap::new(),
+            children: HashMap::new(),
+            arrival_order: VecDeque::new(),
+            max_orphans,
+        }
+    }
+
+    /// Agrega un bloque huerfano al pool. Si ya estaba no hace nada y si el pool esta lleno
+    /// descarta el bloque mas viejo. Devuelve true si el bloque se agrego
+    pub fn add(&mut self, block: Block) -> bool {
+        let hash = block.hash();
+        if self.blocks.contains_key(&hash) || self.max_orphans == 0 {
+            return false;
+        }
+        if self.blocks.len() >= self.max_orphans {
+            if let Some(oldest) = self.arrival_order.pop_front() {
+                self.remove(&oldest);
+            }
+        }
+        self.children
+            .entry(block.block_header.previous_block_header_hash)
+            .or_default()
+            .push(hash);
+        self.arrival_order.push_back(hash);
+        self.blocks.insert(hash, block);
+        true
+    }
+
+    /// Devuelve true si el bloque con el hash recibido esta en el pool
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    /// Devuelve la cantidad de bloques huerfanos del pool
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Devuelve true si no hay bloques huerfanos en el pool
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Saca del pool y devuelve, en el orden en que llegaron, los bloques cuyo padre es el hash recibido
+    pub fn take_children(&mut self, parent_hash: &[u8; 32]) -> Vec<Block> {
+        let children_hashes = self.children.remove(parent_hash).unwrap_or_default();
+        let mut children = vec![];
+        for hash in children_hashes {
+            self.arrival_order.retain(|orphan| *orphan != hash);
+            if let Some(block) = self.blocks.remove(&hash) {
+                children.push(block);
+            }
+        }
+        children
+    }
+
+    /// Elimina del pool el bloque con el hash recibido
+    fn remove(&mut self, hash: &[u8; 32]) {
+        if let Some(block) = self.blocks.remove(hash) {
+            let parent_hash = block.block_header.previous_block_header_hash;
+            if let Some(siblings) = self.children.get_mut(&parent_hash) {
+                siblings.retain(|sibling| sibling != hash);
+                if siblings.is_empty() {
+                    self.children.remove(&parent_hash);
+                }
+            }
+        }
+        self.arrival_order.retain(|orphan| orphan != hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blocks::block_header::BlockHeader, compact_size_uint::CompactSizeUint};
+
+    /// Crea un bloque sin transacciones cuyo padre es el hash recibido
+    fn block_with_parent(parent_hash: [u8; 32], nonce: u32) -> Block {
+        let header = BlockHeader::new(1, parent_hash, [0; 32], 0, 0x1d00ffff, nonce);
+        Block::new(header, CompactSizeUint::new(0), vec![])
+    }
+
+    #[test]
+    fn orphan_children_are_returned_when_parent_arrives() {
+        // GIVEN: un pool con dos hijos del mismo padre y un nieto
+        let mut pool = OrphanBlockPool::default();
+        let parent_hash = [1; 32];
+        let first_child = block_with_parent(parent_hash, 1);
+        let second_child = block_with_parent(parent_hash, 2);
+        let grandchild = block_with_parent(first_child.hash(), 3);
+        pool.add(first_child.clone());
+        pool.add(second_child.clone());
+        pool.add(grandchild.clone());
+        // WHEN: llega el padre
+        let children = pool.take_children(&parent_hash);
+        // THEN: se devuelven los dos hijos en orden de llegada y el nieto queda en el pool
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].hash(), first_child.hash());
+        assert_eq!(children[1].hash(), second_child.hash());
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(&grandchild.hash()));
+        assert_eq!(pool.take_children(&first_child.hash()).len(), 1);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn full_orphan_pool_evicts_the_oldest_block() {
+        // GIVEN: un pool con capacidad para dos bloques
+        let mut pool = OrphanBlockPool::new(2);
+        let oldest = block_with_parent([1; 32], 1);
+        let middle = block_with_parent([2; 32], 2);
+        let newest = block_with_parent([3; 32], 3);
+        pool.add(oldest.clone());
+        pool.add(middle.clone());
+        // WHEN: se agrega un tercer bloque
+        pool.add(newest.clone());
+        // THEN: se descarta el mas viejo
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&oldest.hash()));
+        assert!(pool.contains(&middle.hash()));
+        assert!(pool.contains(&newest.hash()));
+        assert!(pool.take_children(&[1; 32]).is_empty());
+    }
+
+    #[test]
+    fn repeated_orphan_is_not_added_twice() {
+        // GIVEN: un pool con un bloque huerfano
+        let mut pool = OrphanBlockPool::default();
+        let orphan = block_with_parent([1; 32], 1);
+        pool.add(orphan.clone());
+        // WHEN: se vuelve a agregar el mismo bloque
+        let added = pool.add(orphan);
+        // THEN: no se agrega
+        assert!(!added);
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    blockchain_download::{
+        checkpoints::forks_below_checkpoint,
+        header_validation::{chain_work, ConsensusParams},
+    },
+    blocks::block_header::BlockHeader,
+    utxo_tuple::UtxoTuple,
+};
+
+/// Cantidad maxima de headers de ramas laterales que se guardan
+pub const MAX_SIDE_CHAIN_HEADERS: usize = 2000;
+/// Cantidad de bloques conectados de los que se guardan los datos para deshacerlos en una reorganizacion
+pub const MAX_REORG_DEPTH: usize = 100;
+
+/// Cambio de la cadena principal por una rama lateral con mas trabajo acumulado
+#[derive(Debug, Clone)]
+pub struct Reorganization {
+    /// Altura del ultimo header en comun entre las dos ramas
+    pub fork_height: usize,
+    /// Headers que dejaron de ser de la cadena principal, del mas viejo al mas nuevo
+    pub disconnected: Vec<BlockHeader>,
+    /// Headers de la rama que paso a ser la cadena principal, del mas viejo al mas nuevo
+    pub connected: Vec<BlockHeader>,
+}
+
+/// Almacena los headers validos que no extienden la cadena principal pero cuyo padre se conoce, y los outputs
+/// que gasto cada uno de los ultimos bloques conectados. Con eso, cuando una rama lateral acumula mas trabajo
+/// que la cadena principal, se puede volver al punto en que se separan y seguir por la rama
+#[derive(Debug, Clone)]
+pub struct SideChains {
+    headers: HashMap<[u8; 32], BlockHeader>,
+    arrival_order: VecDeque<[u8; 32]>,
+    undo: HashMap<[u8; 32], Vec<UtxoTuple>>,
+    undo_order: VecDeque<[u8; 32]>,
+    max_headers: usize,
+    max_undo: usize,
+}
+
+impl Default for SideChains {
+    fn default() -> Self {
+        Self::new(MAX_SIDE_CHAIN_HEADERS, MAX_REORG_DEPTH)
+    }
+}
+
+impl SideChains {
+    /// Crea las ramas laterales vacias con la cantidad maxima de headers y de bloques a deshacer recibidas
+    pub fn new(max_headers: usize, max_undo: usize) -> Self {
+        SideChains {
+            headers: HashMap::new(),
+            arrival_order: VecDeque::new(),
+            undo: HashMap::new(),
+            undo_order: VecDeque::new(),
+            max_headers,
+            max_undo,
+        }
+    }
+
+    /// Agrega un header de una rama lateral. Si ya estaba no hace nada y si se llego al maximo descarta
+    /// el header mas viejo. Devuelve true si el header se agrego
+    pub fn add_header(&mut self, header: BlockHeader) -> bool {
+        let hash = header.hash();
+        if self.headers.contains_key(&hash) || self.max_headers == 0 {
+            return false;
+        }
+        if self.headers.len() >= self.max_headers {
+            if let Some(oldest) = self.arrival_order.pop_front() {
+                self.headers.remove(&oldest);
+            }
+        }
+        self.arrival_order.push_back(hash);
+        self.headers.insert(hash, header);
+        true
+    }
+
+    /// Devuelve true si el header con el hash recibido esta en alguna rama lateral
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.headers.contains_key(hash)
+    }
+
+    /// Guarda los outputs que gasto el bloque conectado con el hash recibido, para poder deshacerlo.
+    /// Solo se guardan los de los ultimos bloques, descartando los mas viejos
+    pub fn record_undo(&mut self, block_hash: [u8; 32], spent_outputs: Vec<UtxoTuple>) {
+        if self.max_undo == 0 {
+            return;
+        }
+        if self.undo.len() >= self.max_undo {
+            if let Some(oldest) = self.undo_order.pop_front() {
+                self.undo.remove(&oldest);
+            }
+        }
+        if self.undo.insert(block_hash, spent_outputs).is_none() {
+            self.undo_order.push_back(block_hash);
+        }
+    }
+
+    /// Saca y devuelve los outputs que gasto el bloque con el hash recibido, si se guardaron
+    pub fn take_undo(&mut self, block_hash: &[u8; 32]) -> Option<Vec<UtxoTuple>> {
+        self.undo_order.retain(|hash| hash != block_hash);
+        self.undo.remove(block_hash)
+    }
+
+    /// Si la rama lateral que termina en el header recibido tiene mas trabajo acumulado que la cadena principal
+    /// desde el punto en que se separan, la convierte en la cadena principal: saca de los headers y de sus alturas
+    /// los headers desde ese punto, que pasan a ser una rama lateral, y agrega los de la rama.
+    /// `is_connected` indica si el bloque de un header de la cadena principal ya se conecto al utxo set: esos bloques
+    /// se tienen que poder deshacer. Devuelve None si la rama no tiene mas trabajo o error si no se la puede usar,
+    /// por ejemplo porque reemplazaria un bloque de un checkpoint
+    pub fn reorganize(
+        &mut self,
+        params: &ConsensusParams,
+        tip_hash: [u8; 32],
+        headers: &mut Vec<BlockHeader>,
+        header_heights: &mut HashMap<[u8; 32], usize>,
+        is_connected: impl Fn(&[u8; 32]) -> bool,
+    ) -> Result<Option<Reorganization>, &'static str> {
+        let (fork_height, branch) = self.branch(tip_hash, headers, header_heights)?;
+        if chain_work(&branch) <= chain_work(&headers[fork_height + 1..]) {
+            return Ok(None);
+        }
+        if forks_below_checkpoint(params, fork_height, headers.len()) {
+            return Err(
+                "La rama lateral tiene mas trabajo pero reemplazaria un bloque de un checkpoint",
+            );
+        }
+        if headers[fork_height + 1..]
+            .iter()
+            .map(BlockHeader::hash)
+            .any(|hash| is_connected(&hash) && !self.undo.contains_key(&hash))
+        {
+            return Err("La rama lateral tiene mas trabajo pero no se pueden deshacer los bloques de la cadena principal");
+        }
+        let disconnected = headers.split_off(fork_height + 1);
+        for header in &disconnected {
+            header_heights.remove(&header.hash());
+        }
+        for header in &branch {
+            let hash = header.hash();
+            self.headers.remove(&hash);
+            self.arrival_order.retain(|known| *known != hash);
+            header_heights.insert(hash, headers.len());
+            headers.push(*header);
+        }
+        for header in &disconnected {
+            self.add_header(*header);
+        }
+        Ok(Some(Reorganization {
+            fork_height,
+            disconnected,
+            connected: branch,
+        }))
+    }
+
+    /// Recorre hacia atras la rama lateral que termina en el header recibido hasta llegar a un header de la cadena
+    /// principal. Devuelve la altura de ese header y los headers de la rama, del mas viejo al mas nuevo
+    pub fn branch(
+        &self,
+        tip_hash: [u8; 32],
+        headers: &[BlockHeader],
+        header_heights: &HashMap<[u8; 32], usize>,
+    ) -> Result<(usize, Vec<BlockHeader>), &'static str> {
+        let mut branch = vec![];
+        let mut hash = tip_hash;
+        loop {
+            let header = self
+                .headers
+                .get(&hash)
+                .ok_or("La rama lateral no llega a la cadena principal")?;
+            branch.push(*header);
+            hash = header.previous_block_header_hash;
+            if let Some(fork_height) = main_chain_height(&hash, headers, header_heights) {
+                branch.reverse();
+                return Ok((fork_height, branch));
+            }
+        }
+    }
+}
+
+/// Devuelve la altura del header con el hash recibido si esta en la cadena principal
+pub fn main_chain_height(
+    hash: &[u8; 32],
+    headers: &[BlockHeader],
+    header_heights: &HashMap<[u8; 32], usize>,
+) -> Option<usize> {
+    if headers.first().map(BlockHeader::hash) == Some(*hash) {
+        return Some(0);
+    }
+    header_heights
+        .get(hash)
+        .copied()
+        .filter(|height| *height < headers.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reglas de consenso sin checkpoints
+    const PARAMS: ConsensusParams = ConsensusParams {
+        pow_limit_bits: 0x1d00ffff,
+        allow_min_difficulty_blocks: false,
+        checkpoints: &[],
+        min_chain_work: 0,
+    };
+
+    /// Crea un header con el padre y la dificultad recibidos
+    fn header_with_parent(parent_hash: [u8; 32], n_bits: u32, nonce: u32) -> BlockHeader {
+        BlockHeader::new(1, parent_hash, [0; 32], 0, n_bits, nonce)
+    }
+
+    /// Crea una cadena de headers que empieza en un genesis, con sus alturas
+    fn main_chain(length: usize) -> (Vec<BlockHeader>, HashMap<[u8; 32], usize>) {
+        let mut headers = vec![header_with_parent([0; 32], 0x1d00ffff, 0)];
+        let mut header_heights = HashMap::new();
+        for height in 1..length {
+            let header = header_with_parent(headers[height - 1].hash(), 0x1d00ffff, height as u32);
+            header_heights.insert(header.hash(), height);
+            headers.push(header);
+        }
+        (headers, header_heights)
+    }
+
+    #[test]
+    fn side_chain_with_less_work_does_not_replace_the_main_chain() {
+        // GIVEN: una cadena principal de 4 headers y una rama de un header desde el segundo
+        let (mut headers, mut header_heights) = main_chain(4);
+        let mut side_chains = SideChains::default();
+        let side_header = header_with_parent(headers[1].hash(), 0x1d00ffff, 100);
+        side_chains.add_header(side_header);
+        // WHEN: se intenta reorganizar con la rama
+        let reorganization = side_chains.reorganize(
+            &PARAMS,
+            side_header.hash(),
+            &mut headers,
+            &mut header_heights,
+            |_| false,
+        );
+        // THEN: no cambia la cadena principal
+        assert!(reorganization.unwrap().is_none());
+        assert_eq!(headers.len(), 4);
+        assert!(side_chains.contains(&side_header.hash()));
+    }
+
+    #[test]
+    fn side_chain_with_more_work_becomes_the_main_chain() {
+        // GIVEN: una cadena principal de 3 headers y una rama de dos headers desde el primero
+        let (mut headers, mut header_heights) = main_chain(3);
+        let old_tip = headers[2];
+        let mut side_chains = SideChains::default();
+        let first = header_with_parent(headers[1].hash(), 0x1d00ffff, 100);
+        let second = header_with_parent(first.hash(), 0x1d00ffff, 101);
+        side_chains.add_header(first);
+        side_chains.add_header(second);
+        // WHEN: se reorganiza con la punta de la rama
+        let reorganization = side_chains
+            .reorganize(
+                &PARAMS,
+                second.hash(),
+                &mut headers,
+                &mut header_heights,
+                |_| false,
+            )
+            .unwrap()
+            .unwrap();
+        // THEN: la rama pasa a ser la cadena principal y el header reemplazado queda como rama lateral
+        assert_eq!(reorganization.fork_height, 1);
+        assert_eq!(reorganization.disconnected, vec![old_tip]);
+        assert_eq!(reorganization.connected, vec![first, second]);
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers[3], second);
+        assert_eq!(header_heights.get(&second.hash()), Some(&3));
+        assert!(!header_heights.contains_key(&old_tip.hash()));
+        assert!(side_chains.contains(&old_tip.hash()));
+        assert!(!side_chains.contains(&first.hash()));
+    }
+
+    #[test]
+    fn reorganization_needs_the_undo_data_of_the_connected_blocks() {
+        // GIVEN: una cadena principal cuyo ultimo bloque esta conectado y una rama con mas trabajo
+        let (mut headers, mut header_heights) = main_chain(3);
+        let connected_tip = headers[2].hash();
+        let mut side_chains = SideChains::default();
+        let first = header_with_parent(headers[1].hash(), 0x1d00ffff, 100);
+        let second = header_with_parent(first.hash(), 0x1d00ffff, 101);
+        side_chains.add_header(first);
+        side_chains.add_header(second);
+        // WHEN: se reorganiza sin los datos para deshacer el bloque y luego con ellos
+        let is_connected = |hash: &[u8; 32]| *hash == connected_tip;
+        let without_undo = side_chains.reorganize(
+            &PARAMS,
+            second.hash(),
+            &mut headers,
+            &mut header_heights,
+            is_connected,
+        );
+        side_chains.record_undo(connected_tip, vec![]);
+        let with_undo = side_chains.reorganize(
+            &PARAMS,
+            second.hash(),
+            &mut headers,
+            &mut header_heights,
+            is_connected,
+        );
+        // THEN: solo se reorganiza si se puede deshacer el bloque
+        assert!(without_undo.is_err());
+        assert!(with_undo.unwrap().is_some());
+        assert_eq!(headers[3], second);
+    }
+
+    #[test]
+    fn side_chain_replacing_a_checkpoint_is_rejected() {
+        // GIVEN: una cadena principal de 3 headers con un checkpoint en la altura 2 y una rama con mas trabajo
+        // que se separa en la altura 1
+        let params = ConsensusParams {
+            checkpoints: &[(2, "checkpoint")],
+            ..PARAMS
+        };
+        let (mut headers, mut header_heights) = main_chain(3);
+        let mut side_chains = SideChains::default();
+        let first = header_with_parent(headers[1].hash(), 0x1d00ffff, 100);
+        let second = header_with_parent(first.hash(), 0x1d00ffff, 101);
+        side_chains.add_header(first);
+        side_chains.add_header(second);
+        // WHEN: se intenta reorganizar con la punta de la rama
+        let reorganization = side_chains.reorganize(
+            &params,
+            second.hash(),
+            &mut headers,
+            &mut header_heights,
+            |_| false,
+        );
+        // THEN: no se reorganiza y la cadena principal sigue igual
+        assert!(reorganization.is_err());
+        assert_eq!(headers.len(), 3);
+        assert!(side_chains.contains(&second.hash()));
+    }
+}
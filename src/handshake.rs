@@ -1,25 +1,33 @@
 use crate::config::Config;
 use crate::custom_errors::NodeCustomErrors;
 use crate::logwriter::log_writer::{write_in_log, LogSender};
+use crate::messages::addrv2_message::unmarshalling_addrv2;
+use crate::messages::feefilter_message::get_feefilter_message;
 use crate::messages::message_header::{
-    read_verack_message, write_sendheaders_message, write_verack_message,
+    read_payload, write_pong_message, write_sendaddrv2_message, write_sendheaders_message,
+    write_verack_message, write_wtxidrelay_message, HeaderMessage,
 };
 use crate::messages::version_message::{get_version_message, VersionMessage};
+use crate::peer_state::{PeerState, PeersState};
+use std::collections::HashMap;
 use std::error::Error;
+use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, TcpStream};
 use std::result::Result;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::Duration;
 
+type HandshakeResult = Result<(Arc<RwLock<Vec<TcpStream>>>, PeersState), NodeCustomErrors>;
+
 /// Realiza la conexión a los nodos con múltiples threads
 /// Recibe las direcciones IP de los nodos.
-/// Devuelve un vector de sockets o un error si no se pudo completar.
+/// Devuelve un vector de sockets junto con el estado negociado con cada nodo o un error si no se pudo completar.
 pub fn handshake_with_nodes(
     config: &Arc<Config>,
     log_sender: &LogSender,
     node_ips: Vec<Ipv4Addr>,
-) -> Result<Arc<RwLock<Vec<TcpStream>>>, NodeCustomErrors> {
+) -> HandshakeResult {
     write_in_log(&log_sender.info_log_sender, "INICIO DE HANDSHAKE");
     println!("Realizando handshake con los nodos...");
     let chunk_size = (node_ips.len() as f64 / config.n_threads as f64).ceil() as usize;
@@ -31,6 +39,7 @@ pub fn handshake_with_nodes(
     ));
     let sockets = vec![];
     let sockets_lock = Arc::new(RwLock::new(sockets));
+    let peers_state: PeersState = Arc::new(RwLock::new(HashMap::new()));
     let mut thread_handles = vec![];
     for i in 0..config.n_threads {
         if i >= active_nodes_chunks
@@ -47,8 +56,9 @@ pub fn handshake_with_nodes(
         let config = config.clone();
         let log_sender_clone = log_sender.clone();
         let sockets: Arc<RwLock<Vec<TcpStream>>> = Arc::clone(&sockets_lock);
+        let peers_state = peers_state.clone();
         thread_handles.push(thread::spawn(move || {
-            connect_to_nodes(&config, &log_sender_clone, sockets, &chunk)
+            connect_to_nodes(&config, &log_sender_clone, sockets, &peers_state, &chunk)
         }));
     }
     for handle in thread_handles {
@@ -68,25 +78,32 @@ pub fn handshake_with_nodes(
         &log_sender.info_log_sender,
         "Se completo correctamente el handshake\n",
     );
-    Ok(sockets_lock)
+    Ok((sockets_lock, peers_state))
 }
 
 /// Realiza la conexión con todos los nodos de la lista recibida por parámetro.
-/// Guarda el los mismos en la lista de sockets recibida.
+/// Guarda el los mismos en la lista de sockets recibida y lo negociado con cada uno en el estado de los nodos.
 /// En caso de no poder conectarse, continua intentando con el siguiente.
 fn connect_to_nodes(
     config: &Arc<Config>,
     log_sender: &LogSender,
     sockets: Arc<RwLock<Vec<TcpStream>>>,
+    peers_state: &PeersState,
     nodes: &[Ipv4Addr],
 ) -> Result<(), NodeCustomErrors> {
     for node in nodes {
         match connect_to_node(config, log_sender, node) {
-            Ok(stream) => {
+            Ok((stream, peer_state)) => {
                 write_in_log(
                     &log_sender.info_log_sender,
                     format!("Conectado correctamente a: {:?}", node).as_str(),
                 );
+                if let Ok(peer_addr) = stream.peer_addr() {
+                    peers_state
+                        .write()
+                        .map_err(|err| NodeCustomErrors::LockError(format!("{}", err)))?
+                        .insert(peer_addr, peer_state);
+                }
                 sockets
                     .write()
                     .map_err(|err| NodeCustomErrors::LockError(format!("{}", err)))?
@@ -114,22 +131,164 @@ fn connect_to_nodes(
 }
 
 /// Realiza la conexión con un nodo.
-/// Envía y recibe los mensajes necesarios para establecer la conexión
-/// Devuelve el socket o un error
+/// Envía y recibe los mensajes necesarios para establecer la conexión y negociar las funcionalidades
+/// soportadas por ambos nodos (wtxidrelay, sendaddrv2 y feefilter)
+/// Devuelve el socket junto con el estado del nodo o un error
 fn connect_to_node(
     config: &Arc<Config>,
     log_sender: &LogSender,
     node_ip: &Ipv4Addr,
-) -> Result<TcpStream, Box<dyn Error>> {
+) -> Result<(TcpStream, PeerState), Box<dyn Error>> {
     let socket_addr = SocketAddr::new((*node_ip).into(), config.net_port);
     let mut stream: TcpStream =
         TcpStream::connect_timeout(&socket_addr, Duration::from_secs(config.connect_timeout))?;
     let local_ip_addr = stream.local_addr()?;
     let version_message = get_version_message(config, socket_addr, local_ip_addr)?;
     version_message.write_to(&mut stream)?;
-    VersionMessage::read_from(log_sender, &mut stream)?;
+    let peer_version = VersionMessage::read_from(log_sender, &mut stream)?;
+    let mut peer_state = PeerState::new(
+        config.protocol_version,
+        peer_version.payload.version,
+        peer_version.payload.services,
+    );
+    write_peer_features_messages(&mut stream, &peer_state)?;
     write_verack_message(&mut stream)?;
-    read_verack_message(log_sender, &mut stream)?;
+    read_verack_and_peer_features(log_sender, &mut stream, &mut peer_state)?;
     write_sendheaders_message(&mut stream)?;
-    Ok(stream)
+    write_feefilter_if_supported(config, &mut stream, &peer_state)?;
+    Ok((stream, peer_state))
+}
+
+/// Escribe los mensajes que, segun BIP339 y BIP155, deben enviarse entre el version y el verack:
+/// wtxidrelay (solo si la version negociada lo soporta) y sendaddrv2.
+/// Devuelve error si no se pudo escribir en el stream
+pub fn write_peer_features_messages(
+    stream: &mut dyn Write,
+    peer_state: &PeerState,
+) -> Result<(), Box<dyn Error>> {
+    if peer_state.supports_wtxid_relay() {
+        write_wtxidrelay_message(stream)?;
+    }
+    write_sendaddrv2_message(stream)?;
+    Ok(())
+}
+
+/// Lee mensajes del stream hasta recibir el verack. Los mensajes wtxidrelay y sendaddrv2 que el nodo envie antes
+/// del verack se guardan en su estado, los ping se responden y el resto se ignora.
+/// Devuelve error si no se pudo leer o escribir en el stream
+pub fn read_verack_and_peer_features<T: Read + Write>(
+    log_sender: &LogSender,
+    stream: &mut T,
+    peer_state: &mut PeerState,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let mut header_bytes = [0; 24];
+        stream.read_exact(&mut header_bytes)?;
+        let header = HeaderMessage::from_le_bytes(header_bytes)?;
+        let payload = read_payload(stream, &header)?;
+        let command_name = header.command_name.trim_end_matches('\0');
+        match command_name {
+            "verack" => break,
+            "wtxidrelay" => peer_state.wtxid_relay = peer_state.supports_wtxid_relay(),
+            "sendaddrv2" => peer_state.send_addrv2 = true,
+            "addrv2" => peer_state.add_addresses(unmarshalling_addrv2(&payload)?),
+            "ping" => write_pong_message(stream, &payload)?,
+            _ => {}
+        }
+        write_in_log(
+            &log_sender.message_log_sender,
+            format!(
+                "Recibo Correctamente: {} -- Durante el handshake",
+                command_name
+            )
+            .as_str(),
+        );
+    }
+    write_in_log(
+        &log_sender.message_log_sender,
+        "Recibo Correctamente: verack -- Durante el handshake",
+    );
+    Ok(())
+}
+
+/// Le envia al nodo nuestro fee rate minimo de relay (BIP133) si la version negociada soporta el mensaje feefilter.
+/// Devuelve error si no se pudo escribir en el stream
+pub fn write_feefilter_if_supported(
+    config: &Arc<Config>,
+    stream: &mut dyn Write,
+    peer_state: &PeerState,
+) -> Result<(), Box<dyn Error>> {
+    if peer_state.supports_feefilter() {
+        stream.write_all(&get_feefilter_message(config.min_relay_fee))?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+    use std::sync::mpsc::channel;
+
+    /// Stream de prueba: lee de un buffer fijo y guarda lo que se le escribe
+    struct MockStream {
+        read_buffer: Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.read_buffer.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn features_sent_before_verack_are_stored_in_peer_state() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un nodo que envia wtxidrelay, sendaddrv2 y luego verack
+        let (log_tx, _log_rx) = channel();
+        let log_sender = LogSender {
+            info_log_sender: log_tx.clone(),
+            error_log_sender: log_tx.clone(),
+            message_log_sender: log_tx,
+        };
+        let mut incoming = vec![];
+        write_wtxidrelay_message(&mut incoming)?;
+        write_sendaddrv2_message(&mut incoming)?;
+        write_verack_message(&mut incoming)?;
+        let mut stream = MockStream {
+            read_buffer: Cursor::new(incoming),
+            written: vec![],
+        };
+        let mut peer_state = PeerState::new(70016, 70016, 1);
+        // WHEN: se lee hasta el verack
+        read_verack_and_peer_features(&log_sender, &mut stream, &mut peer_state)?;
+        // THEN: el estado del nodo indica que soporta wtxid relay y addrv2
+        assert!(peer_state.wtxid_relay);
+        assert!(peer_state.send_addrv2);
+        Ok(())
+    }
+
+    #[test]
+    fn wtxidrelay_is_not_sent_to_old_peers() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un nodo con version 70015
+        let peer_state = PeerState::new(70016, 70015, 1);
+        let mut written = vec![];
+        // WHEN: se escriben los mensajes previos al verack
+        write_peer_features_messages(&mut written, &peer_state)?;
+        // THEN: solo se envia sendaddrv2
+        assert_eq!(written.len(), 24);
+        assert_eq!(&written[4..14], b"sendaddrv2");
+        Ok(())
+    }
 }
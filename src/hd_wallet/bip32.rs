@@ -0,0 +1,387 @@
+use std::error::Error;
+use std::io;
+
+use bitcoin_hashes::{sha256d, Hash};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+
+use super::mnemonic::hmac_sha512;
+use crate::address_decoder::hash_160;
+
+/// A partir de este indice las derivaciones son hardened
+pub const HARDENED_INDEX: u32 = 0x8000_0000;
+/// Version de serializacion de las claves privadas extendidas de mainnet (xprv)
+pub const MAINNET_PRIVATE_VERSION: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+/// Version de serializacion de las claves privadas extendidas de testnet (tprv)
+pub const TESTNET_PRIVATE_VERSION: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+/// Version de serializacion de las claves publicas extendidas de mainnet (xpub)
+pub const MAINNET_PUBLIC_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+/// Version de serializacion de las claves publicas extendidas de testnet (tpub)
+pub const TESTNET_PUBLIC_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// Clave privada extendida de BIP32: la clave privada junto con el chain code que permite derivar
+/// claves hijas, y los datos de su posicion en el arbol de derivacion
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPrivateKey {
+    pub private_key: [u8; 32],
+    pub chain_code: [u8; 32],
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+}
+
+impl ExtendedPrivateKey {
+    /// Genera la clave maestra a partir de la seed
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let hash = hmac_sha512(b"Bitcoin seed", seed);
+        let (private_key, chain_code) = split_hash(&hash);
+        SecretKey::from_slice(&private_key)?;
+        Ok(ExtendedPrivateKey {
+            private_key,
+            chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+        })
+    }
+
+    /// Deriva la clave hija con el indice recibido. Si el indice es mayor o igual a HARDENED_INDEX
+    /// la derivacion es hardened. Devuelve error si la clave resultante es invalida
+    pub fn derive_child(&self, index: u32) -> Result<Self, Box<dyn Error>> {
+        let mut data = vec![];
+        if index >= HARDENED_INDEX {
+            data.push(0);
+            data.extend_from_slice(&self.private_key);
+        } else {
+            data.extend_from_slice(&self.public_key()?);
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+        let hash = hmac_sha512(&self.chain_code, &data);
+        let (tweak, chain_code) = split_hash(&hash);
+        let child_key =
+            SecretKey::from_slice(&self.private_key)?.add_tweak(&Scalar::from_be_bytes(tweak)?)?;
+        Ok(ExtendedPrivateKey {
+            private_key: child_key.secret_bytes(),
+            chain_code,
+            depth: self.depth + 1,
+            parent_fingerprint: self.fingerprint()?,
+            child_number: index,
+        })
+    }
+
+    /// Deriva la clave del path recibido, por ejemplo "m/44'/1'/0'/0/3".
+    /// Los indices terminados en ' o h son hardened
+    pub fn derive_path(&self, path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut key = self.clone();
+        for index in parse_path(path)? {
+            key = key.derive_child(index)?;
+        }
+        Ok(key)
+    }
+
+    /// Devuelve la clave publica comprimida (33 bytes)
+    pub fn public_key(&self) -> Result<[u8; 33], Box<dyn Error>> {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&self.private_key)?;
+        Ok(PublicKey::from_secret_key(&secp, &secret_key).serialize())
+    }
+
+    /// Devuelve el fingerprint de la clave: los primeros 4 bytes del hash160 de su clave publica
+    pub fn fingerprint(&self) -> Result<[u8; 4], Box<dyn Error>> {
+        let mut fingerprint = [0; 4];
+        fingerprint.copy_from_slice(&hash_160(&self.public_key()?)[..4]);
+        Ok(fingerprint)
+    }
+
+    /// Devuelve la clave publica extendida correspondiente, que permite derivar las claves publicas
+    /// hijas no hardened sin conocer las privadas
+    pub fn to_public(&self) -> Result<ExtendedPublicKey, Box<dyn Error>> {
+        Ok(ExtendedPublicKey {
+            public_key: self.public_key()?,
+            chain_code: self.chain_code,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+        })
+    }
+
+    /// Serializa la clave extendida en base58check con la version recibida (xprv, tprv, ...)
+    pub fn to_base58(&self, version: [u8; 4]) -> String {
+        let mut key_data = vec![0];
+        key_data.extend_from_slice(&self.private_key);
+        encode_extended_key(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &key_data,
+        )
+    }
+
+    /// Decodifica una clave extendida serializada en base58check con la version recibida.
+    /// Devuelve error si el checksum, la version o la clave privada son invalidos
+    pub fn from_base58(encoded: &str, version: [u8; 4]) -> Result<Self, Box<dyn Error>> {
+        let payload = decode_extended_key(encoded)?;
+        if payload[..4] != version || payload[45] != 0 {
+            return Err(invalid_extended_key(
+                "no es una clave privada de la red esperada",
+            ));
+        }
+        let mut key = ExtendedPrivateKey {
+            private_key: [0; 32],
+            chain_code: [0; 32],
+            depth: payload[4],
+            parent_fingerprint: [0; 4],
+            child_number: u32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]),
+        };
+        key.parent_fingerprint.copy_from_slice(&payload[5..9]);
+        key.chain_code.copy_from_slice(&payload[13..45]);
+        key.private_key.copy_from_slice(&payload[46..]);
+        SecretKey::from_slice(&key.private_key)?;
+        Ok(key)
+    }
+}
+
+/// Clave publica extendida de BIP32: la clave publica junto con el chain code. Solo permite derivar
+/// claves publicas hijas no hardened, por lo que sirve para observar una cuenta sin poder gastar
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPublicKey {
+    pub public_key: [u8; 33],
+    pub chain_code: [u8; 32],
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+}
+
+impl ExtendedPublicKey {
+    /// Deriva la clave publica hija con el indice recibido. Devuelve error si el indice es hardened,
+    /// ya que esas derivaciones necesitan la clave privada
+    pub fn derive_child(&self, index: u32) -> Result<Self, Box<dyn Error>> {
+        if index >= HARDENED_INDEX {
+            return Err(invalid_extended_key(
+                "no se puede derivar un indice hardened desde una clave publica",
+            ));
+        }
+        let mut data = self.public_key.to_vec();
+        data.extend_from_slice(&index.to_be_bytes());
+        let hash = hmac_sha512(&self.chain_code, &data);
+        let (tweak, chain_code) = split_hash(&hash);
+        let secp = Secp256k1::new();
+        let child_key = PublicKey::from_slice(&self.public_key)?
+            .add_exp_tweak(&secp, &Scalar::from_be_bytes(tweak)?)?;
+        Ok(ExtendedPublicKey {
+            public_key: child_key.serialize(),
+            chain_code,
+            depth: self.depth + 1,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+        })
+    }
+
+    /// Devuelve el fingerprint de la clave: los primeros 4 bytes del hash160 de su clave publica
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut fingerprint = [0; 4];
+        fingerprint.copy_from_slice(&hash_160(&self.public_key)[..4]);
+        fingerprint
+    }
+
+    /// Serializa la clave extendida en base58check con la version recibida (xpub, tpub, ...)
+    pub fn to_base58(&self, version: [u8; 4]) -> String {
+        encode_extended_key(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &self.public_key,
+        )
+    }
+
+    /// Decodifica una clave publica extendida serializada en base58check con la version recibida.
+    /// Devuelve error si el checksum, la version o la clave publica son invalidos
+    pub fn from_base58(encoded: &str, version: [u8; 4]) -> Result<Self, Box<dyn Error>> {
+        let payload = decode_extended_key(encoded)?;
+        if payload[..4] != version {
+            return Err(invalid_extended_key(
+                "no es una clave publica de la red esperada",
+            ));
+        }
+        let mut key = ExtendedPublicKey {
+            public_key: [0; 33],
+            chain_code: [0; 32],
+            depth: payload[4],
+            parent_fingerprint: [0; 4],
+            child_number: u32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]),
+        };
+        key.parent_fingerprint.copy_from_slice(&payload[5..9]);
+        key.chain_code.copy_from_slice(&payload[13..45]);
+        key.public_key.copy_from_slice(&payload[45..]);
+        PublicKey::from_slice(&key.public_key)?;
+        Ok(key)
+    }
+}
+
+/// Serializa los campos de una clave extendida en base58check. key_data son los 33 bytes de la clave:
+/// la publica comprimida o la privada precedida por un 0
+fn encode_extended_key(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: &[u8; 32],
+    key_data: &[u8],
+) -> String {
+    let mut bytes = version.to_vec();
+    bytes.push(depth);
+    bytes.extend_from_slice(&parent_fingerprint);
+    bytes.extend_from_slice(&child_number.to_be_bytes());
+    bytes.extend_from_slice(chain_code);
+    bytes.extend_from_slice(key_data);
+    let checksum = sha256d::Hash::hash(&bytes).to_byte_array();
+    bytes.extend_from_slice(&checksum[..4]);
+    bs58::encode(bytes).into_string()
+}
+
+/// Decodifica una clave extendida en base58check y devuelve los 78 bytes serializados.
+/// Devuelve error si el largo o el checksum son incorrectos
+fn decode_extended_key(encoded: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = bs58::decode(encoded.trim()).into_vec()?;
+    if bytes.len() != EXTENDED_KEY_LEN + 4 {
+        return Err(invalid_extended_key("el largo es incorrecto"));
+    }
+    let checksum = bytes.split_off(EXTENDED_KEY_LEN);
+    if sha256d::Hash::hash(&bytes).to_byte_array()[..4] != *checksum {
+        return Err(invalid_extended_key("el checksum es incorrecto"));
+    }
+    Ok(bytes)
+}
+
+/// Largo de una clave extendida serializada sin el checksum
+const EXTENDED_KEY_LEN: usize = 78;
+
+/// Recibe un path de derivacion y devuelve sus indices. Devuelve error si el path es invalido
+pub fn parse_path(path: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    let mut parts = path.trim().split('/');
+    if parts.next() != Some("m") {
+        return Err(invalid_path(path));
+    }
+    let mut indexes = vec![];
+    for part in parts {
+        let (number, hardened) = match part.strip_suffix('\'').or(part.strip_suffix('h')) {
+            Some(number) => (number, true),
+            None => (part, false),
+        };
+        let index: u32 = number.parse().map_err(|_| invalid_path(path))?;
+        if index >= HARDENED_INDEX {
+            return Err(invalid_path(path));
+        }
+        indexes.push(if hardened {
+            index + HARDENED_INDEX
+        } else {
+            index
+        });
+    }
+    Ok(indexes)
+}
+
+fn invalid_path(path: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("El path de derivacion {} es invalido", path),
+    ))
+}
+
+fn invalid_extended_key(reason: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("La clave extendida es invalida: {}", reason),
+    ))
+}
+
+/// Separa el resultado del HMAC-SHA512 en sus dos mitades
+fn split_hash(hash: &[u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    left.copy_from_slice(&hash[..32]);
+    right.copy_from_slice(&hash[32..]);
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bip32_test_vector_1_derives_expected_keys() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la seed del primer vector de prueba de BIP32
+        let seed: Vec<u8> = (0..16).collect();
+        // WHEN: se genera la clave maestra y se deriva m/0'/1
+        let master = ExtendedPrivateKey::from_seed(&seed)?;
+        let child = master.derive_path("m/0'/1")?;
+        // THEN: las claves serializadas son las del BIP
+        assert_eq!(
+            master.to_base58(MAINNET_PRIVATE_VERSION),
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+        );
+        assert_eq!(
+            child.to_base58(MAINNET_PRIVATE_VERSION),
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs"
+        );
+        // y se vuelven a decodificar iguales
+        let encoded = child.to_base58(MAINNET_PRIVATE_VERSION);
+        assert_eq!(
+            ExtendedPrivateKey::from_base58(&encoded, MAINNET_PRIVATE_VERSION)?,
+            child
+        );
+        assert!(ExtendedPrivateKey::from_base58(&encoded, TESTNET_PRIVATE_VERSION).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn public_derivation_matches_the_private_one() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la clave maestra del primer vector de prueba de BIP32 y su clave publica extendida
+        let seed: Vec<u8> = (0..16).collect();
+        let master = ExtendedPrivateKey::from_seed(&seed)?;
+        let hardened_child = master.derive_path("m/0'")?;
+        let public_child = hardened_child.to_public()?;
+        // WHEN: se deriva la clave publica hija no hardened
+        let derived = public_child.derive_child(1)?;
+        // THEN: las claves serializadas son las del BIP y coinciden con la derivacion privada
+        assert_eq!(
+            master.to_public()?.to_base58(MAINNET_PUBLIC_VERSION),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+        assert_eq!(
+            derived.to_base58(MAINNET_PUBLIC_VERSION),
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"
+        );
+        assert_eq!(derived, hardened_child.derive_child(1)?.to_public()?);
+        let encoded = derived.to_base58(TESTNET_PUBLIC_VERSION);
+        assert_eq!(
+            ExtendedPublicKey::from_base58(&encoded, TESTNET_PUBLIC_VERSION)?,
+            derived
+        );
+        assert!(public_child.derive_child(HARDENED_INDEX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_derivation_paths_are_rejected() {
+        // GIVEN: paths sin la m inicial o con indices invalidos
+        // WHEN: se parsean
+        // THEN: devuelven error
+        assert!(parse_path("44'/1'/0'").is_err());
+        assert!(parse_path("m/abc").is_err());
+        assert!(parse_path("m/2147483648").is_err());
+        assert_eq!(
+            parse_path("m/84h/1'/0'/0/5").ok(),
+            Some(vec![
+                84 + HARDENED_INDEX,
+                1 + HARDENED_INDEX,
+                HARDENED_INDEX,
+                0,
+                5
+            ])
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+use super::{
+    bip32::{
+        ExtendedPrivateKey, ExtendedPublicKey, HARDENED_INDEX, TESTNET_PRIVATE_VERSION,
+        TESTNET_PUBLIC_VERSION,
+    },
+    mnemonic::{mnemonic_to_seed, validate_mnemonic},
+};
+use crate::{
+    account::erase_bytes,
+    address::{Address, Network},
+    address_decoder::hash_160,
+    bech32::{encode_segwit_address, TESTNET_HRP},
+    descriptor::{Descriptor, DescriptorKey, ExtendedKey},
+    transactions::script::p2wpkh_script::generate_pubkey_script_from_hash,
+};
+
+/// Cantidad de direcciones seguidas sin usar que se derivan por delante de la ultima usada
+pub const GAP_LIMIT: usize = 20;
+/// Coin type de BIP44 para testnet, que es la red en la que trabaja la wallet
+const TESTNET_COIN_TYPE: u32 = 1;
+/// Cadenas de derivacion de las direcciones de recepcion y de las de cambio
+pub const RECEIVE_CHAIN: u32 = 0;
+const CHANGE_CHAIN: u32 = 1;
+
+/// Esquema de derivacion de las cuentas HD. Define el path y el tipo de direccion que se genera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DerivationScheme {
+    /// m/44'/coin'/account' con direcciones P2PKH
+    Bip44,
+    /// m/84'/coin'/account' con direcciones P2WPKH (bech32)
+    Bip84,
+}
+
+impl DerivationScheme {
+    /// Devuelve el purpose del path de derivacion
+    pub fn purpose(&self) -> u32 {
+        match self {
+            DerivationScheme::Bip44 => 44,
+            DerivationScheme::Bip84 => 84,
+        }
+    }
+}
+
+/// Clave extendida de la cuenta. Las cuentas watch-only solo tienen la clave publica extendida
+#[derive(Debug, Clone)]
+enum AccountKey {
+    Private(ExtendedPrivateKey),
+    Public(ExtendedPublicKey),
+}
+
+/// Clave derivada de una cuenta HD, con su direccion y el pubkey script que la paga.
+/// La clave privada es None en las cuentas watch-only
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedKey {
+    pub path: String,
+    pub private_key: Option<[u8; 32]>,
+    pub public_key: [u8; 33],
+    pub address: String,
+    pub pubkey_script: Vec<u8>,
+}
+
+/// Llavero de una cuenta HD (BIP32/BIP44/BIP84). Guarda la clave extendida de la cuenta y las
+/// claves derivadas de la cadena de recepcion y de la de cambio. Siempre mantiene GAP_LIMIT
+/// claves derivadas por delante de la ultima usada en cada cadena
+#[derive(Debug, Clone)]
+pub struct HdKeychain {
+    pub scheme: DerivationScheme,
+    pub account_index: u32,
+    account_key: AccountKey,
+    receive_keys: Vec<DerivedKey>,
+    change_keys: Vec<DerivedKey>,
+    next_receive_index: usize,
+    next_change_index: usize,
+}
+
+impl HdKeychain {
+    /// Crea el llavero de la cuenta a partir del mnemonic de BIP39 y la passphrase (puede ser vacia).
+    /// Devuelve error si el mnemonic es invalido
+    pub fn from_mnemonic(
+        mnemonic: &str,
+        passphrase: &str,
+        scheme: DerivationScheme,
+        account_index: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        validate_mnemonic(mnemonic)?;
+        let seed = mnemonic_to_seed(mnemonic, passphrase);
+        Self::from_seed(&seed, scheme, account_index)
+    }
+
+    /// Crea el llavero de la cuenta a partir de la seed de BIP32
+    pub fn from_seed(
+        seed: &[u8],
+        scheme: DerivationScheme,
+        account_index: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let master_key = ExtendedPrivateKey::from_seed(seed)?;
+        let account_key = master_key
+            .derive_child(scheme.purpose() + HARDENED_INDEX)?
+            .derive_child(TESTNET_COIN_TYPE + HARDENED_INDEX)?
+            .derive_child(account_index + HARDENED_INDEX)?;
+        let mut keychain = HdKeychain {
+            scheme,
+            account_index,
+            account_key: AccountKey::Private(account_key),
+            receive_keys: vec![],
+            change_keys: vec![],
+            next_receive_index: 0,
+            next_change_index: 0,
+        };
+        keychain.fill_gap()?;
+        Ok(keychain)
+    }
+
+    /// Crea el llavero de la cuenta a partir de su clave privada extendida serializada (tprv), como la
+    /// devuelve account_tprv. Devuelve error si la clave es invalida
+    pub fn from_account_tprv(
+        tprv: &str,
+        scheme: DerivationScheme,
+        account_index: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut keychain = HdKeychain {
+            scheme,
+            account_index,
+            account_key: AccountKey::Private(ExtendedPrivateKey::from_base58(
+                tprv,
+                TESTNET_PRIVATE_VERSION,
+            )?),
+            receive_keys: vec![],
+            change_keys: vec![],
+            next_receive_index: 0,
+            next_change_index: 0,
+        };
+        keychain.fill_gap()?;
+        Ok(keychain)
+    }
+
+    /// Crea el llavero watch-only de la cuenta a partir de su clave publica extendida serializada
+    /// (tpub). Deriva las mismas direcciones que la cuenta pero no puede firmar. El indice de la cuenta
+    /// se toma del child number de la clave. Devuelve error si la clave es invalida
+    pub fn from_account_tpub(tpub: &str, scheme: DerivationScheme) -> Result<Self, Box<dyn Error>> {
+        let account_key = ExtendedPublicKey::from_base58(tpub, TESTNET_PUBLIC_VERSION)?;
+        let mut keychain = HdKeychain {
+            scheme,
+            account_index: account_key.child_number & !HARDENED_INDEX,
+            account_key: AccountKey::Public(account_key),
+            receive_keys: vec![],
+            change_keys: vec![],
+            next_receive_index: 0,
+            next_change_index: 0,
+        };
+        keychain.fill_gap()?;
+        Ok(keychain)
+    }
+
+    /// Devuelve true si el llavero solo tiene la clave publica extendida de la cuenta
+    pub fn is_watch_only(&self) -> bool {
+        matches!(self.account_key, AccountKey::Public(_))
+    }
+
+    /// Borra de memoria las claves privadas del llavero pisando sus bytes, por ejemplo al bloquear la wallet,
+    /// y deja solo la clave publica extendida de la cuenta
+    pub fn erase_private_keys(&mut self) -> Result<(), Box<dyn Error>> {
+        if let AccountKey::Private(key) = &mut self.account_key {
+            let public_key = key.to_public()?;
+            erase_bytes(&mut key.private_key);
+            erase_bytes(&mut key.chain_code);
+            self.account_key = AccountKey::Public(public_key);
+        }
+        for key in self
+            .receive_keys
+            .iter_mut()
+            .chain(self.change_keys.iter_mut())
+        {
+            if let Some(private_key) = key.private_key.as_mut() {
+                erase_bytes(private_key);
+            }
+            key.private_key = None;
+        }
+        Ok(())
+    }
+
+    /// Vuelve a cargar las claves privadas del llavero recibido, que tiene que ser de la misma cuenta, y deriva
+    /// las de todas las claves ya derivadas. Devuelve error si el llavero es de otra cuenta
+    pub fn restore_private_keys(&mut self, keychain: &HdKeychain) -> Result<(), Box<dyn Error>> {
+        if keychain.account_tpub()? != self.account_tpub()? {
+            return Err(Box::new(std::io::Error::other(
+                "Las claves privadas son de otra cuenta",
+            )));
+        }
+        self.account_key = keychain.account_key.clone();
+        self.receive_keys = (0..self.receive_keys.len() as u32)
+            .map(|index| self.derive_key(RECEIVE_CHAIN, index))
+            .collect::<Result<_, _>>()?;
+        self.change_keys = (0..self.change_keys.len() as u32)
+            .map(|index| self.derive_key(CHANGE_CHAIN, index))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Devuelve los indices de las proximas claves de recepcion y de cambio a entregar
+    pub fn next_indexes(&self) -> (usize, usize) {
+        (self.next_receive_index, self.next_change_index)
+    }
+
+    /// Restaura los indices de las proximas claves de recepcion y de cambio a entregar, para no
+    /// volver a entregar direcciones ya entregadas, y deriva las claves necesarias para el gap limit
+    pub fn restore_next_indexes(
+        &mut self,
+        next_receive_index: usize,
+        next_change_index: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        self.next_receive_index = self.next_receive_index.max(next_receive_index);
+        self.next_change_index = self.next_change_index.max(next_change_index);
+        self.fill_gap()
+    }
+
+    /// Devuelve el path de derivacion de la cuenta
+    pub fn account_path(&self) -> String {
+        format!(
+            "m/{}'/{}'/{}'",
+            self.scheme.purpose(),
+            TESTNET_COIN_TYPE,
+            self.account_index
+        )
+    }
+
+    /// Devuelve la clave privada extendida de la cuenta serializada (tprv) o None si es watch-only
+    pub fn account_tprv(&self) -> Option<String> {
+        match &self.account_key {
+            AccountKey::Private(key) => Some(key.to_base58(TESTNET_PRIVATE_VERSION)),
+            AccountKey::Public(_) => None,
+        }
+    }
+
+    /// Devuelve la clave publica extendida de la cuenta serializada (tpub)
+    pub fn account_tpub(&self) -> Result<String, Box<dyn Error>> {
+        let public_key = match &self.account_key {
+            AccountKey::Private(key) => key.to_public()?,
+            AccountKey::Public(key) => key.clone(),
+        };
+        Ok(public_key.to_base58(TESTNET_PUBLIC_VERSION))
+    }
+
+    /// Devuelve los descriptors de la cadena de recepcion y de la de cambio de la cuenta. Incluyen la clave
+    /// privada extendida si se pide y el llavero la tiene, sino la publica
+    pub fn descriptors(&self, include_private: bool) -> Result<[Descriptor; 2], Box<dyn Error>> {
+        let account_key = match &self.account_key {
+            AccountKey::Private(key) if include_private => ExtendedKey::Private(key.clone()),
+            AccountKey::Private(key) => ExtendedKey::Public(key.to_public()?),
+            AccountKey::Public(key) => ExtendedKey::Public(key.clone()),
+        };
+        Ok([RECEIVE_CHAIN, CHANGE_CHAIN].map(|chain| {
+            let key = DescriptorKey::ranged(account_key.clone(), vec![chain]);
+            match self.scheme {
+                DerivationScheme::Bip44 => Descriptor::Pkh(key),
+                DerivationScheme::Bip84 => Descriptor::Wpkh(key),
+            }
+        }))
+    }
+
+    /// Devuelve la primera direccion de recepcion, que identifica a la cuenta
+    pub fn first_receive_key(&self) -> &DerivedKey {
+        &self.receive_keys[0]
+    }
+
+    /// Devuelve la proxima direccion de recepcion sin usar y la marca como entregada,
+    /// derivando claves nuevas para mantener el gap limit
+    pub fn next_receive_address(&mut self) -> Result<String, Box<dyn Error>> {
+        let index = self.reserve_receive_index()?;
+        Ok(self.receive_keys[index as usize].address.clone())
+    }
+
+    /// Devuelve la proxima clave de cambio sin usar y la marca como entregada,
+    /// derivando claves nuevas para mantener el gap limit
+    pub fn next_change_key(&mut self) -> Result<DerivedKey, Box<dyn Error>> {
+        let index = self.reserve_change_index()?;
+        Ok(self.change_keys[index as usize].clone())
+    }
+
+    /// Marca como entregada la proxima clave de recepcion sin usar y devuelve su indice,
+    /// derivando claves nuevas para mantener el gap limit
+    pub fn reserve_receive_index(&mut self) -> Result<u32, Box<dyn Error>> {
+        let index = self.next_receive_index;
+        self.next_receive_index += 1;
+        self.fill_gap()?;
+        Ok(index as u32)
+    }
+
+    /// Marca como entregada la proxima clave de cambio sin usar y devuelve su indice,
+    /// derivando claves nuevas para mantener el gap limit
+    pub fn reserve_change_index(&mut self) -> Result<u32, Box<dyn Error>> {
+        let index = self.next_change_index;
+        self.next_change_index += 1;
+        self.fill_gap()?;
+        Ok(index as u32)
+    }
+
+    /// Devuelve la cantidad de claves derivadas de la cadena de recepcion y de la de cambio
+    pub fn derived_counts(&self) -> (u32, u32) {
+        (
+            self.receive_keys.len() as u32,
+            self.change_keys.len() as u32,
+        )
+    }
+
+    /// Devuelve todas las claves derivadas hasta el momento, de ambas cadenas
+    pub fn keys(&self) -> impl Iterator<Item = &DerivedKey> {
+        self.receive_keys.iter().chain(self.change_keys.iter())
+    }
+
+    /// Devuelve los pubkey scripts de todas las claves derivadas
+    pub fn pubkey_scripts(&self) -> HashSet<Vec<u8>> {
+        self.keys().map(|key| key.pubkey_script.clone()).collect()
+    }
+
+    /// Busca la clave derivada que corresponde al pubkey script recibido
+    pub fn key_for_script(&self, pubkey_script: &[u8]) -> Option<&DerivedKey> {
+        self.keys().find(|key| key.pubkey_script == pubkey_script)
+    }
+
+    /// Marca como usadas las claves cuyos pubkey scripts se recibieron, avanzando los proximos
+    /// indices a entregar, y deriva claves nuevas para mantener el gap limit.
+    /// Devuelve true si se derivaron claves nuevas, en cuyo caso hay que volver a buscar fondos
+    pub fn mark_used(&mut self, used_scripts: &HashSet<Vec<u8>>) -> Result<bool, Box<dyn Error>> {
+        if let Some(last_used) = last_used_index(&self.receive_keys, used_scripts) {
+            self.next_receive_index = self.next_receive_index.max(last_used + 1);
+        }
+        if let Some(last_used) = last_used_index(&self.change_keys, used_scripts) {
+            self.next_change_index = self.next_change_index.max(last_used + 1);
+        }
+        let derived_keys = self.receive_keys.len() + self.change_keys.len();
+        self.fill_gap()?;
+        Ok(self.receive_keys.len() + self.change_keys.len() > derived_keys)
+    }
+
+    /// Deriva claves en ambas cadenas hasta tener GAP_LIMIT claves sin entregar en cada una
+    fn fill_gap(&mut self) -> Result<(), Box<dyn Error>> {
+        while self.receive_keys.len() < self.next_receive_index + GAP_LIMIT {
+            let key = self.derive_key(RECEIVE_CHAIN, self.receive_keys.len() as u32)?;
+            self.receive_keys.push(key);
+        }
+        while self.change_keys.len() < self.next_change_index + GAP_LIMIT {
+            let key = self.derive_key(CHANGE_CHAIN, self.change_keys.len() as u32)?;
+            self.change_keys.push(key);
+        }
+        Ok(())
+    }
+
+    /// Deriva la clave con el indice recibido de la cadena (recepcion o cambio) recibida
+    fn derive_key(&self, chain: u32, index: u32) -> Result<DerivedKey, Box<dyn Error>> {
+        let (private_key, public_key) = match &self.account_key {
+            AccountKey::Private(account_key) => {
+                let key = account_key.derive_child(chain)?.derive_child(index)?;
+                (Some(key.private_key), key.public_key()?)
+            }
+            AccountKey::Public(account_key) => {
+                let key = account_key.derive_child(chain)?.derive_child(index)?;
+                (None, key.public_key)
+            }
+        };
+        let pubkey_hash = hash_160(&public_key);
+        let (address, pubkey_script) = match self.scheme {
+            DerivationScheme::Bip44 => {
+                let address = Address::P2pkh(pubkey_hash);
+                (address.encode(Network::Testnet)?, address.script_pubkey())
+            }
+            DerivationScheme::Bip84 => (
+                encode_segwit_address(TESTNET_HRP, 0, &pubkey_hash)?,
+                generate_pubkey_script_from_hash(&pubkey_hash),
+            ),
+        };
+        Ok(DerivedKey {
+            path: format!("{}/{}/{}", self.account_path(), chain, index),
+            private_key,
+            public_key,
+            address,
+            pubkey_script,
+        })
+    }
+}
+
+/// Devuelve el indice de la ultima clave de la cadena cuyo pubkey script fue usado
+fn last_used_index(keys: &[DerivedKey], used_scripts: &HashSet<Vec<u8>>) -> Option<usize> {
+    keys.iter()
+        .rposition(|key| used_scripts.contains(&key.pubkey_script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn bip84_keychain_derives_the_expected_testnet_addresses() -> Result<(), Box<dyn Error>> {
+        // GIVEN: el mnemonic de prueba de BIP84
+        // WHEN: se crea el llavero de la cuenta 0 en testnet
+        let mut keychain = HdKeychain::from_mnemonic(MNEMONIC, "", DerivationScheme::Bip84, 0)?;
+        // THEN: la primera direccion de recepcion es la esperada y se derivan GAP_LIMIT claves por cadena
+        assert_eq!(
+            keychain.next_receive_address()?,
+            "tb1q6rz28mcfaxtmd6v789l9rrlrusdprr9pqcpvkl"
+        );
+        assert_eq!(keychain.first_receive_key().path, "m/84'/1'/0'/0/0");
+        assert_eq!(keychain.keys().count(), 2 * GAP_LIMIT + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn used_keys_extend_the_gap_limit() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un llavero BIP44 y el pubkey script de su ultima clave de recepcion derivada
+        let mut keychain = HdKeychain::from_mnemonic(MNEMONIC, "", DerivationScheme::Bip44, 0)?;
+        let last_receive_script = keychain.receive_keys[GAP_LIMIT - 1].pubkey_script.clone();
+        let used_scripts = HashSet::from([last_receive_script.clone()]);
+        // WHEN: se marca como usada
+        let derived_new_keys = keychain.mark_used(&used_scripts)?;
+        // THEN: se derivan GAP_LIMIT claves mas por delante y la proxima direccion es la siguiente
+        assert!(derived_new_keys);
+        assert_eq!(keychain.receive_keys.len(), 2 * GAP_LIMIT);
+        assert!(keychain.key_for_script(&last_receive_script).is_some());
+        assert_eq!(
+            keychain.next_receive_address()?,
+            keychain.receive_keys[GAP_LIMIT].address
+        );
+        assert!(!keychain.mark_used(&used_scripts)?);
+        Ok(())
+    }
+
+    #[test]
+    fn watch_only_keychain_derives_the_same_addresses() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un llavero BIP84 y la clave publica extendida de su cuenta
+        let keychain = HdKeychain::from_mnemonic(MNEMONIC, "", DerivationScheme::Bip84, 0)?;
+        let tpub = keychain.account_tpub()?;
+        // WHEN: se crea el llavero watch-only a partir de la clave publica extendida
+        let watch_only = HdKeychain::from_account_tpub(&tpub, DerivationScheme::Bip84)?;
+        // THEN: deriva las mismas direcciones pero sin claves privadas
+        assert!(watch_only.is_watch_only());
+        assert_eq!(watch_only.account_path(), "m/84'/1'/0'");
+        assert_eq!(watch_only.account_tprv(), None);
+        assert_eq!(watch_only.pubkey_scripts(), keychain.pubkey_scripts());
+        assert!(watch_only.keys().all(|key| key.private_key.is_none()));
+        let [receive, change] = watch_only.descriptors(false)?;
+        assert_eq!(receive, keychain.descriptors(false)?[0]);
+        assert_eq!(receive.address(0)?, keychain.first_receive_key().address);
+        assert_eq!(
+            change.script_pubkey(0)?,
+            keychain.change_keys[0].pubkey_script
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::io;
+
+use bitcoin_hashes::{hmac, sha256, sha512, Hash, HashEngine};
+use rand::{rngs::OsRng, RngCore};
+
+/// Lista de palabras en ingles de BIP39
+const ENGLISH_WORDLIST: &str = include_str!("english_wordlist.txt");
+/// Cantidad de iteraciones de PBKDF2 para obtener la seed a partir del mnemonic
+const PBKDF2_ROUNDS: u32 = 2048;
+/// Cantidades de palabras que puede tener un mnemonic
+const VALID_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// Genera un mnemonic nuevo con la cantidad de palabras recibida (12, 15, 18, 21 o 24),
+/// usando entropia del generador aleatorio del sistema operativo
+pub fn generate_mnemonic(word_count: usize) -> Result<String, Box<dyn Error>> {
+    validate_word_count(word_count)?;
+    let mut entropy = vec![0u8; word_count * 4 / 3];
+    OsRng.fill_bytes(&mut entropy);
+    entropy_to_mnemonic(&entropy)
+}
+
+/// Convierte la entropia recibida (de 16 a 32 bytes, multiplo de 4) en el mnemonic correspondiente
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> Result<String, Box<dyn Error>> {
+    if entropy.len() < 16 || entropy.len() > 32 || !entropy.len().is_multiple_of(4) {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "La entropia del mnemonic debe tener entre 16 y 32 bytes y ser multiplo de 4",
+        )));
+    }
+    let checksum = sha256::Hash::hash(entropy).to_byte_array();
+    let mut bits = bytes_to_bits(entropy);
+    bits.extend(bytes_to_bits(&checksum).into_iter().take(entropy.len() / 4));
+    let words = wordlist();
+    let mnemonic: Vec<&str> = bits
+        .chunks(11)
+        .map(|chunk| words[bits_to_index(chunk)])
+        .collect();
+    Ok(mnemonic.join(" "))
+}
+
+/// Verifica que todas las palabras del mnemonic esten en la lista de BIP39 y que el checksum sea correcto
+pub fn validate_mnemonic(mnemonic: &str) -> Result<(), Box<dyn Error>> {
+    let words = wordlist();
+    let mnemonic_words: Vec<String> = normalized_words(mnemonic);
+    validate_word_count(mnemonic_words.len())?;
+    let mut bits = vec![];
+    for word in &mnemonic_words {
+        let index = words
+            .binary_search(&word.as_str())
+            .map_err(|_| invalid_mnemonic(&format!("La palabra {} no es valida", word)))?;
+        bits.extend((0..11).rev().map(|shift| (index >> shift) & 1 == 1));
+    }
+    let checksum_len = bits.len() / 33;
+    let entropy = bits_to_bytes(&bits[..bits.len() - checksum_len]);
+    if entropy_to_mnemonic(&entropy)? != mnemonic_words.join(" ") {
+        return Err(invalid_mnemonic("El checksum del mnemonic es invalido"));
+    }
+    Ok(())
+}
+
+/// Obtiene la seed de 64 bytes a partir del mnemonic y la passphrase (que puede ser vacia).
+/// Aplica PBKDF2-HMAC-SHA512 con 2048 iteraciones y "mnemonic" + passphrase como salt
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let password = normalized_words(mnemonic).join(" ");
+    let salt = format!("mnemonic{}", passphrase);
+    pbkdf2_hmac_sha512(password.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS)
+}
+
+/// Calcula PBKDF2 con HMAC-SHA512 para un unico bloque de 64 bytes
+fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], rounds: u32) -> [u8; 64] {
+    let mut first_message = salt.to_vec();
+    first_message.extend_from_slice(&1u32.to_be_bytes());
+    let mut block = hmac_sha512(password, &first_message);
+    let mut result = block;
+    for _ in 1..rounds {
+        block = hmac_sha512(password, &block);
+        for (byte, block_byte) in result.iter_mut().zip(block.iter()) {
+            *byte ^= block_byte;
+        }
+    }
+    result
+}
+
+/// Calcula el HMAC-SHA512 de los datos con la clave recibida
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(key);
+    engine.input(data);
+    hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array()
+}
+
+/// Devuelve la lista de 2048 palabras de BIP39, ordenada alfabeticamente
+fn wordlist() -> Vec<&'static str> {
+    ENGLISH_WORDLIST.lines().collect()
+}
+
+/// Separa el mnemonic en palabras en minuscula, ignorando los espacios de mas
+fn normalized_words(mnemonic: &str) -> Vec<String> {
+    mnemonic
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Devuelve error si la cantidad de palabras no es una de las permitidas por BIP39
+fn validate_word_count(word_count: usize) -> Result<(), Box<dyn Error>> {
+    if !VALID_WORD_COUNTS.contains(&word_count) {
+        return Err(invalid_mnemonic(
+            "El mnemonic debe tener 12, 15, 18, 21 o 24 palabras",
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_mnemonic(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+fn bytes_to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1 == 1))
+        .collect()
+}
+
+fn bits_to_bytes(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |byte, bit| (byte << 1) | *bit as u8))
+        .collect()
+}
+
+fn bits_to_index(bits: &[bool]) -> usize {
+    bits.iter()
+        .fold(0usize, |index, bit| (index << 1) | *bit as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::bytes_to_hex_string;
+
+    #[test]
+    fn bip39_test_vector_generates_expected_mnemonic_and_seed() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la entropia en cero del primer vector de prueba de BIP39
+        let entropy = [0u8; 16];
+        // WHEN: se genera el mnemonic y la seed con la passphrase "TREZOR"
+        let mnemonic = entropy_to_mnemonic(&entropy)?;
+        let seed = mnemonic_to_seed(&mnemonic, "TREZOR");
+        // THEN: se obtienen los valores del BIP
+        assert_eq!(
+            mnemonic,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_eq!(
+            bytes_to_hex_string(&seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mnemonic_with_wrong_checksum_is_rejected() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un mnemonic generado y el del vector de BIP39 con la ultima palabra cambiada
+        let mnemonic = generate_mnemonic(24)?;
+        let wrong_checksum = "abandon ".repeat(12);
+        // WHEN: se validan
+        // THEN: solo el generado es valido
+        assert!(validate_mnemonic(&mnemonic).is_ok());
+        assert!(validate_mnemonic(&wrong_checksum).is_err());
+        assert!(validate_mnemonic("abandon abandon").is_err());
+        Ok(())
+    }
+}
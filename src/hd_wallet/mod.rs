@@ -0,0 +1,3 @@
+pub mod bip32;
+pub mod hd_keychain;
+pub mod mnemonic;
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+
+use crate::{
+    transactions::{transaction::Transaction, tx_out::TxOut},
+    utxo_tuple::UtxoTuple,
+};
+
+/// Bloque de la cadena en el que se confirmo una transaccion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockPosition {
+    pub hash: [u8; 32],
+    pub height: u32,
+}
+
+/// Tipo de una transaccion segun como mueve los fondos de la cuenta
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionKind {
+    /// La cuenta no gasta nada, solo recibe
+    Received,
+    /// La cuenta gasta sus monedas y paga a otros
+    Sent,
+    /// La cuenta gasta sus monedas y todos los outputs son suyos, solo pierde la comision
+    SelfTransfer,
+}
+
+impl TransactionKind {
+    /// Devuelve el nombre con el que se muestra el tipo de transaccion
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionKind::Received => "Received",
+            TransactionKind::Sent => "Sent",
+            TransactionKind::SelfTransfer => "Self-transfer",
+        }
+    }
+}
+
+/// Transaccion que afecta a una cuenta: lo que recibe la cuenta en sus outputs, lo que gasta de sus monedas
+/// en los inputs, la comision si se conocen todos los outputs que gasta y el bloque si ya se confirmo
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub transaction: Transaction,
+    pub block: Option<BlockPosition>,
+    pub received: i64,
+    pub spent: i64,
+    pub fee: Option<i64>,
+}
+
+impl LedgerEntry {
+    /// Devuelve el hash de la transaccion
+    pub fn txid(&self) -> [u8; 32] {
+        self.transaction.hash()
+    }
+
+    /// Devuelve cuanto cambia el balance de la cuenta con la transaccion, negativo si la cuenta gasta
+    pub fn net_amount(&self) -> i64 {
+        self.received - self.spent
+    }
+
+    /// Devuelve el tipo de la transaccion para la cuenta
+    pub fn kind(&self) -> TransactionKind {
+        if self.spent == 0 {
+            TransactionKind::Received
+        } else if self.received == self.transaction.amount() {
+            TransactionKind::SelfTransfer
+        } else {
+            TransactionKind::Sent
+        }
+    }
+
+    /// Devuelve las confirmaciones de la transaccion con la altura actual de la cadena, 0 si esta pendiente
+    pub fn confirmations(&self, tip_height: u32) -> u32 {
+        self.block
+            .map_or(0, |block| tip_height.saturating_sub(block.height) + 1)
+    }
+}
+
+/// Entrada del historial con sus confirmaciones segun la altura de la cadena al pedirlo
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub entry: LedgerEntry,
+    pub confirmations: u32,
+}
+
+/// Historial de las transacciones recibidas, enviadas y a si misma de una cuenta.
+/// Se arma con los bloques y las transacciones sin confirmar que afectan a la cuenta
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    /// Agrega la entrada al ledger. Si la transaccion ya estaba solo actualiza el bloque en el que se
+    /// confirmo, porque lo que recibe y gasta la cuenta se calculo cuando sus monedas todavia no estaban
+    /// gastadas. Devuelve true si la transaccion no estaba
+    pub fn record(&mut self, entry: LedgerEntry) -> bool {
+        let txid = entry.txid();
+        match self.entries.iter_mut().find(|known| known.txid() == txid) {
+            Some(known) => {
+                if entry.block.is_some() {
+                    known.block = entry.block;
+                }
+                false
+            }
+            None => {
+                self.entries.push(entry);
+                true
+            }
+        }
+    }
+
+    /// Saca la transaccion del ledger, por ejemplo si fue reemplazada por otra que paga mas comision
+    pub fn remove(&mut self, txid: [u8; 32]) {
+        self.entries.retain(|entry| entry.txid() != txid);
+    }
+
+    /// Devuelve true si la transaccion esta en el ledger
+    pub fn contains(&self, txid: [u8; 32]) -> bool {
+        self.entries.iter().any(|entry| entry.txid() == txid)
+    }
+
+    /// Devuelve el output con el indice recibido de una transaccion del ledger
+    pub fn output(&self, txid: [u8; 32], index: usize) -> Option<&TxOut> {
+        self.entries
+            .iter()
+            .find(|entry| entry.txid() == txid)
+            .and_then(|entry| entry.transaction.tx_out.get(index))
+    }
+
+    /// Saca del ledger las transacciones pendientes y las confirmadas desde la altura recibida, para volver
+    /// a armarlo al reescanear la cadena desde esa altura
+    pub fn remove_from_height(&mut self, height: u32) {
+        self.entries
+            .retain(|entry| entry.block.is_some_and(|block| block.height < height));
+    }
+
+    /// Agrega las entradas del ledger recibido, por ejemplo el armado por un rescan, sin sacar las que ya estaban
+    pub fn merge(&mut self, other: &Ledger) {
+        for entry in &other.entries {
+            self.record(entry.clone());
+        }
+    }
+
+    /// Saca las transacciones pendientes que gastan alguna de las monedas que gasta la transaccion confirmada
+    /// recibida, porque ya no se van a confirmar, y las pendientes que gastan sus outputs. Devuelve sus txid
+    pub fn remove_conflicts(&mut self, confirmed: &Transaction) -> Vec<[u8; 32]> {
+        let confirmed_txid = confirmed.hash();
+        let spent: HashSet<([u8; 32], usize)> = confirmed
+            .tx_in
+            .iter()
+            .map(|txin| {
+                (
+                    txin.get_previous_output_hash(),
+                    txin.get_previous_output_index(),
+                )
+            })
+            .collect();
+        let mut removed: Vec<[u8; 32]> = vec![];
+        loop {
+            let conflicting: Vec<[u8; 32]> = self
+                .entries
+                .iter()
+                .filter(|entry| entry.block.is_none() && entry.txid() != confirmed_txid)
+                .filter(|entry| {
+                    entry.transaction.tx_in.iter().any(|txin| {
+                        let outpoint = (
+                            txin.get_previous_output_hash(),
+                            txin.get_previous_output_index(),
+                        );
+                        spent.contains(&outpoint) || removed.contains(&outpoint.0)
+                    })
+                })
+                .map(LedgerEntry::txid)
+                .collect();
+            if conflicting.is_empty() {
+                return removed;
+            }
+            self.entries
+                .retain(|entry| !conflicting.contains(&entry.txid()));
+            removed.extend(conflicting);
+        }
+    }
+
+    /// Vuelve a dejar pendientes las transacciones confirmadas en el bloque recibido, cuando el bloque deja de
+    /// ser parte de la cadena principal por una reorganizacion
+    pub fn unconfirm_block(&mut self, block_hash: [u8; 32]) {
+        for entry in self.entries.iter_mut() {
+            if entry.block.is_some_and(|block| block.hash == block_hash) {
+                entry.block = None;
+            }
+        }
+    }
+
+    /// Devuelve los outputs confirmados que cumplen la funcion recibida, normalmente que son de la cuenta, y que
+    /// no gasto ninguna transaccion confirmada del ledger. Como el ledger tiene todas las transacciones que gastan
+    /// monedas de la cuenta, son sus monedas sin gastar aunque esten en bloques que no se descargaron
+    pub fn unspent_outputs(&self, owns_output: impl Fn(&TxOut) -> bool) -> Vec<UtxoTuple> {
+        let spent: HashSet<([u8; 32], usize)> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.block.is_some())
+            .flat_map(|entry| entry.transaction.tx_in.iter())
+            .map(|txin| {
+                (
+                    txin.get_previous_output_hash(),
+                    txin.get_previous_output_index(),
+                )
+            })
+            .collect();
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let block = entry.block?;
+                let txid = entry.txid();
+                let outputs: Vec<(TxOut, usize)> = entry
+                    .transaction
+                    .tx_out
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, tx_out)| {
+                        owns_output(tx_out) && !spent.contains(&(txid, *index))
+                    })
+                    .map(|(index, tx_out)| (tx_out.clone(), index))
+                    .collect();
+                if outputs.is_empty() {
+                    return None;
+                }
+                Some(
+                    UtxoTuple::new(txid, outputs)
+                        .with_height(block.height)
+                        .with_coinbase(entry.transaction.is_coinbase_transaction()),
+                )
+            })
+            .collect()
+    }
+
+    /// Devuelve las entradas del ledger en el orden en que se agregaron
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Devuelve el historial con las confirmaciones de cada transaccion segun la altura actual de la cadena,
+    /// de la mas vieja a la mas nueva y con las pendientes al final
+    pub fn history(&self, tip_height: u32) -> Vec<HistoryEntry> {
+        let mut history: Vec<HistoryEntry> = self
+            .entries
+            .iter()
+            .map(|entry| HistoryEntry {
+                entry: entry.clone(),
+                confirmations: entry.confirmations(tip_height),
+            })
+            .collect();
+        history.sort_by_key(|history_entry| {
+            history_entry
+                .entry
+                .block
+                .map_or(u32::MAX, |block| block.height)
+        });
+        history
+    }
+}
@@ -1,22 +1,37 @@
 pub mod account;
+pub mod address;
 pub mod address_decoder;
+pub mod balance;
+pub mod base64;
+pub mod bech32;
 pub mod blockchain;
 pub mod blockchain_download;
 pub mod blocks;
+pub mod coin_control;
+pub mod coin_selection;
 pub mod compact_size_uint;
 pub mod config;
 pub mod custom_errors;
+pub mod descriptor;
+pub mod fee_estimator;
 pub mod gtk;
 pub mod handler;
 pub mod handshake;
+pub mod hd_wallet;
+pub mod ledger;
 pub mod logwriter;
+pub mod message_signing;
 pub mod messages;
 pub mod network;
 pub mod node;
 pub mod node_data_pointers;
+pub mod peer_state;
+pub mod psbt;
+pub mod rescan;
 pub mod server;
 pub mod terminal_ui;
 pub mod transactions;
 pub mod utxo_tuple;
 pub mod wallet;
 pub mod wallet_event;
+pub mod wallet_storage;
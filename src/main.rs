@@ -1,4 +1,4 @@
-use bitcoin::blockchain_download::initial_block_download;
+use bitcoin::blockchain_download::{initial_block_download, wait_for_minimum_chain_work};
 use bitcoin::config::Config;
 use bitcoin::custom_errors::NodeCustomErrors;
 use bitcoin::gtk::ui_events::{send_event_to_ui, UIEvent};
@@ -79,18 +79,27 @@ fn run_node(
     let config = Config::from(args)?;
     let (log_sender, log_sender_handles) = set_up_loggers(&config)?;
     let node_ips = get_active_nodes_from_dns_seed(&config, &log_sender)?;
-    let nodes = handshake_with_nodes(&config, &log_sender, node_ips)?;
+    let (nodes, peers_state) = handshake_with_nodes(&config, &log_sender, node_ips)?;
     let blockchain = initial_block_download(&config, &log_sender, &ui_sender, nodes.clone())?;
-    let mut node = Node::new(&log_sender, &ui_sender, nodes, blockchain.clone())?;
+    let mut node = Node::new(
+        &config,
+        &log_sender,
+        &ui_sender,
+        nodes,
+        peers_state,
+        blockchain.clone(),
+    )?;
+    wait_for_minimum_chain_work(&config, &log_sender, &blockchain)?;
     send_event_to_ui(
         &ui_sender,
         UIEvent::InitializeUITabs((blockchain.headers, blockchain.blocks)),
     );
-    let mut wallet = Wallet::new(node.clone())?;
+    let mut wallet = Wallet::new(node.clone(), &config)?;
     let server = NodeServer::new(&config, &log_sender, &ui_sender, &mut node)?;
     handle_ui_events(&ui_sender, node_rx, &mut wallet);
+    let saved = wallet.save();
     shut_down(node, server, log_sender, log_sender_handles)?;
-    Ok(())
+    saved.map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
 }
 
 /// Espera a que se presione el boton de start en la interfaz grafica. La UI le envia un evento al nodo
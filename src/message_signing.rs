@@ -0,0 +1,373 @@
+use std::error::Error;
+
+use bitcoin_hashes::{sha256, Hash};
+use k256::{
+    ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey},
+    sha2::{Digest, Sha256},
+};
+
+use crate::{
+    account::Account,
+    address::Address,
+    address_decoder::hash_160,
+    base64,
+    compact_size_uint::CompactSizeUint,
+    transactions::{
+        outpoint::Outpoint,
+        script::{p2wpkh_script, script_opcodes::ScriptOpcodes, sig_script::SigScript},
+        transaction::Transaction,
+        tx_in::TxIn,
+        tx_out::TxOut,
+    },
+};
+
+/// Prefijo con el que se hashean los mensajes firmados en el formato legacy (signmessage)
+const MESSAGE_MAGIC: &str = "Bitcoin Signed Message:\n";
+/// Tag del hash de los mensajes firmados segun BIP322
+const BIP322_TAG: &str = "BIP0322-signed-message";
+/// Largo de una firma compacta legacy: el header de recuperacion, r y s
+const COMPACT_SIGNATURE_LEN: usize = 65;
+/// Header de las firmas compactas sin el recovery id
+const COMPACT_HEADER_BASE: u8 = 27;
+/// Se suma al header de las firmas compactas cuando la clave publica es comprimida
+const COMPRESSED_KEY_FLAG: u8 = 4;
+/// Byte de SIGHASH_ALL con el que terminan las firmas del witness
+const SIG_HASH_ALL_BYTE: u8 = 0x01;
+
+/// Firma el mensaje con la clave de la direccion recibida, que tiene que ser de la cuenta, para probar que
+/// se la controla. Las direcciones P2PKH usan la firma compacta legacy (signmessage) y las P2WPKH la firma
+/// simple de BIP322. Devuelve la firma en base64 o error si la cuenta no puede firmar con esa direccion
+pub fn sign_message(
+    account: &Account,
+    address: &str,
+    message: &str,
+) -> Result<String, Box<dyn Error>> {
+    let parsed_address = Address::parse(address)?;
+    let script_pubkey = parsed_address.script_pubkey();
+    if !account.pubkey_scripts().contains(&script_pubkey) {
+        return Err(Box::new(std::io::Error::other(format!(
+            "La direccion {} no es de la cuenta",
+            address
+        ))));
+    }
+    let (private_key, public_key) = account.signing_keys(&script_pubkey)?;
+    match parsed_address {
+        Address::P2pkh(_) => sign_legacy(&private_key, message),
+        Address::P2wpkh(_) => sign_bip322(&script_pubkey, &private_key, &public_key, message),
+        _ => Err(unsupported_address(address)),
+    }
+}
+
+/// Verifica que la firma en base64 del mensaje sea de la clave de la direccion recibida: la firma compacta
+/// legacy si es P2PKH o la firma simple de BIP322 si es P2WPKH. Devuelve true si es valida, false si es de
+/// otra clave o de otro mensaje y error si la direccion o el formato de la firma son invalidos
+pub fn verify_message(
+    address: &str,
+    message: &str,
+    signature: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let signature = base64::decode(signature)?;
+    match Address::parse(address)? {
+        Address::P2pkh(pubkey_hash) => verify_legacy(&pubkey_hash, message, &signature),
+        Address::P2wpkh(pubkey_hash) => verify_bip322(&pubkey_hash, message, &signature),
+        _ => Err(unsupported_address(address)),
+    }
+}
+
+/// Devuelve el error de las direcciones con las que no se pueden firmar mensajes
+fn unsupported_address(address: &str) -> Box<dyn Error> {
+    Box::new(std::io::Error::other(format!(
+        "Solo se pueden firmar mensajes con direcciones P2PKH o P2WPKH, {} no es de ninguno de los dos tipos",
+        address
+    )))
+}
+
+/// Devuelve el hash del mensaje con el prefijo de signmessage, cada uno precedido por su largo.
+/// Se hashea una sola vez porque la firma aplica el segundo hash
+fn legacy_message_hash(message: &str) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&CompactSizeUint::new(MESSAGE_MAGIC.len() as u128).marshalling());
+    bytes.extend_from_slice(MESSAGE_MAGIC.as_bytes());
+    bytes.extend_from_slice(&CompactSizeUint::new(message.len() as u128).marshalling());
+    bytes.extend_from_slice(message.as_bytes());
+    *sha256::Hash::hash(&bytes).as_byte_array()
+}
+
+/// Firma el mensaje en el formato compacto legacy: el header con el recovery id y la marca de clave
+/// comprimida, seguido de r y s. Devuelve la firma en base64
+fn sign_legacy(private_key: &[u8; 32], message: &str) -> Result<String, Box<dyn Error>> {
+    let signing_key = SigningKey::from_bytes(private_key.into())?;
+    let (signature, recovery_id) = signing_key
+        .sign_digest_recoverable(Sha256::new_with_prefix(legacy_message_hash(message)))?;
+    let mut compact = vec![COMPACT_HEADER_BASE + COMPRESSED_KEY_FLAG + u8::from(recovery_id)];
+    compact.extend_from_slice(&signature.to_bytes());
+    Ok(base64::encode(&compact))
+}
+
+/// Recupera la clave publica de la firma compacta y verifica que su hash sea el de la direccion
+fn verify_legacy(
+    pubkey_hash: &[u8; 20],
+    message: &str,
+    signature: &[u8],
+) -> Result<bool, Box<dyn Error>> {
+    if signature.len() != COMPACT_SIGNATURE_LEN
+        || !(COMPACT_HEADER_BASE..COMPACT_HEADER_BASE + 2 * COMPRESSED_KEY_FLAG)
+            .contains(&signature[0])
+    {
+        return Err(Box::new(std::io::Error::other(
+            "La firma no tiene el formato compacto de signmessage",
+        )));
+    }
+    let header = signature[0] - COMPACT_HEADER_BASE;
+    let compressed = header >= COMPRESSED_KEY_FLAG;
+    let recovery_id = RecoveryId::from_byte(header % COMPRESSED_KEY_FLAG)
+        .ok_or_else(|| std::io::Error::other("El recovery id de la firma es invalido"))?;
+    let signature = Signature::from_slice(&signature[1..])?;
+    let public_key = match VerifyingKey::recover_from_digest(
+        Sha256::new_with_prefix(legacy_message_hash(message)),
+        &signature,
+        recovery_id,
+    ) {
+        Ok(public_key) => public_key,
+        Err(_) => return Ok(false),
+    };
+    let public_key = public_key.to_encoded_point(compressed);
+    Ok(hash_160(public_key.as_bytes()) == *pubkey_hash)
+}
+
+/// Devuelve el tagged hash de BIP322 del mensaje: SHA256(SHA256(tag) || SHA256(tag) || mensaje)
+fn bip322_message_hash(message: &str) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(BIP322_TAG.as_bytes());
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(tag_hash.as_byte_array());
+    bytes.extend_from_slice(tag_hash.as_byte_array());
+    bytes.extend_from_slice(message.as_bytes());
+    *sha256::Hash::hash(&bytes).as_byte_array()
+}
+
+/// Arma la transaccion virtual to_spend de BIP322: gasta un outpoint nulo con el hash del mensaje en el
+/// signature script y paga 0 satoshis al pubkey script de la direccion que firma
+fn bip322_to_spend(script_pubkey: &[u8], message: &str) -> Transaction {
+    let mut sig_script = vec![ScriptOpcodes::OP_0, 32];
+    sig_script.extend_from_slice(&bip322_message_hash(message));
+    let tx_in = TxIn::new(
+        Outpoint::new([0; 32], u32::MAX),
+        CompactSizeUint::new(sig_script.len() as u128),
+        None,
+        SigScript::new(sig_script),
+        0,
+    );
+    let tx_out = TxOut::new(
+        0,
+        CompactSizeUint::new(script_pubkey.len() as u128),
+        script_pubkey.to_vec(),
+    );
+    Transaction::new(
+        0,
+        CompactSizeUint::new(1),
+        vec![tx_in],
+        CompactSizeUint::new(1),
+        vec![tx_out],
+        0,
+    )
+}
+
+/// Arma la transaccion virtual to_sign de BIP322: gasta el output de to_spend y paga 0 satoshis a un
+/// OP_RETURN. La firma del mensaje es el witness de su input
+fn bip322_to_sign(to_spend: &Transaction) -> Transaction {
+    let tx_in = TxIn::new(
+        Outpoint::new(to_spend.hash(), 0),
+        CompactSizeUint::new(0),
+        None,
+        SigScript::new(vec![]),
+        0,
+    );
+    let tx_out = TxOut::new(0, CompactSizeUint::new(1), vec![ScriptOpcodes::OP_RETURN]);
+    Transaction::new(
+        0,
+        CompactSizeUint::new(1),
+        vec![tx_in],
+        CompactSizeUint::new(1),
+        vec![tx_out],
+        0,
+    )
+}
+
+/// Devuelve el mensaje a firmar del input de to_sign, segun BIP143 porque gasta un output P2WPKH
+fn bip322_sighash(script_pubkey: &[u8], message: &str) -> [u8; 32] {
+    bip322_to_sign(&bip322_to_spend(script_pubkey, message)).segwit_v0_message_to_sign(
+        0,
+        &p2wpkh_script::script_code(script_pubkey),
+        0,
+    )
+}
+
+/// Firma el mensaje con la firma simple de BIP322: el witness que gasta el output de to_spend, serializado
+/// y en base64
+fn sign_bip322(
+    script_pubkey: &[u8],
+    private_key: &[u8; 32],
+    public_key: &[u8; 33],
+    message: &str,
+) -> Result<String, Box<dyn Error>> {
+    let signature = SigScript::generate_sig(bip322_sighash(script_pubkey, message), *private_key)?;
+    let mut tx_in = TxIn::incomplete_txin(Outpoint::new([0; 32], 0));
+    tx_in.set_witness(vec![signature, public_key.to_vec()]);
+    let mut witness = Vec::new();
+    tx_in.marshalling_witness(&mut witness);
+    Ok(base64::encode(&witness))
+}
+
+/// Deserializa el witness de la firma simple de BIP322 y verifica que la clave publica sea la de la
+/// direccion y que la firma sea valida para el input de to_sign
+fn verify_bip322(
+    pubkey_hash: &[u8; 20],
+    message: &str,
+    signature: &[u8],
+) -> Result<bool, Box<dyn Error>> {
+    let mut tx_in = TxIn::incomplete_txin(Outpoint::new([0; 32], 0));
+    let mut offset = 0;
+    tx_in
+        .unmarshalling_witness(signature, &mut offset)
+        .map_err(std::io::Error::other)?;
+    if offset != signature.len() {
+        return Err(Box::new(std::io::Error::other(
+            "La firma BIP322 tiene bytes de mas despues del witness",
+        )));
+    }
+    let (signature, public_key) = match tx_in.witness.as_slice() {
+        [signature, public_key] => (signature, public_key),
+        _ => return Ok(false),
+    };
+    if hash_160(public_key) != *pubkey_hash || signature.last() != Some(&SIG_HASH_ALL_BYTE) {
+        return Ok(false);
+    }
+    let script_pubkey = Address::P2wpkh(*pubkey_hash).script_pubkey();
+    Ok(SigScript::verify_sig(
+        &bip322_sighash(&script_pubkey, message),
+        signature,
+        public_key,
+    )
+    .unwrap_or(false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        account::bytes_to_hex_string,
+        address::Network,
+        address_decoder::{decode_wif_private_key, generate_p2wpkh_address, get_pubkey_compressed},
+    };
+
+    /// Direccion de los vectores de prueba de BIP322, codificada para testnet
+    fn bip322_vector_address() -> Result<String, Box<dyn Error>> {
+        Address::parse_for_network(
+            "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l",
+            Network::Mainnet,
+        )?
+        .encode(Network::Testnet)
+    }
+
+    #[test]
+    fn test_las_transacciones_virtuales_de_bip322_coinciden_con_los_vectores_de_prueba(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: la direccion de los vectores de BIP322
+        let script_pubkey = Address::parse(&bip322_vector_address()?)?.script_pubkey();
+        // WHEN: se calculan el hash del mensaje y la transaccion to_spend del mensaje vacio y de "Hello World"
+        // THEN: coinciden con los de BIP322
+        assert_eq!(
+            bytes_to_hex_string(&bip322_message_hash("")),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            bytes_to_hex_string(&bip322_message_hash("Hello World")),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+        assert_eq!(
+            bip322_to_spend(&script_pubkey, "").hex_hash(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            bip322_to_sign(&bip322_to_spend(&script_pubkey, "Hello World")).hex_hash(),
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_la_firma_bip322_coincide_con_los_vectores_de_prueba() -> Result<(), Box<dyn Error>> {
+        // GIVEN: la clave y la direccion de los vectores de BIP322
+        let address = bip322_vector_address()?;
+        let script_pubkey = Address::parse(&address)?.script_pubkey();
+        let private_key =
+            decode_wif_private_key("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k")?;
+        let public_key =
+            get_pubkey_compressed("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k")?;
+        let empty_signature = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let hello_signature = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        // WHEN: se firma con la clave y se verifican la firma y las de los vectores
+        let signature = sign_bip322(&script_pubkey, &private_key, &public_key, "Hello World")?;
+        // THEN: todas son validas y cada una solo para su mensaje
+        assert!(verify_message(&address, "Hello World", &signature)?);
+        assert!(verify_message(&address, "", empty_signature)?);
+        assert!(verify_message(&address, "Hello World", hello_signature)?);
+        assert!(!verify_message(&address, "", hello_signature)?);
+        assert!(!verify_message(&address, "Hello World", empty_signature)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_la_firma_legacy_coincide_con_la_de_signmessage() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta P2PKH y la firma de Bitcoin Core de un mensaje con su clave
+        let address = "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB";
+        let account = Account::new(
+            "cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N".to_string(),
+            address.to_string(),
+        )?;
+        let expected =
+            "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+        // WHEN: se firma el mensaje con la cuenta
+        let signature = sign_message(&account, address, "This is just a test message")?;
+        // THEN: la firma es la misma y solo es valida para ese mensaje y esa direccion
+        assert_eq!(signature, expected);
+        assert!(verify_message(
+            address,
+            "This is just a test message",
+            expected
+        )?);
+        assert!(!verify_message(
+            address,
+            "This is another message",
+            expected
+        )?);
+        assert!(!verify_message(
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV",
+            "This is just a test message",
+            expected
+        )?);
+        assert!(verify_message(address, "This is just a test message", "AAAA").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_una_cuenta_p2wpkh_firma_con_bip322_solo_con_sus_direcciones(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta P2WPKH
+        let private_key = String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
+        let address = generate_p2wpkh_address(&decode_wif_private_key(&private_key)?)?;
+        let account = Account::new(private_key, address.clone())?;
+        // WHEN: se firma un mensaje con su direccion y con una direccion ajena
+        let signature = sign_message(&account, &address, "soy el dueño")?;
+        let foreign = sign_message(
+            &account,
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF",
+            "soy el dueño",
+        );
+        // THEN: la firma verifica solo para el mismo mensaje y no se puede firmar con la direccion ajena
+        assert!(verify_message(&address, "soy el dueño", &signature)?);
+        assert!(!verify_message(&address, "no soy el dueño", &signature)?);
+        assert!(foreign.is_err());
+        Ok(())
+    }
+}
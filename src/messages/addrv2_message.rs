@@ -0,0 +1,275 @@
+use std::{
+    error::Error,
+    io,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{account::bytes_to_hex_string, compact_size_uint::CompactSizeUint};
+
+/// Cantidad maxima de direcciones que puede tener un mensaje addrv2
+const MAX_ADDRESSES: u64 = 1000;
+/// Largo maximo que puede tener una direccion dentro del mensaje addrv2
+const MAX_ADDRESS_LENGTH: u64 = 512;
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Tipos de red que puede tener una direccion del mensaje addrv2 (BIP155)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkId {
+    IPv4,
+    IPv6,
+    TorV2,
+    TorV3,
+    I2P,
+    Cjdns,
+}
+
+impl NetworkId {
+    /// Recibe el byte que identifica a la red y devuelve el NetworkId correspondiente
+    /// o None si es una red desconocida (que segun BIP155 debe ignorarse)
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(NetworkId::IPv4),
+            0x02 => Some(NetworkId::IPv6),
+            0x03 => Some(NetworkId::TorV2),
+            0x04 => Some(NetworkId::TorV3),
+            0x05 => Some(NetworkId::I2P),
+            0x06 => Some(NetworkId::Cjdns),
+            _ => None,
+        }
+    }
+
+    /// Devuelve el largo en bytes que debe tener una direccion de esta red
+    pub fn address_length(&self) -> usize {
+        match self {
+            NetworkId::IPv4 => 4,
+            NetworkId::IPv6 => 16,
+            NetworkId::TorV2 => 10,
+            NetworkId::TorV3 => 32,
+            NetworkId::I2P => 32,
+            NetworkId::Cjdns => 16,
+        }
+    }
+}
+
+/// Representa una de las direcciones que llegan en el mensaje addrv2
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddrV2 {
+    pub time: u32,
+    pub services: u64,
+    pub network_id: NetworkId,
+    pub addr: Vec<u8>,
+    pub port: u16,
+}
+
+impl AddrV2 {
+    /// Devuelve la direccion como string segun el tipo de red.
+    /// Las direcciones I2P se muestran en base32 con el sufijo .b32.i2p y las
+    /// de Tor con la clave publica (v3) o el hash (v2) en hexadecimal
+    pub fn address_to_string(&self) -> String {
+        match self.network_id {
+            NetworkId::IPv4 => {
+                Ipv4Addr::new(self.addr[0], self.addr[1], self.addr[2], self.addr[3]).to_string()
+            }
+            NetworkId::IPv6 | NetworkId::Cjdns => {
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(&self.addr);
+                Ipv6Addr::from(ip).to_string()
+            }
+            NetworkId::TorV2 => format!("torv2:{}", bytes_to_hex_string(&self.addr)),
+            NetworkId::TorV3 => format!("torv3:{}", bytes_to_hex_string(&self.addr)),
+            NetworkId::I2P => format!("{}.b32.i2p", base32_encode(&self.addr)),
+        }
+    }
+}
+
+/// Deserializa el payload del mensaje addrv2 y devuelve las direcciones que contiene.
+/// Las direcciones de redes desconocidas se ignoran. Devuelve error si el mensaje tiene
+/// mas direcciones de las permitidas, si esta incompleto o si alguna direccion de una red
+/// conocida no tiene el largo correcto
+pub fn unmarshalling_addrv2(payload: &[u8]) -> Result<Vec<AddrV2>, Box<dyn Error>> {
+    let mut offset: usize = 0;
+    let count = read_compact_size(payload, &mut offset)?;
+    if count > MAX_ADDRESSES {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "El mensaje addrv2 tiene {} direcciones, el maximo es 1000",
+                count
+            ),
+        )));
+    }
+    let mut addresses = vec![];
+    for _ in 0..count {
+        let time = u32::from_le_bytes(read_bytes(payload, &mut offset, 4)?.try_into()?);
+        let services = read_compact_size(payload, &mut offset)?;
+        let network_byte = read_bytes(payload, &mut offset, 1)?[0];
+        let addr_len = read_compact_size(payload, &mut offset)?;
+        if addr_len > MAX_ADDRESS_LENGTH {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Direccion del mensaje addrv2 demasiado larga",
+            )));
+        }
+        let addr = read_bytes(payload, &mut offset, addr_len as usize)?.to_vec();
+        let port = u16::from_be_bytes(read_bytes(payload, &mut offset, 2)?.try_into()?);
+        let network_id = match NetworkId::from_byte(network_byte) {
+            Some(network_id) => network_id,
+            None => continue,
+        };
+        if addr.len() != network_id.address_length() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Direccion {:?} con largo invalido: {} bytes",
+                    network_id,
+                    addr.len()
+                ),
+            )));
+        }
+        addresses.push(AddrV2 {
+            time,
+            services,
+            network_id,
+            addr,
+            port,
+        });
+    }
+    Ok(addresses)
+}
+
+/// Lee un CompactSize del payload verificando que esten todos sus bytes y devuelve su valor
+fn read_compact_size(payload: &[u8], offset: &mut usize) -> Result<u64, Box<dyn Error>> {
+    check_remaining_bytes(payload, *offset, 1)?;
+    let size_of_value = match payload[*offset] {
+        0xfd => 3,
+        0xfe => 5,
+        0xff => 9,
+        _ => 1,
+    };
+    check_remaining_bytes(payload, *offset, size_of_value)?;
+    Ok(CompactSizeUint::unmarshalling(payload, offset)?.decoded_value())
+}
+
+/// Devuelve los siguientes `amount` bytes del payload y actualiza el offset.
+/// Devuelve error si no hay suficientes bytes
+fn read_bytes<'a>(
+    payload: &'a [u8],
+    offset: &mut usize,
+    amount: usize,
+) -> Result<&'a [u8], Box<dyn Error>> {
+    check_remaining_bytes(payload, *offset, amount)?;
+    let bytes = &payload[*offset..*offset + amount];
+    *offset += amount;
+    Ok(bytes)
+}
+
+/// Devuelve error si a partir del offset no quedan al menos `amount` bytes en el payload
+fn check_remaining_bytes(
+    payload: &[u8],
+    offset: usize,
+    amount: usize,
+) -> Result<(), Box<dyn Error>> {
+    if payload.len() < offset + amount {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "El mensaje addrv2 esta incompleto",
+        )));
+    }
+    Ok(())
+}
+
+/// Codifica los bytes recibidos en base32 (RFC 4648, minusculas y sin padding)
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits_in_buffer = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits_in_buffer += 8;
+        while bits_in_buffer >= 5 {
+            bits_in_buffer -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits_in_buffer) & 0x1f) as usize] as char);
+        }
+    }
+    if bits_in_buffer > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits_in_buffer)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializa una direccion con el formato del mensaje addrv2
+    fn addrv2_entry(network_byte: u8, addr: &[u8], port: u16) -> Vec<u8> {
+        let mut entry = vec![];
+        entry.extend_from_slice(&1_690_000_000u32.to_le_bytes());
+        entry.extend(CompactSizeUint::new(1033).marshalling());
+        entry.push(network_byte);
+        entry.extend(CompactSizeUint::new(addr.len() as u128).marshalling());
+        entry.extend_from_slice(addr);
+        entry.extend_from_slice(&port.to_be_bytes());
+        entry
+    }
+
+    #[test]
+    fn addrv2_with_ipv4_tor_v3_and_i2p_addresses_is_parsed_correctly() -> Result<(), Box<dyn Error>>
+    {
+        // GIVEN: un payload addrv2 con una direccion IPv4, una Tor v3 y una I2P
+        let mut payload = CompactSizeUint::new(3).marshalling();
+        payload.extend(addrv2_entry(0x01, &[1, 2, 3, 4], 18333));
+        payload.extend(addrv2_entry(0x04, &[0xab; 32], 18333));
+        payload.extend(addrv2_entry(0x05, &[0u8; 32], 0));
+        // WHEN: se deserializa
+        let addresses = unmarshalling_addrv2(&payload)?;
+        // THEN: se obtienen las tres direcciones con sus redes correspondientes
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(addresses[0].address_to_string(), "1.2.3.4");
+        assert_eq!(addresses[0].port, 18333);
+        assert_eq!(addresses[0].services, 1033);
+        assert_eq!(addresses[1].network_id, NetworkId::TorV3);
+        assert_eq!(addresses[1].addr, vec![0xab; 32]);
+        assert_eq!(addresses[2].network_id, NetworkId::I2P);
+        assert_eq!(
+            addresses[2].address_to_string(),
+            format!("{}.b32.i2p", "a".repeat(52))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn addrv2_ignores_unknown_networks() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un payload addrv2 con una direccion de una red desconocida y una IPv4
+        let mut payload = CompactSizeUint::new(2).marshalling();
+        payload.extend(addrv2_entry(0x2a, &[7; 20], 1));
+        payload.extend(addrv2_entry(0x01, &[1, 2, 3, 4], 18333));
+        // WHEN: se deserializa
+        let addresses = unmarshalling_addrv2(&payload)?;
+        // THEN: solo se devuelve la direccion IPv4
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].network_id, NetworkId::IPv4);
+        Ok(())
+    }
+
+    #[test]
+    fn addrv2_with_invalid_address_length_returns_error() {
+        // GIVEN: un payload addrv2 con una direccion Tor v3 de 16 bytes
+        let mut payload = CompactSizeUint::new(1).marshalling();
+        payload.extend(addrv2_entry(0x04, &[1; 16], 18333));
+        // WHEN: se deserializa
+        // THEN: devuelve error
+        assert!(unmarshalling_addrv2(&payload).is_err());
+    }
+
+    #[test]
+    fn incomplete_addrv2_returns_error() {
+        // GIVEN: un payload addrv2 cortado a la mitad
+        let mut payload = CompactSizeUint::new(1).marshalling();
+        payload.extend(addrv2_entry(0x01, &[1, 2, 3, 4], 18333));
+        payload.truncate(payload.len() - 3);
+        // WHEN: se deserializa
+        // THEN: devuelve error
+        assert!(unmarshalling_addrv2(&payload).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{error::Error, io};
+
+use super::message_header::HeaderMessage;
+
+/// Recibe el fee rate minimo en satoshis por kilobyte y devuelve el mensaje feefilter (BIP133) serializado.
+/// Con este mensaje se le indica al otro nodo que no nos anuncie transacciones con un fee rate menor
+pub fn get_feefilter_message(fee_rate: u64) -> Vec<u8> {
+    let payload = fee_rate.to_le_bytes();
+    let header = HeaderMessage::new("feefilter".to_string(), Some(&payload));
+    let mut message = vec![];
+    message.extend_from_slice(&header.to_le_bytes());
+    message.extend_from_slice(&payload);
+    message
+}
+
+/// Deserializa el payload del mensaje feefilter y devuelve el fee rate en satoshis por kilobyte.
+/// Devuelve error si el payload no tiene 8 bytes
+pub fn unmarshalling_feefilter(payload: &[u8]) -> Result<u64, Box<dyn Error>> {
+    let fee_rate_bytes: [u8; 8] = payload.try_into().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "El payload del mensaje feefilter debe tener 8 bytes",
+        )
+    })?;
+    Ok(u64::from_le_bytes(fee_rate_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feefilter_message_is_marshalled_and_unmarshalled_correctly() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un fee rate de 1000 sat/kB
+        // WHEN: se genera el mensaje feefilter y se deserializa su payload
+        let message = get_feefilter_message(1000);
+        let fee_rate = unmarshalling_feefilter(&message[24..])?;
+        // THEN: el comando es feefilter y el fee rate es el mismo
+        assert_eq!(&message[4..13], b"feefilter");
+        assert_eq!(message.len(), 32);
+        assert_eq!(fee_rate, 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn feefilter_with_invalid_payload_returns_error() {
+        // GIVEN: un payload de 4 bytes
+        // WHEN: se deserializa
+        // THEN: devuelve error
+        assert!(unmarshalling_feefilter(&[0, 1, 2, 3]).is_err());
+    }
+}
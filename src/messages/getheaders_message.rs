@@ -27,6 +27,33 @@ impl GetHeadersMessage {
         Ok(())
     }
 
+    /// Recibe la version del protocolo, los hashes del block locator (del mas nuevo al mas viejo) y el stop hash
+    /// y arma el mensaje getheaders. Con stop hash en 0 el otro nodo devuelve hasta 2000 headers
+    pub fn new(version: u32, locator_hashes: Vec<[u8; 32]>, stop_hash: [u8; 32]) -> Self {
+        let getheaders_payload = GetHeadersPayload {
+            version,
+            hash_count: CompactSizeUint::new(locator_hashes.len() as u128),
+            locator_hashes,
+            stop_hash,
+        };
+        let header = HeaderMessage::new(
+            "getheaders".to_string(),
+            Some(&getheaders_payload.to_le_bytes()),
+        );
+        GetHeadersMessage {
+            header,
+            payload: getheaders_payload,
+        }
+    }
+
+    /// Serializa el mensaje getheaders y devuelve el vector de bytes para ser escrito en la red
+    pub fn marshalling(&self) -> Vec<u8> {
+        let mut message: Vec<u8> = Vec::new();
+        message.extend_from_slice(&self.header.to_le_bytes());
+        message.extend(self.payload.to_le_bytes());
+        message
+    }
+
     /// Dado un vector de bytes, intenta interpretar el mismo como un mensaje getheaders
     pub fn read_from(payload_bytes: &[u8]) -> Result<GetHeadersMessage, Box<dyn Error>> {
         let payload = GetHeadersPayload::read_from(payload_bytes)?;
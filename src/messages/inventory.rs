@@ -28,6 +28,15 @@ impl Inventory {
         }
     }
 
+    /// Crea un inventory con el wtxid de una transacción (BIP339).
+    /// Es el que se usa con los nodos que negociaron wtxid relay.
+    pub fn new_wtx(hash: [u8; 32]) -> Inventory {
+        Inventory {
+            type_identifier: 5, // 5: Witness Transaction
+            hash,
+        }
+    }
+
     /// Convierte el Inventory a little endian bytes, tal como requiere el protocolo bitcoin
     /// para enviarlo por la red.
     pub fn to_le_bytes(&self) -> Vec<u8> {
@@ -157,7 +157,7 @@ pub fn is_terminated(finish: Option<Arc<RwLock<bool>>>) -> bool {
 
 /// Recibe el HeaderMessage y lee el payload correspondiente del stream,
 /// Devuelve los bytes leidos del stream
-fn read_payload(stream: &mut dyn Read, header: &HeaderMessage) -> io::Result<Vec<u8>> {
+pub fn read_payload(stream: &mut dyn Read, header: &HeaderMessage) -> io::Result<Vec<u8>> {
     let payload_size = header.payload_size as usize;
     let mut payload_buffer_num: Vec<u8> = vec![0; payload_size];
     stream.read_exact(&mut payload_buffer_num)?;
@@ -196,6 +196,24 @@ pub fn write_sendheaders_message(stream: &mut dyn Write) -> Result<(), Box<dyn s
     header.write_to(stream)?;
     Ok(())
 }
+
+/// Recibe un stream que implemente el trait Write (algo donde se pueda escribir) y escribe el mensaje wtxidrelay (BIP339) segun
+/// el protocolo de bitcoin, si se escribe correctamente devuelve Ok(()) y sino devuelve un error.
+/// Debe enviarse antes del verack
+pub fn write_wtxidrelay_message(stream: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    let header = HeaderMessage::new("wtxidrelay".to_string(), None);
+    header.write_to(stream)?;
+    Ok(())
+}
+
+/// Recibe un stream que implemente el trait Write (algo donde se pueda escribir) y escribe el mensaje sendaddrv2 (BIP155) segun
+/// el protocolo de bitcoin, si se escribe correctamente devuelve Ok(()) y sino devuelve un error.
+/// Debe enviarse antes del verack
+pub fn write_sendaddrv2_message(stream: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    let header = HeaderMessage::new("sendaddrv2".to_string(), None);
+    header.write_to(stream)?;
+    Ok(())
+}
 /// Recibe un stream que implemente el trait Read (algo donde se pueda Leer) y lee el mensaje verack segun
 /// el protocolo de bitcoin, si se lee correctamente devuelve Ok(HeaderMessage) y sino devuelve un error
 pub fn read_verack_message(
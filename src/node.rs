@@ -3,23 +3,37 @@ use gtk::glib;
 use crate::{
     account::Account,
     blockchain::Blockchain,
+    blockchain_download::checkpoints::consensus_params,
     blocks::{block::Block, block_header::BlockHeader},
+    config::Config,
     custom_errors::NodeCustomErrors,
+    fee_estimator::FeeTarget,
     gtk::ui_events::UIEvent,
     handler::node_message_handler::NodeMessageHandler,
     logwriter::log_writer::LogSender,
-    messages::inventory::{inv_mershalling, Inventory},
+    messages::{
+        get_data_message::GetDataMessage,
+        inventory::{inv_mershalling, Inventory},
+    },
     node_data_pointers::NodeDataPointers,
+    peer_state::PeersState,
+    rescan::{RESCAN_BATCH_SIZE, RESCAN_BLOCK_TIMEOUT, RESCAN_MAX_ATTEMPTS},
+    transactions::transaction::Transaction,
     utxo_tuple::UtxoTuple,
 };
 use std::{
     error::Error,
     net::TcpStream,
     sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
 };
 
 type MerkleProofOfInclusionResult = Result<Option<Vec<([u8; 32], bool)>>, NodeCustomErrors>;
 
+/// Cada cuanto se revisa si llegaron los bloques pedidos para el rescan
+const RESCAN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Almacena la blockchain y el utxo set. Mantiene referencias a las cuentas y los nodos conectados.
 /// Inicializa también el NodeMessageHandler que es quien realiza la comunicación con los nodos.
 #[derive(Debug, Clone)]
@@ -32,11 +46,14 @@ pub struct Node {
 }
 
 impl Node {
-    /// Inicializa el nodo. Recibe la blockchain ya descargada.
+    /// Inicializa el nodo. Recibe la configuracion, la blockchain ya descargada y el estado negociado con cada nodo
+    /// en el handshake.
     pub fn new(
+        config: &Config,
         log_sender: &LogSender,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
+        peers_state: PeersState,
         blockchain: Blockchain,
     ) -> Result<Self, NodeCustomErrors> {
         let pointer_to_accounts_in_node = Arc::new(RwLock::new(Arc::new(RwLock::new(vec![]))));
@@ -44,6 +61,9 @@ impl Node {
             connected_nodes.clone(),
             blockchain.clone(),
             pointer_to_accounts_in_node.clone(),
+            peers_state,
+            config.protocol_version as u32,
+            consensus_params(config.start_string),
         );
         let peers_handler = NodeMessageHandler::new(log_sender, ui_sender, node_pointers.clone())?;
         Ok(Node {
@@ -59,10 +79,10 @@ impl Node {
         block.validate()
     }
 
-    /// Devuelve las utxos asociadas a la address recibida.
+    /// Devuelve las utxos que pagan a alguno de los scripts derivados de los descriptors de la cuenta recibida
     pub fn utxos_referenced_to_account(
         &self,
-        address: &str,
+        account: &Account,
     ) -> Result<Vec<UtxoTuple>, Box<dyn Error>> {
         let mut account_utxo_set: Vec<UtxoTuple> = Vec::new();
         for utxo in self
@@ -72,7 +92,7 @@ impl Node {
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
             .values()
         {
-            let aux_utxo = utxo.referenced_utxos(address);
+            let aux_utxo = utxo.referenced_utxos(&account.scripts);
             let utxo_to_push = match aux_utxo {
                 Some(value) => value,
                 None => continue,
@@ -86,12 +106,33 @@ impl Node {
         self.peers_handler.finish()
     }
 
-    /// Recibe un vector de bytes que representa a la raw format transaction para se enviada por
-    /// la red a todos los nodos conectados
-    pub fn broadcast_tx(&self, raw_tx: [u8; 32]) -> Result<(), NodeCustomErrors> {
-        let inventories = vec![Inventory::new_tx(raw_tx)];
-        let inv_message_bytes = inv_mershalling(inventories);
-        self.peers_handler.broadcast_to_nodes(inv_message_bytes)
+    /// Recibe la transaccion a anunciar y su fee rate en satoshis por kilobyte y le envia el inv a todos
+    /// los nodos conectados cuyo fee filter (BIP133) lo permita. A los nodos que negociaron wtxid relay (BIP339)
+    /// se les anuncia por wtxid y al resto por txid
+    pub fn broadcast_tx(
+        &self,
+        transaction: &Transaction,
+        fee_rate: u64,
+    ) -> Result<(), NodeCustomErrors> {
+        let txid_inv_message = inv_mershalling(vec![Inventory::new_tx(transaction.hash())]);
+        let wtxid_inv_message = inv_mershalling(vec![Inventory::new_wtx(transaction.wtxid())]);
+        self.peers_handler.broadcast_tx_to_nodes(
+            &self.node_pointers.peers_state,
+            fee_rate,
+            txid_inv_message,
+            wtxid_inv_message,
+        )
+    }
+
+    /// Devuelve el fee rate en sat/vB estimado para el objetivo recibido, segun cuanto tardaron en
+    /// confirmarse las transacciones que recibio el nodo
+    pub fn estimate_fee_rate(&self, target: FeeTarget) -> Result<f64, NodeCustomErrors> {
+        Ok(self
+            .node_pointers
+            .fee_estimator
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .fee_rate_for(target))
     }
 
     /// Actualiza lo que apunta el puntero de accounts a otro puntero que es pasado por parametro
@@ -145,6 +186,90 @@ impl Node {
         )
     }
 
+    /// Le pide a los nodos conectados los bloques de los hashes recibidos para reescanear la cadena, de a
+    /// RESCAN_BATCH_SIZE y esperando a que lleguen. Si un nodo no los envia a tiempo se los pide al siguiente.
+    /// Llama a la funcion recibida con la cantidad de bloques que ya llegaron. Devuelve error si despues de
+    /// RESCAN_MAX_ATTEMPTS pedidos siguen faltando bloques o si no se le pudo escribir a ningun nodo
+    pub fn fetch_blocks(
+        &self,
+        hashes: &[[u8; 32]],
+        mut on_progress: impl FnMut(usize),
+    ) -> Result<(), NodeCustomErrors> {
+        let result = self.request_rescan_blocks(hashes, &mut on_progress);
+        if result.is_err() {
+            self.node_pointers
+                .rescan_requests
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .clear();
+        }
+        result
+    }
+
+    /// Pide los bloques del rescan de a tandas, rotando el nodo al que se le piden
+    fn request_rescan_blocks(
+        &self,
+        hashes: &[[u8; 32]],
+        on_progress: &mut impl FnMut(usize),
+    ) -> Result<(), NodeCustomErrors> {
+        let mut fetched = 0;
+        let mut next_node = 0;
+        for batch in hashes.chunks(RESCAN_BATCH_SIZE) {
+            self.node_pointers
+                .rescan_requests
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .request(batch);
+            let mut missing = batch.to_vec();
+            for _ in 0..RESCAN_MAX_ATTEMPTS {
+                let inventories = missing.iter().map(|hash| Inventory::new_block(*hash));
+                self.peers_handler.send_to_one_node(
+                    next_node,
+                    GetDataMessage::new(inventories.collect()).marshalling(),
+                )?;
+                next_node += 1;
+                missing = self.wait_for_rescan_blocks(&missing)?;
+                if missing.is_empty() {
+                    break;
+                }
+            }
+            if !missing.is_empty() {
+                return Err(NodeCustomErrors::BlockchainDownloadError(format!(
+                    "Ningun nodo envio {} de los bloques pedidos para el rescan",
+                    missing.len()
+                )));
+            }
+            fetched += batch.len();
+            on_progress(fetched);
+        }
+        Ok(())
+    }
+
+    /// Espera hasta RESCAN_BLOCK_TIMEOUT a que lleguen los bloques recibidos y devuelve los que siguen faltando
+    fn wait_for_rescan_blocks(
+        &self,
+        hashes: &[[u8; 32]],
+    ) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let start = Instant::now();
+        loop {
+            let pending = self
+                .node_pointers
+                .rescan_requests
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .pending();
+            let missing: Vec<[u8; 32]> = hashes
+                .iter()
+                .filter(|hash| pending.contains(hash))
+                .copied()
+                .collect();
+            if missing.is_empty() || start.elapsed() >= RESCAN_BLOCK_TIMEOUT {
+                return Ok(missing);
+            }
+            thread::sleep(RESCAN_POLL_INTERVAL);
+        }
+    }
+
     /// Busca un bloque en la blockchain
     /// Recibe el hash del bloque en formato hex
     /// Devuelve el bloque si lo encuentra, None en caso contrario
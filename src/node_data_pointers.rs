@@ -3,7 +3,15 @@ use std::{
     sync::{Arc, RwLock},
 };
 
-use crate::{account::Account, blockchain::Blockchain};
+use crate::{
+    account::Account,
+    blockchain::Blockchain,
+    blockchain_download::header_validation::ConsensusParams,
+    fee_estimator::FeeEstimator,
+    handler::{orphan_blocks::OrphanBlockPool, side_chains::SideChains},
+    peer_state::PeersState,
+    rescan::RescanRequests,
+};
 
 /// Almacena los punteros de los datos del nodo que se comparten entre los hilos.
 #[derive(Debug, Clone)]
@@ -11,19 +19,39 @@ pub struct NodeDataPointers {
     pub connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
     pub blockchain: Blockchain,
     pub accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+    pub peers_state: PeersState,
+    pub orphan_blocks: Arc<RwLock<OrphanBlockPool>>,
+    pub side_chains: Arc<RwLock<SideChains>>,
+    pub fee_estimator: Arc<RwLock<FeeEstimator>>,
+    pub rescan_requests: Arc<RwLock<RescanRequests>>,
+    /// Version del protocolo de la configuracion, con la que se piden los headers faltantes
+    pub protocol_version: u32,
+    /// Reglas de consenso de la red, con las que se validan los headers anunciados
+    pub consensus_params: ConsensusParams,
 }
 
 impl NodeDataPointers {
     /// Almacena los punteros de los datos del nodo que se comparten entre los hilos.
+    /// Inicializa vacios el pool de bloques huerfanos, las ramas laterales, el estimador de fees y los bloques pedidos para reescanear.
     pub fn new(
         connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
         blockchain: Blockchain,
         accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+        peers_state: PeersState,
+        protocol_version: u32,
+        consensus_params: ConsensusParams,
     ) -> Self {
         NodeDataPointers {
             connected_nodes,
             blockchain,
             accounts,
+            peers_state,
+            orphan_blocks: Arc::new(RwLock::new(OrphanBlockPool::default())),
+            side_chains: Arc::new(RwLock::new(SideChains::default())),
+            fee_estimator: Arc::new(RwLock::new(FeeEstimator::new())),
+            rescan_requests: Arc::new(RwLock::new(RescanRequests::default())),
+            protocol_version,
+            consensus_params,
         }
     }
 }
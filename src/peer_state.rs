@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use crate::messages::addrv2_message::AddrV2;
+
+/// Version minima del protocolo que soporta el mensaje feefilter (BIP133)
+pub const FEEFILTER_VERSION: i32 = 70013;
+/// Version minima del protocolo que soporta el mensaje wtxidrelay (BIP339)
+pub const WTXID_RELAY_VERSION: i32 = 70016;
+/// Cantidad maxima de direcciones recibidas por addrv2 que se guardan por nodo
+const MAX_ADDRESSES_PER_PEER: usize = 1000;
+
+/// Estado de todos los nodos conectados, indexado por la direccion del socket del nodo
+pub type PeersState = Arc<RwLock<HashMap<SocketAddr, PeerState>>>;
+
+/// Almacena lo negociado con un nodo durante el handshake y lo que este nos fue anunciando despues:
+/// version del protocolo, servicios, si usa wtxid relay (BIP339), si acepta addrv2 (BIP155),
+/// su fee filter (BIP133) y las direcciones que nos envio por addrv2
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerState {
+    pub version: i32,
+    pub services: u64,
+    pub wtxid_relay: bool,
+    pub send_addrv2: bool,
+    pub fee_filter: u64,
+    pub addresses: Vec<AddrV2>,
+}
+
+impl PeerState {
+    /// Crea el estado del nodo a partir de la version que nos envio y la nuestra.
+    /// La version guardada es la menor de las dos, que es la que se usa en la conexion
+    pub fn new(our_version: i32, peer_version: i32, services: u64) -> Self {
+        PeerState {
+            version: our_version.min(peer_version),
+            services,
+            wtxid_relay: false,
+            send_addrv2: false,
+            fee_filter: 0,
+            addresses: Vec::new(),
+        }
+    }
+
+    /// Devuelve true si la version negociada permite enviar y recibir el mensaje wtxidrelay
+    pub fn supports_wtxid_relay(&self) -> bool {
+        self.version >= WTXID_RELAY_VERSION
+    }
+
+    /// Devuelve true si la version negociada permite enviar y recibir el mensaje feefilter
+    pub fn supports_feefilter(&self) -> bool {
+        self.version >= FEEFILTER_VERSION
+    }
+
+    /// Recibe el fee rate de una transaccion en satoshis por kilobyte y devuelve true
+    /// si el nodo quiere que se le anuncie segun el fee filter que nos envio
+    pub fn accepts_fee_rate(&self, fee_rate: u64) -> bool {
+        fee_rate >= self.fee_filter
+    }
+
+    /// Agrega las direcciones recibidas por addrv2 sin repetir y sin superar el maximo por nodo
+    pub fn add_addresses(&mut self, addresses: Vec<AddrV2>) {
+        for address in addresses {
+            if self.addresses.len() >= MAX_ADDRESSES_PER_PEER {
+                break;
+            }
+            if !self
+                .addresses
+                .iter()
+                .any(|known| known.network_id == address.network_id && known.addr == address.addr)
+            {
+                self.addresses.push(address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::addrv2_message::NetworkId;
+
+    #[test]
+    fn peer_state_uses_the_lowest_version() {
+        // GIVEN: nuestra version 70016 y la del nodo 70015
+        // WHEN: se crea el estado del nodo
+        let state = PeerState::new(70016, 70015, 1);
+        // THEN: se usa la version 70015, que no soporta wtxid relay pero si feefilter
+        assert_eq!(state.version, 70015);
+        assert!(!state.supports_wtxid_relay());
+        assert!(state.supports_feefilter());
+    }
+
+    #[test]
+    fn peer_state_filters_fee_rates_below_its_fee_filter() {
+        // GIVEN: un nodo con fee filter de 1000 sat/kB
+        let mut state = PeerState::new(70016, 70016, 1);
+        state.fee_filter = 1000;
+        // WHEN: se consulta si acepta distintos fee rates
+        // THEN: solo acepta los que son mayores o iguales al filtro
+        assert!(!state.accepts_fee_rate(999));
+        assert!(state.accepts_fee_rate(1000));
+        assert!(state.accepts_fee_rate(5000));
+    }
+
+    #[test]
+    fn peer_state_does_not_store_repeated_addresses() {
+        // GIVEN: un nodo y dos direcciones iguales
+        let mut state = PeerState::new(70016, 70016, 1);
+        let address = AddrV2 {
+            time: 0,
+            services: 1,
+            network_id: NetworkId::IPv4,
+            addr: vec![127, 0, 0, 1],
+            port: 18333,
+        };
+        // WHEN: se agregan ambas
+        state.add_addresses(vec![address.clone(), address]);
+        // THEN: se guarda una sola
+        assert_eq!(state.addresses.len(), 1);
+    }
+}
@@ -0,0 +1,995 @@
+use std::{collections::BTreeMap, error::Error, fs, io, path::Path};
+
+use crate::{
+    account::Account,
+    address_decoder::hash_160,
+    base64,
+    compact_size_uint::CompactSizeUint,
+    transactions::{
+        outpoint::Outpoint,
+        script::{
+            multisig_script::{push_data, MultisigKind, MultisigScript},
+            p2wpkh_script,
+            script_type::ScriptType,
+            sig_script::SigScript,
+        },
+        transaction::Transaction,
+        tx_in::TxIn,
+        tx_out::TxOut,
+    },
+    utxo_tuple::UtxoTuple,
+};
+
+/// Bytes con los que empieza todo PSBT: "psbt" y 0xff
+const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+/// Unico sighash que usa la wallet para firmar
+const SIGHASH_ALL: u32 = 1;
+/// Sequence que se usa en los inputs de PSBT version 2 que no la indican
+const DEFAULT_SEQUENCE: u32 = 0xffffffff;
+
+// Tipos de clave del mapa global
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+// Tipos de clave de los mapas de inputs
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+// Tipos de clave de los mapas de outputs
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+/// Mapa de clave (tipo y datos de la clave) a valor, que es como se serializa cada seccion del PSBT
+type KeyValueMap = BTreeMap<Vec<u8>, Vec<u8>>;
+/// Transaccion sin firmar y los mapas de sus inputs y outputs
+type TransactionMaps = (Transaction, Vec<KeyValueMap>, Vec<KeyValueMap>);
+
+/// Partially Signed Bitcoin Transaction (BIP174 version 0 y BIP370 version 2).
+/// Guarda la transaccion sin firmar y, por cada input y output, los datos que necesitan los firmantes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Psbt {
+    pub version: u32,
+    pub unsigned_tx: Transaction,
+    pub inputs: Vec<PsbtInput>,
+    pub outputs: Vec<PsbtOutput>,
+    unknown: KeyValueMap,
+}
+
+/// Datos de un input del PSBT
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PsbtInput {
+    pub non_witness_utxo: Option<Transaction>,
+    pub witness_utxo: Option<TxOut>,
+    /// Firmas parciales por clave publica
+    pub partial_sigs: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub sighash_type: Option<u32>,
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    pub final_script_sig: Option<Vec<u8>>,
+    pub final_script_witness: Option<Vec<Vec<u8>>>,
+    /// Campos que la wallet no usa (derivaciones BIP32, propietarios, etc), se conservan tal cual
+    unknown: KeyValueMap,
+}
+
+/// Datos de un output del PSBT
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PsbtOutput {
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    unknown: KeyValueMap,
+}
+
+/// Script que se ejecuta para gastar un input, segun los templates que sabe firmar la wallet
+enum InputScript {
+    P2pkh([u8; 20]),
+    P2wpkh([u8; 20]),
+    Multisig(MultisigScript),
+}
+
+impl Psbt {
+    /// Crea el PSBT version 0 de la transaccion sin firmar recibida, sin datos de los inputs ni outputs.
+    /// Devuelve error si la transaccion ya tiene firmas
+    pub fn new(unsigned_tx: Transaction) -> Result<Psbt, Box<dyn Error>> {
+        if unsigned_tx
+            .tx_in
+            .iter()
+            .any(|txin| !txin.signature_script.get_bytes().is_empty() || !txin.witness.is_empty())
+        {
+            return Err(invalid_psbt(
+                "La transacción del PSBT no puede estar firmada",
+            ));
+        }
+        Ok(Psbt {
+            version: 0,
+            inputs: vec![PsbtInput::default(); unsigned_tx.tx_in.len()],
+            outputs: vec![PsbtOutput::default(); unsigned_tx.tx_out.len()],
+            unsigned_tx,
+            unknown: KeyValueMap::new(),
+        })
+    }
+
+    /// Crea el PSBT de una transaccion de la wallet. Recibe las utxos que gasta y el multisig de la cuenta, si tiene.
+    /// Todos los inputs llevan el output que gastan como witness utxo, ya que el nodo guarda las utxos y
+    /// no las transacciones completas. Los inputs del multisig llevan ademas su redeem script o witness script
+    pub fn from_unsigned_transaction(
+        unsigned_tx: Transaction,
+        utxos_to_spend: &[UtxoTuple],
+        multisig: Option<&MultisigScript>,
+    ) -> Result<Psbt, Box<dyn Error>> {
+        let mut psbt = Psbt::new(unsigned_tx)?;
+        for (txin, input) in psbt.unsigned_tx.tx_in.iter().zip(psbt.inputs.iter_mut()) {
+            let previous_output = utxos_to_spend
+                .iter()
+                .find_map(|utxos| {
+                    utxos.find_tx_out(
+                        txin.get_previous_output_hash(),
+                        txin.get_previous_output_index(),
+                    )
+                })
+                .ok_or_else(|| invalid_psbt("No se encontró el output que gasta un input"))?;
+            if let Some(multisig) = multisig.filter(|multisig| {
+                multisig.script_pubkey() == *previous_output.get_pub_key_script()
+            }) {
+                match multisig.kind {
+                    MultisigKind::P2sh => input.redeem_script = Some(multisig.redeem_script()),
+                    MultisigKind::P2shP2wsh => {
+                        input.redeem_script = Some(multisig.witness_program());
+                        input.witness_script = Some(multisig.redeem_script());
+                    }
+                    MultisigKind::P2wsh => input.witness_script = Some(multisig.redeem_script()),
+                }
+            }
+            input.witness_utxo = Some(previous_output.clone());
+        }
+        Ok(psbt)
+    }
+
+    /// Decodifica un PSBT version 0 o 2 serializado en binario.
+    /// Devuelve error si no cumple con el formato de BIP174 o BIP370
+    pub fn deserialize(bytes: &[u8]) -> Result<Psbt, Box<dyn Error>> {
+        if !bytes.starts_with(&PSBT_MAGIC) {
+            return Err(invalid_psbt(
+                "Los bytes no empiezan con el magic de un PSBT",
+            ));
+        }
+        let mut offset = PSBT_MAGIC.len();
+        let mut global = read_map(bytes, &mut offset)?;
+        let version = match global.remove(&vec![PSBT_GLOBAL_VERSION]) {
+            Some(value) => read_u32(&value)?,
+            None => 0,
+        };
+        let (unsigned_tx, mut input_maps, output_maps) = match version {
+            0 => {
+                let raw_tx = global
+                    .remove(&vec![PSBT_GLOBAL_UNSIGNED_TX])
+                    .ok_or_else(|| invalid_psbt("El PSBT no tiene la transacción sin firmar"))?;
+                let unsigned_tx =
+                    Transaction::unmarshalling(&raw_tx, &mut 0).map_err(invalid_psbt)?;
+                let input_maps = read_maps(bytes, &mut offset, unsigned_tx.tx_in.len())?;
+                let output_maps = read_maps(bytes, &mut offset, unsigned_tx.tx_out.len())?;
+                (unsigned_tx, input_maps, output_maps)
+            }
+            2 => Self::deserialize_v2_transaction(bytes, &mut offset, &mut global)?,
+            _ => return Err(invalid_psbt("La versión del PSBT no está soportada")),
+        };
+        if offset != bytes.len() {
+            return Err(invalid_psbt("El PSBT tiene bytes de más"));
+        }
+        let mut psbt = Psbt::new(unsigned_tx)?;
+        psbt.version = version;
+        psbt.unknown = global;
+        for (input, map) in psbt.inputs.iter_mut().zip(input_maps.iter_mut()) {
+            *input = PsbtInput::from_map(std::mem::take(map))?;
+        }
+        for (output, map) in psbt.outputs.iter_mut().zip(output_maps) {
+            *output = PsbtOutput::from_map(map);
+        }
+        Ok(psbt)
+    }
+
+    /// Lee los mapas de un PSBT version 2 y arma la transaccion sin firmar con los campos de la
+    /// transaccion de cada mapa (BIP370). Usa el fallback locktime como lock time de la transaccion
+    fn deserialize_v2_transaction(
+        bytes: &[u8],
+        offset: &mut usize,
+        global: &mut KeyValueMap,
+    ) -> Result<TransactionMaps, Box<dyn Error>> {
+        let mut required_field = |key_type: u8| {
+            global
+                .remove(&vec![key_type])
+                .ok_or_else(|| invalid_psbt("Al PSBT version 2 le falta un campo global"))
+        };
+        let tx_version = read_u32(&required_field(PSBT_GLOBAL_TX_VERSION)?)?;
+        let input_count = read_compact_size(&required_field(PSBT_GLOBAL_INPUT_COUNT)?, &mut 0)?;
+        let output_count = read_compact_size(&required_field(PSBT_GLOBAL_OUTPUT_COUNT)?, &mut 0)?;
+        let lock_time = match global.remove(&vec![PSBT_GLOBAL_FALLBACK_LOCKTIME]) {
+            Some(value) => read_u32(&value)?,
+            None => 0,
+        };
+        let mut input_maps = read_maps(bytes, offset, input_count as usize)?;
+        let mut output_maps = read_maps(bytes, offset, output_count as usize)?;
+        let mut tx_in = Vec::new();
+        for map in input_maps.iter_mut() {
+            let missing_field = || invalid_psbt("A un input del PSBT version 2 le falta un campo");
+            let previous_txid: [u8; 32] = map
+                .remove(&vec![PSBT_IN_PREVIOUS_TXID])
+                .ok_or_else(missing_field)?
+                .try_into()
+                .map_err(|_| missing_field())?;
+            let output_index = read_u32(
+                &map.remove(&vec![PSBT_IN_OUTPUT_INDEX])
+                    .ok_or_else(missing_field)?,
+            )?;
+            let sequence = match map.remove(&vec![PSBT_IN_SEQUENCE]) {
+                Some(value) => read_u32(&value)?,
+                None => DEFAULT_SEQUENCE,
+            };
+            let outpoint = Outpoint::new(previous_txid, output_index);
+            tx_in.push(TxIn::new(
+                outpoint,
+                CompactSizeUint::new(0),
+                None,
+                SigScript::new(vec![]),
+                sequence,
+            ));
+        }
+        let mut tx_out = Vec::new();
+        for map in output_maps.iter_mut() {
+            let missing_field = || invalid_psbt("A un output del PSBT version 2 le falta un campo");
+            let amount_bytes: [u8; 8] = map
+                .remove(&vec![PSBT_OUT_AMOUNT])
+                .ok_or_else(missing_field)?
+                .try_into()
+                .map_err(|_| missing_field())?;
+            let script = map
+                .remove(&vec![PSBT_OUT_SCRIPT])
+                .ok_or_else(missing_field)?;
+            tx_out.push(TxOut::new(
+                i64::from_le_bytes(amount_bytes),
+                CompactSizeUint::new(script.len() as u128),
+                script,
+            ));
+        }
+        let transaction = Transaction::new(
+            tx_version as i32,
+            CompactSizeUint::new(tx_in.len() as u128),
+            tx_in,
+            CompactSizeUint::new(tx_out.len() as u128),
+            tx_out,
+            lock_time,
+        );
+        Ok((transaction, input_maps, output_maps))
+    }
+
+    /// Serializa el PSBT en binario, en la version con la que se creo o se leyo
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = PSBT_MAGIC.to_vec();
+        let mut global = self.unknown.clone();
+        let mut input_maps: Vec<KeyValueMap> = self.inputs.iter().map(PsbtInput::to_map).collect();
+        let mut output_maps: Vec<KeyValueMap> =
+            self.outputs.iter().map(PsbtOutput::to_map).collect();
+        if self.version == 2 {
+            let tx = &self.unsigned_tx;
+            global.insert(
+                vec![PSBT_GLOBAL_TX_VERSION],
+                tx.version.to_le_bytes().to_vec(),
+            );
+            global.insert(
+                vec![PSBT_GLOBAL_FALLBACK_LOCKTIME],
+                tx.lock_time.to_le_bytes().to_vec(),
+            );
+            global.insert(
+                vec![PSBT_GLOBAL_INPUT_COUNT],
+                CompactSizeUint::new(tx.tx_in.len() as u128).marshalling(),
+            );
+            global.insert(
+                vec![PSBT_GLOBAL_OUTPUT_COUNT],
+                CompactSizeUint::new(tx.tx_out.len() as u128).marshalling(),
+            );
+            global.insert(
+                vec![PSBT_GLOBAL_VERSION],
+                self.version.to_le_bytes().to_vec(),
+            );
+            for (txin, map) in tx.tx_in.iter().zip(input_maps.iter_mut()) {
+                map.insert(
+                    vec![PSBT_IN_PREVIOUS_TXID],
+                    txin.get_previous_output_hash().to_vec(),
+                );
+                map.insert(
+                    vec![PSBT_IN_OUTPUT_INDEX],
+                    (txin.get_previous_output_index() as u32)
+                        .to_le_bytes()
+                        .to_vec(),
+                );
+                map.insert(
+                    vec![PSBT_IN_SEQUENCE],
+                    txin.sequence().to_le_bytes().to_vec(),
+                );
+            }
+            for (txout, map) in tx.tx_out.iter().zip(output_maps.iter_mut()) {
+                map.insert(vec![PSBT_OUT_AMOUNT], txout.value().to_le_bytes().to_vec());
+                map.insert(vec![PSBT_OUT_SCRIPT], txout.get_pub_key_script().clone());
+            }
+        } else {
+            let mut raw_tx = Vec::new();
+            self.unsigned_tx.marshalling_without_witness(&mut raw_tx);
+            global.insert(vec![PSBT_GLOBAL_UNSIGNED_TX], raw_tx);
+        }
+        write_map(&mut bytes, &global);
+        for map in input_maps.iter().chain(output_maps.iter()) {
+            write_map(&mut bytes, map);
+        }
+        bytes
+    }
+
+    /// Devuelve el PSBT codificado en base64, que es el formato con el que se comparte como texto
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.serialize())
+    }
+
+    /// Decodifica un PSBT en base64
+    pub fn from_base64(encoded: &str) -> Result<Psbt, Box<dyn Error>> {
+        Self::deserialize(&base64::decode(encoded)?)
+    }
+
+    /// Guarda el PSBT en binario en el archivo recibido, que es el formato de los archivos .psbt
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.serialize())?;
+        Ok(())
+    }
+
+    /// Lee el PSBT del archivo recibido, en binario o en base64
+    pub fn read_from_file(path: &str) -> Result<Psbt, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(&PSBT_MAGIC) {
+            return Self::deserialize(&bytes);
+        }
+        Self::from_base64(&String::from_utf8(bytes)?)
+    }
+
+    /// Recibe un PSBT en base64 o la ruta de un archivo que lo contiene y lo decodifica
+    pub fn import(input: &str) -> Result<Psbt, Box<dyn Error>> {
+        let input = input.trim();
+        if Path::new(input).is_file() {
+            return Self::read_from_file(input);
+        }
+        Self::from_base64(input)
+    }
+
+    /// Agrega las firmas de la cuenta a los inputs que todavia no estan finalizados y gastan
+    /// outputs de los que la cuenta tiene la clave. Devuelve la cantidad de firmas agregadas
+    pub fn sign(&mut self, account: &Account) -> Result<usize, Box<dyn Error>> {
+        let keys = account.keys();
+        let mut signatures_added = 0;
+        for index in 0..self.inputs.len() {
+            if self.inputs[index].is_finalized() {
+                continue;
+            }
+            if self.inputs[index]
+                .sighash_type
+                .is_some_and(|sighash_type| sighash_type != SIGHASH_ALL)
+            {
+                return Err(invalid_psbt("Solo se pueden firmar inputs con SIGHASH_ALL"));
+            }
+            let previous_output = self.previous_output(index)?;
+            let script = self.input_script(index, &previous_output)?;
+            let message = self.message_to_sign(index, &script, &previous_output);
+            for (private_key, public_key) in &keys {
+                if !script.is_signed_by(public_key)
+                    || self.inputs[index]
+                        .partial_sigs
+                        .contains_key(public_key.as_slice())
+                {
+                    continue;
+                }
+                let signature = SigScript::generate_sig(message, *private_key)?;
+                self.inputs[index]
+                    .partial_sigs
+                    .insert(public_key.to_vec(), signature);
+                signatures_added += 1;
+            }
+        }
+        Ok(signatures_added)
+    }
+
+    /// Combina el PSBT con otro de la misma transaccion, agregando las firmas y los datos que tenga el otro
+    /// (BIP174 combiner). Devuelve error si los PSBT son de transacciones distintas
+    pub fn combine(&mut self, other: Psbt) -> Result<(), Box<dyn Error>> {
+        if self.unsigned_tx.hash() != other.unsigned_tx.hash() {
+            return Err(invalid_psbt("Los PSBT no son de la misma transacción"));
+        }
+        for (input, other_input) in self.inputs.iter_mut().zip(other.inputs) {
+            input.combine(other_input);
+        }
+        for (output, other_output) in self.outputs.iter_mut().zip(other.outputs) {
+            output.redeem_script = output.redeem_script.take().or(other_output.redeem_script);
+            output.witness_script = output.witness_script.take().or(other_output.witness_script);
+            merge_unknown(&mut output.unknown, other_output.unknown);
+        }
+        merge_unknown(&mut self.unknown, other.unknown);
+        Ok(())
+    }
+
+    /// Arma el signature script y el witness final de cada input con sus firmas parciales y borra
+    /// los datos que ya no hacen falta (BIP174 finalizer). Devuelve error si a algun input le faltan firmas
+    pub fn finalize(&mut self) -> Result<(), Box<dyn Error>> {
+        for index in 0..self.inputs.len() {
+            if self.inputs[index].is_finalized() {
+                continue;
+            }
+            let previous_output = self.previous_output(index)?;
+            let script = self.input_script(index, &previous_output)?;
+            let input = &mut self.inputs[index];
+            let missing_signatures =
+                || invalid_psbt(&format!("Al input {} le faltan firmas", index));
+            let (sig_script, witness) = match script {
+                InputScript::P2pkh(_) => {
+                    let (public_key, signature) = input
+                        .partial_sigs
+                        .iter()
+                        .next()
+                        .ok_or_else(missing_signatures)?;
+                    let mut sig_script = push_data(signature);
+                    sig_script.extend_from_slice(&push_data(public_key));
+                    (sig_script, vec![])
+                }
+                InputScript::P2wpkh(_) => {
+                    let (public_key, signature) = input
+                        .partial_sigs
+                        .iter()
+                        .next()
+                        .ok_or_else(missing_signatures)?;
+                    (vec![], vec![signature.clone(), public_key.clone()])
+                }
+                InputScript::Multisig(multisig) => {
+                    let signatures: Vec<Vec<u8>> = multisig
+                        .pubkeys
+                        .iter()
+                        .filter_map(|public_key| input.partial_sigs.get(public_key.as_slice()))
+                        .take(multisig.required as usize)
+                        .cloned()
+                        .collect();
+                    if signatures.len() < multisig.required as usize {
+                        return Err(missing_signatures());
+                    }
+                    multisig.spending_data(&signatures)
+                }
+            };
+            input.final_script_sig = Some(sig_script).filter(|script| !script.is_empty());
+            input.final_script_witness = Some(witness).filter(|witness| !witness.is_empty());
+            input.partial_sigs.clear();
+            input.sighash_type = None;
+            input.redeem_script = None;
+            input.witness_script = None;
+            input
+                .unknown
+                .retain(|key, _| key[0] != PSBT_IN_BIP32_DERIVATION);
+        }
+        Ok(())
+    }
+
+    /// Devuelve la transaccion firmada con los datos finales de cada input (BIP174 extractor).
+    /// Devuelve error si algun input no esta finalizado o la transaccion no pasa la validacion
+    pub fn extract(&self) -> Result<Transaction, Box<dyn Error>> {
+        let mut transaction = self.unsigned_tx.clone();
+        for (txin, input) in transaction.tx_in.iter_mut().zip(&self.inputs) {
+            if !input.is_finalized() {
+                return Err(invalid_psbt("El PSBT tiene inputs sin finalizar"));
+            }
+            txin.set_signature_script(input.final_script_sig.clone().unwrap_or_default());
+            txin.set_witness(input.final_script_witness.clone().unwrap_or_default());
+        }
+        transaction.validate(&self.previous_utxos()?)?;
+        Ok(transaction)
+    }
+
+    /// Devuelve el fee de la transaccion: lo que suman los outputs que gasta menos lo que suman sus outputs
+    pub fn fee(&self) -> Result<i64, Box<dyn Error>> {
+        let mut inputs_amount = 0;
+        for index in 0..self.inputs.len() {
+            inputs_amount += self.previous_output(index)?.value();
+        }
+        Ok(inputs_amount - self.unsigned_tx.amount())
+    }
+
+    /// Devuelve las utxos que gasta la transaccion, segun los datos de los inputs
+    fn previous_utxos(&self) -> Result<Vec<UtxoTuple>, Box<dyn Error>> {
+        let mut utxos = Vec::new();
+        for (index, txin) in self.unsigned_tx.tx_in.iter().enumerate() {
+            utxos.push(UtxoTuple::new(
+                txin.get_previous_output_hash(),
+                vec![(
+                    self.previous_output(index)?,
+                    txin.get_previous_output_index(),
+                )],
+            ));
+        }
+        Ok(utxos)
+    }
+
+    /// Devuelve el output que gasta el input, del witness utxo o de la transaccion previa completa
+    fn previous_output(&self, index: usize) -> Result<TxOut, Box<dyn Error>> {
+        let input = &self.inputs[index];
+        if let Some(witness_utxo) = &input.witness_utxo {
+            return Ok(witness_utxo.clone());
+        }
+        let txin = &self.unsigned_tx.tx_in[index];
+        input
+            .non_witness_utxo
+            .as_ref()
+            .filter(|previous_tx| previous_tx.hash() == txin.get_previous_output_hash())
+            .and_then(|previous_tx| previous_tx.tx_out.get(txin.get_previous_output_index()))
+            .cloned()
+            .ok_or_else(|| {
+                invalid_psbt(&format!("El input {} no tiene el output que gasta", index))
+            })
+    }
+
+    /// Devuelve el script que gasta el input, a partir del pubkey script del output que gasta y del
+    /// redeem script y witness script del input. Devuelve error si no es un tipo de script soportado
+    fn input_script(
+        &self,
+        index: usize,
+        previous_output: &TxOut,
+    ) -> Result<InputScript, Box<dyn Error>> {
+        let input = &self.inputs[index];
+        let previous_pubkey_script = previous_output.get_pub_key_script();
+        let (kind, script) = match ScriptType::classify(previous_pubkey_script) {
+            ScriptType::P2pkh(hash) => return Ok(InputScript::P2pkh(hash)),
+            ScriptType::P2wpkh(hash) => return Ok(InputScript::P2wpkh(hash)),
+            ScriptType::P2wsh(_) => (MultisigKind::P2wsh, input.witness_script.as_ref()),
+            ScriptType::P2sh(_) if input.witness_script.is_some() => {
+                (MultisigKind::P2shP2wsh, input.witness_script.as_ref())
+            }
+            ScriptType::P2sh(_) => (MultisigKind::P2sh, input.redeem_script.as_ref()),
+            _ => return Err(unsupported_input(index)),
+        };
+        let (required, pubkeys) = match script.map(|script| ScriptType::classify(script)) {
+            Some(ScriptType::Multisig { required, pubkeys }) => (required, pubkeys),
+            _ => return Err(unsupported_input(index)),
+        };
+        let mut compressed_pubkeys = Vec::new();
+        for pubkey in pubkeys {
+            compressed_pubkeys.push(pubkey.try_into().map_err(|_| unsupported_input(index))?);
+        }
+        let multisig = MultisigScript {
+            kind,
+            required,
+            pubkeys: compressed_pubkeys,
+        };
+        if multisig.script_pubkey() != *previous_pubkey_script {
+            return Err(invalid_psbt(&format!(
+                "El script del input {} no corresponde al output que gasta",
+                index
+            )));
+        }
+        Ok(InputScript::Multisig(multisig))
+    }
+
+    /// Devuelve el mensaje que se firma en el input, con el sighash original o el de BIP143 segun el script
+    fn message_to_sign(
+        &self,
+        index: usize,
+        script: &InputScript,
+        previous_output: &TxOut,
+    ) -> [u8; 32] {
+        let tx = &self.unsigned_tx;
+        match script {
+            InputScript::P2pkh(_) => {
+                tx.generate_message_to_sign(index, previous_output.get_pub_key_script())
+            }
+            InputScript::P2wpkh(_) => tx.segwit_v0_message_to_sign(
+                index,
+                &p2wpkh_script::script_code(previous_output.get_pub_key_script()),
+                previous_output.value(),
+            ),
+            InputScript::Multisig(multisig) => {
+                tx.multisig_message_to_sign(index, multisig, previous_output.value())
+            }
+        }
+    }
+}
+
+impl InputScript {
+    /// Devuelve true si la clave publica puede firmar el input
+    fn is_signed_by(&self, public_key: &[u8; 33]) -> bool {
+        match self {
+            InputScript::P2pkh(hash) | InputScript::P2wpkh(hash) => hash_160(public_key) == *hash,
+            InputScript::Multisig(multisig) => multisig.pubkeys.contains(public_key),
+        }
+    }
+}
+
+impl PsbtInput {
+    /// Devuelve true si el input ya tiene su signature script o witness final
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_script_witness.is_some()
+    }
+
+    /// Agrega los datos del otro input que este no tiene
+    fn combine(&mut self, other: PsbtInput) {
+        self.non_witness_utxo = self.non_witness_utxo.take().or(other.non_witness_utxo);
+        self.witness_utxo = self.witness_utxo.take().or(other.witness_utxo);
+        merge_unknown(&mut self.partial_sigs, other.partial_sigs);
+        self.sighash_type = self.sighash_type.or(other.sighash_type);
+        self.redeem_script = self.redeem_script.take().or(other.redeem_script);
+        self.witness_script = self.witness_script.take().or(other.witness_script);
+        self.final_script_sig = self.final_script_sig.take().or(other.final_script_sig);
+        self.final_script_witness = self
+            .final_script_witness
+            .take()
+            .or(other.final_script_witness);
+        merge_unknown(&mut self.unknown, other.unknown);
+    }
+
+    fn from_map(map: KeyValueMap) -> Result<PsbtInput, Box<dyn Error>> {
+        let mut input = PsbtInput::default();
+        for (key, value) in map {
+            match (key[0], key.len()) {
+                (PSBT_IN_NON_WITNESS_UTXO, 1) => {
+                    input.non_witness_utxo =
+                        Some(Transaction::unmarshalling(&value, &mut 0).map_err(invalid_psbt)?)
+                }
+                (PSBT_IN_WITNESS_UTXO, 1) => {
+                    input.witness_utxo =
+                        Some(TxOut::unmarshalling(&value, &mut 0).map_err(invalid_psbt)?)
+                }
+                (PSBT_IN_PARTIAL_SIG, _) => {
+                    input.partial_sigs.insert(key[1..].to_vec(), value);
+                }
+                (PSBT_IN_SIGHASH_TYPE, 1) => input.sighash_type = Some(read_u32(&value)?),
+                (PSBT_IN_REDEEM_SCRIPT, 1) => input.redeem_script = Some(value),
+                (PSBT_IN_WITNESS_SCRIPT, 1) => input.witness_script = Some(value),
+                (PSBT_IN_FINAL_SCRIPTSIG, 1) => input.final_script_sig = Some(value),
+                (PSBT_IN_FINAL_SCRIPTWITNESS, 1) => {
+                    input.final_script_witness = Some(read_witness(&value)?)
+                }
+                _ => {
+                    input.unknown.insert(key, value);
+                }
+            }
+        }
+        Ok(input)
+    }
+
+    fn to_map(&self) -> KeyValueMap {
+        let mut map = self.unknown.clone();
+        if let Some(previous_tx) = &self.non_witness_utxo {
+            let mut raw_tx = Vec::new();
+            previous_tx.marshalling(&mut raw_tx);
+            map.insert(vec![PSBT_IN_NON_WITNESS_UTXO], raw_tx);
+        }
+        if let Some(witness_utxo) = &self.witness_utxo {
+            let mut raw_tx_out = Vec::new();
+            witness_utxo.marshalling(&mut raw_tx_out);
+            map.insert(vec![PSBT_IN_WITNESS_UTXO], raw_tx_out);
+        }
+        for (public_key, signature) in &self.partial_sigs {
+            let mut key = vec![PSBT_IN_PARTIAL_SIG];
+            key.extend_from_slice(public_key);
+            map.insert(key, signature.clone());
+        }
+        if let Some(sighash_type) = self.sighash_type {
+            map.insert(
+                vec![PSBT_IN_SIGHASH_TYPE],
+                sighash_type.to_le_bytes().to_vec(),
+            );
+        }
+        insert_optional(&mut map, PSBT_IN_REDEEM_SCRIPT, &self.redeem_script);
+        insert_optional(&mut map, PSBT_IN_WITNESS_SCRIPT, &self.witness_script);
+        insert_optional(&mut map, PSBT_IN_FINAL_SCRIPTSIG, &self.final_script_sig);
+        if let Some(witness) = &self.final_script_witness {
+            let mut txin = TxIn::incomplete_txin(Outpoint::new([0; 32], 0));
+            txin.set_witness(witness.clone());
+            let mut raw_witness = Vec::new();
+            txin.marshalling_witness(&mut raw_witness);
+            map.insert(vec![PSBT_IN_FINAL_SCRIPTWITNESS], raw_witness);
+        }
+        map
+    }
+}
+
+impl PsbtOutput {
+    fn from_map(map: KeyValueMap) -> PsbtOutput {
+        let mut output = PsbtOutput::default();
+        for (key, value) in map {
+            match (key[0], key.len()) {
+                (PSBT_OUT_REDEEM_SCRIPT, 1) => output.redeem_script = Some(value),
+                (PSBT_OUT_WITNESS_SCRIPT, 1) => output.witness_script = Some(value),
+                _ => {
+                    output.unknown.insert(key, value);
+                }
+            }
+        }
+        output
+    }
+
+    fn to_map(&self) -> KeyValueMap {
+        let mut map = self.unknown.clone();
+        insert_optional(&mut map, PSBT_OUT_REDEEM_SCRIPT, &self.redeem_script);
+        insert_optional(&mut map, PSBT_OUT_WITNESS_SCRIPT, &self.witness_script);
+        map
+    }
+}
+
+/// Combina el PSBT con los demas recibidos. Devuelve error si no hay ninguno o son de transacciones distintas
+pub fn combine_psbts(psbts: Vec<Psbt>) -> Result<Psbt, Box<dyn Error>> {
+    let mut psbts = psbts.into_iter();
+    let mut combined = psbts
+        .next()
+        .ok_or_else(|| invalid_psbt("No se recibió ningún PSBT para combinar"))?;
+    for psbt in psbts {
+        combined.combine(psbt)?;
+    }
+    Ok(combined)
+}
+
+fn insert_optional(map: &mut KeyValueMap, key_type: u8, value: &Option<Vec<u8>>) {
+    if let Some(value) = value {
+        map.insert(vec![key_type], value.clone());
+    }
+}
+
+/// Agrega al mapa las entradas del otro que no tiene
+fn merge_unknown(map: &mut BTreeMap<Vec<u8>, Vec<u8>>, other: BTreeMap<Vec<u8>, Vec<u8>>) {
+    for (key, value) in other {
+        map.entry(key).or_insert(value);
+    }
+}
+
+/// Lee la cantidad de mapas recibida. Actualiza el offset
+fn read_maps(
+    bytes: &[u8],
+    offset: &mut usize,
+    count: usize,
+) -> Result<Vec<KeyValueMap>, Box<dyn Error>> {
+    (0..count).map(|_| read_map(bytes, offset)).collect()
+}
+
+/// Lee un mapa de claves y valores, que termina con una clave vacia. Actualiza el offset.
+/// Devuelve error si los bytes no alcanzan o hay claves repetidas
+fn read_map(bytes: &[u8], offset: &mut usize) -> Result<KeyValueMap, Box<dyn Error>> {
+    let mut map = KeyValueMap::new();
+    loop {
+        let key_len = read_compact_size(bytes, offset)? as usize;
+        if key_len == 0 {
+            return Ok(map);
+        }
+        let key = read_bytes(bytes, offset, key_len)?.to_vec();
+        let value_len = read_compact_size(bytes, offset)? as usize;
+        let value = read_bytes(bytes, offset, value_len)?.to_vec();
+        if map.insert(key, value).is_some() {
+            return Err(invalid_psbt("El PSBT tiene claves repetidas"));
+        }
+    }
+}
+
+/// Serializa el mapa de claves y valores y el separador que lo termina
+fn write_map(bytes: &mut Vec<u8>, map: &KeyValueMap) {
+    for (key, value) in map {
+        bytes.extend_from_slice(&CompactSizeUint::new(key.len() as u128).marshalling());
+        bytes.extend_from_slice(key);
+        bytes.extend_from_slice(&CompactSizeUint::new(value.len() as u128).marshalling());
+        bytes.extend_from_slice(value);
+    }
+    bytes.push(0x00);
+}
+
+fn read_compact_size(bytes: &[u8], offset: &mut usize) -> Result<u64, Box<dyn Error>> {
+    let first_byte = read_bytes(bytes, offset, 1)?[0];
+    let len = match first_byte {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        _ => return Ok(first_byte as u64),
+    };
+    let mut value = [0; 8];
+    value[..len].copy_from_slice(read_bytes(bytes, offset, len)?);
+    Ok(u64::from_le_bytes(value))
+}
+
+fn read_bytes<'a>(
+    bytes: &'a [u8],
+    offset: &mut usize,
+    len: usize,
+) -> Result<&'a [u8], Box<dyn Error>> {
+    let read = bytes
+        .get(*offset..offset.saturating_add(len))
+        .ok_or_else(|| invalid_psbt("Los bytes del PSBT están incompletos"))?;
+    *offset += len;
+    Ok(read)
+}
+
+fn read_u32(value: &[u8]) -> Result<u32, Box<dyn Error>> {
+    let bytes: [u8; 4] = value
+        .try_into()
+        .map_err(|_| invalid_psbt("Un campo de 4 bytes del PSBT tiene otro largo"))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Lee un witness serializado: la cantidad de elementos y cada elemento precedido por su largo
+fn read_witness(value: &[u8]) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let mut offset = 0;
+    let count = read_compact_size(value, &mut offset)?;
+    let mut witness = Vec::new();
+    for _ in 0..count {
+        let len = read_compact_size(value, &mut offset)? as usize;
+        witness.push(read_bytes(value, &mut offset, len)?.to_vec());
+    }
+    Ok(witness)
+}
+
+fn unsupported_input(index: usize) -> Box<dyn Error> {
+    invalid_psbt(&format!(
+        "El input {} no es P2PKH, P2WPKH ni un multisig P2SH o P2WSH",
+        index
+    ))
+}
+
+fn invalid_psbt(message: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        address::Address, address_decoder, balance::SpendPolicy,
+        hd_wallet::hd_keychain::DerivationScheme, transactions::recipient::Recipient,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    };
+
+    /// Devuelve la cuenta con un output de 10000 satoshis a su direccion
+    fn account_with_funds(mut account: Account) -> Result<Account, Box<dyn Error>> {
+        let script = Address::parse(&account.address)?.script_pubkey();
+        let tx_out = TxOut::new(10000, CompactSizeUint::new(script.len() as u128), script);
+        let utxo_set = HashMap::from([([1; 32], UtxoTuple::new([1; 32], vec![(tx_out, 0)]))]);
+        account.set_utxos(Arc::new(RwLock::new(utxo_set)))?;
+        Ok(account)
+    }
+
+    #[test]
+    fn bip174_psbt_is_parsed_and_serialized_back() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un PSBT valido de los vectores de BIP174, con un input con la transaccion previa completa
+        let encoded = "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA";
+        // WHEN: se decodifica
+        let psbt = Psbt::from_base64(encoded)?;
+        // THEN: se obtienen la transaccion y el output que gasta el input, y se vuelve a codificar igual
+        assert_eq!(psbt.version, 0);
+        assert_eq!(psbt.unsigned_tx.tx_in.len(), 1);
+        assert_eq!(psbt.unsigned_tx.tx_out.len(), 2);
+        assert!(psbt.inputs[0].non_witness_utxo.is_some());
+        assert_eq!(psbt.previous_output(0)?.value(), 200000000);
+        assert_eq!(psbt.to_base64(), encoded);
+        Ok(())
+    }
+
+    #[test]
+    fn multisig_psbt_is_signed_by_two_cosigners_combined_and_finalized(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: dos firmantes de un multisig P2WSH 2 de 3 con fondos y el PSBT que crea el primero
+        let private_keys: Vec<String> = [[1; 32], [2; 32], [3; 32]]
+            .iter()
+            .map(address_decoder::encode_wif_private_key)
+            .collect();
+        let pubkeys = private_keys
+            .iter()
+            .map(|key| address_decoder::get_pubkey_compressed(key))
+            .collect::<Result<Vec<[u8; 33]>, Box<dyn Error>>>()?;
+        let new_cosigner = |private_key: &String| {
+            Account::new_multisig(private_key.clone(), MultisigKind::P2wsh, 2, pubkeys.clone())
+                .and_then(account_with_funds)
+        };
+        let mut first_cosigner = new_cosigner(&private_keys[0])?;
+        let second_cosigner = new_cosigner(&private_keys[1])?;
+        let mut psbt = first_cosigner.create_psbt(
+            &[Recipient::new(
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+                4000,
+                false,
+            )],
+            5.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        let mut second_psbt = Psbt::from_base64(&psbt.to_base64())?;
+        // WHEN: cada uno lo firma por separado y se combinan
+        assert_eq!(psbt.sign(&first_cosigner)?, 1);
+        assert!(psbt.clone().finalize().is_err());
+        assert_eq!(second_psbt.sign(&second_cosigner)?, 1);
+        let mut combined = combine_psbts(vec![psbt, second_psbt])?;
+        combined.finalize()?;
+        // THEN: la transaccion extraida tiene las dos firmas en el witness y es valida
+        let transaction = combined.extract()?;
+        assert_eq!(transaction.tx_in[0].witness.len(), 4);
+        assert!(combined.fee()? as f64 / transaction.vsize() as f64 >= 5.0);
+        assert!(second_cosigner.is_fully_signed(&transaction));
+        Ok(())
+    }
+
+    #[test]
+    fn p2wpkh_psbt_version_2_is_signed_finalized_and_extracted() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta BIP84 con fondos y un PSBT version 2 de una transaccion suya
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut account = account_with_funds(Account::from_mnemonic(
+            mnemonic,
+            "",
+            DerivationScheme::Bip84,
+            0,
+        )?)?;
+        let mut psbt = account.create_psbt(
+            &[Recipient::new(
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+                4000,
+                false,
+            )],
+            5.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        psbt.version = 2;
+        // WHEN: se exporta, se importa, se firma y se finaliza
+        let mut imported = Psbt::deserialize(&psbt.serialize())?;
+        assert_eq!(imported, psbt);
+        assert_eq!(imported.sign(&account)?, 1);
+        imported.finalize()?;
+        // THEN: la firma va en el witness y la transaccion extraida es valida
+        let transaction = imported.extract()?;
+        assert!(transaction.tx_in[0].signature_script.get_bytes().is_empty());
+        assert_eq!(transaction.tx_in[0].witness.len(), 2);
+        assert!(account.is_fully_signed(&transaction));
+        Ok(())
+    }
+
+    #[test]
+    fn psbts_of_different_transactions_are_not_combined() -> Result<(), Box<dyn Error>> {
+        // GIVEN: dos PSBT de transacciones con distinto monto
+        let account = Account::new(
+            "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR".to_string(),
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+        )?;
+        let mut account = account_with_funds(account)?;
+        let mut psbt = account.create_psbt(
+            &[Recipient::new(
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+                4000,
+                false,
+            )],
+            5.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        let other = account.create_psbt(
+            &[Recipient::new(
+                "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+                3000,
+                false,
+            )],
+            5.0,
+            &[],
+            &SpendPolicy::new(0, 0),
+        )?;
+        // WHEN: se combinan
+        // THEN: devuelve error
+        assert!(psbt.combine(other).is_err());
+        assert!(Psbt::from_base64("cHNidP8=").is_err());
+        Ok(())
+    }
+}
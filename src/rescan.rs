@@ -0,0 +1,185 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    time::Duration,
+};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use crate::{blocks::block_header::BlockHeader, utxo_tuple::UtxoTuple};
+
+/// Cantidad de bloques que se le piden juntos a un nodo al reescanear la cadena
+pub const RESCAN_BATCH_SIZE: usize = 16;
+/// Tiempo que se espera a que lleguen los bloques pedidos antes de pedirselos a otro nodo
+pub const RESCAN_BLOCK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Cantidad de veces que se piden los bloques que faltan antes de cancelar el rescan
+pub const RESCAN_MAX_ATTEMPTS: usize = 3;
+/// Cada cuantos bloques escaneados se le informa el progreso a la UI
+pub const RESCAN_PROGRESS_INTERVAL: usize = 50;
+
+/// Desde donde se reescanea la cadena: una altura o el dia en que se creo la clave (key birthday)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RescanStart {
+    Height(u32),
+    /// Timestamp del comienzo del dia, en segundos
+    Date(u32),
+}
+
+impl RescanStart {
+    /// Recibe una altura o una fecha con el formato AAAA-MM-DD y devuelve desde donde reescanear.
+    /// Devuelve error si no es ninguna de las dos
+    pub fn parse(text: &str) -> Result<RescanStart, Box<dyn Error>> {
+        let text = text.trim();
+        if let Ok(height) = text.parse::<u32>() {
+            return Ok(RescanStart::Height(height));
+        }
+        let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+            std::io::Error::other(format!(
+                "Invalid rescan start '{}'. Enter a block height or a date as YYYY-MM-DD",
+                text
+            ))
+        })?;
+        let start_of_day = date
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| std::io::Error::other(format!("Invalid rescan date '{}'", text)))?;
+        Ok(RescanStart::Date(
+            Utc.from_utc_datetime(&start_of_day).timestamp() as u32,
+        ))
+    }
+
+    /// Devuelve la altura desde la que se reescanea con los headers de la cadena: la altura recibida o la del
+    /// primer bloque minado desde la fecha. Si ningun bloque es posterior a la fecha devuelve la altura siguiente al tip
+    pub fn start_height(&self, headers: &[BlockHeader]) -> usize {
+        match self {
+            RescanStart::Height(height) => (*height as usize).min(headers.len()),
+            RescanStart::Date(timestamp) => headers
+                .iter()
+                .position(|header| header.time >= *timestamp)
+                .unwrap_or(headers.len()),
+        }
+    }
+}
+
+/// Bloques viejos que se le pidieron a los nodos para reescanear la cadena. Cuando llegan se guardan con los
+/// bloques descargados sin conectarlos a la cadena, porque su utxo set ya se armo
+#[derive(Debug, Clone, Default)]
+pub struct RescanRequests {
+    pending: HashSet<[u8; 32]>,
+}
+
+impl RescanRequests {
+    /// Agrega los hashes de los bloques pedidos
+    pub fn request(&mut self, hashes: &[[u8; 32]]) {
+        self.pending.extend(hashes.iter().copied());
+    }
+
+    /// Marca como recibido el bloque con el hash recibido. Devuelve true si se lo habia pedido para el rescan
+    pub fn deliver(&mut self, hash: &[u8; 32]) -> bool {
+        self.pending.remove(hash)
+    }
+
+    /// Devuelve los hashes de los bloques que todavia no llegaron
+    pub fn pending(&self) -> Vec<[u8; 32]> {
+        self.pending.iter().copied().collect()
+    }
+
+    /// Olvida los bloques pedidos, por ejemplo si se cancela el rescan
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Agrega al utxo set recibido los outputs de las monedas que no estaban, por ejemplo los de bloques anteriores a
+/// la fecha de inicio del proyecto. Devuelve la cantidad de outputs agregados
+pub fn add_coins_to_utxo_set(
+    utxo_set: &mut HashMap<[u8; 32], UtxoTuple>,
+    coins: Vec<UtxoTuple>,
+) -> usize {
+    let mut added = 0;
+    for coin in coins {
+        let utxo = utxo_set
+            .entry(coin.hash)
+            .or_insert_with(|| UtxoTuple::new(coin.hash, vec![]));
+        if utxo.height.is_none() {
+            utxo.height = coin.height;
+        }
+        for (tx_out, index) in coin.utxo_set {
+            if !utxo.utxo_set.iter().any(|(_, known)| *known == index) {
+                utxo.utxo_set.push((tx_out, index));
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compact_size_uint::CompactSizeUint, transactions::tx_out::TxOut};
+
+    fn header_with_time(time: u32) -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            previous_block_header_hash: [0; 32],
+            merkle_root_hash: [0; 32],
+            time,
+            n_bits: 0,
+            nonce: 0,
+        }
+    }
+
+    fn tx_out(value: i64) -> TxOut {
+        TxOut::new(value, CompactSizeUint::new(0), vec![])
+    }
+
+    #[test]
+    fn test_el_inicio_del_rescan_se_lee_como_altura_o_como_fecha() {
+        // GIVEN: una altura, una fecha y un texto invalido
+        // WHEN: se leen como inicio del rescan
+        // THEN: la fecha se convierte al timestamp del comienzo del dia y el texto invalido da error
+        assert_eq!(
+            RescanStart::parse(" 2500000 ").unwrap(),
+            RescanStart::Height(2500000)
+        );
+        assert_eq!(
+            RescanStart::parse("2023-04-10").unwrap(),
+            RescanStart::Date(1681084800)
+        );
+        assert!(RescanStart::parse("ayer").is_err());
+    }
+
+    #[test]
+    fn test_la_altura_de_inicio_de_una_fecha_es_la_del_primer_bloque_posterior() {
+        // GIVEN: headers con timestamps crecientes
+        let headers: Vec<BlockHeader> = [100, 200, 300, 400]
+            .into_iter()
+            .map(header_with_time)
+            .collect();
+        // WHEN: se calcula la altura de inicio de distintas fechas y alturas
+        // THEN: es la del primer header minado desde la fecha, o la altura recibida sin pasarse del tip
+        assert_eq!(RescanStart::Date(250).start_height(&headers), 2);
+        assert_eq!(RescanStart::Date(50).start_height(&headers), 0);
+        assert_eq!(RescanStart::Date(500).start_height(&headers), 4);
+        assert_eq!(RescanStart::Height(1).start_height(&headers), 1);
+        assert_eq!(RescanStart::Height(10).start_height(&headers), 4);
+    }
+
+    #[test]
+    fn test_solo_se_agregan_al_utxo_set_los_outputs_que_no_estaban() {
+        // GIVEN: un utxo set con un output de una transaccion y monedas de esa y de otra transaccion
+        let mut utxo_set = HashMap::new();
+        utxo_set.insert([1; 32], UtxoTuple::new([1; 32], vec![(tx_out(10), 0)]));
+        let coins = vec![
+            UtxoTuple::new([1; 32], vec![(tx_out(10), 0), (tx_out(20), 1)]),
+            UtxoTuple::new([2; 32], vec![(tx_out(30), 0)]).with_height(7),
+        ];
+        // WHEN: se agregan las monedas al utxo set
+        let added = add_coins_to_utxo_set(&mut utxo_set, coins);
+        // THEN: se agregan solo los outputs nuevos, con la altura de su bloque
+        assert_eq!(added, 2);
+        assert_eq!(utxo_set[&[1; 32]].balance(), 30);
+        assert_eq!(utxo_set[&[2; 32]].balance(), 30);
+        assert_eq!(utxo_set[&[2; 32]].height, Some(7));
+    }
+}
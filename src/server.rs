@@ -13,12 +13,16 @@ use crate::{
     config::Config,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::UIEvent,
+    handshake::{
+        read_verack_and_peer_features, write_feefilter_if_supported, write_peer_features_messages,
+    },
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{
-        message_header::{read_verack_message, write_verack_message},
+        message_header::write_verack_message,
         version_message::{get_version_message, VersionMessage},
     },
     node::Node,
+    peer_state::PeerState,
 };
 
 const LOCALHOST: &str = "127.0.0.1";
@@ -132,17 +136,31 @@ impl NodeServer {
         let socket_addr = stream
             .peer_addr()
             .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
-        VersionMessage::read_from(log_sender, &mut stream)
+        let peer_version = VersionMessage::read_from(log_sender, &mut stream)
             .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?;
+        let mut peer_state = PeerState::new(
+            config.protocol_version,
+            peer_version.payload.version,
+            peer_version.payload.services,
+        );
         let version_message = get_version_message(config, socket_addr, local_ip_addr)
             .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
         version_message
             .write_to(&mut stream)
             .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
-        read_verack_message(log_sender, &mut stream)
+        write_peer_features_messages(&mut stream, &peer_state)
+            .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+        read_verack_and_peer_features(log_sender, &mut stream, &mut peer_state)
             .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?;
         write_verack_message(&mut stream)
             .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+        write_feefilter_if_supported(config, &mut stream, &peer_state)
+            .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+        node.node_pointers
+            .peers_state
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .insert(socket_addr, peer_state);
         write_in_log(
             &log_sender.info_log_sender,
             format!("Handshake con nodo {:?} realizado con exito!", socket_addr).as_str(),
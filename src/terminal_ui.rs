@@ -1,10 +1,28 @@
-use crate::{gtk::ui_events::UIEvent, wallet};
+use crate::{
+    account::{bytes_to_hex_string, hex_string_to_bytes},
+    address::Address,
+    address_decoder::AddressType,
+    coin_control::{format_coin_id, parse_coin_id, CoinAction, CoinId},
+    fee_estimator::FeeTarget,
+    gtk::ui_events::UIEvent,
+    hd_wallet::hd_keychain::DerivationScheme,
+    message_signing::verify_message,
+    psbt::{combine_psbts, Psbt},
+    rescan::RescanStart,
+    transactions::{recipient::Recipient, script::multisig_script::MultisigKind},
+    wallet,
+};
 use ::gtk::glib;
 use wallet::Wallet;
 
 /// Muestra las opciones para interactuar con el programa desde la terminal, espera algun comando
 /// y lo handlea o muestra un mensaje de error
 pub fn terminal_ui(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    if wallet.is_locked().unwrap_or(false) {
+        println!(
+            "LA WALLET GUARDADA ESTA BLOQUEADA. Desbloqueela (opcion 14) para cargar sus cuentas"
+        );
+    }
     show_options();
     loop {
         let mut input = String::new();
@@ -30,6 +48,54 @@ pub fn terminal_ui(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Walle
                         4 => {
                             handle_poi_request(wallet);
                         }
+                        5 => {
+                            handle_create_hd_account_request(ui_sender, wallet);
+                        }
+                        6 => {
+                            handle_restore_hd_account_request(ui_sender, wallet);
+                        }
+                        7 => {
+                            handle_new_receive_address_request(ui_sender, wallet);
+                        }
+                        8 => {
+                            handle_add_multisig_account_request(ui_sender, wallet);
+                        }
+                        9 => {
+                            handle_sign_multisig_transaction_request(ui_sender, wallet);
+                        }
+                        10 => {
+                            handle_psbt_request(ui_sender, wallet);
+                        }
+                        11 => {
+                            handle_bump_fee_request(ui_sender, wallet);
+                        }
+                        12 => {
+                            handle_accelerate_transaction_request(ui_sender, wallet);
+                        }
+                        13 => {
+                            handle_coin_control_request(ui_sender, wallet);
+                        }
+                        14 => {
+                            handle_wallet_lock_request(ui_sender, wallet);
+                        }
+                        15 => {
+                            handle_create_account_request(ui_sender, wallet);
+                        }
+                        16 => {
+                            handle_add_watch_only_account_request(ui_sender, wallet);
+                        }
+                        17 => {
+                            handle_descriptors_request(ui_sender, wallet);
+                        }
+                        18 => {
+                            handle_transaction_history_request(ui_sender, wallet);
+                        }
+                        19 => {
+                            handle_rescan_request(ui_sender, wallet);
+                        }
+                        20 => {
+                            handle_message_signing_request(ui_sender, wallet);
+                        }
                         _ => {
                             println!("Número no reconocido. Inténtalo de nuevo! \n");
                         }
@@ -55,6 +121,22 @@ fn show_options() {
     println!("2: Mostrar balance de las cuentas");
     println!("3: Hacer transaccion desde una cuenta");
     println!("4: Prueba de inclusion de una transaccion en un bloque");
+    println!("5: Crear una wallet HD con un mnemonic nuevo");
+    println!("6: Restaurar una wallet HD desde su mnemonic");
+    println!("7: Obtener una nueva direccion de recepcion de una cuenta");
+    println!("8: Añadir una cuenta multisig a la wallet");
+    println!("9: Firmar una transaccion multisig");
+    println!("10: Crear, firmar, combinar o finalizar un PSBT");
+    println!("11: Aumentar el fee de una transaccion pendiente (RBF)");
+    println!("12: Acelerar una transaccion pendiente recibida (CPFP)");
+    println!("13: Ver, bloquear o congelar las monedas de una cuenta (coin control)");
+    println!("14: Definir la passphrase de la wallet, desbloquearla o bloquearla");
+    println!("15: Crear una cuenta con una clave privada nueva");
+    println!("16: Añadir una cuenta watch-only (direccion, script o clave publica extendida)");
+    println!("17: Importar una cuenta desde un output descriptor o exportar sus descriptors");
+    println!("18: Ver el historial de transacciones de una cuenta");
+    println!("19: Reescanear la cadena para una cuenta desde una altura o fecha");
+    println!("20: Firmar un mensaje con una direccion de una cuenta o verificar una firma");
     println!("-----------------------------------------------------------\n");
 }
 
@@ -72,23 +154,285 @@ fn handle_transaccion_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet:
     wallet
         .change_account(ui_sender, account_index)
         .unwrap_or_else(|err| println!("Error al cambiar de cuenta: {}", err));
-    let address_receiver: String = read_input("Dirección del receptor: ").unwrap_or_else(|err| {
-        println!("Error al leer la entrada: {}", err);
-        String::new()
-    });
-    let amount: i64 = read_input("Cantidad(Satoshis): ").unwrap_or_else(|err| {
+    let recipients = match read_recipients() {
+        Some(recipients) => recipients,
+        None => return,
+    };
+    let fee_rate = read_fee_rate(wallet);
+    let coins = match read_chosen_coins(wallet) {
+        Some(coins) => coins,
+        None => return,
+    };
+    println!("Realizando y broadcasteando transaccion...");
+    match wallet.make_transaction(ui_sender, &recipients, fee_rate, &coins) {
+        Err(error) => println!("Error al realizar la transacción: {}", error),
+        Ok(partial_transaction) => show_signed_transaction(partial_transaction),
+    }
+}
+
+/// Muestra las transacciones pendientes de la cuenta que elija el usuario, le pide el hash de la que
+/// quiere reemplazar y el nuevo fee rate, y hace el broadcast del reemplazo. En caso de error lo imprime
+fn handle_bump_fee_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    let tx_hash = match read_pending_transaction(ui_sender, wallet) {
+        Some(tx_hash) => tx_hash,
+        None => return,
+    };
+    let fee_rate = read_fee_rate(wallet);
+    println!("Reemplazando y broadcasteando transaccion...");
+    match wallet.bump_fee(ui_sender, &tx_hash, fee_rate) {
+        Err(error) => println!("Error al aumentar el fee: {}", error),
+        Ok(fee) => println!(
+            "TRANSACCION REEMPLAZADA CORRECTAMENTE! Comision: {} satoshis",
+            fee
+        ),
+    }
+}
+
+/// Muestra las transacciones pendientes de la cuenta que elija el usuario, le pide el hash de la que
+/// quiere acelerar y el fee rate del paquete, y hace el broadcast de la transaccion hija. En caso de error lo imprime
+fn handle_accelerate_transaction_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    let tx_hash = match read_pending_transaction(ui_sender, wallet) {
+        Some(tx_hash) => tx_hash,
+        None => return,
+    };
+    let fee_rate = read_fee_rate(wallet);
+    println!("Creando y broadcasteando transaccion hija...");
+    match wallet.accelerate_transaction(ui_sender, &tx_hash, fee_rate) {
+        Err(error) => println!("Error al acelerar la transaccion: {}", error),
+        Ok(package_fee_rate) => println!(
+            "TRANSACCION ACELERADA CORRECTAMENTE! El paquete paga {:.2} sat/vB",
+            package_fee_rate
+        ),
+    }
+}
+
+/// Le pide al usuario la cuenta, le muestra sus transacciones pendientes y le pide el hash de una.
+/// Devuelve None si no se pudo seleccionar la cuenta
+fn read_pending_transaction(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) -> Option<String> {
+    wallet.show_indexes_of_accounts().ok()?;
+    let account_index: usize = read_input("Índice de la cuenta: ").unwrap_or_else(|err| {
         println!("Error al leer la entrada: {}", err);
         0
     });
-    let fee: i64 = read_input("Tarifa(Satoshis): ").unwrap_or_else(|err| {
+    if let Err(err) = wallet.change_account(ui_sender, account_index) {
+        println!("Error al cambiar de cuenta: {}", err);
+        return None;
+    }
+    println!("Transacciones pendientes:");
+    for history_entry in wallet.get_transactions().unwrap_or_default() {
+        let entry = history_entry.entry;
+        if entry.block.is_none() {
+            println!(
+                "  {} ({} satoshis)",
+                entry.transaction.hex_hash(),
+                entry.net_amount()
+            );
+        }
+    }
+    Some(read_input("Hash de la transaccion: ").unwrap_or_default())
+}
+
+/// Le pide al usuario los pagos de la transaccion hasta que ingrese una direccion vacia. Por cada uno
+/// pide la direccion, el monto y si la comision se descuenta del monto. Devuelve None si alguna direccion es invalida
+fn read_recipients() -> Option<Vec<Recipient>> {
+    let mut recipients = Vec::new();
+    loop {
+        let prompt = if recipients.is_empty() {
+            "Dirección del receptor: "
+        } else {
+            "Dirección de otro receptor (vacio para terminar): "
+        };
+        let address_receiver: String = read_input(prompt).unwrap_or_default();
+        if address_receiver.is_empty() && !recipients.is_empty() {
+            return Some(recipients);
+        }
+        if let Err(err) = Address::parse(&address_receiver) {
+            println!("La dirección {} es inválida: {}", address_receiver, err);
+            return None;
+        }
+        let amount: i64 = read_input("Cantidad(Satoshis): ").unwrap_or_else(|err| {
+            println!("Error al leer la entrada: {}", err);
+            0
+        });
+        let subtract_fee: String =
+            read_input("¿Descontar la comision de este monto? (s/n): ").unwrap_or_default();
+        recipients.push(Recipient::new(
+            address_receiver,
+            amount,
+            subtract_fee.eq_ignore_ascii_case("s"),
+        ));
+    }
+}
+
+/// Muestra el fee rate estimado para cada objetivo de confirmacion y le pide al usuario que elija uno
+/// o que ingrese el fee rate en sat/vB. Devuelve 0 si la entrada es invalida
+fn read_fee_rate(wallet: &Wallet) -> f64 {
+    println!("Fee rates estimados:");
+    for target in FeeTarget::all() {
+        match wallet.estimate_fee_rate(target) {
+            Ok(fee_rate) => println!(
+                "  {}: {:.1} sat/vB (~{} bloques)",
+                target.name(),
+                fee_rate,
+                target.blocks()
+            ),
+            Err(err) => println!("  {}: error al estimar ({})", target.name(), err),
+        }
+    }
+    let input: String = read_input("Tarifa (sat/vB) o objetivo (Economical, Normal, Priority): ")
+        .unwrap_or_default();
+    if let Some(target) = FeeTarget::from_name(&input) {
+        return wallet.estimate_fee_rate(target).unwrap_or(0.0);
+    }
+    input.trim().parse().unwrap_or_else(|err| {
         println!("Error al leer la entrada: {}", err);
-        0
-    });
-    println!("Realizando y broadcasteando transaccion...");
-    if let Err(error) = wallet.make_transaction(ui_sender, &address_receiver, amount, fee) {
-        println!("Error al realizar la transacción: {}", error);
-    } else {
-        println!("TRANSACCION REALIZADA CORRECTAMENTE!");
+        0.0
+    })
+}
+
+/// Muestra las monedas de la cuenta actual y le pide al usuario las que quiere gastar, separadas por comas.
+/// Devuelve una lista vacia si no elige ninguna, para que se elijan automaticamente, o None si alguna es invalida
+fn read_chosen_coins(wallet: &Wallet) -> Option<Vec<CoinId>> {
+    if !show_coins(wallet) {
+        return None;
+    }
+    let input: String =
+        read_input("Monedas a gastar como txid:indice separadas por comas (vacio para elegirlas automaticamente): ")
+            .unwrap_or_default();
+    let mut coins = Vec::new();
+    for coin in input.split(',').filter(|coin| !coin.trim().is_empty()) {
+        match parse_coin_id(coin) {
+            Ok(coin) => coins.push(coin),
+            Err(err) => {
+                println!("La moneda {} es inválida: {}", coin.trim(), err);
+                return None;
+            }
+        }
+    }
+    Some(coins)
+}
+
+/// Muestra las monedas de la cuenta actual con su monto, confirmaciones, direccion y estado.
+/// Devuelve false si no se pudieron obtener
+fn show_coins(wallet: &Wallet) -> bool {
+    let coins = match wallet.list_coins() {
+        Ok(coins) => coins,
+        Err(err) => {
+            println!("Error al obtener las monedas: {}", err);
+            return false;
+        }
+    };
+    println!("Monedas de la cuenta:");
+    for coin in coins {
+        let state = match (coin.locked, coin.frozen) {
+            (_, true) => " [congelada]",
+            (true, false) => " [bloqueada]",
+            _ => "",
+        };
+        println!(
+            "  {} - {:.8} tBTC - {} confirmaciones - {}{}",
+            format_coin_id(&coin.id),
+            coin.value as f64 / 1e8,
+            coin.confirmations,
+            coin.address,
+            state
+        );
+    }
+    true
+}
+
+/// Le pide al usuario la cuenta, le muestra sus monedas y le pide la moneda a bloquear, desbloquear,
+/// congelar o descongelar. En caso de error lo imprime
+fn handle_coin_control_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    if !select_account(ui_sender, wallet) || !show_coins(wallet) {
+        return;
+    }
+    println!("1: Bloquear una moneda para que no se use automaticamente");
+    println!("2: Desbloquear una moneda");
+    println!("3: Congelar una moneda para que no se gaste");
+    println!("4: Descongelar una moneda");
+    let action = match read_input("Operacion: ").unwrap_or(0) {
+        1 => CoinAction::Lock,
+        2 => CoinAction::Unlock,
+        3 => CoinAction::Freeze,
+        4 => CoinAction::Unfreeze,
+        _ => {
+            println!("Operacion invalida\n");
+            return;
+        }
+    };
+    let coin: String = read_input("Moneda (txid:indice): ").unwrap_or_default();
+    let result = parse_coin_id(&coin).and_then(|coin| wallet.change_coin_state(coin, action));
+    match result {
+        Ok(()) => println!("ESTADO DE LA MONEDA ACTUALIZADO!"),
+        Err(err) => println!("Error al actualizar la moneda: {}", err),
+    }
+}
+
+/// Muestra el estado de la wallet y le pide al usuario que defina o cambie su passphrase, la desbloquee
+/// o la bloquee. En caso de error lo imprime
+fn handle_wallet_lock_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    match (wallet.has_passphrase(), wallet.is_locked()) {
+        (Ok(false), _) => {
+            println!(
+                "La wallet no tiene passphrase: sus cuentas no se guardan al cerrar el programa"
+            )
+        }
+        (Ok(true), Ok(true)) => println!("La wallet esta bloqueada"),
+        (Ok(true), Ok(false)) => println!("La wallet esta desbloqueada"),
+        (Err(err), _) | (_, Err(err)) => println!("Error al leer el estado de la wallet: {}", err),
+    }
+    println!("1: Definir o cambiar la passphrase de la wallet");
+    println!("2: Desbloquear la wallet");
+    println!("3: Bloquear la wallet");
+    let result = match read_input("Operacion: ").unwrap_or(0) {
+        1 => wallet.set_passphrase(&read_wallet_passphrase()),
+        2 => wallet.unlock(ui_sender, &read_wallet_passphrase()),
+        3 => wallet.lock().map_err(|err| err.into()),
+        _ => {
+            println!("Operacion invalida\n");
+            return;
+        }
+    };
+    match result {
+        Ok(()) => println!("ESTADO DE LA WALLET ACTUALIZADO!"),
+        Err(err) => println!("Error al actualizar la wallet: {}", err),
+    }
+}
+
+/// Avisa que las cuentas agregadas no se guardan al cerrar el programa si la wallet no tiene passphrase
+fn warn_if_accounts_are_not_saved(wallet: &Wallet) {
+    if let Ok(false) = wallet.has_passphrase() {
+        println!("ATENCION: la wallet no tiene passphrase, la cuenta no se guardara al cerrar el programa hasta definirla (opcion 14)\n");
+    }
+}
+
+/// Le pide al usuario la passphrase con la que se cifran las claves de la wallet
+fn read_wallet_passphrase() -> String {
+    println!("Ingrese la passphrase de la wallet: ");
+    let mut passphrase_input = String::new();
+    if let Err(error) = std::io::stdin().read_line(&mut passphrase_input) {
+        println!("Error al leer la entrada: {}", error);
+    }
+    passphrase_input.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Muestra que la transaccion se realizo, o la transaccion parcialmente firmada si faltan las firmas
+/// de otros firmantes del multisig
+fn show_signed_transaction(partial_transaction: Option<String>) {
+    match partial_transaction {
+        None => println!("TRANSACCION REALIZADA CORRECTAMENTE!"),
+        Some(raw_transaction) => {
+            println!("FALTAN LAS FIRMAS DE OTROS FIRMANTES DEL MULTISIG.");
+            println!("Envieles la transaccion parcialmente firmada para que la firmen:");
+            println!("{}\n", raw_transaction);
+        }
     }
 }
 
@@ -135,6 +479,7 @@ fn handle_add_account_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet:
                             "CUENTA -- {} -- AÑADIDA CORRECTAMENTE A LA WALLET!\n",
                             address
                         );
+                        warn_if_accounts_are_not_saved(wallet);
                     }
                 }
                 Err(error) => {
@@ -148,6 +493,310 @@ fn handle_add_account_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet:
     }
 }
 
+/// Le pide al usuario el tipo de direccion, crea una cuenta con una clave privada nueva y la muestra
+/// por pantalla con una advertencia para que el usuario la respalde
+fn handle_create_account_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    let address_type: String =
+        read_input("Tipo de direccion (legacy o segwit): ").unwrap_or_default();
+    let address_type = match AddressType::parse(&address_type) {
+        Ok(address_type) => address_type,
+        Err(err) => {
+            println!("ERROR: {err}\n");
+            return;
+        }
+    };
+    println!("Creando la cuenta...\n");
+    match wallet.create_account(ui_sender, address_type) {
+        Ok((wif_private_key, address)) => {
+            println!("CUENTA {} CREADA CORRECTAMENTE!\n", address);
+            println!("Guarde esta clave privada en un lugar seguro, es la unica forma de recuperar los fondos de la cuenta:");
+            println!("{}\n", wif_private_key);
+            warn_if_accounts_are_not_saved(wallet);
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario la direccion, el pubkey script o la clave publica extendida a observar y agrega la cuenta
+/// watch-only. Si es una clave publica extendida tambien le pide el tipo de direcciones que se derivan
+fn handle_add_watch_only_account_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    let watched: String = read_input(
+        "Direccion, script en hexadecimal, clave publica extendida (tpub) o descriptor: ",
+    )
+    .unwrap_or_default();
+    let scheme = if watched.trim().starts_with("tpub") {
+        match read_derivation_scheme() {
+            Some(scheme) => scheme,
+            None => return,
+        }
+    } else {
+        DerivationScheme::Bip84
+    };
+    match wallet.add_watch_only_account(ui_sender, &watched, scheme) {
+        Ok(address) => {
+            println!("CUENTA WATCH-ONLY {} AGREGADA CORRECTAMENTE!", address);
+            println!("Puede ver su balance y crear PSBT sin firmar (opcion 10), pero no firmar transacciones\n");
+            warn_if_accounts_are_not_saved(wallet);
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario la cuenta y muestra su historial de transacciones recibidas, enviadas y a si misma,
+/// con el monto neto, la comision, las confirmaciones y el bloque de cada una
+fn handle_transaction_history_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    if !select_account(ui_sender, wallet) {
+        return;
+    }
+    let history = wallet.get_transactions().unwrap_or_default();
+    if history.is_empty() {
+        println!("La cuenta no tiene transacciones\n");
+        return;
+    }
+    println!("HISTORIAL DE TRANSACCIONES:");
+    for history_entry in history {
+        let entry = history_entry.entry;
+        let fee = entry
+            .fee
+            .map_or("desconocida".to_string(), |fee| format!("{} satoshis", fee));
+        println!(
+            "{} {}: {} satoshis, comision {}",
+            entry.transaction.hex_hash(),
+            entry.kind().name(),
+            entry.net_amount(),
+            fee
+        );
+        match entry.block {
+            Some(block) => {
+                let mut block_hash = block.hash;
+                block_hash.reverse();
+                println!(
+                    "    {} confirmaciones, bloque {} ({})",
+                    history_entry.confirmations,
+                    block.height,
+                    bytes_to_hex_string(&block_hash)
+                );
+            }
+            None => println!("    pendiente, sin confirmaciones"),
+        }
+    }
+    println!();
+}
+
+/// Le pide al usuario la cuenta y la altura o fecha desde la que reescanear la cadena, y reescanea para
+/// recuperar las monedas y el historial de la cuenta anteriores a la fecha de inicio. Imprime el resultado
+fn handle_rescan_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    if !select_account(ui_sender, wallet) {
+        return;
+    }
+    let start: String = match read_input("Altura o fecha (AAAA-MM-DD) desde la que reescanear: ") {
+        Ok(start) => start,
+        Err(err) => {
+            println!("Error al leer la entrada: {}", err);
+            return;
+        }
+    };
+    let start = match RescanStart::parse(&start) {
+        Ok(start) => start,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    println!("Reescaneando la cadena, puede tardar si hay que descargar bloques...");
+    match wallet.rescan(ui_sender, start) {
+        Ok(scanned) => println!(
+            "RESCAN TERMINADO! Se escanearon {} bloques. Balance: {} satoshis\n",
+            scanned,
+            wallet
+                .get_current_account()
+                .map_or(0, |account| account.balance())
+        ),
+        Err(err) => println!("Error al reescanear la cadena: {}", err),
+    }
+}
+
+/// Le pide al usuario la operacion con mensajes firmados: elegir una cuenta y firmar un mensaje con una de sus
+/// direcciones para probar que la controla, o verificar la firma de un mensaje con una direccion
+fn handle_message_signing_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    println!("1: Firmar un mensaje (legacy en direcciones P2PKH, BIP322 en P2WPKH)");
+    println!("2: Verificar la firma de un mensaje");
+    match read_input("Operacion: ").unwrap_or(0) {
+        1 => {
+            if !select_account(ui_sender, wallet) {
+                return;
+            }
+            let address: String =
+                read_input("Direccion (vacio para la de la cuenta): ").unwrap_or_default();
+            let message: String = read_input("Mensaje: ").unwrap_or_default();
+            match wallet.sign_message(&address, &message) {
+                Ok(signature) => println!("FIRMA DEL MENSAJE:\n{}\n", signature),
+                Err(err) => println!("Error al firmar el mensaje: {}", err),
+            }
+        }
+        2 => {
+            let address: String = read_input("Direccion: ").unwrap_or_default();
+            let message: String = read_input("Mensaje: ").unwrap_or_default();
+            let signature: String = read_input("Firma (base64): ").unwrap_or_default();
+            match verify_message(&address, &message, &signature) {
+                Ok(true) => println!("LA FIRMA ES VALIDA para la direccion {}\n", address),
+                Ok(false) => println!("LA FIRMA NO ES VALIDA para la direccion {}\n", address),
+                Err(err) => println!("Error al verificar la firma: {}", err),
+            }
+        }
+        _ => println!("Operacion invalida\n"),
+    }
+}
+
+/// Le pide al usuario la operacion con descriptors: importar una cuenta desde un output descriptor, o elegir
+/// una cuenta y mostrar sus descriptors con o sin las claves privadas
+fn handle_descriptors_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    println!("1: Importar una cuenta desde un output descriptor");
+    println!("2: Exportar los descriptors publicos de una cuenta");
+    println!("3: Exportar los descriptors de una cuenta con sus claves privadas");
+    let include_private = match read_input("Operacion: ").unwrap_or(0) {
+        1 => {
+            let descriptor: String = read_input("Descriptor: ").unwrap_or_default();
+            match wallet.import_descriptor(ui_sender, &descriptor) {
+                Ok(address) => {
+                    println!("CUENTA {} IMPORTADA CORRECTAMENTE!\n", address);
+                    warn_if_accounts_are_not_saved(wallet);
+                }
+                Err(err) => println!("ERROR: {err}\n"),
+            }
+            return;
+        }
+        2 => false,
+        3 => true,
+        _ => {
+            println!("Operacion invalida\n");
+            return;
+        }
+    };
+    if !select_account(ui_sender, wallet) {
+        return;
+    }
+    match wallet.export_descriptors(include_private) {
+        Ok(descriptors) => {
+            println!("DESCRIPTORS DE LA CUENTA:");
+            for descriptor in descriptors {
+                println!("{}", descriptor);
+            }
+            if include_private {
+                println!("Contienen las claves privadas de la cuenta: no los comparta\n");
+            }
+        }
+        Err(err) => println!("Error al exportar los descriptors: {}", err),
+    }
+}
+
+/// Le pide al usuario la cantidad de palabras, la passphrase y el tipo de direcciones, crea las cuentas HD
+/// con un mnemonic nuevo y lo muestra por pantalla para que el usuario lo guarde
+fn handle_create_hd_account_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    let word_count: usize = read_input("Cantidad de palabras del mnemonic (12 o 24): ")
+        .unwrap_or_else(|err| {
+            println!("Error al leer la entrada: {}", err);
+            12
+        });
+    let passphrase = read_passphrase();
+    let scheme = match read_derivation_scheme() {
+        Some(scheme) => scheme,
+        None => return,
+    };
+    println!("Creando la wallet HD...\n");
+    match wallet.create_hd_account(ui_sender, word_count, &passphrase, scheme) {
+        Ok(mnemonic) => {
+            println!("WALLET HD CREADA CORRECTAMENTE!\n");
+            println!("Guarde estas palabras en un lugar seguro, son necesarias para restaurar la wallet:");
+            println!("{}\n", mnemonic);
+            warn_if_accounts_are_not_saved(wallet);
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario el mnemonic, la passphrase y el tipo de direcciones y restaura las cuentas HD,
+/// buscando los fondos de cada una
+fn handle_restore_hd_account_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    println!("Ingrese el MNEMONIC (palabras separadas por espacios): ");
+    let mut mnemonic_input = String::new();
+    if let Err(error) = std::io::stdin().read_line(&mut mnemonic_input) {
+        println!("Error al leer la entrada: {}", error);
+        return;
+    }
+    let passphrase = read_passphrase();
+    let scheme = match read_derivation_scheme() {
+        Some(scheme) => scheme,
+        None => return,
+    };
+    println!("Restaurando la wallet HD y buscando sus fondos...\n");
+    match wallet.restore_hd_account(ui_sender, mnemonic_input.trim(), &passphrase, scheme) {
+        Ok(accounts) => {
+            println!("SE RESTAURARON {} CUENTAS HD!\n", accounts);
+            warn_if_accounts_are_not_saved(wallet);
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario el indice de una cuenta y muestra una direccion nueva para recibir fondos en ella
+fn handle_new_receive_address_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    if wallet.show_indexes_of_accounts().is_err() {
+        return;
+    }
+    let account_index: usize = read_input("Índice de la cuenta: ").unwrap_or_else(|err| {
+        println!("Error al leer la entrada: {}", err);
+        0
+    });
+    if let Err(err) = wallet.change_account(ui_sender, account_index) {
+        println!("Error al cambiar de cuenta: {}", err);
+        return;
+    }
+    match wallet.new_receive_address() {
+        Ok(address) => println!("DIRECCION DE RECEPCION: {}\n", address),
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario la passphrase opcional del mnemonic
+fn read_passphrase() -> String {
+    println!("Ingrese la passphrase (deje vacio si no tiene): ");
+    let mut passphrase_input = String::new();
+    if let Err(error) = std::io::stdin().read_line(&mut passphrase_input) {
+        println!("Error al leer la entrada: {}", error);
+    }
+    passphrase_input.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Le pide al usuario el tipo de direcciones de la wallet HD. Devuelve None si la opcion es invalida
+fn read_derivation_scheme() -> Option<DerivationScheme> {
+    let purpose: u32 =
+        read_input("Tipo de direcciones: 44 (P2PKH, BIP44) o 84 (P2WPKH, BIP84): ").unwrap_or(0);
+    match purpose {
+        44 => Some(DerivationScheme::Bip44),
+        84 => Some(DerivationScheme::Bip84),
+        _ => {
+            println!("Tipo de direcciones invalido\n");
+            None
+        }
+    }
+}
+
 /// Muestra el balance de todas las cuentas de la wallet por pantalla
 fn handle_balance_request(wallet: &mut Wallet) {
     println!("Calculando el balance de las cuentas...\n");
@@ -197,3 +846,218 @@ fn handle_poi_request(wallet: &mut Wallet) {
         }
     }
 }
+
+/// Le pide al usuario el tipo de multisig, la cantidad de firmas requeridas, las claves publicas de los
+/// firmantes y su private key, y agrega la cuenta multisig a la wallet mostrando su direccion
+fn handle_add_multisig_account_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    let kind = match read_input::<String>("Tipo de multisig (p2sh, p2sh-p2wsh o p2wsh): ")
+        .map_err(|err| err.to_string())
+        .and_then(|kind| MultisigKind::parse(&kind).map_err(|err| err.to_string()))
+    {
+        Ok(kind) => kind,
+        Err(err) => {
+            println!("ERROR: {err}\n");
+            return;
+        }
+    };
+    let required: u8 = match read_input("Cantidad de firmas requeridas: ") {
+        Ok(required) => required,
+        Err(err) => {
+            println!("Error al leer la entrada: {}", err);
+            return;
+        }
+    };
+    let pubkeys = match read_pubkeys() {
+        Some(pubkeys) => pubkeys,
+        None => return,
+    };
+    let wif_private_key: String =
+        read_input("Ingrese su PRIVATE KEY en formato WIF: ").unwrap_or_default();
+    match wallet.add_multisig_account(ui_sender, wif_private_key, kind, required, pubkeys) {
+        Ok(address) => {
+            println!(
+                "CUENTA MULTISIG -- {} -- AÑADIDA CORRECTAMENTE A LA WALLET!\n",
+                address
+            );
+            warn_if_accounts_are_not_saved(wallet);
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Le pide al usuario las claves publicas comprimidas de los firmantes en hexadecimal, separadas por comas.
+/// Devuelve None si alguna es invalida
+fn read_pubkeys() -> Option<Vec<[u8; 33]>> {
+    let input: String = read_input(
+        "Claves publicas comprimidas de los firmantes en hexadecimal, separadas por comas: ",
+    )
+    .unwrap_or_default();
+    let mut pubkeys = Vec::new();
+    for pubkey in input.split(',') {
+        match hex_string_to_bytes(pubkey).map(<[u8; 33]>::try_from) {
+            Ok(Ok(pubkey)) => pubkeys.push(pubkey),
+            _ => {
+                println!("La clave publica {} es invalida\n", pubkey.trim());
+                return None;
+            }
+        }
+    }
+    Some(pubkeys)
+}
+
+/// Le pide al usuario el indice de la cuenta multisig y la transaccion parcialmente firmada en hexadecimal,
+/// y le agrega la firma de la cuenta. Si se completan las firmas requeridas se hace el broadcast
+fn handle_sign_multisig_transaction_request(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    if wallet.show_indexes_of_accounts().is_err() {
+        return;
+    }
+    let account_index: usize = read_input("Índice de la cuenta multisig: ").unwrap_or_else(|err| {
+        println!("Error al leer la entrada: {}", err);
+        0
+    });
+    if let Err(err) = wallet.change_account(ui_sender, account_index) {
+        println!("Error al cambiar de cuenta: {}", err);
+        return;
+    }
+    let raw_transaction: String =
+        read_input("Transaccion parcialmente firmada en hexadecimal: ").unwrap_or_default();
+    match wallet.sign_multisig_transaction(ui_sender, &raw_transaction) {
+        Err(error) => println!("Error al firmar la transacción: {}", error),
+        Ok(partial_transaction) => show_signed_transaction(partial_transaction),
+    }
+}
+
+/// Muestra las operaciones que se pueden hacer con un PSBT, le pide al usuario que elija una y la realiza
+fn handle_psbt_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    println!("1: Crear un PSBT desde una cuenta");
+    println!("2: Firmar un PSBT con una cuenta");
+    println!("3: Combinar PSBTs firmados por distintos firmantes");
+    println!("4: Finalizar un PSBT y broadcastear la transaccion");
+    let option: u32 = read_input("Operacion: ").unwrap_or(0);
+    match option {
+        1 => handle_create_psbt_request(ui_sender, wallet),
+        2 => handle_sign_psbt_request(ui_sender, wallet),
+        3 => handle_combine_psbts_request(),
+        4 => handle_finalize_psbt_request(ui_sender, wallet),
+        _ => println!("Operacion invalida\n"),
+    }
+}
+
+/// Le pide al usuario la cuenta y los datos de la transaccion y crea el PSBT sin firmar
+fn handle_create_psbt_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    if !select_account(ui_sender, wallet) {
+        return;
+    }
+    let recipients = match read_recipients() {
+        Some(recipients) => recipients,
+        None => return,
+    };
+    let fee_rate = read_fee_rate(wallet);
+    let coins = match read_chosen_coins(wallet) {
+        Some(coins) => coins,
+        None => return,
+    };
+    match wallet.create_psbt(&recipients, fee_rate, &coins) {
+        Ok(psbt) => export_psbt(&psbt),
+        Err(err) => println!("Error al crear el PSBT: {}", err),
+    }
+}
+
+/// Le pide al usuario la cuenta y el PSBT y le agrega las firmas de la cuenta
+fn handle_sign_psbt_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    if !select_account(ui_sender, wallet) {
+        return;
+    }
+    let mut psbt = match import_psbt() {
+        Some(psbt) => psbt,
+        None => return,
+    };
+    match wallet.sign_psbt(&mut psbt) {
+        Ok(signatures) => {
+            println!("SE AGREGARON {} FIRMAS AL PSBT!", signatures);
+            export_psbt(&psbt);
+        }
+        Err(err) => println!("Error al firmar el PSBT: {}", err),
+    }
+}
+
+/// Le pide al usuario la cantidad de PSBTs y cada uno de ellos, y los combina
+fn handle_combine_psbts_request() {
+    let count: usize = read_input("Cantidad de PSBTs a combinar: ").unwrap_or(0);
+    let mut psbts = Vec::new();
+    for _ in 0..count {
+        match import_psbt() {
+            Some(psbt) => psbts.push(psbt),
+            None => return,
+        }
+    }
+    match combine_psbts(psbts) {
+        Ok(psbt) => export_psbt(&psbt),
+        Err(err) => println!("Error al combinar los PSBTs: {}", err),
+    }
+}
+
+/// Le pide al usuario el PSBT, lo finaliza y broadcastea la transaccion firmada
+fn handle_finalize_psbt_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    let mut psbt = match import_psbt() {
+        Some(psbt) => psbt,
+        None => return,
+    };
+    println!("Finalizando el PSBT y broadcasteando la transaccion...");
+    match wallet.finalize_and_broadcast_psbt(ui_sender, &mut psbt) {
+        Ok(transaction) => println!(
+            "TRANSACCION {} REALIZADA CORRECTAMENTE!",
+            transaction.hex_hash()
+        ),
+        Err(err) => println!("Error al finalizar el PSBT: {}", err),
+    }
+}
+
+/// Le pide al usuario el indice de una cuenta y la selecciona. Devuelve false si no se pudo seleccionar
+fn select_account(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) -> bool {
+    if wallet.show_indexes_of_accounts().is_err() {
+        return false;
+    }
+    let account_index: usize = read_input("Índice de la cuenta: ").unwrap_or_else(|err| {
+        println!("Error al leer la entrada: {}", err);
+        0
+    });
+    if let Err(err) = wallet.change_account(ui_sender, account_index) {
+        println!("Error al cambiar de cuenta: {}", err);
+        return false;
+    }
+    true
+}
+
+/// Le pide al usuario un PSBT en base64 o la ruta del archivo que lo contiene y lo decodifica.
+/// Devuelve None si es invalido
+fn import_psbt() -> Option<Psbt> {
+    let input: String = read_input("PSBT en base64 o ruta del archivo: ").unwrap_or_default();
+    match Psbt::import(&input) {
+        Ok(psbt) => Some(psbt),
+        Err(err) => {
+            println!("El PSBT es invalido: {}\n", err);
+            None
+        }
+    }
+}
+
+/// Muestra el PSBT en base64 y, si el usuario ingresa una ruta, lo guarda en ese archivo
+fn export_psbt(psbt: &Psbt) {
+    println!("PSBT:\n{}\n", psbt.to_base64());
+    println!("Ruta del archivo donde guardar el PSBT (deje vacio para no guardarlo): ");
+    let mut path = String::new();
+    if std::io::stdin().read_line(&mut path).is_err() || path.trim().is_empty() {
+        return;
+    }
+    match psbt.write_to_file(path.trim()) {
+        Ok(_) => println!("PSBT GUARDADO EN {}\n", path.trim()),
+        Err(err) => println!("Error al guardar el PSBT: {}\n", err),
+    }
+}
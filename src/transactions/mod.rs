@@ -1,4 +1,5 @@
 pub mod outpoint;
+pub mod recipient;
 pub mod script;
 pub mod transaction;
 pub mod tx_in;
@@ -0,0 +1,24 @@
+/// Representa un pago de una transaccion: la address receptora, el monto en satoshis y si la
+/// comision se descuenta de ese monto en lugar de pagarse con el cambio
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipient {
+    pub address: String,
+    pub amount: i64,
+    pub subtract_fee: bool,
+}
+
+impl Recipient {
+    /// Crea el pago a la address con el monto recibido
+    pub fn new(address: String, amount: i64, subtract_fee: bool) -> Self {
+        Recipient {
+            address,
+            amount,
+            subtract_fee,
+        }
+    }
+}
+
+/// Devuelve la suma de los montos de los pagos recibidos
+pub fn total_amount(recipients: &[Recipient]) -> i64 {
+    recipients.iter().map(|recipient| recipient.amount).sum()
+}
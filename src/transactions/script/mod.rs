@@ -1,4 +1,7 @@
+pub mod multisig_script;
 pub mod p2pkh_script;
+pub mod p2wpkh_script;
 pub mod pubkey;
 pub mod script_opcodes;
+pub mod script_type;
 pub mod sig_script;
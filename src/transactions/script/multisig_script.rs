@@ -0,0 +1,348 @@
+use super::{script_opcodes::ScriptOpcodes, script_type::ScriptType, sig_script::SigScript};
+use crate::{address::Address, address_decoder::hash_160, transactions::tx_in::TxIn};
+use bitcoin_hashes::{sha256, Hash};
+use k256::ecdsa;
+use std::error::Error;
+
+/// Maxima cantidad de claves de un multisig. Con 15 claves comprimidas el redeem script
+/// ocupa 513 bytes, por debajo del limite de 520 bytes de los pushes de P2SH
+const MAX_PUBKEYS: usize = 15;
+/// Largo de una clave publica comprimida
+const COMPRESSED_PUBKEY_LEN: usize = 33;
+/// Maximo largo de un push que se hace con el largo como opcode
+const MAX_DIRECT_PUSH: usize = 75;
+
+// Cada tipo de multisig gasta el mismo script, OP_m <pubkey>... OP_n OP_CHECKMULTISIG, pero lo ubica distinto:
+//
+// P2SH:        scriptPubKey: OP_HASH160 <hash160(redeem script)> OP_EQUAL
+//              scriptSig:    OP_0 <sig>... <redeem script>
+// P2WSH:       scriptPubKey: OP_0 <sha256(witness script)>
+//              witness:      <> <sig>... <witness script>
+// P2SH-P2WSH:  scriptPubKey: OP_HASH160 <hash160(OP_0 <sha256(witness script)>)> OP_EQUAL
+//              scriptSig:    <OP_0 <sha256(witness script)>>
+//              witness:      <> <sig>... <witness script>
+//
+// El OP_0 (o el elemento vacio del witness) es por el bug de OP_CHECKMULTISIG que consume un elemento de mas.
+
+/// Forma en que se paga al multisig
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MultisigKind {
+    P2sh,
+    P2shP2wsh,
+    P2wsh,
+}
+
+impl MultisigKind {
+    /// Recibe el nombre del tipo de multisig (p2sh, p2sh-p2wsh o p2wsh) sin importar mayusculas.
+    /// Devuelve error si no es ninguno de ellos
+    pub fn parse(name: &str) -> Result<MultisigKind, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "p2sh" => Ok(MultisigKind::P2sh),
+            "p2sh-p2wsh" => Ok(MultisigKind::P2shP2wsh),
+            "p2wsh" => Ok(MultisigKind::P2wsh),
+            _ => Err(Box::new(std::io::Error::other(
+                "El tipo de multisig debe ser p2sh, p2sh-p2wsh o p2wsh",
+            ))),
+        }
+    }
+
+    /// Devuelve el nombre del tipo de multisig, el mismo que recibe parse
+    pub fn name(&self) -> &'static str {
+        match self {
+            MultisigKind::P2sh => "p2sh",
+            MultisigKind::P2shP2wsh => "p2sh-p2wsh",
+            MultisigKind::P2wsh => "p2wsh",
+        }
+    }
+}
+
+/// Multisig m de n: se necesitan `required` firmas de las claves publicas para gastar sus outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultisigScript {
+    pub kind: MultisigKind,
+    pub required: u8,
+    pub pubkeys: Vec<[u8; COMPRESSED_PUBKEY_LEN]>,
+}
+
+impl MultisigScript {
+    /// Crea el multisig con las claves publicas comprimidas recibidas. Las ordena como indica BIP67
+    /// para que todos los firmantes obtengan la misma direccion sin importar el orden en que las ingresen.
+    /// Devuelve error si hay claves invalidas o repetidas, o si la cantidad de firmas no es valida
+    pub fn new(
+        kind: MultisigKind,
+        required: u8,
+        mut pubkeys: Vec<[u8; COMPRESSED_PUBKEY_LEN]>,
+    ) -> Result<MultisigScript, Box<dyn Error>> {
+        pubkeys.sort();
+        if pubkeys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(Box::new(std::io::Error::other(
+                "El multisig tiene claves publicas repetidas",
+            )));
+        }
+        if required == 0 || required as usize > pubkeys.len() || pubkeys.len() > MAX_PUBKEYS {
+            return Err(Box::new(std::io::Error::other(format!(
+                "El multisig debe requerir entre 1 y {} firmas de a lo sumo {} claves",
+                pubkeys.len(),
+                MAX_PUBKEYS
+            ))));
+        }
+        for pubkey in &pubkeys {
+            ecdsa::VerifyingKey::from_sec1_bytes(pubkey)?;
+        }
+        Ok(MultisigScript {
+            kind,
+            required,
+            pubkeys,
+        })
+    }
+
+    /// Devuelve el multisig que gasta el input recibido a partir del redeem script o witness script
+    /// que lleva el input, o None si el input no gasta un multisig que pague al pubkey script recibido
+    pub fn from_input(previous_pubkey_script: &[u8], txin: &TxIn) -> Option<MultisigScript> {
+        let (kind, script) = match ScriptType::classify(previous_pubkey_script) {
+            ScriptType::P2wsh(_) => (MultisigKind::P2wsh, txin.witness.last()?.clone()),
+            ScriptType::P2sh(_) if txin.witness.is_empty() => (
+                MultisigKind::P2sh,
+                parse_pushes(txin.signature_script.get_bytes())?
+                    .last()?
+                    .clone(),
+            ),
+            ScriptType::P2sh(_) => (MultisigKind::P2shP2wsh, txin.witness.last()?.clone()),
+            _ => return None,
+        };
+        let (required, pubkeys) = match ScriptType::classify(&script) {
+            ScriptType::Multisig { required, pubkeys } => (required, pubkeys),
+            _ => return None,
+        };
+        let mut compressed_pubkeys = Vec::new();
+        for pubkey in pubkeys {
+            compressed_pubkeys.push(pubkey.try_into().ok()?);
+        }
+        // se respeta el orden de las claves del script, que es el que se usa para verificar las firmas
+        let multisig = MultisigScript {
+            kind,
+            required,
+            pubkeys: compressed_pubkeys,
+        };
+        if multisig.script_pubkey() != previous_pubkey_script {
+            return None;
+        }
+        Some(multisig)
+    }
+
+    /// Devuelve el script OP_m <pubkey>... OP_n OP_CHECKMULTISIG.
+    /// Es el redeem script en P2SH y el witness script en P2WSH y P2SH-P2WSH
+    pub fn redeem_script(&self) -> Vec<u8> {
+        let mut script = vec![ScriptOpcodes::OP_1 + self.required - 1];
+        for pubkey in &self.pubkeys {
+            script.push(COMPRESSED_PUBKEY_LEN as u8);
+            script.extend_from_slice(pubkey);
+        }
+        script.push(ScriptOpcodes::OP_1 + self.pubkeys.len() as u8 - 1);
+        script.push(ScriptOpcodes::OP_CHECKMULTISIG);
+        script
+    }
+
+    /// Devuelve el witness program version 0 del witness script: OP_0 <sha256(witness script)>
+    pub fn witness_program(&self) -> Vec<u8> {
+        Address::P2wsh(*sha256::Hash::hash(&self.redeem_script()).as_byte_array()).script_pubkey()
+    }
+
+    /// Devuelve la direccion del multisig
+    pub fn address(&self) -> Address {
+        match self.kind {
+            MultisigKind::P2sh => Address::P2sh(hash_160(&self.redeem_script())),
+            MultisigKind::P2shP2wsh => Address::P2sh(hash_160(&self.witness_program())),
+            MultisigKind::P2wsh => {
+                Address::P2wsh(*sha256::Hash::hash(&self.redeem_script()).as_byte_array())
+            }
+        }
+    }
+
+    /// Devuelve el pubkey script de los outputs que pagan al multisig
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        self.address().script_pubkey()
+    }
+
+    /// Devuelve true si las firmas del multisig se hacen segun BIP143 y van en el witness
+    pub fn is_segwit(&self) -> bool {
+        self.kind != MultisigKind::P2sh
+    }
+
+    /// Devuelve las firmas que ya tiene el input, que puede estar parcialmente firmado
+    pub fn signatures(&self, txin: &TxIn) -> Vec<Vec<u8>> {
+        let items = if self.is_segwit() {
+            txin.witness.clone()
+        } else {
+            parse_pushes(txin.signature_script.get_bytes()).unwrap_or_default()
+        };
+        if items.len() < 2 {
+            return Vec::new();
+        }
+        // el ultimo elemento es el script, el resto son firmas o elementos vacios
+        items[..items.len() - 1]
+            .iter()
+            .filter(|item| !item.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    /// Recibe el mensaje firmado y las firmas de un input. Devuelve las firmas validas, a lo sumo
+    /// una por clave y `required` en total, en el orden de las claves como exige OP_CHECKMULTISIG
+    pub fn sort_signatures(&self, message: &[u8], signatures: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut sorted = Vec::new();
+        for pubkey in &self.pubkeys {
+            if sorted.len() == self.required as usize {
+                break;
+            }
+            if let Some(signature) = signatures.iter().find(|signature| {
+                SigScript::verify_sig(message, signature, pubkey).unwrap_or(false)
+            }) {
+                sorted.push(signature.clone());
+            }
+        }
+        sorted
+    }
+
+    /// Devuelve el signature script y el witness que gastan el multisig con las firmas recibidas,
+    /// que deben estar en el orden de las claves. Si faltan firmas el input queda parcialmente firmado
+    pub fn spending_data(&self, signatures: &[Vec<u8>]) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut stack = vec![vec![]];
+        stack.extend_from_slice(signatures);
+        stack.push(self.redeem_script());
+        match self.kind {
+            MultisigKind::P2sh => {
+                let mut sig_script = Vec::new();
+                for item in stack {
+                    sig_script.extend_from_slice(&push_data(&item));
+                }
+                (sig_script, vec![])
+            }
+            MultisigKind::P2shP2wsh => (push_data(&self.witness_program()), stack),
+            MultisigKind::P2wsh => (vec![], stack),
+        }
+    }
+
+    /// Recibe el input y el mensaje que firma. Devuelve true si tiene las firmas requeridas,
+    /// validas y en el orden de las claves
+    pub fn validate(&self, txin: &TxIn, message: &[u8]) -> bool {
+        let signatures = self.signatures(txin);
+        signatures.len() == self.required as usize
+            && self.sort_signatures(message, &signatures) == signatures
+            && self.spending_data(&signatures)
+                == (
+                    txin.signature_script.get_bytes().clone(),
+                    txin.witness.clone(),
+                )
+    }
+}
+
+/// Devuelve el push de los datos recibidos: OP_0 si estan vacios, el largo si ocupan hasta 75 bytes
+/// o el opcode OP_PUSHDATA correspondiente y el largo
+pub fn push_data(data: &[u8]) -> Vec<u8> {
+    let mut push = Vec::new();
+    match data.len() {
+        0 => push.push(ScriptOpcodes::OP_0),
+        len if len <= MAX_DIRECT_PUSH => push.push(len as u8),
+        len if len <= u8::MAX as usize => {
+            push.extend_from_slice(&[ScriptOpcodes::OP_PUSHDATA1, len as u8])
+        }
+        len => {
+            push.push(ScriptOpcodes::OP_PUSHDATA2);
+            push.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    push.extend_from_slice(data);
+    push
+}
+
+/// Devuelve los datos de cada push del script, o None si tiene algo que no es un push
+pub fn parse_pushes(script: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut pushes = Vec::new();
+    let mut offset = 0;
+    while offset < script.len() {
+        let opcode = script[offset];
+        offset += 1;
+        let len = match opcode {
+            ScriptOpcodes::OP_0 => 0,
+            len if len as usize <= MAX_DIRECT_PUSH => len as usize,
+            ScriptOpcodes::OP_PUSHDATA1 => {
+                offset += 1;
+                *script.get(offset - 1)? as usize
+            }
+            ScriptOpcodes::OP_PUSHDATA2 => {
+                offset += 2;
+                u16::from_le_bytes([*script.get(offset - 2)?, *script.get(offset - 1)?]) as usize
+            }
+            _ => return None,
+        };
+        pushes.push(script.get(offset..offset + len)?.to_vec());
+        offset += len;
+    }
+    Some(pushes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_decoder::{encode_wif_private_key, get_pubkey_compressed};
+
+    fn pubkeys() -> Result<Vec<[u8; 33]>, Box<dyn Error>> {
+        [[1; 32], [2; 32], [3; 32]]
+            .iter()
+            .map(|key| get_pubkey_compressed(&encode_wif_private_key(key)))
+            .collect()
+    }
+
+    #[test]
+    fn multisig_address_does_not_depend_on_the_order_of_the_keys() -> Result<(), Box<dyn Error>> {
+        // GIVEN: las mismas claves publicas en distinto orden
+        let mut reversed = pubkeys()?;
+        reversed.reverse();
+        for kind in [
+            MultisigKind::P2sh,
+            MultisigKind::P2shP2wsh,
+            MultisigKind::P2wsh,
+        ] {
+            // WHEN: se crean los multisig 2 de 3
+            let multisig = MultisigScript::new(kind, 2, pubkeys()?)?;
+            let reversed_multisig = MultisigScript::new(kind, 2, reversed.clone())?;
+            // THEN: tienen la misma direccion, del tipo que corresponde
+            assert_eq!(multisig.address(), reversed_multisig.address());
+            let address = multisig.address().to_string();
+            match kind {
+                MultisigKind::P2wsh => assert!(address.starts_with("tb1q") && address.len() == 62),
+                _ => assert!(address.starts_with('2')),
+            }
+        }
+        let redeem_script = MultisigScript::new(MultisigKind::P2sh, 2, pubkeys()?)?.redeem_script();
+        assert_eq!(ScriptType::classify(&redeem_script).name(), "MULTISIG");
+        assert_eq!(redeem_script[0], 0x52);
+        assert_eq!(redeem_script[redeem_script.len() - 2], 0x53);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_multisigs_are_rejected() -> Result<(), Box<dyn Error>> {
+        // GIVEN: multisig con mas firmas que claves, sin firmas, con claves repetidas y con una clave invalida
+        let keys = pubkeys()?;
+        // WHEN: se crean
+        // THEN: devuelven error
+        assert!(MultisigScript::new(MultisigKind::P2sh, 4, keys.clone()).is_err());
+        assert!(MultisigScript::new(MultisigKind::P2sh, 0, keys.clone()).is_err());
+        assert!(MultisigScript::new(MultisigKind::P2wsh, 1, vec![keys[0], keys[0]]).is_err());
+        assert!(MultisigScript::new(MultisigKind::P2wsh, 1, vec![[5; 33]]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pushes_are_encoded_and_parsed_back() {
+        // GIVEN: datos vacios, cortos y mas largos que 75 bytes
+        let data = vec![vec![], vec![1; 72], vec![2; 105], vec![3; 300]];
+        // WHEN: se codifican como pushes en un script
+        let script: Vec<u8> = data.iter().flat_map(|item| push_data(item)).collect();
+        // THEN: se vuelven a obtener los mismos datos
+        assert_eq!(parse_pushes(&script), Some(data));
+        assert_eq!(parse_pushes(&[ScriptOpcodes::OP_DUP]), None);
+    }
+}
@@ -14,14 +14,19 @@ const BYTES_TO_PUSH: u8 = 20;
 /// Genera el pubkey script a partir de la address comprimida.
 pub fn generate_pubkey_script(address: &str) -> Result<Vec<u8>, Box<dyn Error>> {
     let pubkey_hash = get_pubkey_hash_from_address(address)?;
+    Ok(generate_pubkey_script_from_hash(&pubkey_hash))
+}
+
+/// Genera el pubkey script a partir del hash160 de la clave publica.
+pub fn generate_pubkey_script_from_hash(pubkey_hash: &[u8]) -> Vec<u8> {
     let mut pk_script: Vec<u8> = Vec::new();
     pk_script.push(ScriptOpcodes::OP_DUP);
     pk_script.push(ScriptOpcodes::OP_HASH160);
     pk_script.push(BYTES_TO_PUSH);
-    pk_script.extend_from_slice(&pubkey_hash);
+    pk_script.extend_from_slice(pubkey_hash);
     pk_script.push(ScriptOpcodes::OP_EQUALVERIFY);
     pk_script.push(ScriptOpcodes::OP_CHECKSIG);
-    Ok(pk_script)
+    pk_script
 }
 
 /// Recibe el p2pkh_script y el sig_script.
@@ -110,7 +115,7 @@ mod test {
         let account = Account::new(private_key, address.to_string())?;
 
         let p2pkh_script = generate_pubkey_script(address)?;
-        let sig = SigScript::generate_sig_script(hash, &account)?;
+        let sig = SigScript::generate_sig_script(hash, &account, &p2pkh_script)?;
         let validation = p2pkh_script::validate(&p2pkh_script, sig.get_bytes())?;
 
         assert!(validation);
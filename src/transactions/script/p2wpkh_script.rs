@@ -0,0 +1,55 @@
+use super::{p2pkh_script, script_opcodes::ScriptOpcodes, sig_script::SigScript};
+use crate::address_decoder::{self, get_pubkey_hash_from_segwit_address};
+use std::error::Error;
+
+const BYTES_TO_PUSH: u8 = 20;
+// scriptPubKey: OP_0 <bytes_to_push> <pubKeyHash>
+// HEXA:         0x00 0x14            <pubKeyHash>
+// Largo bytes:  1 + 1 + 20 = 22
+// witness:      <sig> <pubKey>
+// El input que lo gasta tiene el signature script vacio y la firma y la clave publica en el witness (BIP141)
+
+/// Genera el pubkey script P2WPKH a partir de la address bech32.
+pub fn generate_pubkey_script(address: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let pubkey_hash = get_pubkey_hash_from_segwit_address(address)?;
+    Ok(generate_pubkey_script_from_hash(&pubkey_hash))
+}
+
+/// Genera el pubkey script P2WPKH a partir del hash160 de la clave publica
+pub fn generate_pubkey_script_from_hash(pubkey_hash: &[u8; 20]) -> Vec<u8> {
+    let mut pk_script: Vec<u8> = vec![ScriptOpcodes::OP_0, BYTES_TO_PUSH];
+    pk_script.extend_from_slice(pubkey_hash);
+    pk_script
+}
+
+/// Devuelve true si el pubkey script es P2WPKH
+pub fn is_p2wpkh(pubkey_script: &[u8]) -> bool {
+    pubkey_script.len() == 22
+        && pubkey_script[0] == ScriptOpcodes::OP_0
+        && pubkey_script[1] == BYTES_TO_PUSH
+}
+
+/// Devuelve el script code con el que se firma un input P2WPKH (BIP143):
+/// el script P2PKH del mismo pubkey hash
+pub fn script_code(p2wpkh_script: &[u8]) -> Vec<u8> {
+    p2pkh_script::generate_pubkey_script_from_hash(&p2wpkh_script[2..22])
+}
+
+/// Recibe el p2wpkh_script, el witness del input que lo gasta y el mensaje firmado (BIP143).
+/// Realiza la validación y devuelve true o false
+pub fn validate(
+    p2wpkh_script: &[u8],
+    witness: &[Vec<u8>],
+    message: &[u8; 32],
+) -> Result<bool, Box<dyn Error>> {
+    // 1) Chequeo que el script sea P2WPKH y que el witness tenga la firma y la clave publica comprimida
+    if !is_p2wpkh(p2wpkh_script) || witness.len() != 2 || witness[1].len() != 33 {
+        return Ok(false);
+    }
+    // 2) Chequeo que el hash160 de la clave publica coincida con el del script
+    if p2wpkh_script[2..22] != address_decoder::hash_160(&witness[1]) {
+        return Ok(false);
+    }
+    // 3) Chequeo la firma
+    SigScript::verify_sig(message, &witness[0], &witness[1])
+}
@@ -1,6 +1,4 @@
-use super::script_opcodes::ScriptOpcodes;
-use k256::sha2::Digest;
-use k256::sha2::Sha256;
+use super::script_type::ScriptType;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Pubkey {
@@ -14,30 +12,16 @@ impl Pubkey {
     pub fn bytes(&self) -> &Vec<u8> {
         &self.bytes
     }
-    /// Genera la address a partir del pubkey.
+    /// Devuelve el tipo del pubkey script
+    pub fn script_type(&self) -> ScriptType {
+        ScriptType::classify(&self.bytes)
+    }
+    /// Genera la address de testnet a partir del pubkey.
+    /// Devuelve error si el script no tiene una direccion asociada (multisig, OP_RETURN o no estandar)
     pub fn generate_address(&self) -> Result<String, &'static str> {
-        // vector que generara el address
-        let mut adress_bytes: Vec<u8> = vec![0x6f];
-        let bytes = &self.bytes;
-        let lenght: usize = bytes.len();
-        if lenght <= 3 {
-            return Err("El campo pubkey no tiene el largo esperado");
-        }
-
-        let first_byte = self.bytes[0];
-        if first_byte == 0x00 {
-            // se trata de una transanccion del tipo P2WPKH
-            adress_bytes.extend_from_slice(&bytes[2..lenght]);
-        }
-        if first_byte == ScriptOpcodes::OP_DUP {
-            // se trata de una transanccion del tipo P2PKH
-            adress_bytes.extend_from_slice(&bytes[3..(lenght - 2)]);
+        match self.script_type().address() {
+            Some(address) => Ok(address.to_string()),
+            None => Err("El pubkey script no tiene una direccion asociada"),
         }
-        let copy_adress_bytes: Vec<u8> = adress_bytes.clone();
-        let checksum = Sha256::digest(Sha256::digest(copy_adress_bytes));
-        adress_bytes.extend_from_slice(&checksum[..4]);
-        let encoded: bs58::encode::EncodeBuilder<&Vec<u8>> = bs58::encode(&adress_bytes);
-        let string = encoded.into_string();
-        Ok(string)
     }
 }
@@ -1,8 +1,16 @@
 pub struct ScriptOpcodes;
 
 impl ScriptOpcodes {
+    pub const OP_0: u8 = 0x00;
+    pub const OP_PUSHDATA1: u8 = 0x4c;
+    pub const OP_PUSHDATA2: u8 = 0x4d;
+    pub const OP_1: u8 = 0x51;
+    pub const OP_16: u8 = 0x60;
+    pub const OP_RETURN: u8 = 0x6a;
     pub const OP_DUP: u8 = 0x76;
     pub const OP_HASH160: u8 = 0xA9;
+    pub const OP_EQUAL: u8 = 0x87;
     pub const OP_EQUALVERIFY: u8 = 0x88;
     pub const OP_CHECKSIG: u8 = 0xAC;
+    pub const OP_CHECKMULTISIG: u8 = 0xAE;
 }
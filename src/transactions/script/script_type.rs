@@ -0,0 +1,216 @@
+use super::script_opcodes::ScriptOpcodes;
+use crate::{address::Address, address_decoder::hash_160};
+
+const COMPRESSED_PUBKEY_LEN: usize = 33;
+const UNCOMPRESSED_PUBKEY_LEN: usize = 65;
+
+/// Tipo de un pubkey script segun los templates estandar de Bitcoin Core.
+/// Guarda los datos que identifican a quien puede gastar el output
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptType {
+    /// <pubkey> OP_CHECKSIG
+    P2pk(Vec<u8>),
+    /// OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+    P2pkh([u8; 20]),
+    /// OP_HASH160 <20 bytes> OP_EQUAL
+    P2sh([u8; 20]),
+    /// OP_0 <20 bytes>
+    P2wpkh([u8; 20]),
+    /// OP_0 <32 bytes>
+    P2wsh([u8; 32]),
+    /// OP_1 <32 bytes>
+    P2tr([u8; 32]),
+    /// OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+    Multisig { required: u8, pubkeys: Vec<Vec<u8>> },
+    /// OP_RETURN <datos>. El output no se puede gastar
+    NullData(Vec<u8>),
+    /// Cualquier otro script
+    NonStandard,
+}
+
+impl ScriptType {
+    /// Clasifica el pubkey script recibido
+    pub fn classify(script: &[u8]) -> ScriptType {
+        let len = script.len();
+        if len == 25
+            && script[0] == ScriptOpcodes::OP_DUP
+            && script[1] == ScriptOpcodes::OP_HASH160
+            && script[2] == 20
+            && script[23] == ScriptOpcodes::OP_EQUALVERIFY
+            && script[24] == ScriptOpcodes::OP_CHECKSIG
+        {
+            return ScriptType::P2pkh(to_array(&script[3..23]));
+        }
+        if len == 23
+            && script[0] == ScriptOpcodes::OP_HASH160
+            && script[1] == 20
+            && script[22] == ScriptOpcodes::OP_EQUAL
+        {
+            return ScriptType::P2sh(to_array(&script[2..22]));
+        }
+        if len == 22 && script[0] == ScriptOpcodes::OP_0 && script[1] == 20 {
+            return ScriptType::P2wpkh(to_array(&script[2..22]));
+        }
+        if len == 34 && script[0] == ScriptOpcodes::OP_0 && script[1] == 32 {
+            return ScriptType::P2wsh(to_array(&script[2..34]));
+        }
+        if len == 34 && script[0] == ScriptOpcodes::OP_1 && script[1] == 32 {
+            return ScriptType::P2tr(to_array(&script[2..34]));
+        }
+        if len > 0 && script[0] == ScriptOpcodes::OP_RETURN {
+            return ScriptType::NullData(script[1..].to_vec());
+        }
+        if let Some(pubkey) = classify_p2pk(script) {
+            return ScriptType::P2pk(pubkey);
+        }
+        classify_multisig(script).unwrap_or(ScriptType::NonStandard)
+    }
+
+    /// Devuelve la direccion que corresponde al script, si tiene una.
+    /// Los P2PK se muestran como la direccion P2PKH de su clave publica, como hace Bitcoin Core
+    pub fn address(&self) -> Option<Address> {
+        match self {
+            ScriptType::P2pk(pubkey) => Some(Address::P2pkh(hash_160(pubkey))),
+            ScriptType::P2pkh(hash) => Some(Address::P2pkh(*hash)),
+            ScriptType::P2sh(hash) => Some(Address::P2sh(*hash)),
+            ScriptType::P2wpkh(hash) => Some(Address::P2wpkh(*hash)),
+            ScriptType::P2wsh(hash) => Some(Address::P2wsh(*hash)),
+            ScriptType::P2tr(key) => Some(Address::P2tr(*key)),
+            ScriptType::Multisig { .. } | ScriptType::NullData(_) | ScriptType::NonStandard => None,
+        }
+    }
+
+    /// Devuelve el nombre del tipo de script
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptType::P2pk(_) => "P2PK",
+            ScriptType::P2pkh(_) => "P2PKH",
+            ScriptType::P2sh(_) => "P2SH",
+            ScriptType::P2wpkh(_) => "P2WPKH",
+            ScriptType::P2wsh(_) => "P2WSH",
+            ScriptType::P2tr(_) => "P2TR",
+            ScriptType::Multisig { .. } => "MULTISIG",
+            ScriptType::NullData(_) => "OP_RETURN",
+            ScriptType::NonStandard => "NONSTANDARD",
+        }
+    }
+}
+
+/// Devuelve la clave publica si el script es <pubkey> OP_CHECKSIG
+fn classify_p2pk(script: &[u8]) -> Option<Vec<u8>> {
+    let (pubkey, rest) = read_pubkey(script)?;
+    if rest != [ScriptOpcodes::OP_CHECKSIG] {
+        return None;
+    }
+    Some(pubkey)
+}
+
+/// Devuelve el multisig si el script es OP_m <pubkey>... OP_n OP_CHECKMULTISIG con 1 <= m <= n <= 16
+fn classify_multisig(script: &[u8]) -> Option<ScriptType> {
+    if script.len() < 3 || script[script.len() - 1] != ScriptOpcodes::OP_CHECKMULTISIG {
+        return None;
+    }
+    let required = small_integer(script[0])?;
+    let total = small_integer(script[script.len() - 2])?;
+    let mut pubkeys = Vec::new();
+    let mut rest = &script[1..script.len() - 2];
+    while !rest.is_empty() {
+        let (pubkey, remaining) = read_pubkey(rest)?;
+        pubkeys.push(pubkey);
+        rest = remaining;
+    }
+    if required == 0 || required > total || pubkeys.len() != total as usize {
+        return None;
+    }
+    Some(ScriptType::Multisig { required, pubkeys })
+}
+
+/// Lee un push de una clave publica comprimida o sin comprimir.
+/// Devuelve la clave y el resto del script
+fn read_pubkey(script: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let push_len = *script.first()? as usize;
+    if push_len != COMPRESSED_PUBKEY_LEN && push_len != UNCOMPRESSED_PUBKEY_LEN {
+        return None;
+    }
+    if script.len() < push_len + 1 {
+        return None;
+    }
+    Some((script[1..=push_len].to_vec(), &script[push_len + 1..]))
+}
+
+/// Devuelve el numero de los opcodes OP_1 a OP_16
+fn small_integer(opcode: u8) -> Option<u8> {
+    if (ScriptOpcodes::OP_1..=ScriptOpcodes::OP_16).contains(&opcode) {
+        return Some(opcode - ScriptOpcodes::OP_1 + 1);
+    }
+    None
+}
+
+fn to_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    array
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_scripts_are_classified_with_their_address() {
+        // GIVEN: los pubkey scripts de cada tipo de direccion
+        for address in [
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV",
+            "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc",
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+        ] {
+            let script = match Address::parse(address) {
+                Ok(parsed) => parsed.script_pubkey(),
+                Err(err) => panic!("{}", err),
+            };
+            // WHEN: se clasifican
+            let script_type = ScriptType::classify(&script);
+            // THEN: se obtiene la misma direccion
+            assert_eq!(
+                script_type.address().map(|address| address.to_string()),
+                Some(address.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn p2pk_multisig_and_null_data_scripts_are_recognized() {
+        // GIVEN: un P2PK, un multisig 1 de 2, un OP_RETURN y un script cualquiera
+        let pubkey = [2; 33];
+        let mut p2pk = vec![33];
+        p2pk.extend_from_slice(&pubkey);
+        p2pk.push(ScriptOpcodes::OP_CHECKSIG);
+        let mut multisig = vec![ScriptOpcodes::OP_1, 33];
+        multisig.extend_from_slice(&pubkey);
+        multisig.push(33);
+        multisig.extend_from_slice(&[3; 33]);
+        multisig.extend_from_slice(&[0x52, ScriptOpcodes::OP_CHECKMULTISIG]);
+        let null_data = vec![ScriptOpcodes::OP_RETURN, 2, 0xca, 0xfe];
+        // WHEN: se clasifican
+        // THEN: se reconocen y solo el P2PK tiene direccion
+        assert_eq!(
+            ScriptType::classify(&p2pk).address(),
+            Some(Address::P2pkh(hash_160(&pubkey)))
+        );
+        assert_eq!(
+            ScriptType::classify(&multisig),
+            ScriptType::Multisig {
+                required: 1,
+                pubkeys: vec![pubkey.to_vec(), vec![3; 33]]
+            }
+        );
+        assert_eq!(ScriptType::classify(&null_data).name(), "OP_RETURN");
+        assert_eq!(ScriptType::classify(&null_data).address(), None);
+        assert_eq!(
+            ScriptType::classify(&[ScriptOpcodes::OP_DUP, 0x01]),
+            ScriptType::NonStandard
+        );
+    }
+}
@@ -21,7 +21,7 @@ impl SigScript {
 
     /// Recibe el hash a firmar y la private key
     /// Devuelve el signature
-    fn generate_sig(hash: [u8; 32], private_key: [u8; 32]) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn generate_sig(hash: [u8; 32], private_key: [u8; 32]) -> Result<Vec<u8>, Box<dyn Error>> {
         // Signing
         let secret_key = elliptic_curve::SecretKey::from_bytes((&private_key).into())?;
         let signing_key = ecdsa::SigningKey::from(secret_key);
@@ -32,13 +32,15 @@ impl SigScript {
         Ok(signature_bytes)
     }
 
-    /// Devuelve el signature script con la clave publica comprimida
+    /// Devuelve el signature script con la clave publica comprimida.
+    /// Firma con la clave de la cuenta que corresponde al pubkey script del output que se gasta
     pub fn generate_sig_script(
         hash_transaction: [u8; 32],
         account: &Account,
+        previous_pubkey_script: &[u8],
     ) -> Result<SigScript, Box<dyn Error>> {
         let mut sig_script_bytes: Vec<u8> = Vec::new();
-        let private_key = account.get_private_key()?;
+        let (private_key, bytes_public_key) = account.signing_keys(previous_pubkey_script)?;
         let sig = Self::generate_sig(hash_transaction, private_key)?;
         let lenght_sig = sig.len();
 
@@ -46,7 +48,6 @@ impl SigScript {
         // se carga el campo sig
         sig_script_bytes.extend_from_slice(&sig);
 
-        let bytes_public_key = account.get_pubkey_compressed()?;
         let lenght_pubkey = bytes_public_key.len();
         // se carga el largo de los bytes de la clave publica
         sig_script_bytes.push(lenght_pubkey as u8);
@@ -56,6 +57,29 @@ impl SigScript {
         Ok(sig_script)
     }
 
+    /// Devuelve el witness de un input P2WPKH: la firma y la clave publica comprimida (BIP141).
+    /// Firma con la clave de la cuenta que corresponde al pubkey script del output que se gasta
+    pub fn generate_witness(
+        hash_transaction: [u8; 32],
+        account: &Account,
+        previous_pubkey_script: &[u8],
+    ) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let (private_key, bytes_public_key) = account.signing_keys(previous_pubkey_script)?;
+        let sig = Self::generate_sig(hash_transaction, private_key)?;
+        Ok(vec![sig, bytes_public_key.to_vec()])
+    }
+
+    /// Devuelve la firma de un input multisig con la clave de la cuenta, que es una de las claves del multisig.
+    /// Las demas firmas las agregan los otros firmantes
+    pub fn generate_multisig_signature(
+        hash_transaction: [u8; 32],
+        account: &Account,
+        previous_pubkey_script: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (private_key, _) = account.signing_keys(previous_pubkey_script)?;
+        Self::generate_sig(hash_transaction, private_key)
+    }
+
     /// Recive el hash, sig y public key.
     /// Devuelve true o false dependiendo si el sig es correcto.
     pub fn verify_sig(
@@ -7,24 +7,32 @@ use std::{
 
 use bitcoin_hashes::{sha256, sha256d, Hash};
 use gtk::glib;
+use rand::Rng;
 
 use crate::{
-    account::Account, compact_size_uint::CompactSizeUint, custom_errors::NodeCustomErrors,
-    gtk::ui_events::UIEvent, logwriter::log_writer::LogSender, utxo_tuple::UtxoTuple,
+    account::Account,
+    address::Address,
+    coin_selection::{dust_threshold, input_vsize, output_vsize},
+    compact_size_uint::CompactSizeUint,
+    custom_errors::NodeCustomErrors,
+    gtk::ui_events::UIEvent,
+    logwriter::log_writer::LogSender,
+    utxo_tuple::UtxoTuple,
 };
 
 use super::{
     outpoint::Outpoint,
-    script::{
-        p2pkh_script::{self, generate_pubkey_script},
-        sig_script::SigScript,
-    },
+    recipient::{total_amount, Recipient},
+    script::{multisig_script::MultisigScript, p2pkh_script, p2wpkh_script, sig_script::SigScript},
     tx_in::TxIn,
     tx_out::TxOut,
 };
 
 const SIG_HASH_ALL: u32 = 0x00000001;
 const TRANSACTION_VERSION: i32 = 0x00000002;
+/// Marker y flag que indican que la transaccion se serializo con witness (BIP144)
+const SEGWIT_MARKER: u8 = 0x00;
+const SEGWIT_FLAG: u8 = 0x01;
 
 /// Representa una transacción del protocolo bitcoin
 #[derive(Debug, PartialEq, Clone)]
@@ -57,7 +65,7 @@ impl Transaction {
         }
     }
 
-    /// Deserializa la transacción a partir de una cadena de bytes.
+    /// Deserializa la transacción a partir de una cadena de bytes, con o sin witness (BIP144).
     /// Devuelve la transacción o un error en caso de que la cadena no cumpla con el formato
     pub fn unmarshalling(bytes: &Vec<u8>, offset: &mut usize) -> Result<Transaction, &'static str> {
         // en teoria se lee el coinbase transaccion primero
@@ -70,15 +78,30 @@ impl Transaction {
         version_bytes.copy_from_slice(&bytes[*offset..(*offset + 4)]);
         *offset += 4;
         let version = i32::from_le_bytes(version_bytes);
+        // si despues de la version vienen el marker y el flag, la transaccion tiene witness
+        let has_witness = bytes.len() > *offset + 1
+            && bytes[*offset] == SEGWIT_MARKER
+            && bytes[*offset + 1] == SEGWIT_FLAG;
+        if has_witness {
+            *offset += 2;
+        }
         let txin_count: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, &mut *offset)?;
         let amount_txin: u64 = txin_count.decoded_value();
-        let tx_in: Vec<TxIn> = TxIn::unmarshalling_txins(bytes, amount_txin, &mut *offset)?; // aca se actualizaria el *offset tambien
+        let mut tx_in: Vec<TxIn> = TxIn::unmarshalling_txins(bytes, amount_txin, &mut *offset)?; // aca se actualizaria el *offset tambien
         if tx_in[0].is_coinbase() && txin_count.decoded_value() != 1 {
             return Err("una coinbase transaction no puede tener mas de un input");
         }
         let txout_count: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, &mut *offset)?;
         let amount_txout: u64 = txout_count.decoded_value();
         let tx_out: Vec<TxOut> = TxOut::unmarshalling_txouts(bytes, amount_txout, &mut *offset)?; // aca se actualizaria el *offset tambien
+        if has_witness {
+            for txin in tx_in.iter_mut() {
+                txin.unmarshalling_witness(bytes, offset)?;
+            }
+        }
+        if bytes.len() < *offset + 4 {
+            return Err("Los bytes recibidos no alcanzan para leer el lock time del Transaction");
+        }
         let mut lock_time_bytes: [u8; 4] = [0; 4];
         lock_time_bytes.copy_from_slice(&bytes[*offset..(*offset + 4)]);
         *offset += 4;
@@ -93,11 +116,30 @@ impl Transaction {
         })
     }
 
-    /// Serializa la transacción.
+    /// Serializa la transacción. Si algun input tiene witness la serializa con witness (BIP144).
     /// Guarda los bytes en la referencia del vector recibido.
     pub fn marshalling(&self, bytes: &mut Vec<u8>) {
+        self.marshalling_with_witness(bytes, self.has_witness());
+    }
+
+    /// Serializa la transacción sin los datos de witness, que es el formato con el que se calcula el txid.
+    /// Guarda los bytes en la referencia del vector recibido.
+    pub fn marshalling_without_witness(&self, bytes: &mut Vec<u8>) {
+        self.marshalling_with_witness(bytes, false);
+    }
+
+    /// Devuelve true si algun input de la transacción tiene witness
+    pub fn has_witness(&self) -> bool {
+        self.tx_in.iter().any(|txin| !txin.witness.is_empty())
+    }
+
+    fn marshalling_with_witness(&self, bytes: &mut Vec<u8>, with_witness: bool) {
         let version_bytes: [u8; 4] = self.version.to_le_bytes();
         bytes.extend_from_slice(&version_bytes);
+        if with_witness {
+            bytes.push(SEGWIT_MARKER);
+            bytes.push(SEGWIT_FLAG);
+        }
         bytes.extend_from_slice(&self.txin_count.marshalling());
         for tx_in in &self.tx_in {
             tx_in.marshalling(bytes);
@@ -106,19 +148,48 @@ impl Transaction {
         for tx_out in &self.tx_out {
             tx_out.marshalling(bytes);
         }
+        if with_witness {
+            for tx_in in &self.tx_in {
+                tx_in.marshalling_witness(bytes);
+            }
+        }
         let locktime_bytes: [u8; 4] = self.lock_time.to_le_bytes();
         bytes.extend_from_slice(&locktime_bytes);
     }
+    /// Devuelve el peso de la transaccion (BIP141): los bytes sin witness pesan 4 y los del witness 1
+    pub fn weight(&self) -> usize {
+        let mut base_bytes = Vec::new();
+        self.marshalling_without_witness(&mut base_bytes);
+        let mut total_bytes = Vec::new();
+        self.marshalling(&mut total_bytes);
+        base_bytes.len() * 3 + total_bytes.len()
+    }
+
+    /// Devuelve el tamaño virtual de la transaccion en vbytes, con el que se calcula su fee rate
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
     ///Devuelve el hash de la transaccion
     pub fn hash(&self) -> [u8; 32] {
         self.hash_message(false)
     }
-    /// Realiza el hash de la transaccion.
+    /// Devuelve el wtxid de la transaccion (BIP141): el hash de la serializacion con witness.
+    /// Si la transaccion no tiene witness, coincide con el txid
+    pub fn wtxid(&self) -> [u8; 32] {
+        if !self.has_witness() {
+            return self.hash();
+        }
+        let mut raw_transaction_bytes: Vec<u8> = Vec::new();
+        self.marshalling(&mut raw_transaction_bytes);
+        *sha256d::Hash::hash(&raw_transaction_bytes).as_byte_array()
+    }
+    /// Realiza el hash de la transaccion, siempre sin los datos de witness.
     /// Si recibe true pushea dentro del vector los bytes correspondientes al SIGHASH_ALL.
     /// Caso contrario realiza el hash normalmente
     fn hash_message(&self, is_message: bool) -> [u8; 32] {
         let mut raw_transaction_bytes: Vec<u8> = Vec::new();
-        self.marshalling(&mut raw_transaction_bytes);
+        self.marshalling_without_witness(&mut raw_transaction_bytes);
         if is_message {
             let bytes = SIG_HASH_ALL.to_le_bytes();
             raw_transaction_bytes.extend_from_slice(&bytes);
@@ -184,10 +255,11 @@ impl Transaction {
         Ok(())
     }
 
-    /// Genera el UtxoTuple y lo guarda en el utxo_set
+    /// Genera el UtxoTuple confirmado a la altura recibida, marcado si es una coinbase, y lo guarda en el utxo_set
     pub fn load_utxos(
         &self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
+        height: u32,
     ) -> Result<(), Box<dyn Error>> {
         let hash = self.hash();
         let mut utxos_and_index = Vec::new();
@@ -195,7 +267,9 @@ impl Transaction {
             let utxo_and_index = (utxo.clone(), position);
             utxos_and_index.push(utxo_and_index);
         }
-        let utxo_tuple = UtxoTuple::new(hash, utxos_and_index);
+        let utxo_tuple = UtxoTuple::new(hash, utxos_and_index)
+            .with_height(height)
+            .with_coinbase(self.is_coinbase_transaction());
         utxo_set
             .write()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -222,25 +296,36 @@ impl Transaction {
     }
 
     /// Recibe un puntero a un puntero con las cuentas de la wallet y se fija si alguna tx_out tiene una address
-    /// igual que alguna de la wallet. Devuelve Ok(()) en caso de no ocurrir ningun error o Error especifico en caso contrario
+    /// igual que alguna de la wallet. Ademas la agrega como pendiente al ledger de las cuentas a las que les paga
+    /// o cuyas monedas gasta. Devuelve Ok(()) en caso de no ocurrir ningun error o Error especifico en caso contrario
     pub fn check_if_tx_involves_user_account(
         &self,
         log_sender: &LogSender,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
     ) -> Result<(), NodeCustomErrors> {
+        for account in &*accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        {
+            account
+                .record_in_ledger(self, None)
+                .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+        }
         for tx_out in self.tx_out.clone() {
             tx_out.involves_user_account(log_sender, ui_sender, accounts.clone(), self.clone())?;
         }
         Ok(())
     }
-    /// Esta funcion genera la transaccion sin firmar , los parametros indican la adrress
-    /// donde se enviara el monto(value), la recompensa por agregar la nueva transaccion
-    /// al bloque(fee) y la direccion para retornar el cambio en caso de que se genere(change_address)
+    /// Esta funcion genera la transaccion sin firmar con un output por cada pago recibido, la recompensa
+    /// por agregar la nueva transaccion al bloque(fee) y la direccion para retornar el cambio en caso de que se genere(change_address).
+    /// Si el cambio es dust no se crea el output y pasa a la comision. El output de cambio se agrega en una
+    /// posicion al azar para que no se pueda distinguir de los pagos por su posicion
     pub fn generate_unsigned_transaction(
-        address_receiver: &str,
+        recipients: &[Recipient],
         change_adress: &str,
-        value: i64,
         fee: i64,
         utxos_to_spend: &Vec<UtxoTuple>,
     ) -> Result<Transaction, Box<dyn Error>> {
@@ -259,25 +344,43 @@ impl Transaction {
             }
         }
         // esta variable contiene el monto correspondiente al sobrante de la tx
-        let change_amount: i64 = input_balance - (value + fee);
+        let change_amount: i64 = input_balance - (total_amount(recipients) + fee);
+        if change_amount < 0 {
+            return Err(Box::new(std::io::Error::other(
+                "Las utxos a gastar no alcanzan para pagar el monto y la comision",
+            )));
+        }
         // esta variable indica la cantidad de txIn creados en los pasos anteriores
         let txin_count: CompactSizeUint = CompactSizeUint::new(tx_ins.len() as u128);
         // este vector contiene los outputs de nuestra transaccion
         let mut tx_outs: Vec<TxOut> = Vec::new();
-        // creacion del pubkey_script donde transferimos los satoshis
-        let target_pk_script: Vec<u8> = generate_pubkey_script(address_receiver)?;
-        let target_pk_script_bytes: CompactSizeUint =
-            CompactSizeUint::new(target_pk_script.len() as u128);
-        // creacion del txOut(utxo) referenciado al address que nos enviaron
-        let utxo_to_send: TxOut = TxOut::new(value, target_pk_script_bytes, target_pk_script);
-        tx_outs.push(utxo_to_send);
+        // creacion de un txOut(utxo) referenciado a cada address que nos enviaron
+        for recipient in recipients {
+            let target_pk_script: Vec<u8> = Address::parse(&recipient.address)?.script_pubkey();
+            let target_pk_script_bytes: CompactSizeUint =
+                CompactSizeUint::new(target_pk_script.len() as u128);
+            tx_outs.push(TxOut::new(
+                recipient.amount,
+                target_pk_script_bytes,
+                target_pk_script,
+            ));
+        }
         // creacion del pubkey_script donde enviaremos el cambio de nuestra tx
-        let change_pk_script: Vec<u8> = generate_pubkey_script(change_adress)?;
+        let change_pk_script: Vec<u8> = Address::parse(change_adress)?.script_pubkey();
         let change_pk_script_bytes: CompactSizeUint =
             CompactSizeUint::new(change_pk_script.len() as u128);
-        let change_utxo: TxOut =
-            TxOut::new(change_amount, change_pk_script_bytes, change_pk_script);
-        tx_outs.push(change_utxo);
+        // si el cambio es dust no se crea el output y queda de comision
+        if change_amount
+            >= dust_threshold(
+                output_vsize(&change_pk_script),
+                input_vsize(&change_pk_script, None),
+            )
+        {
+            let change_utxo: TxOut =
+                TxOut::new(change_amount, change_pk_script_bytes, change_pk_script);
+            let change_position = rand::thread_rng().gen_range(0..=tx_outs.len());
+            tx_outs.insert(change_position, change_utxo);
+        }
         let txout_count = CompactSizeUint::new(tx_outs.len() as u128);
         // lock_time = 0 => Not locked
         let lock_time: u32 = 0;
@@ -292,62 +395,202 @@ impl Transaction {
         Ok(incomplete_transaction)
     }
 
+    /// Devuelve true si alguno de los inputs señala que la transaccion se puede reemplazar (BIP125)
+    pub fn signals_rbf(&self) -> bool {
+        self.tx_in.iter().any(|txin| txin.signals_rbf())
+    }
+
+    /// Genera la transaccion sin firmar que reemplaza a esta (BIP125).
+    /// Gasta los mismos outputs que la original, con los outputs recibidos
+    pub fn replacement(&self, tx_outs: Vec<TxOut>) -> Transaction {
+        let tx_ins: Vec<TxIn> = self
+            .tx_in
+            .iter()
+            .map(|txin| TxIn::incomplete_txin(txin.outpoint()))
+            .collect();
+        Transaction::new(
+            self.version,
+            CompactSizeUint::new(tx_ins.len() as u128),
+            tx_ins,
+            CompactSizeUint::new(tx_outs.len() as u128),
+            tx_outs,
+            self.lock_time,
+        )
+    }
+
     /// Firma la transacción.
     /// Recibe la lista de utxos a gastar y agrega el signature_script a cada TxIn.
+    /// Los inputs que gastan outputs P2WPKH se firman segun BIP143 y la firma va en el witness.
+    /// En los inputs que gastan el multisig de la cuenta se agrega su firma a las que ya tenga el input,
+    /// que queda parcialmente firmado hasta que los demas firmantes agreguen las suyas
     pub fn sign(
         &mut self,
         account: &Account,
-        utxos_to_spend: &Vec<UtxoTuple>,
+        utxos_to_spend: &[UtxoTuple],
     ) -> Result<(), Box<dyn Error>> {
         let mut signatures = Vec::new();
         for index in 0..self.tx_in.len() {
-            // agregar el signature a cada input
-            let z = self.generate_message_to_sign(index, utxos_to_spend);
-            signatures.push(SigScript::generate_sig_script(z, account)?);
+            // agregar el signature a cada input, con la clave que corresponde al output que gasta
+            let previous_output = self.previous_output(index, utxos_to_spend)?;
+            let previous_pubkey_script = previous_output.get_pub_key_script();
+            if let Some(multisig) = account.multisig_for_script(previous_pubkey_script) {
+                let z = self.multisig_message_to_sign(index, multisig, previous_output.value());
+                let mut multisig_signatures = multisig.signatures(&self.tx_in[index]);
+                multisig_signatures.push(SigScript::generate_multisig_signature(
+                    z,
+                    account,
+                    previous_pubkey_script,
+                )?);
+                let (sig_script, witness) =
+                    multisig.spending_data(&multisig.sort_signatures(&z, &multisig_signatures));
+                signatures.push((SigScript::new(sig_script), witness));
+            } else if p2wpkh_script::is_p2wpkh(previous_pubkey_script) {
+                let z = self.segwit_v0_message_to_sign(
+                    index,
+                    &p2wpkh_script::script_code(previous_pubkey_script),
+                    previous_output.value(),
+                );
+                let witness = SigScript::generate_witness(z, account, previous_pubkey_script)?;
+                signatures.push((SigScript::new(vec![]), witness));
+            } else {
+                let z = self.generate_message_to_sign(index, previous_pubkey_script);
+                let sig_script =
+                    SigScript::generate_sig_script(z, account, previous_pubkey_script)?;
+                signatures.push((sig_script, vec![]));
+            }
         }
-        for (index, signature) in signatures.into_iter().enumerate() {
+        for (index, (signature, witness)) in signatures.into_iter().enumerate() {
             self.tx_in[index].add(signature);
+            self.tx_in[index].set_witness(witness);
         }
         Ok(())
     }
 
+    /// Busca entre las utxos a gastar el output que gasta el tx_in recibido.
+    /// Devuelve error si no lo encuentra
+    fn previous_output(
+        &self,
+        tx_in_index: usize,
+        utxos_to_spend: &[UtxoTuple],
+    ) -> Result<TxOut, Box<dyn Error>> {
+        let input = &self.tx_in[tx_in_index];
+        utxos_to_spend
+            .iter()
+            .find_map(|utxos| {
+                utxos.find_tx_out(
+                    input.get_previous_output_hash(),
+                    input.get_previous_output_index(),
+                )
+            })
+            .cloned()
+            .ok_or_else(|| {
+                Box::new(std::io::Error::other(
+                    "No se encontró el output que gasta el input de la transacción.",
+                )) as Box<dyn Error>
+            })
+    }
+
+    /// Genera el preimage de la firma del input recibido segun BIP143 (segwit version 0)
+    /// con SIGHASH_ALL. Recibe el script code y el monto del output que se gasta
+    fn segwit_v0_preimage(&self, tx_in_index: usize, script_code: &[u8], amount: i64) -> Vec<u8> {
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for txin in &self.tx_in {
+            txin.outpoint().marshalling(&mut prevouts);
+            sequences.extend_from_slice(&txin.sequence().to_le_bytes());
+        }
+        let mut outputs = Vec::new();
+        for txout in &self.tx_out {
+            txout.marshalling(&mut outputs);
+        }
+        let input = &self.tx_in[tx_in_index];
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&self.version.to_le_bytes());
+        preimage.extend_from_slice(sha256d::Hash::hash(&prevouts).as_byte_array());
+        preimage.extend_from_slice(sha256d::Hash::hash(&sequences).as_byte_array());
+        input.outpoint().marshalling(&mut preimage);
+        preimage.extend_from_slice(&CompactSizeUint::new(script_code.len() as u128).marshalling());
+        preimage.extend_from_slice(script_code);
+        preimage.extend_from_slice(&amount.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence().to_le_bytes());
+        preimage.extend_from_slice(sha256d::Hash::hash(&outputs).as_byte_array());
+        preimage.extend_from_slice(&self.lock_time.to_le_bytes());
+        preimage.extend_from_slice(&SIG_HASH_ALL.to_le_bytes());
+        preimage
+    }
+
+    /// Devuelve el mensaje a firmar del input segwit recibido (BIP143).
+    /// Al igual que en los inputs P2PKH, se hashea una sola vez porque la firma aplica el segundo hash
+    pub fn segwit_v0_message_to_sign(
+        &self,
+        tx_in_index: usize,
+        script_code: &[u8],
+        amount: i64,
+    ) -> [u8; 32] {
+        let preimage = self.segwit_v0_preimage(tx_in_index, script_code, amount);
+        *sha256::Hash::hash(&preimage).as_byte_array()
+    }
+
+    /// Devuelve el mensaje a firmar de un input que gasta el multisig recibido. El script code es el
+    /// redeem script, con el sighash original en P2SH y con el de BIP143 en P2WSH y P2SH-P2WSH
+    pub fn multisig_message_to_sign(
+        &self,
+        tx_in_index: usize,
+        multisig: &MultisigScript,
+        amount: i64,
+    ) -> [u8; 32] {
+        let script_code = multisig.redeem_script();
+        if multisig.is_segwit() {
+            return self.segwit_v0_message_to_sign(tx_in_index, &script_code, amount);
+        }
+        self.generate_message_to_sign(tx_in_index, &script_code)
+    }
+
     /// Genera la txin con el previous pubkey del tx_in recibido.
+    /// Los signature scripts de los demas inputs se vacian, ya que pueden tener firmas parciales.
     /// Devuelve el hash
-    fn generate_message_to_sign(
+    pub fn generate_message_to_sign(
         &self,
         tx_in_index: usize,
-        utxos_to_spend: &Vec<UtxoTuple>,
+        previous_pubkey_script: &[u8],
     ) -> [u8; 32] {
         let mut tx_copy = self.clone();
-        let mut script = Vec::new();
-        let input_to_sign = &tx_copy.tx_in[tx_in_index];
-        for utxos in utxos_to_spend {
-            let pubkey = utxos.find(
-                input_to_sign.get_previous_output_hash(),
-                input_to_sign.get_previous_output_index(),
-            );
-            script = match pubkey {
-                Some(value) => value.to_vec(),
-                None => continue,
-            };
+        for txin in tx_copy.tx_in.iter_mut() {
+            txin.set_signature_script(vec![]);
         }
-        tx_copy.tx_in[tx_in_index].set_signature_script(script);
+        tx_copy.tx_in[tx_in_index].set_signature_script(previous_pubkey_script.to_vec());
         tx_copy.hash_message(true)
     }
 
     /// Valida la transacción.
     /// Ejecuta el script y devuelve error en caso de que no pase la validación.
-    pub fn validate(&self, utxos_to_spend: &Vec<UtxoTuple>) -> Result<(), Box<dyn Error>> {
-        let mut p2pkh_scripts = Vec::new();
-        for utxo in utxos_to_spend {
-            for (txout, _) in &utxo.utxo_set {
-                p2pkh_scripts.push(txout.get_pub_key_script())
-            }
-        }
-
+    pub fn validate(&self, utxos_to_spend: &[UtxoTuple]) -> Result<(), Box<dyn Error>> {
         for (index, txin) in self.tx_in.iter().enumerate() {
-            //txin.
-            if !p2pkh_script::validate(p2pkh_scripts[index], txin.signature_script.get_bytes())? {
+            let previous_output = self.previous_output(index, utxos_to_spend)?;
+            let previous_pubkey_script = previous_output.get_pub_key_script();
+            if let Some(multisig) = MultisigScript::from_input(previous_pubkey_script, txin) {
+                let message =
+                    self.multisig_message_to_sign(index, &multisig, previous_output.value());
+                if !multisig.validate(txin, &message) {
+                    return Err(Box::new(std::io::Error::other(
+                        "El multisig_script no tiene las firmas requeridas.",
+                    )));
+                }
+            } else if p2wpkh_script::is_p2wpkh(previous_pubkey_script) {
+                let message = self.segwit_v0_message_to_sign(
+                    index,
+                    &p2wpkh_script::script_code(previous_pubkey_script),
+                    previous_output.value(),
+                );
+                if !p2wpkh_script::validate(previous_pubkey_script, &txin.witness, &message)? {
+                    return Err(Box::new(std::io::Error::other(
+                        "El p2wpkh_script no pasó la validación.",
+                    )));
+                }
+            } else if !p2pkh_script::validate(
+                previous_pubkey_script,
+                txin.signature_script.get_bytes(),
+            )? {
                 return Err(Box::new(std::io::Error::new(
                     io::ErrorKind::Other,
                     "El p2pkh_script no pasó la validación.",
@@ -370,17 +613,6 @@ impl Transaction {
     pub fn get_height(&self) -> u32 {
         self.tx_in[0].get_height()
     }
-
-    /// Devuelve el monto enviado a direcciones distintas de la recibida por parámetro
-    pub fn amount_spent_by_account(&self, address: &String) -> Result<i64, Box<dyn Error>> {
-        let mut amount = 0;
-        for txout in &self.tx_out {
-            if !txout.is_sent_to_account(address)? {
-                amount += txout.value();
-            }
-        }
-        Ok(amount)
-    }
 }
 
 #[cfg(test)]
@@ -388,11 +620,23 @@ impl Transaction {
 mod test {
     use super::Transaction;
     use crate::{
+        account::{bytes_to_hex_string, Account},
         compact_size_uint::CompactSizeUint,
-        transactions::script::sig_script::SigScript,
-        transactions::{outpoint::Outpoint, tx_in::TxIn, tx_out::TxOut},
+        hd_wallet::hd_keychain::DerivationScheme,
+        transactions::script::{p2wpkh_script, sig_script::SigScript},
+        transactions::{outpoint::Outpoint, recipient::Recipient, tx_in::TxIn, tx_out::TxOut},
+        utxo_tuple::UtxoTuple,
     };
     use bitcoin_hashes::{sha256d, Hash};
+    use std::error::Error;
+
+    /// Funcion auxiliar que convierte un string hexadecimal a bytes
+    fn hex_to_bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_default())
+            .collect()
+    }
 
     /// Funcion auxiliar que crea los txin
     fn crear_txins(cantidad: u128) -> Vec<TxIn> {
@@ -683,4 +927,64 @@ mod test {
         assert_eq!(transaction.len(), 2);
         Ok(())
     }
+
+    #[test]
+    fn test_el_preimage_bip143_de_un_input_p2wpkh_es_el_del_vector_del_bip(
+    ) -> Result<(), &'static str> {
+        // GIVEN: la transaccion sin firmar del ejemplo "P2SH-P2WPKH" de BIP143
+        let bytes = hex_to_bytes("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000");
+        let transaction = Transaction::unmarshalling(&bytes, &mut 0)?;
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend_from_slice(&hex_to_bytes("79091972186c449eb1ded22b78e40d009bdf0089"));
+        // WHEN: se genera el preimage del input, que gasta 10 BTC de un witness program P2WPKH
+        let preimage =
+            transaction.segwit_v0_preimage(0, &p2wpkh_script::script_code(&p2wpkh), 1000000000);
+        // THEN: su doble sha256 es el sighash del BIP
+        assert_eq!(
+            bytes_to_hex_string(sha256d::Hash::hash(&preimage).as_byte_array()),
+            "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaccion_p2wpkh_se_firma_valida_y_serializa_con_witness(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: una cuenta BIP84 con un output P2WPKH a su primera direccion
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut account = Account::from_mnemonic(mnemonic, "", DerivationScheme::Bip84, 0)?;
+        let address = account.address.clone();
+        let pubkey_script = p2wpkh_script::generate_pubkey_script(&address)?;
+        let tx_out = TxOut::new(
+            10000,
+            CompactSizeUint::new(pubkey_script.len() as u128),
+            pubkey_script,
+        );
+        let utxos = vec![UtxoTuple::new([7; 32], vec![(tx_out, 1)])];
+        let change_address = account.next_receive_address()?;
+        let mut transaction = Transaction::generate_unsigned_transaction(
+            &[Recipient::new(address, 4000, false)],
+            &change_address,
+            1000,
+            &utxos,
+        )?;
+        // WHEN: se firma
+        transaction.sign(&account, &utxos)?;
+        // THEN: la firma va en el witness, pasa la validacion y se serializa con marker y flag
+        assert!(transaction.tx_in[0].signature_script.get_bytes().is_empty());
+        assert_eq!(transaction.tx_in[0].witness.len(), 2);
+        assert!(transaction.validate(&utxos).is_ok());
+        let mut bytes = Vec::new();
+        transaction.marshalling(&mut bytes);
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+        let mut base_bytes = Vec::new();
+        transaction.marshalling_without_witness(&mut base_bytes);
+        assert_eq!(transaction.weight(), base_bytes.len() * 3 + bytes.len());
+        assert!(transaction.vsize() < bytes.len());
+        let unmarshalled = Transaction::unmarshalling(&bytes, &mut 0)?;
+        assert_eq!(unmarshalled, transaction);
+        assert_eq!(unmarshalled.hash(), transaction.hash());
+        assert_ne!(transaction.wtxid(), transaction.hash());
+        Ok(())
+    }
 }
@@ -2,6 +2,11 @@ use crate::compact_size_uint::CompactSizeUint;
 
 use super::{outpoint::Outpoint, script::sig_script::SigScript};
 
+/// Sequence con el que los inputs señalan que la transaccion se puede reemplazar (BIP125)
+pub const RBF_SEQUENCE: u32 = 0xfffffffd;
+/// Los sequence menores a este valor señalan que la transaccion se puede reemplazar (BIP125)
+const MAX_RBF_SEQUENCE: u32 = 0xfffffffe;
+
 /// Representa la estructura TxIn del protocolo bitcoin
 #[derive(Debug, PartialEq, Clone)]
 pub struct TxIn {
@@ -10,6 +15,7 @@ pub struct TxIn {
     pub height: Option<Vec<u8>>,
     pub signature_script: SigScript,
     sequence: u32,
+    pub witness: Vec<Vec<u8>>,
 }
 
 impl TxIn {
@@ -27,16 +33,18 @@ impl TxIn {
             height,
             signature_script,
             sequence,
+            witness: vec![],
         }
     }
 
     /// Crea el TxIn incompleto.
-    /// Se utiliza al momento de crear una transacción, el campo signature_script está vacío
+    /// Se utiliza al momento de crear una transacción, el campo signature_script está vacío.
+    /// Señala que la transaccion se puede reemplazar para poder aumentarle la comision (BIP125)
     pub fn incomplete_txin(previous_output: Outpoint) -> TxIn {
         let script_bytes: CompactSizeUint = CompactSizeUint::new(0);
         let height: Option<Vec<u8>> = None;
         let signature_script: SigScript = SigScript::new(vec![]);
-        let sequence: u32 = 0xffffffff;
+        let sequence: u32 = RBF_SEQUENCE;
         Self::new(
             previous_output,
             script_bytes,
@@ -86,6 +94,7 @@ impl TxIn {
             height,
             signature_script: SigScript::new(signature_script),
             sequence,
+            witness: vec![],
         })
     }
 
@@ -121,6 +130,36 @@ impl TxIn {
         bytes.extend_from_slice(&sequence_bytes);
     }
 
+    /// Deserializa el witness del TxIn (BIP144): la cantidad de elementos y cada elemento
+    /// precedido por su largo. Actualiza el offset
+    pub fn unmarshalling_witness(
+        &mut self,
+        bytes: &[u8],
+        offset: &mut usize,
+    ) -> Result<(), &'static str> {
+        let witness_count = CompactSizeUint::unmarshalling(bytes, offset)?.decoded_value();
+        let mut witness = Vec::new();
+        for _ in 0..witness_count {
+            let item_len = CompactSizeUint::unmarshalling(bytes, offset)?.decoded_value() as usize;
+            if bytes.len() < *offset + item_len {
+                return Err("Los bytes recibidos no alcanzan para leer el witness del TxIn");
+            }
+            witness.push(bytes[*offset..*offset + item_len].to_vec());
+            *offset += item_len;
+        }
+        self.witness = witness;
+        Ok(())
+    }
+
+    /// Serializa el witness del TxIn (BIP144) y lo guarda en el vector recibido
+    pub fn marshalling_witness(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&CompactSizeUint::new(self.witness.len() as u128).marshalling());
+        for item in &self.witness {
+            bytes.extend_from_slice(&CompactSizeUint::new(item.len() as u128).marshalling());
+            bytes.extend_from_slice(item);
+        }
+    }
+
     /// Devuelve true o false dependiendo si la TxIn es de una coinbase transaction.
     pub fn is_coinbase(&self) -> bool {
         self.height.is_some()
@@ -135,18 +174,17 @@ impl TxIn {
     /// Si es una coinbase transaction devuelve la altura del bloque en el que se encuentra.
     /// Si no es una coinbase transaction devuelve 0.
     pub fn get_height(&self) -> u32 {
-        let mut bytes: Vec<u8> = vec![0];
-        let height = &self.height;
-        let mut bytes_from_height: Vec<u8>;
-        match height {
-            Some(value) => bytes_from_height = value.clone(),
+        let height = match &self.height {
+            Some(value) => value,
             None => return 0,
-        }
-        bytes_from_height.reverse();
-        bytes.extend_from_slice(&bytes_from_height[..bytes_from_height.len() - 1]);
-        let mut aux_bytes: [u8; 4] = [0; 4];
-        aux_bytes.copy_from_slice(&bytes);
-        u32::from_be_bytes(aux_bytes)
+        };
+        // el primer byte es el largo del push, le sigue la altura en little endian
+        height
+            .iter()
+            .skip(1)
+            .take(4)
+            .rev()
+            .fold(0, |height, byte| (height << 8) | *byte as u32)
     }
     /// Compara el hash recibido con el del output previo de la TxIn
     pub fn is_same_hash(&self, hash: &[u8; 32]) -> bool {
@@ -162,6 +200,18 @@ impl TxIn {
         self.script_bytes = CompactSizeUint::new(signature.get_bytes().len() as u128);
         self.signature_script = signature
     }
+    /// Setea en el TxIn el witness recibido (BIP141)
+    pub fn set_witness(&mut self, witness: Vec<Vec<u8>>) {
+        self.witness = witness;
+    }
+    /// Devuelve el sequence del TxIn
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    /// Devuelve true si el TxIn señala que la transaccion se puede reemplazar (BIP125)
+    pub fn signals_rbf(&self) -> bool {
+        self.sequence < MAX_RBF_SEQUENCE
+    }
     /// Devuelve el hash del output previo
     pub fn get_previous_output_hash(&self) -> [u8; 32] {
         self.previous_output.hash()
@@ -200,6 +250,7 @@ mod test {
             height,
             signature_script: SigScript::new(signature_script),
             sequence,
+            witness: vec![],
         };
         txin_to_marshalling.marshalling(&mut bytes_txin);
         bytes_txin
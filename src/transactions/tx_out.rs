@@ -4,13 +4,17 @@ use gtk::glib;
 
 use crate::{
     account::Account,
+    address::Address,
     compact_size_uint::CompactSizeUint,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
     logwriter::log_writer::{write_in_log, LogSender},
 };
 
-use super::{script::pubkey::Pubkey, transaction::Transaction};
+use super::{
+    script::{pubkey::Pubkey, script_type::ScriptType},
+    transaction::Transaction,
+};
 /// Representa la estructura TxOut del protocolo bitcoin
 #[derive(Debug, PartialEq, Clone)]
 pub struct TxOut {
@@ -84,6 +88,11 @@ impl TxOut {
         self.value
     }
 
+    /// Devuelve el tipo del pub key script del TxOut
+    pub fn script_type(&self) -> ScriptType {
+        self.pk_script.script_type()
+    }
+
     /// Obtiene la address del receptor del TxOut
     pub fn get_address(&self) -> Result<String, &'static str> {
         self.pk_script.generate_address()
@@ -113,45 +122,40 @@ impl TxOut {
                 .pending_transactions
                 .read()
                 .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                .contains(&tx)
+                .iter()
+                .any(|pending_tx| pending_tx.hash() == tx.hash())
+                && account.owns_output(self)
             {
-                let tx_asociate_address = match self.get_address() {
-                    Ok(address) => address,
-                    Err(e) => e.to_string(),
-                };
-                if tx_asociate_address == account.address {
-                    write_in_log(
-                        &log_sender.info_log_sender,
-                        format!(
-                            "Transaccion pendiente {:?} -- involucra a la cuenta {:?}",
-                            tx.hex_hash(),
-                            account.address
-                        )
-                        .as_str(),
-                    );
-                    println!("\nTRANSACCION: {} \nINVOLUCRA A LA CUENTA: {}\nAUN NO SE ENCUENTRA EN UN BLOQUE (PENDIENTE)", tx.hex_hash(), account.address);
-                    send_event_to_ui(
-                        ui_sender,
-                        UIEvent::ShowPendingTransaction(account.clone(), tx.clone()),
-                    );
-                    account
-                        .pending_transactions
-                        .write()
-                        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                        .push(tx.clone());
-                }
+                write_in_log(
+                    &log_sender.info_log_sender,
+                    format!(
+                        "Transaccion pendiente {:?} -- involucra a la cuenta {:?}",
+                        tx.hex_hash(),
+                        account.address
+                    )
+                    .as_str(),
+                );
+                println!("\nTRANSACCION: {} \nINVOLUCRA A LA CUENTA: {}\nAUN NO SE ENCUENTRA EN UN BLOQUE (PENDIENTE)", tx.hex_hash(), account.address);
+                send_event_to_ui(
+                    ui_sender,
+                    UIEvent::ShowPendingTransaction(account.clone(), tx.clone()),
+                );
+                account
+                    .pending_transactions
+                    .write()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                    .push(tx.clone());
             }
         }
         Ok(())
     }
 
-    /// Devuelve true o false dependiendo de si la transaccion fue enviada a la cuenta recibida por parametro
-    pub fn is_sent_to_account(&self, address: &String) -> Result<bool, &'static str> {
-        let tx_asociate_address = self.get_address()?;
-        if tx_asociate_address.eq(address) {
-            return Ok(true);
-        }
-        Ok(false)
+    /// Devuelve true o false dependiendo de si la transaccion fue enviada a la cuenta recibida por parametro.
+    /// Compara el pub key script con el que genera la address, para no confundir otros tipos de script
+    /// que se muestran con la misma address (por ejemplo P2PK)
+    pub fn is_sent_to_account(&self, address: &str) -> Result<bool, &'static str> {
+        let address = Address::parse(address).map_err(|_| "La address de la cuenta es inválida")?;
+        Ok(address.script_pubkey() == *self.get_pub_key_script())
     }
 }
 
@@ -261,4 +265,24 @@ mod tests {
         assert_eq!(*tx_out_expected.pk_script.bytes(), pk_script_expected);
         Ok(())
     }
+
+    #[test]
+    fn test_output_p2pk_se_muestra_con_la_address_pero_no_se_asocia_a_la_cuenta(
+    ) -> Result<(), &'static str> {
+        // la cuenta mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV tiene esta clave publica comprimida
+        let pubkey = [
+            0x02, 0x61, 0x47, 0x71, 0x4b, 0x5b, 0x35, 0xe1, 0x8e, 0x32, 0x71, 0x88, 0x2d, 0x83,
+            0xa0, 0x42, 0xcb, 0x2c, 0x97, 0x1b, 0xc6, 0x5a, 0xa0, 0xf7, 0x0e, 0x36, 0x15, 0xeb,
+            0x37, 0x89, 0xcf, 0x67, 0x67,
+        ];
+        let address = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
+        let mut p2pk_script = vec![33];
+        p2pk_script.extend_from_slice(&pubkey);
+        p2pk_script.push(0xac);
+        let p2pk = TxOut::new(1000, CompactSizeUint::new(35), p2pk_script);
+        assert_eq!(p2pk.script_type().name(), "P2PK");
+        assert_eq!(p2pk.get_address()?, address);
+        assert!(!p2pk.is_sent_to_account(&address)?);
+        Ok(())
+    }
 }
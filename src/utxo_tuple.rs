@@ -1,39 +1,67 @@
+use std::collections::HashSet;
+
 use crate::transactions::tx_out::TxOut;
 
 /// Guarda el hash de la transacción y un array con los TxOut sin gastar, referentes a esa transacción
 /// La tupla guarda el TxOut y el indice en el que se encuentra en la tx
+/// Tambien guarda la altura del bloque que confirmo la transaccion, si se conoce, y si es una coinbase
 #[derive(Debug, Clone)]
 pub struct UtxoTuple {
     pub hash: [u8; 32],
     pub utxo_set: Vec<(TxOut, usize)>,
+    pub height: Option<u32>,
+    pub coinbase: bool,
 }
 
 impl UtxoTuple {
     pub fn new(hash: [u8; 32], utxo_set: Vec<(TxOut, usize)>) -> Self {
-        UtxoTuple { hash, utxo_set }
+        UtxoTuple {
+            hash,
+            utxo_set,
+            height: None,
+            coinbase: false,
+        }
     }
 
-    /// Devuelve la utxoTuple con las TxOut que referencian a la dirección recibida
-    /// En caso de que no encuentre ninguna, devuelve None
-    pub fn referenced_utxos(&self, address: &str) -> Option<UtxoTuple> {
-        let hash = self.hash;
-        let mut utxo_set: Vec<(TxOut, usize)> = Vec::new();
-        for utxo in &self.utxo_set {
-            match utxo.0.get_address() {
-                Ok(value) => {
-                    if *address == value {
-                        utxo_set.push(utxo.clone());
-                    }
-                }
-                Err(_) => {
-                    continue;
-                }
-            }
+    /// Devuelve la utxoTuple confirmada en el bloque de la altura recibida
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Devuelve la utxoTuple marcada segun si la transaccion es una coinbase
+    pub fn with_coinbase(mut self, coinbase: bool) -> Self {
+        self.coinbase = coinbase;
+        self
+    }
+
+    /// Devuelve la cantidad de confirmaciones de la transaccion con la altura del ultimo bloque recibida.
+    /// Devuelve 0 si no esta confirmada
+    pub fn confirmations(&self, tip_height: u32) -> u32 {
+        match self.height {
+            Some(height) if height <= tip_height => tip_height - height + 1,
+            _ => 0,
         }
+    }
+
+    /// Devuelve la utxoTuple con las TxOut cuyo pub key script es alguno de los recibidos, normalmente los
+    /// derivados de los descriptors de una cuenta. En caso de que no encuentre ninguna, devuelve None
+    pub fn referenced_utxos(&self, pubkey_scripts: &HashSet<Vec<u8>>) -> Option<UtxoTuple> {
+        let utxo_set: Vec<(TxOut, usize)> = self
+            .utxo_set
+            .iter()
+            .filter(|(tx_out, _)| pubkey_scripts.contains(tx_out.get_pub_key_script()))
+            .cloned()
+            .collect();
         if utxo_set.is_empty() {
             return None;
         }
-        Some(UtxoTuple { hash, utxo_set })
+        Some(UtxoTuple {
+            hash: self.hash,
+            utxo_set,
+            height: self.height,
+            coinbase: self.coinbase,
+        })
     }
 
     /// Devuelve el monto en satoshis de las TxOut del Utxo
@@ -59,25 +87,6 @@ impl UtxoTuple {
         indexes
     }
 
-    /// Recive el monto total a gastar, y monto que ya se juntó
-    /// Remueve las utxos necesarias hasta llegar al monto total y las devuelve en un nuevo UtxoTuple
-    pub fn utxos_to_spend(&mut self, value: i64, partial_amount: &mut i64) -> UtxoTuple {
-        let mut utxos_to_spend = Vec::new();
-        let mut position: usize = 0;
-        let lenght: usize = self.utxo_set.len();
-        while position < lenght {
-            *partial_amount += self.utxo_set[position].0.value();
-            // No corresponde removerlas mientras la tx no está confirmada
-            // let utxo = self.utxo_set.remove(position);
-            utxos_to_spend.push(self.utxo_set[position].clone());
-            if *partial_amount > value {
-                break;
-            }
-            position += 1;
-        }
-        Self::new(self.hash, utxos_to_spend)
-    }
-
     /// Busca la utxo que corresponde al hash e indice recibido.
     /// Devuelve su pub key script en formato bytes
     pub fn find(&self, previous_hash: [u8; 32], previous_index: usize) -> Option<&Vec<u8>> {
@@ -92,6 +101,18 @@ impl UtxoTuple {
         None
     }
 
+    /// Busca la utxo que corresponde al hash e indice recibido.
+    /// Devuelve el TxOut completo (el monto es necesario para firmar inputs segwit)
+    pub fn find_tx_out(&self, previous_hash: [u8; 32], previous_index: usize) -> Option<&TxOut> {
+        if self.hash != previous_hash {
+            return None;
+        }
+        self.utxo_set
+            .iter()
+            .find(|(_, index)| *index == previous_index)
+            .map(|(tx_out, _)| tx_out)
+    }
+
     /// Remueve el output que contiene el indice recibido.
     pub fn remove_utxo(&mut self, output_index: usize) {
         for index in 0..self.utxo_set.len() {
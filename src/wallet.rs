@@ -2,21 +2,37 @@ use std::{
     error::Error,
     io,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use gtk::glib;
 
 use crate::{
-    account::Account,
+    account::{bytes_to_hex_string, hex_string_to_bytes, Account},
+    address_decoder::AddressType,
+    balance::{BalanceBreakdown, SpendPolicy},
     blocks::{
         block::Block,
         block_header::BlockHeader,
         utils_block::{make_merkle_proof, string_to_bytes},
     },
+    coin_control::{format_coin_id, CoinAction, CoinControl, CoinId, CoinInfo},
+    config::Config,
     custom_errors::NodeCustomErrors,
+    fee_estimator::{transaction_fee, FeeTarget, MIN_RELAY_FEE_RATE},
     gtk::ui_events::{send_event_to_ui, UIEvent},
+    hd_wallet::{hd_keychain::DerivationScheme, mnemonic::generate_mnemonic},
+    ledger::HistoryEntry,
+    message_signing,
     node::Node,
-    transactions::transaction::Transaction,
+    psbt::Psbt,
+    rescan::{RescanStart, RESCAN_PROGRESS_INTERVAL},
+    transactions::{
+        recipient::Recipient, script::multisig_script::MultisigKind, transaction::Transaction,
+    },
+    wallet_storage::{
+        AccountMetadata, RestoredWallet, StoredLedgerEntry, StoredTransaction, WalletStorage,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -25,48 +41,311 @@ pub struct Wallet {
     pub node: Node,
     pub current_account_index: Option<usize>,
     pub accounts: Arc<RwLock<Vec<Account>>>,
+    pub coin_control: Arc<RwLock<CoinControl>>,
+    pub storage: Arc<RwLock<WalletStorage>>,
+    pub min_confirmations: u32,
 }
 
 impl Wallet {
-    /// Crea la wallet. Inicializa el nodo con la referencia de las cuentas de la wallet
-    pub fn new(node: Node) -> Result<Self, NodeCustomErrors> {
+    /// Crea la wallet. Inicializa el nodo con la referencia de las cuentas de la wallet, carga
+    /// las monedas bloqueadas y congeladas del archivo de coin control y abre el archivo de la wallet
+    /// de la configuracion. Si el archivo existe, sus cuentas se cargan al desbloquear la wallet.
+    /// Las monedas se gastan con el minimo de confirmaciones de la configuracion
+    pub fn new(node: Node, config: &Config) -> Result<Self, NodeCustomErrors> {
+        let coin_control = CoinControl::load(&config.archivo_coin_control)
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let storage = WalletStorage::open(
+            &config.archivo_wallet,
+            Duration::from_secs(config.wallet_lock_timeout),
+        )
+        .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
         let mut wallet = Wallet {
             node,
             current_account_index: None,
             accounts: Arc::new(RwLock::new(Vec::new())),
+            coin_control: Arc::new(RwLock::new(coin_control)),
+            storage: Arc::new(RwLock::new(storage)),
+            min_confirmations: config.min_confirmations,
         };
         wallet.node.set_accounts(wallet.accounts.clone())?;
         Ok(wallet)
     }
 
     /// Realiza una transacción con la cuenta actual de la wallet y hace el broadcast.
-    /// Recibe la address receptora, monto y fee.
+    /// Recibe los pagos a realizar (address receptora y monto), el fee rate en sat/vB y las monedas
+    /// elegidas a mano para gastar (si no hay ninguna se eligen automaticamente).
+    /// Si la cuenta es multisig y faltan las firmas de otros firmantes no se hace el broadcast y se
+    /// devuelve la transaccion parcialmente firmada en hexadecimal para que la firmen.
     /// Devuelve error en caso de que algo falle.
     pub fn make_transaction(
         &self,
         ui_sender: &Option<glib::Sender<UIEvent>>,
-        address_receiver: &str,
-        amount: i64,
-        fee: i64,
-    ) -> Result<(), Box<dyn Error>> {
-        let account_index = match self.current_account_index {
-            Some(index) => index,
-            None => {
-                return Err(Box::new(std::io::Error::new(
-                    io::ErrorKind::Other,
-                    "Error trying to make transaction. No account selected",
-                )));
-            }
+        recipients: &[Recipient],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let account_index = self.selected_account_index("make transaction")?;
+        self.ensure_unlocked("make transaction")?;
+        validate_transaction_data(recipients, fee_rate)?;
+        let policy = self.spend_policy()?;
+        let (transaction, fully_signed, fee) = {
+            let mut accounts = self
+                .accounts
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            let account = &mut accounts[account_index];
+            let transaction =
+                account.make_transaction(recipients, fee_rate, chosen_coins, &policy)?;
+            let fully_signed = account.is_fully_signed(&transaction);
+            let fee = account.spent_amount(&transaction) - transaction.amount();
+            (transaction, fully_signed, fee)
         };
-        validate_transaction_data(amount, fee)?;
-        let transaction: Transaction = self
+        if !fully_signed {
+            return Ok(Some(transaction_to_hex(&transaction)));
+        }
+        self.broadcast_transaction(ui_sender, &transaction, fee)?;
+        Ok(None)
+    }
+
+    /// Agrega la firma de la cuenta multisig actual a la transaccion parcialmente firmada recibida en hexadecimal.
+    /// Si se completan las firmas requeridas hace el broadcast, sino devuelve la transaccion con la firma agregada
+    /// en hexadecimal para que la firmen los demas firmantes.
+    /// Devuelve error si la transaccion es invalida o la cuenta no es un firmante del multisig
+    pub fn sign_multisig_transaction(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        raw_transaction: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let account_index = self.selected_account_index("sign transaction")?;
+        self.ensure_unlocked("sign transaction")?;
+        let bytes = hex_string_to_bytes(raw_transaction)?;
+        let transaction = Transaction::unmarshalling(&bytes, &mut 0)
+            .map_err(|err| Box::new(std::io::Error::other(err)) as Box<dyn Error>)?;
+        let (transaction, fully_signed, fee) = {
+            let accounts = self
+                .accounts
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            let account = &accounts[account_index];
+            let transaction = account.sign_multisig_transaction(transaction)?;
+            let fee = account.spent_amount(&transaction) - transaction.amount();
+            (
+                transaction.clone(),
+                account.is_fully_signed(&transaction),
+                fee,
+            )
+        };
+        if !fully_signed {
+            return Ok(Some(transaction_to_hex(&transaction)));
+        }
+        self.broadcast_transaction(ui_sender, &transaction, fee)?;
+        Ok(None)
+    }
+
+    /// Aumenta el fee de la transaccion pendiente de la cuenta actual con el hash recibido en hexadecimal,
+    /// reemplazandola por otra que paga el fee rate en sat/vB recibido (BIP125), y hace el broadcast del reemplazo.
+    /// Devuelve la comision que paga el reemplazo o error si la transaccion no se puede reemplazar
+    pub fn bump_fee(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        tx_hash_hex: &str,
+        fee_rate: f64,
+    ) -> Result<i64, Box<dyn Error>> {
+        let account_index = self.selected_account_index("bump fee")?;
+        self.ensure_unlocked("bump fee")?;
+        validate_fee_rate(fee_rate)?;
+        let mut tx_hash: [u8; 32] = string_to_bytes(tx_hash_hex)?;
+        tx_hash.reverse();
+        let (transaction, fee) = self
             .accounts
             .write()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
-            .make_transaction(address_receiver, amount, fee)?;
-        self.node.broadcast_tx(transaction.hash())?;
+            .bump_fee(tx_hash, fee_rate)?;
+        self.broadcast_transaction(ui_sender, &transaction, fee)?;
+        Ok(fee)
+    }
+
+    /// Acelera la transaccion pendiente que le paga a la cuenta actual, con el hash recibido en hexadecimal,
+    /// creando una transaccion hija que gasta sus outputs para que el paquete pague el fee rate en sat/vB recibido
+    /// (child pays for parent). Hace el broadcast del paquete y devuelve su fee rate efectivo en sat/vB.
+    /// Devuelve error si no se conocen los montos que gasta la transaccion padre o no se puede crear la hija
+    pub fn accelerate_transaction(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        tx_hash_hex: &str,
+        fee_rate: f64,
+    ) -> Result<f64, Box<dyn Error>> {
+        let account_index = self.selected_account_index("accelerate transaction")?;
+        self.ensure_unlocked("accelerate transaction")?;
+        validate_fee_rate(fee_rate)?;
+        let mut tx_hash: [u8; 32] = string_to_bytes(tx_hash_hex)?;
+        tx_hash.reverse();
+        let mut accounts = self
+            .accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let account = &mut accounts[account_index];
+        let parent = account.find_pending_transaction(tx_hash)?;
+        let utxo_set = self
+            .node
+            .blockchain
+            .utxo_set
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let parent_fee = transaction_fee(&parent, &utxo_set).ok_or_else(|| {
+            std::io::Error::other(
+                "No se conocen los outputs que gasta la transaccion, puede que su padre no este confirmado",
+            )
+        })?;
+        drop(utxo_set);
+        let (child, child_fee) = account.make_cpfp_transaction(&parent, parent_fee, fee_rate)?;
+        drop(accounts);
+        let package_fee_rate =
+            (parent_fee + child_fee) as f64 / (parent.vsize() + child.vsize()) as f64;
+        // los nodos evaluan el paquete con el fee rate de ambas, asi que se anuncian las dos
+        let feefilter_rate = (package_fee_rate * 1000.0) as u64;
+        self.node.broadcast_tx(&parent, feefilter_rate)?;
+        self.node.broadcast_tx(&child, feefilter_rate)?;
         send_event_to_ui(ui_sender, UIEvent::NewPendingTx());
-        Ok(())
+        self.save_after_broadcast()?;
+        Ok(package_fee_rate)
+    }
+
+    /// Crea un PSBT sin firmar de una transaccion desde la cuenta actual, con los pagos a realizar, el fee rate en sat/vB
+    /// y las monedas elegidas a mano para gastar (si no hay ninguna se eligen automaticamente).
+    /// Devuelve error si no hay cuenta seleccionada o no se puede crear la transaccion
+    pub fn create_psbt(
+        &self,
+        recipients: &[Recipient],
+        fee_rate: f64,
+        chosen_coins: &[CoinId],
+    ) -> Result<Psbt, Box<dyn Error>> {
+        let account_index = self.selected_account_index("create PSBT")?;
+        validate_transaction_data(recipients, fee_rate)?;
+        let policy = self.spend_policy()?;
+        self.accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
+            .create_psbt(recipients, fee_rate, chosen_coins, &policy)
+    }
+
+    /// Agrega al PSBT las firmas de la cuenta actual. Devuelve la cantidad de firmas agregadas
+    pub fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize, Box<dyn Error>> {
+        let account_index = self.selected_account_index("sign PSBT")?;
+        self.ensure_unlocked("sign PSBT")?;
+        let accounts = self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        accounts[account_index].ensure_can_sign()?;
+        psbt.sign(&accounts[account_index])
+    }
+
+    /// Firma el mensaje con la clave de la direccion recibida de la cuenta actual, o con la de su direccion si
+    /// no se recibe ninguna. Devuelve la firma en base64 o error si la cuenta no puede firmar con esa direccion
+    pub fn sign_message(&self, address: &str, message: &str) -> Result<String, Box<dyn Error>> {
+        let account_index = self.selected_account_index("sign a message")?;
+        self.ensure_unlocked("sign a message")?;
+        let accounts = self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let account = &accounts[account_index];
+        let address = match address.trim() {
+            "" => account.address.as_str(),
+            address => address,
+        };
+        message_signing::sign_message(account, address, message)
+    }
+
+    /// Finaliza el PSBT, extrae la transaccion firmada y hace el broadcast. La transaccion se agrega
+    /// a las pendientes de las cuentas de la wallet que gasta. Devuelve error si al PSBT le faltan firmas
+    pub fn finalize_and_broadcast_psbt(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        psbt: &mut Psbt,
+    ) -> Result<Transaction, Box<dyn Error>> {
+        psbt.finalize()?;
+        let transaction = psbt.extract()?;
+        for account in self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter()
+            .filter(|account| account.spent_amount(&transaction) > 0)
+        {
+            account.add_transaction(transaction.clone())?;
+        }
+        self.broadcast_transaction(ui_sender, &transaction, psbt.fee()?)?;
+        Ok(transaction)
+    }
+
+    /// Devuelve el fee rate en sat/vB estimado para confirmar en el tiempo del objetivo recibido
+    pub fn estimate_fee_rate(&self, target: FeeTarget) -> Result<f64, NodeCustomErrors> {
+        self.node.estimate_fee_rate(target)
+    }
+
+    /// Agrega a la wallet una cuenta multisig m de n con las claves publicas de los firmantes y la
+    /// WIF private key de uno de ellos. Devuelve la direccion del multisig.
+    /// Devuelve error si los datos son invalidos y envia el error a la UI
+    pub fn add_multisig_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        wif_private_key: String,
+        kind: MultisigKind,
+        required: u8,
+        pubkeys: Vec<[u8; 33]>,
+    ) -> Result<String, NodeCustomErrors> {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let mut account =
+            Account::new_multisig(wif_private_key, kind, required, pubkeys).map_err(|err| {
+                send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+                NodeCustomErrors::UnmarshallingError(err.to_string())
+            })?;
+        self.load_data(&mut account)
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let address = account.address.clone();
+        self.accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .push(account.clone());
+        send_event_to_ui(ui_sender, UIEvent::AccountAddedSuccesfully(account));
+        self.save_after_adding_account(ui_sender)?;
+        Ok(address)
+    }
+
+    /// Devuelve el indice de la cuenta actual o error si no hay cuenta seleccionada
+    fn selected_account_index(&self, action: &str) -> Result<usize, Box<dyn Error>> {
+        match self.current_account_index {
+            Some(index) => Ok(index),
+            None => Err(Box::new(std::io::Error::other(format!(
+                "Error trying to {}. No account selected",
+                action
+            )))),
+        }
+    }
+
+    /// Hace el broadcast de la transaccion firmada con el fee rate que corresponde al fee recibido
+    fn broadcast_transaction(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        transaction: &Transaction,
+        fee: i64,
+    ) -> Result<(), Box<dyn Error>> {
+        let fee_rate = (fee as u64 * 1000) / transaction.vsize() as u64;
+        self.node.broadcast_tx(transaction, fee_rate)?;
+        send_event_to_ui(ui_sender, UIEvent::NewPendingTx());
+        self.save_after_broadcast()
+    }
+
+    /// Guarda la wallet despues de hacer el broadcast de una transaccion, para no perderla del historial
+    fn save_after_broadcast(&self) -> Result<(), Box<dyn Error>> {
+        self.save().map_err(|err| {
+            Box::new(std::io::Error::other(format!(
+                "The transaction was sent but the wallet could not be saved: {}",
+                err
+            ))) as Box<dyn Error>
+        })
     }
 
     /// Agrega una cuenta a la wallet.
@@ -78,10 +357,95 @@ impl Wallet {
         wif_private_key: String,
         address: String,
     ) -> Result<(), NodeCustomErrors> {
-        let mut account = Account::new(wif_private_key, address).map_err(|err| {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let account = Account::new(wif_private_key, address).map_err(|err| {
+            send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+            NodeCustomErrors::UnmarshallingError(err.to_string())
+        })?;
+        self.push_account(ui_sender, account)
+    }
+
+    /// Crea una cuenta con una clave privada nueva y una address del tipo recibido y la agrega a la wallet.
+    /// Devuelve la WIF private key y la address de la cuenta. El usuario tiene que guardar la WIF como respaldo,
+    /// porque es la unica forma de recuperar los fondos si se pierde el archivo de la wallet
+    pub fn create_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        address_type: AddressType,
+    ) -> Result<(String, String), NodeCustomErrors> {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let account = Account::generate(address_type).map_err(|err| {
+            send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+            NodeCustomErrors::OtherError(err.to_string())
+        })?;
+        let keys = (account.private_key.clone(), account.address.clone());
+        self.push_account(ui_sender, account)?;
+        Ok(keys)
+    }
+
+    /// Agrega a la wallet una cuenta watch-only que observa una clave publica extendida (tpub), cuyas direcciones
+    /// se derivan con el esquema recibido, un output descriptor, una direccion o un pubkey script en hexadecimal. La cuenta sigue su
+    /// balance y su historial y crea PSBT sin firmar, pero no puede firmar. Devuelve la address de la cuenta.
+    /// Devuelve error si lo que se quiere observar es invalido y envia el error a la UI
+    pub fn add_watch_only_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        watched: &str,
+        scheme: DerivationScheme,
+    ) -> Result<String, NodeCustomErrors> {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let account = Account::new_watch_only(watched, scheme).map_err(|err| {
             send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
             NodeCustomErrors::UnmarshallingError(err.to_string())
         })?;
+        let address = account.address.clone();
+        self.push_account(ui_sender, account)?;
+        Ok(address)
+    }
+
+    /// Agrega a la wallet la cuenta del output descriptor recibido: con claves privadas si el descriptor las tiene
+    /// y la wallet puede firmar con ellas, o watch-only si no. Devuelve la address de la cuenta.
+    /// Devuelve error si el descriptor es invalido o no se puede importar y envia el error a la UI
+    pub fn import_descriptor(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        descriptor: &str,
+    ) -> Result<String, NodeCustomErrors> {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let account = Account::from_descriptor(descriptor).map_err(|err| {
+            send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+            NodeCustomErrors::UnmarshallingError(err.to_string())
+        })?;
+        let address = account.address.clone();
+        self.push_account(ui_sender, account)?;
+        Ok(address)
+    }
+
+    /// Devuelve los output descriptors de la cuenta actual con su checksum, para importarla en otra wallet.
+    /// Si include_private es true incluyen las claves privadas y la wallet tiene que estar desbloqueada.
+    /// Devuelve error si no hay cuenta seleccionada o si se piden las claves privadas de una cuenta watch-only
+    pub fn export_descriptors(&self, include_private: bool) -> Result<Vec<String>, Box<dyn Error>> {
+        let account_index = self.selected_account_index("export descriptors")?;
+        if include_private {
+            self.ensure_unlocked("export private descriptors")?;
+        }
+        let descriptors = self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
+            .descriptors(include_private)?;
+        Ok(descriptors
+            .iter()
+            .map(|descriptor| descriptor.to_string())
+            .collect())
+    }
+
+    /// Carga los datos de la cuenta, la agrega a la wallet, se la envia a la UI y guarda la wallet
+    fn push_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        mut account: Account,
+    ) -> Result<(), NodeCustomErrors> {
         self.load_data(&mut account)
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
         self.accounts
@@ -89,18 +453,511 @@ impl Wallet {
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
             .push(account.clone());
         send_event_to_ui(ui_sender, UIEvent::AccountAddedSuccesfully(account));
+        self.save_after_adding_account(ui_sender)
+    }
+
+    /// Crea cuentas HD a partir de un mnemonic nuevo con la cantidad de palabras recibida y la passphrase
+    /// (puede ser vacia). Devuelve el mnemonic, que el usuario tiene que guardar para poder restaurar la wallet
+    pub fn create_hd_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        word_count: usize,
+        passphrase: &str,
+        scheme: DerivationScheme,
+    ) -> Result<String, NodeCustomErrors> {
+        let mnemonic = generate_mnemonic(word_count)
+            .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+        self.restore_hd_account(ui_sender, &mnemonic, passphrase, scheme)?;
+        Ok(mnemonic)
+    }
+
+    /// Restaura las cuentas HD del mnemonic y la passphrase recibidos. Agrega la cuenta 0 y las siguientes
+    /// mientras tengan fondos, buscandolos en cada una con el gap limit. Devuelve la cantidad de cuentas agregadas.
+    /// Devuelve error si el mnemonic es invalido y envia el error a la UI
+    pub fn restore_hd_account(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        mnemonic: &str,
+        passphrase: &str,
+        scheme: DerivationScheme,
+    ) -> Result<u32, NodeCustomErrors> {
+        self.ensure_unlocked_to_add_account(ui_sender)?;
+        let mut account_index = 0;
+        loop {
+            let mut account = Account::from_mnemonic(mnemonic, passphrase, scheme, account_index)
+                .map_err(|err| {
+                send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+                NodeCustomErrors::UnmarshallingError(err.to_string())
+            })?;
+            self.load_data(&mut account)
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            if account_index > 0 && account.utxo_set.is_empty() {
+                self.save_after_adding_account(ui_sender)?;
+                return Ok(account_index);
+            }
+            self.accounts
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .push(account.clone());
+            send_event_to_ui(ui_sender, UIEvent::AccountAddedSuccesfully(account));
+            account_index += 1;
+        }
+    }
+
+    /// Devuelve una direccion para recibir fondos en la cuenta actual. Si la cuenta es HD es una direccion nueva.
+    /// Devuelve error si no hay cuenta seleccionada
+    pub fn new_receive_address(&self) -> Result<String, Box<dyn Error>> {
+        let account_index = match self.current_account_index {
+            Some(index) => index,
+            None => {
+                return Err(Box::new(std::io::Error::other(
+                    "Error trying to get a new address. No account selected",
+                )));
+            }
+        };
+        let address = self
+            .accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
+            .next_receive_address()?;
+        // se guarda el indice para no volver a entregar la direccion despues de reiniciar
+        self.save()?;
+        Ok(address)
+    }
+
+    /// Devuelve las reglas para gastar monedas: el minimo de confirmaciones de la wallet y la altura actual de la cadena
+    fn spend_policy(&self) -> Result<SpendPolicy, NodeCustomErrors> {
+        Ok(SpendPolicy::new(
+            self.min_confirmations,
+            self.node.blockchain.tip_height()?,
+        ))
+    }
+
+    /// Devuelve el balance de la cuenta actual separado en confirmado, pendiente de recibir, cambio pendiente,
+    /// coinbase inmaduro y bloqueado. Devuelve error si no hay cuenta seleccionada
+    pub fn balance_breakdown(&self) -> Result<BalanceBreakdown, Box<dyn Error>> {
+        let account_index = self.selected_account_index("get the balance")?;
+        let policy = self.spend_policy()?;
+        self.accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
+            .balance_breakdown(&policy)
+    }
+
+    /// Le envia a la UI la cuenta recibida para que la muestre, junto con el balance separado de la cuenta actual
+    pub fn send_account_to_ui(&self, ui_sender: &Option<glib::Sender<UIEvent>>, account: Account) {
+        send_event_to_ui(ui_sender, UIEvent::AccountChanged(account));
+        if let Ok(breakdown) = self.balance_breakdown() {
+            send_event_to_ui(ui_sender, UIEvent::BalanceUpdated(breakdown));
+        }
+    }
+
+    /// Devuelve las monedas de la cuenta actual con sus confirmaciones y su estado en el coin control.
+    /// Devuelve error si no hay cuenta seleccionada
+    pub fn list_coins(&self) -> Result<Vec<CoinInfo>, Box<dyn Error>> {
+        let account_index = self.selected_account_index("list coins")?;
+        let tip_height = self.node.blockchain.tip_height()?;
+        self.accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
+            .coins(tip_height)
+    }
+
+    /// Bloquea, desbloquea, congela o descongela la moneda recibida de la cuenta actual y guarda el cambio
+    /// en el archivo de coin control. Devuelve error si la moneda no es de la cuenta actual
+    pub fn change_coin_state(
+        &self,
+        coin: CoinId,
+        action: CoinAction,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.list_coins()?.iter().any(|info| info.id == coin) {
+            return Err(Box::new(std::io::Error::other(format!(
+                "La moneda {} no es una utxo de la cuenta actual",
+                format_coin_id(&coin)
+            ))));
+        }
+        self.coin_control
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .apply(action, coin)
+    }
+
+    /// Define la passphrase con la que se cifran las claves de la wallet, o la cambia si ya tenia una, y guarda
+    /// la wallet en su archivo. Desde entonces la wallet se guarda con cada cambio y al cerrar el programa.
+    /// Devuelve error si la wallet esta bloqueada
+    pub fn set_passphrase(&self, passphrase: &str) -> Result<(), Box<dyn Error>> {
+        let records = self.account_records()?;
+        self.storage
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .set_passphrase(passphrase, &records)?;
+        self.save()
+    }
+
+    /// Desbloquea la wallet con la passphrase hasta que venza el timeout de la configuracion. La primera vez
+    /// agrega las cuentas guardadas en el archivo con su historial de transacciones, y las siguientes vuelve
+    /// a cargar en las cuentas las claves privadas que se borraron al bloquearla.
+    /// Devuelve error si la passphrase es incorrecta
+    pub fn unlock(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        passphrase: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let restored = self
+            .storage
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .unlock(passphrase)?;
+        match restored {
+            Some(restored) => self.restore_accounts(ui_sender, restored),
+            None => {
+                let restored_keys = self.restore_private_keys();
+                if restored_keys.is_err() {
+                    self.lock()?;
+                }
+                restored_keys
+            }
+        }
+    }
+
+    /// Bloquea la wallet y borra de memoria las claves privadas de las cuentas. Las cuentas se siguen mostrando
+    /// pero no se puede firmar con sus claves ni agregar cuentas hasta volver a desbloquearla
+    pub fn lock(&self) -> Result<(), NodeCustomErrors> {
+        self.storage
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .lock();
+        self.erase_private_keys()
+    }
+
+    /// Borra de memoria las claves privadas de todas las cuentas
+    fn erase_private_keys(&self) -> Result<(), NodeCustomErrors> {
+        for account in self
+            .accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter_mut()
+        {
+            account
+                .erase_private_keys()
+                .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+        }
         Ok(())
     }
-    /// Funcion que se encarga de cargar los respectivos utxos asociados a la cuenta
-    fn load_data(&self, account: &mut Account) -> Result<(), Box<dyn Error>> {
-        let address = account.get_address().clone();
-        let utxos_to_account = self.node.utxos_referenced_to_account(&address)?;
-        account.load_utxos(utxos_to_account);
+
+    /// Vuelve a cargar en las cuentas las claves privadas de sus registros guardados en el archivo de la wallet
+    fn restore_private_keys(&self) -> Result<(), Box<dyn Error>> {
+        let records = self
+            .storage
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .records()?;
+        let keys = records
+            .iter()
+            .map(|record| Account::from_record(record))
+            .collect::<Result<Vec<Account>, _>>()?;
+        for account in self
+            .accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter_mut()
+        {
+            if let Some(keys) = keys.iter().find(|keys| keys.address == account.address) {
+                account.restore_private_keys(keys)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Devuelve true si la wallet tiene passphrase y esta bloqueada
+    pub fn is_locked(&self) -> Result<bool, NodeCustomErrors> {
+        Ok(self
+            .storage
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .is_locked())
+    }
+
+    /// Devuelve true si la wallet tiene passphrase, es decir si se guarda en su archivo
+    pub fn has_passphrase(&self) -> Result<bool, NodeCustomErrors> {
+        Ok(self
+            .storage
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .has_passphrase())
+    }
+
+    /// Guarda en el archivo de la wallet las claves cifradas de las cuentas, sus indices de direcciones y su historial
+    /// de transacciones. No hace nada si la wallet no tiene passphrase: sus cuentas solo viven en memoria
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let records = self.account_records()?;
+        let mut metadata = vec![];
+        let mut transactions = vec![];
+        let mut ledger = vec![];
+        for account in self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter()
+        {
+            let (next_receive_index, next_change_index) = account
+                .hd_keychain
+                .as_ref()
+                .map_or((0, 0), |keychain| keychain.next_indexes());
+            metadata.push(AccountMetadata {
+                address: account.address.clone(),
+                next_receive_index,
+                next_change_index,
+            });
+            for (list, confirmed) in [
+                (&account.pending_transactions, false),
+                (&account.confirmed_transactions, true),
+            ] {
+                for transaction in list
+                    .read()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                    .iter()
+                {
+                    transactions.push(StoredTransaction {
+                        address: account.address.clone(),
+                        confirmed,
+                        transaction: transaction.clone(),
+                    });
+                }
+            }
+            for entry in account
+                .ledger
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .entries()
+            {
+                ledger.push(StoredLedgerEntry {
+                    address: account.address.clone(),
+                    entry: entry.clone(),
+                });
+            }
+        }
+        self.storage
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .save(&records, metadata, transactions, ledger)
+    }
+
+    /// Devuelve los registros con las claves de las cuentas de la wallet
+    fn account_records(&self) -> Result<Vec<String>, NodeCustomErrors> {
+        Ok(self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter()
+            .map(Account::to_record)
+            .collect())
+    }
+
+    /// Agrega a la wallet las cuentas leidas del archivo, con los indices de sus direcciones, su historial y su
+    /// ledger. Las transacciones que estaban pendientes se consideran confirmadas si alguna de sus utxos ya esta
+    /// en el utxo set, porque pudieron confirmarse mientras el programa estaba cerrado. Las entradas del ledger
+    /// guardadas se cargan antes de recorrer los bloques descargados, para reconocer los gastos de las monedas
+    /// encontradas en un rescan, y esas monedas se vuelven a agregar a las utxos de la cuenta
+    fn restore_accounts(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        restored: RestoredWallet,
+    ) -> Result<(), Box<dyn Error>> {
+        for record in &restored.records {
+            let mut account = Account::from_record(record)?;
+            let metadata = restored
+                .accounts
+                .iter()
+                .find(|metadata| metadata.address == account.address);
+            if let Some(metadata) = metadata {
+                account.restore_next_indexes(
+                    metadata.next_receive_index,
+                    metadata.next_change_index,
+                )?;
+            }
+            let mut ledger = account
+                .ledger
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            for stored in restored
+                .ledger
+                .iter()
+                .filter(|stored| stored.address == account.address)
+            {
+                ledger.record(stored.entry.clone());
+            }
+            drop(ledger);
+            self.load_data(&mut account)?;
+            account.set_utxos(self.node.blockchain.utxo_set.clone())?;
+            for stored in restored
+                .transactions
+                .iter()
+                .filter(|stored| stored.address == account.address)
+            {
+                let hash = stored.transaction.hash();
+                let transactions =
+                    if stored.confirmed || account.utxo_set.iter().any(|utxo| utxo.hash == hash) {
+                        &account.confirmed_transactions
+                    } else {
+                        &account.pending_transactions
+                    };
+                transactions
+                    .write()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                    .push(stored.transaction.clone());
+            }
+            self.accounts
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .push(account.clone());
+            send_event_to_ui(ui_sender, UIEvent::AccountAddedSuccesfully(account));
+        }
         Ok(())
     }
 
-    /// Muestra el balance de las cuentas.
+    /// Devuelve error si la wallet esta bloqueada, indicando la accion que no se pudo realizar. Si se bloqueo
+    /// porque vencio el timeout, tambien borra de memoria las claves privadas de las cuentas
+    fn ensure_unlocked(&self, action: &str) -> Result<(), Box<dyn Error>> {
+        let result = self
+            .storage
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .ensure_unlocked(action);
+        if result.is_err() {
+            self.erase_private_keys()?;
+        }
+        result
+    }
+
+    /// Devuelve error si la wallet esta bloqueada y le envia el error a la UI
+    fn ensure_unlocked_to_add_account(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+    ) -> Result<(), NodeCustomErrors> {
+        self.ensure_unlocked("add an account").map_err(|err| {
+            send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+            NodeCustomErrors::OtherError(err.to_string())
+        })
+    }
+
+    /// Guarda la wallet despues de agregarle una cuenta. Si la wallet no tiene passphrase no se guarda nada,
+    /// asi que le avisa a la UI que la cuenta se pierde al cerrar el programa
+    fn save_after_adding_account(
+        &self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+    ) -> Result<(), NodeCustomErrors> {
+        if !self.has_passphrase()? {
+            send_event_to_ui(
+                ui_sender,
+                UIEvent::WalletStatus(
+                    "The wallet has no passphrase: the account will not be saved when the program closes until one is set"
+                        .to_string(),
+                ),
+            );
+        }
+        self.save()
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
+    }
+
+    /// Funcion que se encarga de cargar los respectivos utxos asociados a la cuenta, de armar su ledger
+    /// con los bloques descargados y de compartirle el coin control de la wallet
+    fn load_data(&self, account: &mut Account) -> Result<(), Box<dyn Error>> {
+        account.coin_control = self.coin_control.clone();
+        account.set_utxos(self.node.blockchain.utxo_set.clone())?;
+        self.node
+            .blockchain
+            .visit_blocks_from(0, |block| account.record_block(block))
+    }
+
+    /// Reescanea la cadena para la cuenta actual desde la altura o la fecha recibida, por ejemplo el dia en que
+    /// se creo su clave. Les pide a los nodos los bloques que no estan descargados, vuelve a armar el ledger de la
+    /// cuenta con los bloques desde esa altura en una copia y agrega a sus utxos las monedas que no estaban.
+    /// El resultado se combina con la cuenta guardada bajo el lock de las cuentas, para no perder las transacciones
+    /// y utxos que se registraron mientras se escaneaba. Le informa el progreso a la UI y devuelve la cantidad de
+    /// bloques escaneados
+    pub fn rescan(
+        &mut self,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        start: RescanStart,
+    ) -> Result<usize, Box<dyn Error>> {
+        let index = self.selected_account_index("rescan the chain")?;
+        let start_height = start.start_height(
+            &self
+                .node
+                .blockchain
+                .headers
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        );
+        let missing_blocks = self.node.blockchain.missing_blocks_from(start_height)?;
+        let blocks_to_scan =
+            (self.node.blockchain.tip_height()? as usize + 1).saturating_sub(start_height);
+        let total = missing_blocks.len() + blocks_to_scan;
+        self.node.fetch_blocks(&missing_blocks, |fetched| {
+            send_event_to_ui(ui_sender, UIEvent::RescanProgress(fetched, total))
+        })?;
+        let mut account = self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .get(index)
+            .cloned()
+            .ok_or_else(|| std::io::Error::other("The selected account does not exist"))?;
+        let mut ledger = account
+            .ledger
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .clone();
+        ledger.remove_from_height(start_height as u32);
+        account.ledger = Arc::new(RwLock::new(ledger));
+        let mut scanned = 0;
+        self.node
+            .blockchain
+            .visit_blocks_from(start_height, |block| {
+                account.record_block(block)?;
+                scanned += 1;
+                if scanned % RESCAN_PROGRESS_INTERVAL == 0 {
+                    send_event_to_ui(
+                        ui_sender,
+                        UIEvent::RescanProgress(missing_blocks.len() + scanned, total),
+                    );
+                }
+                Ok(())
+            })?;
+        let pending_transactions = account
+            .pending_transactions
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .clone();
+        for transaction in &pending_transactions {
+            account.record_in_ledger(transaction, None)?;
+        }
+        let scanned_ledger = account
+            .ledger
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .clone();
+        let mut accounts = self
+            .accounts
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let stored = accounts
+            .get_mut(index)
+            .ok_or_else(|| std::io::Error::other("The selected account does not exist"))?;
+        stored.merge_rescan(&scanned_ledger, self.node.blockchain.utxo_set.clone())?;
+        let account = stored.clone();
+        drop(accounts);
+        send_event_to_ui(ui_sender, UIEvent::RescanProgress(total, total));
+        self.send_account_to_ui(ui_sender, account);
+        self.save()?;
+        Ok(scanned)
+    }
+
+    /// Muestra el balance de las cuentas, separado en confirmado, pendiente de recibir, cambio pendiente,
+    /// coinbase inmaduro y bloqueado segun el minimo de confirmaciones de la wallet
     pub fn show_accounts_balance(&self) -> Result<(), Box<dyn Error>> {
+        let policy = self.spend_policy()?;
         if self
             .accounts
             .read()
@@ -115,10 +972,25 @@ impl Wallet {
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
             .iter()
         {
+            let breakdown = account.balance_breakdown(&policy)?;
             println!(
-                "Cuenta: {} - Balance: {:.8} tBTC",
+                "Cuenta: {}{} - Balance: {:.8} tBTC",
                 account.address,
-                account.balance() as f64 / 1e8
+                if account.is_watch_only() {
+                    " (watch-only)"
+                } else {
+                    ""
+                },
+                breakdown.total() as f64 / 1e8
+            );
+            println!(
+                "    Confirmado ({}+ confirmaciones): {:.8} | Pendiente de recibir: {:.8} | Cambio pendiente: {:.8} | Coinbase inmaduro: {:.8} | Bloqueado: {:.8}",
+                self.min_confirmations,
+                breakdown.confirmed as f64 / 1e8,
+                breakdown.unconfirmed_incoming as f64 / 1e8,
+                breakdown.unconfirmed_change as f64 / 1e8,
+                breakdown.immature as f64 / 1e8,
+                breakdown.locked as f64 / 1e8
             );
         }
         Ok(())
@@ -149,7 +1021,7 @@ impl Wallet {
             .read()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[index_of_new_account]
             .clone();
-        send_event_to_ui(ui_sender, UIEvent::AccountChanged(new_account));
+        self.send_account_to_ui(ui_sender, new_account);
         Ok(())
     }
 
@@ -217,16 +1089,17 @@ impl Wallet {
         None
     }
 
-    /// Devuelve una lista con las transacciones de la cuenta actual
+    /// Devuelve el historial de transacciones de la cuenta actual con sus confirmaciones
     /// Si no hay cuenta actual devuelve None
-    pub fn get_transactions(&self) -> Option<Vec<(String, Transaction, i64)>> {
+    pub fn get_transactions(&self) -> Option<Vec<HistoryEntry>> {
         if let Some(index) = self.current_account_index {
+            let tip_height = self.node.blockchain.tip_height().ok()?;
             match self
                 .accounts
                 .read()
                 .map_err(|err| NodeCustomErrors::LockError(err.to_string()))
                 .unwrap()[index]
-                .get_transactions()
+                .get_transactions(tip_height)
             {
                 Ok(transactions) => return Some(transactions),
                 Err(_) => return None,
@@ -249,12 +1122,39 @@ impl Wallet {
     }
 }
 
-fn validate_transaction_data(amount: i64, fee: i64) -> Result<(), Box<dyn Error>> {
-    if (amount + fee) <= 0 {
+/// Devuelve error si no hay pagos, si algun monto no es positivo o si el fee rate es invalido
+fn validate_transaction_data(
+    recipients: &[Recipient],
+    fee_rate: f64,
+) -> Result<(), Box<dyn Error>> {
+    if recipients.is_empty() {
+        return Err(Box::new(std::io::Error::other(
+            "La transaccion debe tener al menos un receptor.",
+        )));
+    }
+    if recipients.iter().any(|recipient| recipient.amount <= 0) {
         return Err(Box::new(std::io::Error::new(
             io::ErrorKind::Other,
             "El monto a gastar debe ser mayor a cero.",
         )));
     }
+    validate_fee_rate(fee_rate)
+}
+
+/// Devuelve error si el fee rate es menor al minimo que relayan los nodos
+fn validate_fee_rate(fee_rate: f64) -> Result<(), Box<dyn Error>> {
+    if fee_rate.is_nan() || fee_rate < MIN_RELAY_FEE_RATE {
+        return Err(Box::new(std::io::Error::other(format!(
+            "El fee rate debe ser de al menos {} sat/vB.",
+            MIN_RELAY_FEE_RATE
+        ))));
+    }
     Ok(())
 }
+
+/// Devuelve la transaccion serializada en hexadecimal, con el witness si lo tiene
+fn transaction_to_hex(transaction: &Transaction) -> String {
+    let mut raw_transaction = vec![];
+    transaction.marshalling(&mut raw_transaction);
+    bytes_to_hex_string(&raw_transaction)
+}
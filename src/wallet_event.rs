@@ -1,25 +1,43 @@
 use crate::{
+    address_decoder::AddressType,
+    coin_control::{CoinAction, CoinId},
     custom_errors::NodeCustomErrors,
+    fee_estimator::FeeTarget,
     gtk::ui_events::{send_event_to_ui, UIEvent},
+    hd_wallet::hd_keychain::DerivationScheme,
+    message_signing::verify_message,
+    psbt::{combine_psbts, Psbt},
+    rescan::RescanStart,
+    transactions::recipient::Recipient,
     wallet::Wallet,
 };
 use gtk::glib;
-use std::sync::mpsc::Receiver;
+use std::{error::Error, sync::mpsc::Receiver};
 
 type Address = String;
 type WifPrivateKey = String;
 type AccountIndex = usize;
-type Amount = i64;
-type Fee = i64;
+type FeeRate = f64;
 type BlockHash = [u8; 32];
 type BlockHashString = String;
 type TransactionHash = String;
+type PsbtString = String;
+type Passphrase = String;
+type WatchedKeyOrScript = String;
+type DescriptorString = String;
+type IncludePrivateKeys = bool;
+type Message = String;
+type MessageSignature = String;
 
 /// Representa los eventos que la UI le envia a la wallet
 pub enum WalletEvent {
     Start,
     AddAccountRequest(WifPrivateKey, Address),
-    MakeTransaction(Address, Amount, Fee),
+    CreateAccount(AddressType),
+    AddWatchOnlyAccount(WatchedKeyOrScript, DerivationScheme),
+    ImportDescriptor(DescriptorString),
+    ExportDescriptors(IncludePrivateKeys),
+    MakeTransaction(Vec<Recipient>, FeeRate, Vec<CoinId>),
     PoiOfTransactionRequest(BlockHashString, TransactionHash),
     Finish,
     ChangeAccount(AccountIndex),
@@ -27,6 +45,21 @@ pub enum WalletEvent {
     GetTransactionsRequest,
     SearchBlock(BlockHash),
     SearchHeader(BlockHash),
+    CreatePsbt(Vec<Recipient>, FeeRate, Vec<CoinId>),
+    EstimateFee(FeeTarget),
+    SignPsbt(PsbtString),
+    CombinePsbts(Vec<PsbtString>),
+    FinalizePsbt(PsbtString),
+    BumpFee(TransactionHash, FeeRate),
+    AccelerateTransaction(TransactionHash, FeeRate),
+    ListCoins,
+    ChangeCoinState(CoinId, CoinAction),
+    SetWalletPassphrase(Passphrase),
+    UnlockWallet(Passphrase),
+    LockWallet,
+    Rescan(RescanStart),
+    SignMessage(Address, Message),
+    VerifyMessage(Address, Message, MessageSignature),
 }
 
 /// Recibe un sender que envia eventos a la UI, un receiver que recibe eventos de la UI y una wallet
@@ -42,14 +75,35 @@ pub fn handle_ui_request(
             WalletEvent::AddAccountRequest(wif, address) => {
                 handle_add_account(ui_sender, wallet, wif, address);
             }
+            WalletEvent::CreateAccount(address_type) => {
+                handle_create_account(ui_sender, wallet, address_type);
+            }
+            WalletEvent::AddWatchOnlyAccount(watched, scheme) => {
+                handle_add_watch_only_account(ui_sender, wallet, watched, scheme);
+            }
+            WalletEvent::ImportDescriptor(descriptor) => {
+                handle_import_descriptor(ui_sender, wallet, descriptor);
+            }
+            WalletEvent::ExportDescriptors(include_private) => {
+                handle_export_descriptors(ui_sender, wallet, include_private);
+            }
             WalletEvent::ChangeAccount(account_index) => {
                 handle_change_account(ui_sender, wallet, account_index);
             }
             WalletEvent::GetAccountRequest => {
                 handle_get_account(ui_sender, wallet);
             }
-            WalletEvent::MakeTransaction(address, amount, fee) => {
-                handle_make_transaction(ui_sender, wallet, address, amount, fee)
+            WalletEvent::MakeTransaction(recipients, fee_rate, coins) => {
+                handle_make_transaction(ui_sender, wallet, recipients, fee_rate, coins)
+            }
+            WalletEvent::BumpFee(tx_hash, fee_rate) => {
+                handle_bump_fee(ui_sender, wallet, tx_hash, fee_rate);
+            }
+            WalletEvent::AccelerateTransaction(tx_hash, fee_rate) => {
+                handle_accelerate_transaction(ui_sender, wallet, tx_hash, fee_rate);
+            }
+            WalletEvent::EstimateFee(target) => {
+                handle_estimate_fee(ui_sender, wallet, target);
             }
             WalletEvent::PoiOfTransactionRequest(block_hash, transaction_hash) => {
                 handle_poi(ui_sender, wallet, block_hash, transaction_hash);
@@ -63,6 +117,45 @@ pub fn handle_ui_request(
             WalletEvent::GetTransactionsRequest => {
                 handle_get_transactions(ui_sender, wallet);
             }
+            WalletEvent::CreatePsbt(recipients, fee_rate, coins) => {
+                handle_create_psbt(ui_sender, wallet, recipients, fee_rate, coins);
+            }
+            WalletEvent::ListCoins => {
+                handle_list_coins(ui_sender, wallet);
+            }
+            WalletEvent::ChangeCoinState(coin, action) => {
+                handle_change_coin_state(ui_sender, wallet, coin, action);
+            }
+            WalletEvent::SetWalletPassphrase(passphrase) => {
+                let result = wallet.set_passphrase(&passphrase);
+                send_wallet_status(ui_sender, wallet, result);
+            }
+            WalletEvent::UnlockWallet(passphrase) => {
+                let result = wallet.unlock(ui_sender, &passphrase);
+                send_wallet_status(ui_sender, wallet, result);
+            }
+            WalletEvent::LockWallet => {
+                let result = wallet.lock().map_err(|err| err.into());
+                send_wallet_status(ui_sender, wallet, result);
+            }
+            WalletEvent::Rescan(start) => {
+                handle_rescan(ui_sender, wallet, start);
+            }
+            WalletEvent::SignMessage(address, message) => {
+                handle_sign_message(ui_sender, wallet, address, message);
+            }
+            WalletEvent::VerifyMessage(address, message, signature) => {
+                handle_verify_message(ui_sender, address, message, signature);
+            }
+            WalletEvent::SignPsbt(psbt) => {
+                handle_sign_psbt(ui_sender, wallet, psbt);
+            }
+            WalletEvent::CombinePsbts(psbts) => {
+                handle_combine_psbts(ui_sender, psbts);
+            }
+            WalletEvent::FinalizePsbt(psbt) => {
+                handle_finalize_psbt(ui_sender, wallet, psbt);
+            }
             WalletEvent::Finish => {
                 break;
             }
@@ -87,6 +180,115 @@ fn handle_add_account(
     }
 }
 
+/// Recibe un sender que envia eventos a la UI, una wallet, lo que observa la cuenta watch-only y el esquema
+/// de derivacion si es una clave publica extendida. Se encarga de llamar al metodo de la wallet que agrega la
+/// cuenta. En caso de error al cargar sus datos envia un evento a la UI para que muestre el error
+fn handle_add_watch_only_account(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    watched: String,
+    scheme: DerivationScheme,
+) {
+    if let Err(NodeCustomErrors::LockError(err)) =
+        wallet.add_watch_only_account(ui_sender, &watched, scheme)
+    {
+        send_event_to_ui(ui_sender, UIEvent::AddAccountError(err));
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y un output descriptor. Se encarga de llamar al
+/// metodo de la wallet que importa la cuenta del descriptor. En caso de error al cargar sus datos envia un
+/// evento a la UI para que muestre el error
+fn handle_import_descriptor(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    descriptor: String,
+) {
+    if let Err(NodeCustomErrors::LockError(err)) = wallet.import_descriptor(ui_sender, &descriptor)
+    {
+        send_event_to_ui(ui_sender, UIEvent::AddAccountError(err));
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y si se incluyen las claves privadas.
+/// Le envia a la UI los descriptors de la cuenta actual o el error si no se pudieron exportar
+fn handle_export_descriptors(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    include_private: bool,
+) {
+    match wallet.export_descriptors(include_private) {
+        Ok(descriptors) => send_event_to_ui(ui_sender, UIEvent::DescriptorsExported(descriptors)),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::WalletStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y desde donde reescanear la cadena. Se encarga de llamar
+/// al metodo de la wallet que reescanea la cadena para la cuenta actual y le envia a la UI el resultado y el
+/// historial actualizado
+fn handle_rescan(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    start: RescanStart,
+) {
+    match wallet.rescan(ui_sender, start) {
+        Ok(scanned) => {
+            send_event_to_ui(
+                ui_sender,
+                UIEvent::WalletStatus(format!("Rescan finished, {} blocks scanned", scanned)),
+            );
+            handle_get_transactions(ui_sender, wallet);
+        }
+        Err(err) => send_event_to_ui(
+            ui_sender,
+            UIEvent::WalletStatus(format!("Error rescanning the chain: {}", err)),
+        ),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet, una direccion de la cuenta actual y un mensaje.
+/// Le envia a la UI la firma del mensaje o el error si no se pudo firmar
+fn handle_sign_message(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    address: String,
+    message: String,
+) {
+    match wallet.sign_message(&address, &message) {
+        Ok(signature) => send_event_to_ui(ui_sender, UIEvent::MessageSigned(signature)),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MessageStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una direccion, un mensaje y su firma en base64.
+/// Le envia a la UI si la firma es valida para la direccion o el error si no se pudo verificar
+fn handle_verify_message(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    address: String,
+    message: String,
+    signature: String,
+) {
+    let status = match verify_message(&address, &message, &signature) {
+        Ok(true) => format!("The signature is valid for the address {}", address),
+        Ok(false) => format!("The signature is NOT valid for the address {}", address),
+        Err(err) => format!("Error verifying the signature: {}", err),
+    };
+    send_event_to_ui(ui_sender, UIEvent::MessageStatus(status));
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y el tipo de direccion de la cuenta a crear
+/// Se encarga de llamar al metodo de la wallet que crea una cuenta con una clave nueva y le envia a la UI
+/// la WIF private key para que el usuario la respalde. Los errores ya se los envia la wallet a la UI
+fn handle_create_account(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    address_type: AddressType,
+) {
+    if let Ok((wif_private_key, address)) = wallet.create_account(ui_sender, address_type) {
+        send_event_to_ui(ui_sender, UIEvent::AccountCreated(wif_private_key, address));
+    }
+}
+
 /// Recibe un sender que envia eventos a la UI, una wallet y el indice de la cuenta a cambiar
 /// Se encarga de llamar al metodo de la wallet que cambia la cuenta actual. En caso de error al cambiar la cuenta
 /// envia un evento a la UI para que muestre el error
@@ -102,31 +304,193 @@ fn handle_change_account(
 
 /// Recibe un sender que envia eventos a la UI y una wallet
 /// Se encarga de llamar al metodo de la wallet que devuelve la cuenta actual. En caso de que la cuenta exista
-/// envia un evento a la UI para que muestre la cuenta actual
+/// envia un evento a la UI para que muestre la cuenta actual y su balance separado
 fn handle_get_account(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
     if let Some(account) = wallet.get_current_account() {
-        send_event_to_ui(ui_sender, UIEvent::AccountChanged(account));
+        wallet.send_account_to_ui(ui_sender, account);
     }
 }
 
-/// Recibe un sender que envia eventos a la UI, una wallet, una direccion, un monto y una comision
-/// Se encarga de llamar al metodo de la wallet que realiza una transaccion. En caso de error al realizar la transaccion
+/// Recibe un sender que envia eventos a la UI, una wallet, los pagos a realizar, un fee rate en sat/vB y las monedas
+/// elegidas a mano. Se encarga de llamar al metodo de la wallet que realiza una transaccion. En caso de error al realizar la transaccion
 /// envia un evento a la UI para que muestre el error. En caso de que la transaccion se realice correctamente envia un evento
 /// a la UI para que muestre que la transaccion se realizo correctamente
 fn handle_make_transaction(
     ui_sender: &Option<glib::Sender<UIEvent>>,
     wallet: &mut Wallet,
-    address: String,
-    amount: i64,
-    fee: i64,
+    recipients: Vec<Recipient>,
+    fee_rate: f64,
+    coins: Vec<CoinId>,
 ) {
-    if let Err(err) = wallet.make_transaction(ui_sender, &address, amount, fee) {
-        send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string()));
-    } else {
-        send_event_to_ui(
+    match wallet.make_transaction(ui_sender, &recipients, fee_rate, &coins) {
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
+        Ok(Some(partial_transaction)) => send_event_to_ui(
+            ui_sender,
+            UIEvent::MakeTransactionStatus(format!(
+                "The transaction needs the signatures of the other cosigners: {}",
+                partial_transaction
+            )),
+        ),
+        Ok(None) => send_event_to_ui(
             ui_sender,
             UIEvent::MakeTransactionStatus("The transaction was made succesfuly!".to_string()),
-        );
+        ),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet, el hash de una transaccion pendiente y un fee rate en sat/vB
+/// Se encarga de llamar al metodo de la wallet que reemplaza la transaccion por otra con mas fee y envia a la UI
+/// el resultado
+fn handle_bump_fee(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    tx_hash: String,
+    fee_rate: f64,
+) {
+    match wallet.bump_fee(ui_sender, &tx_hash, fee_rate) {
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
+        Ok(fee) => send_event_to_ui(
+            ui_sender,
+            UIEvent::MakeTransactionStatus(format!(
+                "The transaction was replaced paying a fee of {} satoshis!",
+                fee
+            )),
+        ),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet, el hash de una transaccion pendiente y un fee rate en sat/vB
+/// Se encarga de llamar al metodo de la wallet que la acelera con una transaccion hija y envia a la UI
+/// el fee rate efectivo del paquete o el error
+fn handle_accelerate_transaction(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    tx_hash: String,
+    fee_rate: f64,
+) {
+    match wallet.accelerate_transaction(ui_sender, &tx_hash, fee_rate) {
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
+        Ok(package_fee_rate) => send_event_to_ui(
+            ui_sender,
+            UIEvent::MakeTransactionStatus(format!(
+                "The transaction was accelerated! The package pays {:.2} sat/vB",
+                package_fee_rate
+            )),
+        ),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet, los pagos a realizar, un fee rate en sat/vB y las monedas
+/// elegidas a mano. Crea un PSBT sin firmar desde la cuenta actual y se lo envia a la UI. En caso de error envia el error a la UI
+fn handle_create_psbt(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    recipients: Vec<Recipient>,
+    fee_rate: f64,
+    coins: Vec<CoinId>,
+) {
+    match wallet.create_psbt(&recipients, fee_rate, &coins) {
+        Ok(psbt) => send_event_to_ui(ui_sender, UIEvent::PsbtUpdated(psbt.to_base64())),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::PsbtStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI y una wallet
+/// Envia a la UI las monedas de la cuenta actual. En caso de error envia el error a la UI
+fn handle_list_coins(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+    match wallet.list_coins() {
+        Ok(coins) => send_event_to_ui(ui_sender, UIEvent::CoinsListed(coins)),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet, una moneda y la accion a aplicarle
+/// Bloquea, desbloquea, congela o descongela la moneda y envia a la UI las monedas actualizadas.
+/// En caso de error envia el error a la UI
+fn handle_change_coin_state(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    coin: CoinId,
+    action: CoinAction,
+) {
+    match wallet.change_coin_state(coin, action) {
+        Ok(()) => handle_list_coins(ui_sender, wallet),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y el resultado de definir la passphrase, desbloquear
+/// o bloquear la wallet. Envia a la UI el error o el estado en que quedo la wallet
+fn send_wallet_status(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &Wallet,
+    result: Result<(), Box<dyn Error>>,
+) {
+    let status = match (result, wallet.is_locked()) {
+        (Err(err), _) => err.to_string(),
+        (Ok(()), Err(err)) => err.to_string(),
+        (Ok(()), Ok(true)) => "The wallet is locked".to_string(),
+        (Ok(()), Ok(false)) => "The wallet is unlocked".to_string(),
+    };
+    send_event_to_ui(ui_sender, UIEvent::WalletStatus(status));
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y un PSBT en base64 o la ruta de un archivo
+/// Agrega las firmas de la cuenta actual y envia el PSBT firmado a la UI. En caso de error envia el error a la UI
+fn handle_sign_psbt(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet, input: String) {
+    let signed = Psbt::import(&input).and_then(|mut psbt| {
+        wallet.sign_psbt(&mut psbt)?;
+        Ok(psbt)
+    });
+    match signed {
+        Ok(psbt) => send_event_to_ui(ui_sender, UIEvent::PsbtUpdated(psbt.to_base64())),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::PsbtStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI y varios PSBT en base64 o rutas de archivos
+/// Combina los PSBT y envia el resultado a la UI. En caso de error envia el error a la UI
+fn handle_combine_psbts(ui_sender: &Option<glib::Sender<UIEvent>>, inputs: Vec<String>) {
+    let combined = inputs
+        .iter()
+        .map(|input| Psbt::import(input))
+        .collect::<Result<Vec<Psbt>, _>>()
+        .and_then(combine_psbts);
+    match combined {
+        Ok(psbt) => send_event_to_ui(ui_sender, UIEvent::PsbtUpdated(psbt.to_base64())),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::PsbtStatus(err.to_string())),
+    }
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y un PSBT en base64 o la ruta de un archivo
+/// Finaliza el PSBT y hace el broadcast de la transaccion. Envia a la UI el resultado
+fn handle_finalize_psbt(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    input: String,
+) {
+    let broadcasted = Psbt::import(&input)
+        .and_then(|mut psbt| wallet.finalize_and_broadcast_psbt(ui_sender, &mut psbt));
+    let status = match broadcasted {
+        Ok(transaction) => format!(
+            "The transaction {} was made succesfuly!",
+            transaction.hex_hash()
+        ),
+        Err(err) => err.to_string(),
+    };
+    send_event_to_ui(ui_sender, UIEvent::PsbtStatus(status));
+}
+
+/// Recibe un sender que envia eventos a la UI, una wallet y un objetivo de tiempo de confirmacion
+/// Envia a la UI el fee rate estimado para ese objetivo. En caso de error envia el error a la UI
+fn handle_estimate_fee(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    target: FeeTarget,
+) {
+    match wallet.estimate_fee_rate(target) {
+        Ok(fee_rate) => send_event_to_ui(ui_sender, UIEvent::FeeEstimated(target, fee_rate)),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string())),
     }
 }
 
@@ -0,0 +1,561 @@
+use std::{
+    error::Error,
+    fs, io,
+    time::{Duration, Instant},
+};
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+
+use crate::{
+    account::{bytes_to_hex_string, hex_string_to_bytes},
+    ledger::{BlockPosition, LedgerEntry},
+    transactions::transaction::Transaction,
+};
+
+/// Clave simetrica derivada de la passphrase con la que se cifran las claves privadas
+pub type VaultKey = [u8; 32];
+
+/// Costo de scrypt (N = 2^SCRYPT_LOG_N) con el que se derivan las claves de los archivos nuevos
+pub const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Primera linea del archivo, con la version del formato
+const FILE_HEADER: &str = "wallet 1";
+const KDF_TAG: &str = "kdf";
+const KEYS_TAG: &str = "keys";
+const ACCOUNT_TAG: &str = "account";
+const TX_TAG: &str = "tx";
+const PENDING_TAG: &str = "pending";
+const CONFIRMED_TAG: &str = "confirmed";
+const LEDGER_TAG: &str = "ledger";
+/// Valor de los campos vacios de una linea: el bloque de una transaccion pendiente o la comision desconocida
+const EMPTY_FIELD: &str = "-";
+
+/// Claves privadas de la wallet cifradas con AES-256-GCM. La clave de cifrado se deriva de la
+/// passphrase con scrypt y el salt, por lo que una passphrase incorrecta no pasa la autenticacion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vault {
+    log_n: u8,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+/// Datos de una cuenta que se guardan sin cifrar para no volver a entregar sus direcciones
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountMetadata {
+    pub address: String,
+    pub next_receive_index: usize,
+    pub next_change_index: usize,
+}
+
+/// Transaccion del historial de la cuenta con la address recibida
+#[derive(Debug, Clone)]
+pub struct StoredTransaction {
+    pub address: String,
+    pub confirmed: bool,
+    pub transaction: Transaction,
+}
+
+/// Entrada del ledger de la cuenta con la address recibida
+#[derive(Debug, Clone)]
+pub struct StoredLedgerEntry {
+    pub address: String,
+    pub entry: LedgerEntry,
+}
+
+/// Contenido del archivo de la wallet: las claves cifradas y, sin cifrar, los datos de las
+/// cuentas, el historial de transacciones y el ledger, que no permiten gastar los fondos
+#[derive(Debug, Clone)]
+pub struct WalletFile {
+    pub vault: Vault,
+    pub accounts: Vec<AccountMetadata>,
+    pub transactions: Vec<StoredTransaction>,
+    pub ledger: Vec<StoredLedgerEntry>,
+}
+
+/// Lo que se recupera del archivo al desbloquear la wallet por primera vez: un registro por cuenta
+/// con sus claves (ver Account::to_record), los datos de las cuentas, el historial y el ledger
+#[derive(Debug, Clone)]
+pub struct RestoredWallet {
+    pub records: Vec<String>,
+    pub accounts: Vec<AccountMetadata>,
+    pub transactions: Vec<StoredTransaction>,
+    pub ledger: Vec<StoredLedgerEntry>,
+}
+
+/// Maneja el archivo de la wallet y su bloqueo. Mientras esta desbloqueada guarda la clave derivada de
+/// la passphrase hasta que vence el timeout. Si nunca se definio una passphrase la wallet no se bloquea
+/// y tampoco se guarda: las cuentas solo viven en memoria y se pierden al cerrar el programa, porque las
+/// claves privadas solo se escriben cifradas
+#[derive(Debug, Clone)]
+pub struct WalletStorage {
+    path: String,
+    lock_timeout: Duration,
+    log_n: u8,
+    vault: Option<Vault>,
+    session: Option<(VaultKey, Instant)>,
+    unrestored: Option<WalletFile>,
+}
+
+impl Vault {
+    /// Cifra los secretos con una clave derivada de la passphrase con un salt nuevo.
+    /// Devuelve el vault y la clave derivada, para volver a cifrar sin repetir la derivacion
+    pub fn seal(
+        secrets: &str,
+        passphrase: &str,
+        log_n: u8,
+    ) -> Result<(Vault, VaultKey), Box<dyn Error>> {
+        let mut vault = Vault {
+            log_n,
+            salt: rand::random(),
+            nonce: [0; NONCE_LEN],
+            ciphertext: vec![],
+        };
+        let key = vault.derive_key(passphrase)?;
+        vault = vault.reseal(secrets, &key)?;
+        Ok((vault, key))
+    }
+
+    /// Deriva la clave de cifrado de la passphrase con los parametros de scrypt del vault
+    pub fn derive_key(&self, passphrase: &str) -> Result<VaultKey, Box<dyn Error>> {
+        let params = scrypt::Params::new(self.log_n, SCRYPT_R, SCRYPT_P, 32)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        let mut key = [0; 32];
+        scrypt::scrypt(passphrase.as_bytes(), &self.salt, &params, &mut key)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        Ok(key)
+    }
+
+    /// Devuelve un vault con los secretos recibidos cifrados con la misma clave y un nonce nuevo
+    pub fn reseal(&self, secrets: &str, key: &VaultKey) -> Result<Vault, Box<dyn Error>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = Aes256Gcm::new(key.into())
+            .encrypt(&Nonce::from(nonce), secrets.as_bytes())
+            .map_err(|_| io::Error::other("No se pudieron cifrar las claves de la wallet"))?;
+        Ok(Vault {
+            nonce,
+            ciphertext,
+            ..self.clone()
+        })
+    }
+
+    /// Descifra los secretos con la clave recibida. Devuelve error si la clave no es la del vault
+    pub fn open(&self, key: &VaultKey) -> Result<String, Box<dyn Error>> {
+        let plaintext = Aes256Gcm::new(key.into())
+            .decrypt(&Nonce::from(self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| io::Error::other("La passphrase de la wallet es incorrecta"))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+impl WalletFile {
+    /// Lee el archivo de la wallet. Devuelve None si no existe o error si tiene un formato invalido
+    pub fn read(path: &str) -> Result<Option<WalletFile>, Box<dyn Error>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        if lines.next() != Some(FILE_HEADER) {
+            return Err(invalid_line(FILE_HEADER));
+        }
+        let (mut kdf, mut keys) = (None, None);
+        let (mut accounts, mut transactions, mut ledger) = (vec![], vec![], vec![]);
+        for line in lines {
+            let fields: Vec<&str> = line.split(' ').collect();
+            match fields.as_slice() {
+                [KDF_TAG, "scrypt", log_n, salt] => {
+                    kdf = Some((log_n.parse::<u8>()?, hex_to_array(salt, line)?))
+                }
+                [KEYS_TAG, nonce, ciphertext] => {
+                    keys = Some((hex_to_array(nonce, line)?, hex_string_to_bytes(ciphertext)?))
+                }
+                [ACCOUNT_TAG, address, next_receive_index, next_change_index] => {
+                    accounts.push(AccountMetadata {
+                        address: address.to_string(),
+                        next_receive_index: next_receive_index.parse()?,
+                        next_change_index: next_change_index.parse()?,
+                    })
+                }
+                [TX_TAG, address, state @ (PENDING_TAG | CONFIRMED_TAG), raw_transaction] => {
+                    let transaction =
+                        Transaction::unmarshalling(&hex_string_to_bytes(raw_transaction)?, &mut 0)
+                            .map_err(|_| invalid_line(line))?;
+                    transactions.push(StoredTransaction {
+                        address: address.to_string(),
+                        confirmed: *state == CONFIRMED_TAG,
+                        transaction,
+                    })
+                }
+                [LEDGER_TAG, address, block_hash, height, received, spent, fee, raw_transaction] => {
+                    let transaction =
+                        Transaction::unmarshalling(&hex_string_to_bytes(raw_transaction)?, &mut 0)
+                            .map_err(|_| invalid_line(line))?;
+                    let block = match (*block_hash, *height) {
+                        (EMPTY_FIELD, EMPTY_FIELD) => None,
+                        (block_hash, height) => Some(BlockPosition {
+                            hash: hex_to_array(block_hash, line)?,
+                            height: height.parse()?,
+                        }),
+                    };
+                    let fee = match *fee {
+                        EMPTY_FIELD => None,
+                        fee => Some(fee.parse()?),
+                    };
+                    ledger.push(StoredLedgerEntry {
+                        address: address.to_string(),
+                        entry: LedgerEntry {
+                            transaction,
+                            block,
+                            received: received.parse()?,
+                            spent: spent.parse()?,
+                            fee,
+                        },
+                    })
+                }
+                _ => return Err(invalid_line(line)),
+            }
+        }
+        let ((log_n, salt), (nonce, ciphertext)) = kdf
+            .zip(keys)
+            .ok_or_else(|| io::Error::other("El archivo de la wallet no tiene las claves"))?;
+        Ok(Some(WalletFile {
+            vault: Vault {
+                log_n,
+                salt,
+                nonce,
+                ciphertext,
+            },
+            accounts,
+            transactions,
+            ledger,
+        }))
+    }
+
+    /// Escribe el archivo de la wallet. Lo escribe primero en un archivo temporal y despues lo renombra
+    /// para no perder las claves si el programa se corta en el medio
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let vault = &self.vault;
+        let mut content = format!(
+            "{}\n{} scrypt {} {}\n{} {} {}\n",
+            FILE_HEADER,
+            KDF_TAG,
+            vault.log_n,
+            bytes_to_hex_string(&vault.salt),
+            KEYS_TAG,
+            bytes_to_hex_string(&vault.nonce),
+            bytes_to_hex_string(&vault.ciphertext)
+        );
+        for account in &self.accounts {
+            content.push_str(&format!(
+                "{} {} {} {}\n",
+                ACCOUNT_TAG, account.address, account.next_receive_index, account.next_change_index
+            ));
+        }
+        for stored in &self.transactions {
+            let mut raw_transaction = vec![];
+            stored.transaction.marshalling(&mut raw_transaction);
+            let state = if stored.confirmed {
+                CONFIRMED_TAG
+            } else {
+                PENDING_TAG
+            };
+            content.push_str(&format!(
+                "{} {} {} {}\n",
+                TX_TAG,
+                stored.address,
+                state,
+                bytes_to_hex_string(&raw_transaction)
+            ));
+        }
+        for stored in &self.ledger {
+            let entry = &stored.entry;
+            let mut raw_transaction = vec![];
+            entry.transaction.marshalling(&mut raw_transaction);
+            let (block_hash, height) = match entry.block {
+                Some(block) => (bytes_to_hex_string(&block.hash), block.height.to_string()),
+                None => (EMPTY_FIELD.to_string(), EMPTY_FIELD.to_string()),
+            };
+            content.push_str(&format!(
+                "{} {} {} {} {} {} {} {}\n",
+                LEDGER_TAG,
+                stored.address,
+                block_hash,
+                height,
+                entry.received,
+                entry.spent,
+                entry
+                    .fee
+                    .map_or(EMPTY_FIELD.to_string(), |fee| fee.to_string()),
+                bytes_to_hex_string(&raw_transaction)
+            ));
+        }
+        let temporary_path = format!("{}.tmp", path);
+        fs::write(&temporary_path, content)?;
+        fs::rename(temporary_path, path)?;
+        Ok(())
+    }
+}
+
+impl WalletStorage {
+    /// Lee el archivo de la wallet de la ruta recibida, donde se guardaran los cambios. Si existe la
+    /// wallet empieza bloqueada y sus cuentas se cargan al desbloquearla.
+    /// Devuelve error si el archivo tiene un formato invalido
+    pub fn open(path: &str, lock_timeout: Duration) -> Result<WalletStorage, Box<dyn Error>> {
+        let unrestored = WalletFile::read(path)?;
+        Ok(WalletStorage {
+            path: path.to_string(),
+            lock_timeout,
+            log_n: SCRYPT_LOG_N,
+            vault: unrestored.as_ref().map(|file| file.vault.clone()),
+            session: None,
+            unrestored,
+        })
+    }
+
+    /// Devuelve true si la wallet tiene passphrase
+    pub fn has_passphrase(&self) -> bool {
+        self.vault.is_some()
+    }
+
+    /// Devuelve true si la wallet tiene passphrase y no se desbloqueo o ya vencio el timeout
+    pub fn is_locked(&self) -> bool {
+        self.vault.is_some() && self.session_key().is_none()
+    }
+
+    /// Devuelve error si la wallet esta bloqueada, indicando la accion que no se pudo realizar
+    pub fn ensure_unlocked(&self, action: &str) -> Result<(), Box<dyn Error>> {
+        if self.is_locked() {
+            return Err(Box::new(io::Error::other(format!(
+                "Error trying to {}. The wallet is locked, unlock it with the passphrase",
+                action
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Desbloquea la wallet hasta que venza el timeout. La primera vez devuelve las cuentas y el historial
+    /// leidos del archivo para que se carguen en la wallet. Devuelve error si la passphrase es incorrecta
+    pub fn unlock(&mut self, passphrase: &str) -> Result<Option<RestoredWallet>, Box<dyn Error>> {
+        let vault = self
+            .vault
+            .as_ref()
+            .ok_or_else(|| io::Error::other("The wallet has no passphrase"))?;
+        let key = vault.derive_key(passphrase)?;
+        let secrets = vault.open(&key)?;
+        self.session = Some((key, Instant::now() + self.lock_timeout));
+        Ok(self.unrestored.take().map(|file| RestoredWallet {
+            records: secrets.lines().map(String::from).collect(),
+            accounts: file.accounts,
+            transactions: file.transactions,
+            ledger: file.ledger,
+        }))
+    }
+
+    /// Devuelve los registros de las cuentas guardados en el archivo, con sus claves privadas.
+    /// Devuelve error si la wallet esta bloqueada o no tiene passphrase
+    pub fn records(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let (vault, key) = match (&self.vault, self.session_key()) {
+            (Some(vault), Some(key)) => (vault, key),
+            _ => {
+                return Err(Box::new(io::Error::other(
+                    "The wallet is locked or has no passphrase",
+                )))
+            }
+        };
+        Ok(vault.open(&key)?.lines().map(String::from).collect())
+    }
+
+    /// Bloquea la wallet olvidando la clave derivada de la passphrase
+    pub fn lock(&mut self) {
+        self.session = None;
+    }
+
+    /// Define la passphrase de la wallet, o la cambia si ya tenia una, y cifra con ella los registros de
+    /// las cuentas recibidos. Deja la wallet desbloqueada. Devuelve error si la wallet esta bloqueada
+    pub fn set_passphrase(
+        &mut self,
+        passphrase: &str,
+        records: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        self.ensure_unlocked("change the passphrase")?;
+        if passphrase.is_empty() {
+            return Err(Box::new(io::Error::other(
+                "The passphrase can not be empty",
+            )));
+        }
+        let (vault, key) = Vault::seal(&records.join("\n"), passphrase, self.log_n)?;
+        self.vault = Some(vault);
+        self.session = Some((key, Instant::now() + self.lock_timeout));
+        Ok(())
+    }
+
+    /// Guarda en el archivo las claves de las cuentas (si la wallet esta desbloqueada, sino quedan las que
+    /// ya estaban, que no cambian mientras esta bloqueada), sus datos, el historial de transacciones y el ledger.
+    /// No hace nada si todavia no se cargo lo que habia en el archivo, ni si la wallet no tiene passphrase: en ese
+    /// caso no se escribe nada hasta que se defina una, y quien agrega cuentas tiene que avisarle al usuario
+    pub fn save(
+        &mut self,
+        records: &[String],
+        accounts: Vec<AccountMetadata>,
+        transactions: Vec<StoredTransaction>,
+        ledger: Vec<StoredLedgerEntry>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.unrestored.is_some() {
+            return Ok(());
+        }
+        let vault = match (&self.vault, self.session_key()) {
+            (None, _) => return Ok(()),
+            (Some(vault), Some(key)) => vault.reseal(&records.join("\n"), &key)?,
+            (Some(vault), None) => vault.clone(),
+        };
+        self.vault = Some(vault.clone());
+        WalletFile {
+            vault,
+            accounts,
+            transactions,
+            ledger,
+        }
+        .write(&self.path)
+    }
+
+    /// Devuelve la clave de la sesion si la wallet esta desbloqueada
+    fn session_key(&self) -> Option<VaultKey> {
+        match self.session {
+            Some((key, expires)) if Instant::now() < expires => Some(key),
+            _ => None,
+        }
+    }
+}
+
+fn hex_to_array<const N: usize>(hex: &str, line: &str) -> Result<[u8; N], Box<dyn Error>> {
+    hex_string_to_bytes(hex)?
+        .try_into()
+        .map_err(|_| invalid_line(line))
+}
+
+fn invalid_line(line: &str) -> Box<dyn Error> {
+    Box::new(io::Error::other(format!(
+        "Linea invalida en el archivo de la wallet: {}",
+        line
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Costo bajo de scrypt para que los tests sean rapidos
+    const TEST_LOG_N: u8 = 4;
+    const RECORD: &str = "wif cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
+
+    /// Transaccion del ejemplo "P2SH-P2WPKH" de BIP143
+    const RAW_TRANSACTION: &str = "0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000";
+
+    fn temporary_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn the_keys_can_only_be_opened_with_the_passphrase() -> Result<(), Box<dyn Error>> {
+        // GIVEN: un registro de una cuenta cifrado con una passphrase
+        let (vault, key) = Vault::seal(RECORD, "correct horse", TEST_LOG_N)?;
+        // WHEN: se deriva la clave con la passphrase correcta y con una incorrecta
+        let derived = vault.derive_key("correct horse")?;
+        let wrong = vault.derive_key("battery staple")?;
+        // THEN: solo la correcta descifra el registro, que no queda en claro en el vault
+        assert_eq!(derived, key);
+        assert_eq!(vault.open(&derived)?, RECORD);
+        assert!(vault.open(&wrong).is_err());
+        assert!(!String::from_utf8_lossy(&vault.ciphertext).contains("cMoBjaYS"));
+        Ok(())
+    }
+
+    #[test]
+    fn the_wallet_is_restored_from_the_file_after_unlocking() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una wallet con passphrase guardada con una cuenta, una transaccion pendiente y el ledger
+        let path = temporary_path("wallet_storage_test");
+        let mut storage = WalletStorage::open(&path, Duration::from_secs(60))?;
+        storage.log_n = TEST_LOG_N;
+        let records = vec![RECORD.to_string()];
+        storage.set_passphrase("passphrase", &records)?;
+        let metadata = AccountMetadata {
+            address: "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV".to_string(),
+            next_receive_index: 3,
+            next_change_index: 1,
+        };
+        let transaction =
+            Transaction::unmarshalling(&hex_string_to_bytes(RAW_TRANSACTION)?, &mut 0)?;
+        storage.save(
+            &records,
+            vec![metadata.clone()],
+            vec![StoredTransaction {
+                address: metadata.address.clone(),
+                confirmed: false,
+                transaction: transaction.clone(),
+            }],
+            vec![StoredLedgerEntry {
+                address: metadata.address.clone(),
+                entry: LedgerEntry {
+                    transaction: transaction.clone(),
+                    block: Some(BlockPosition {
+                        hash: [7; 32],
+                        height: 1170,
+                    }),
+                    received: 800_000_000,
+                    spent: 0,
+                    fee: None,
+                },
+            }],
+        )?;
+        // WHEN: se vuelve a abrir el archivo
+        let mut reopened = WalletStorage::open(&path, Duration::from_secs(60))?;
+        let locked = reopened.is_locked();
+        let wrong_passphrase = reopened.unlock("otra");
+        let restored = reopened.unlock("passphrase")?;
+        fs::remove_file(&path)?;
+        // THEN: empieza bloqueada y al desbloquearla con la passphrase devuelve lo guardado una sola vez
+        assert!(locked && wrong_passphrase.is_err());
+        let restored = restored.ok_or("la wallet no se restauro")?;
+        assert_eq!(restored.records, records);
+        assert_eq!(restored.accounts, vec![metadata]);
+        assert_eq!(
+            restored.transactions[0].transaction.hash(),
+            transaction.hash()
+        );
+        assert!(!restored.transactions[0].confirmed);
+        let entry = &restored.ledger[0].entry;
+        assert_eq!(entry.txid(), transaction.hash());
+        assert_eq!(entry.block.map(|block| block.height), Some(1170));
+        assert_eq!(
+            (entry.received, entry.spent, entry.fee),
+            (800_000_000, 0, None)
+        );
+        assert!(!reopened.is_locked());
+        assert!(reopened.unlock("passphrase")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn the_wallet_locks_itself_when_the_timeout_expires() -> Result<(), Box<dyn Error>> {
+        // GIVEN: una wallet sin archivo con un timeout nulo
+        let mut storage =
+            WalletStorage::open(&temporary_path("wallet_timeout_test"), Duration::ZERO)?;
+        storage.log_n = TEST_LOG_N;
+        assert!(!storage.is_locked());
+        // WHEN: se le define una passphrase, que la desbloquea
+        storage.set_passphrase("passphrase", &[RECORD.to_string()])?;
+        // THEN: queda bloqueada apenas vence el timeout y no se puede cambiar la passphrase
+        assert!(storage.is_locked());
+        assert!(storage.set_passphrase("otra", &[]).is_err());
+        Ok(())
+    }
+}